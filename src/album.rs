@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, IsTerminal, Write},
+};
+
+use alloy::{
+    analysis::{self, Loudness, ReplayGain},
+    extract, parse, storage, transform,
+    values::TrackNumber,
+};
+
+/// Values shared by every track in a folder when it is tagged as a single album
+#[derive(Debug, Default)]
+pub struct AlbumValues {
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub recording_time: Option<String>,
+    // file location -> number/total
+    pub track_numbers: HashMap<String, TrackNumber>,
+}
+
+fn prompt_choice(frame_id: &str, candidates: &[&String]) -> Option<String> {
+    println!(
+        "Tracks disagree on {} with no majority, choose a value:",
+        frame_id
+    );
+    for (idx, candidate) in candidates.iter().enumerate() {
+        println!("  [{}] {}", idx + 1, candidate);
+    }
+
+    loop {
+        print!("> ");
+        io::stdout().flush().ok()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).ok()? == 0 {
+            return None;
+        }
+
+        match input.trim().parse::<usize>() {
+            Ok(x) if x >= 1 && x <= candidates.len() => return Some(candidates[x - 1].clone()),
+            _ => println!("Enter a number between 1 and {}", candidates.len()),
+        }
+    }
+}
+
+fn majority_value(frame_id: &str, values: &[Option<String>], interactive: bool) -> Option<String> {
+    // Kept in first-seen order so ties without a prompt resolve predictably
+    let mut counts: Vec<(&String, u32)> = vec![];
+
+    for value in values.iter().flatten().filter(|x| !x.is_empty()) {
+        match counts.iter_mut().find(|(x, _)| *x == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+
+    if counts.len() > 1 {
+        let summary: Vec<String> = counts
+            .iter()
+            .map(|(x, count)| format!("'{}' ({})", x, count))
+            .collect();
+        println!(
+            "Album mode: {} disagrees across tracks: {}",
+            frame_id,
+            summary.join(", ")
+        );
+    }
+
+    let top = counts.iter().map(|(_, count)| *count).max()?;
+    let tied: Vec<&String> = counts
+        .iter()
+        .filter(|(_, count)| *count == top)
+        .map(|(x, _)| *x)
+        .collect();
+
+    let value = if tied.len() > 1 && interactive && io::stdin().is_terminal() {
+        prompt_choice(frame_id, &tied).unwrap_or(tied[0].clone())
+    } else {
+        tied[0].clone()
+    };
+
+    if counts.len() > 1 {
+        println!("Album mode: using '{}' for {}", value, frame_id);
+    }
+
+    Some(value)
+}
+
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|x| x.is_ascii_digit())
+        .collect();
+
+    digits.parse().ok()
+}
+
+/// Cross-checks TALB, TPE2 and TDRC across the given tracks and numbers them consistently,
+/// ordering by their existing track numbers and then by path. Values already present in
+/// `given` (e.g. passed on the command line) are kept as-is instead of being cross-checked.
+/// Ties are only prompted for when `interactive`, otherwise the first value seen wins.
+pub fn resolve_album(files: &[String], given: AlbumValues, interactive: bool) -> AlbumValues {
+    let mut albums = vec![];
+    let mut album_artists = vec![];
+    let mut recording_times = vec![];
+    let mut tracks: Vec<(Option<u32>, &String)> = vec![];
+
+    for file in files {
+        let tag = match storage::read(file).and_then(|x| parse::parse_bytes(&x)) {
+            Ok((x, _)) => x,
+            Err(x) => {
+                eprintln!("{} | {}, excluded from album", file, x);
+                continue;
+            }
+        };
+
+        albums.push(tag.get_album_title());
+        album_artists.push(tag.get_album_artist_name());
+        recording_times.push(tag.get_recording_time());
+        tracks.push((
+            tag.get_track_number().and_then(|x| leading_number(&x)),
+            file,
+        ));
+    }
+
+    // Tracks without a number sort after numbered ones
+    tracks.sort_by_key(|(number, path)| (number.unwrap_or(u32::MAX), *path));
+
+    let total = tracks.len();
+    let track_numbers = tracks
+        .iter()
+        .enumerate()
+        .map(|(idx, (_, path))| {
+            let track = TrackNumber {
+                number: u32::try_from(idx + 1).unwrap(),
+                total: Some(u32::try_from(total).unwrap()),
+            };
+            ((*path).clone(), track)
+        })
+        .collect();
+
+    AlbumValues {
+        album: given
+            .album
+            .or_else(|| majority_value("TALB", &albums, interactive)),
+        album_artist: given
+            .album_artist
+            .or_else(|| majority_value("TPE2", &album_artists, interactive)),
+        recording_time: given
+            .recording_time
+            .or_else(|| majority_value("TDRC", &recording_times, interactive)),
+        track_numbers,
+    }
+}
+
+/// Loudness of each track scanned for `--scan-loudness album`, and the ReplayGain values of each
+/// folder's tracks together
+#[derive(Debug, Default)]
+pub struct AlbumLoudness {
+    // file location -> loudness
+    pub tracks: HashMap<String, Loudness>,
+    // folder -> album gain and peak
+    pub albums: HashMap<String, ReplayGain>,
+}
+
+/// Scans the loudness of every track, taking the tracks in each folder as one album. Tracks that
+/// can't be scanned are left out of their album, and fail when tagged.
+pub fn scan_album_loudness(files: &[String]) -> AlbumLoudness {
+    let mut result = AlbumLoudness::default();
+    let mut folders: HashMap<&str, Vec<&Loudness>> = HashMap::new();
+
+    for file in files {
+        let loudness = storage::read(file)
+            .and_then(|x| extract::extract_tag(&x).map(|(_, audio)| audio))
+            .and_then(|x| analysis::scan_loudness(&x));
+
+        match loudness {
+            Ok(x) => {
+                result.tracks.insert(file.clone(), x);
+            }
+            Err(x) => eprintln!("{} | {}, excluded from album gain", file, x),
+        }
+    }
+
+    for (file, loudness) in &result.tracks {
+        folders
+            .entry(storage::parent(file))
+            .or_default()
+            .push(loudness);
+    }
+
+    result.albums = folders
+        .into_iter()
+        .filter_map(|(folder, tracks)| {
+            let tracks: Vec<Loudness> = tracks.into_iter().cloned().collect();
+            let gain = ReplayGain::from_loudness(&Loudness::combine(&tracks))?;
+            Some((folder.to_string(), gain))
+        })
+        .collect();
+
+    result
+}
+
+/// Folders holding a compilation: tracks by at least two artists (TPE1, by their first artist so
+/// "A feat. B" counts as A), none of them on more than half the tracks, so an album with a few
+/// guest tracks isn't one
+pub fn find_compilations(files: &[String]) -> HashSet<String> {
+    let mut artists: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for file in files {
+        let tag = match storage::read(file).and_then(|x| parse::parse_bytes(&x)) {
+            Ok((x, _)) => x,
+            Err(x) => {
+                eprintln!("{} | {}, excluded from compilation detection", file, x);
+                continue;
+            }
+        };
+
+        artists.entry(storage::parent(file)).or_default().push(
+            tag.get_song_artist_name()
+                .map(|x| transform::first_artist(&x).to_lowercase())
+                .unwrap_or_default(),
+        );
+    }
+
+    artists
+        .into_iter()
+        .filter(|(_, artists)| {
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for x in artists.iter().filter(|x| !x.is_empty()) {
+                *counts.entry(x).or_default() += 1;
+            }
+
+            counts.len() > 1 && counts.values().all(|x| x * 2 <= artists.len())
+        })
+        .map(|(folder, _)| folder.to_string())
+        .collect()
+}
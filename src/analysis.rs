@@ -0,0 +1,555 @@
+#[cfg(feature = "analysis")]
+use std::io::{Cursor, ErrorKind};
+
+#[cfg(feature = "analysis")]
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+// Samples per block the onset envelope is computed over, and between the start of blocks
+#[cfg(feature = "analysis")]
+const BLOCK_SIZE: usize = 1024;
+#[cfg(feature = "analysis")]
+const HOP_SIZE: usize = 512;
+
+// Tempos considered, and the one they are biased toward when several fit, as a beat can be heard
+// at half or double speed
+#[cfg(feature = "analysis")]
+const MIN_BPM: f64 = 60.0;
+#[cfg(feature = "analysis")]
+const MAX_BPM: f64 = 200.0;
+#[cfg(feature = "analysis")]
+const PREFERRED_BPM: f64 = 120.0;
+
+/// Decoded audio, as samples from -1.0 to 1.0
+#[cfg(feature = "analysis")]
+pub struct Decoded {
+    /// One sample of each channel in turn
+    pub samples: Vec<f32>,
+    pub channels: usize,
+    pub sample_rate: u32,
+}
+
+#[cfg(feature = "analysis")]
+impl Decoded {
+    /// The average of every channel
+    pub fn mono(&self) -> Vec<f32> {
+        self.samples
+            .chunks(self.channels)
+            .map(|x| x.iter().sum::<f32>() / x.len() as f32)
+            .collect()
+    }
+}
+
+/// Decodes MPEG audio (the audio after a file's tag), skipping packets that fail to decode
+#[cfg(feature = "analysis")]
+pub fn decode(audio: &[u8]) -> Result<Decoded, String> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(audio.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension("mp3");
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|x| format!("unable to read the audio: {}", x))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|x| x.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("no audio track to decode")?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or("unknown sample rate")?;
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|x| format!("unable to decode the audio: {}", x))?;
+
+    let mut samples = vec![];
+    let mut channels = 1;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(x) => x,
+            Err(Error::IoError(x)) if x.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(x) => return Err(format!("unable to read the audio: {}", x)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(x) => x,
+            Err(Error::DecodeError(_)) => continue,
+            Err(x) => return Err(format!("unable to decode the audio: {}", x)),
+        };
+
+        let spec = *decoded.spec();
+        channels = spec.channels.count().max(1);
+
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+
+    Ok(Decoded {
+        samples,
+        channels,
+        sample_rate,
+    })
+}
+
+// How much louder each block is than the one before, which peaks on beats
+#[cfg(feature = "analysis")]
+fn onset_envelope(samples: &[f32]) -> Vec<f64> {
+    let energies: Vec<f64> = samples
+        .windows(BLOCK_SIZE)
+        .step_by(HOP_SIZE)
+        .map(|x| (x.iter().map(|x| f64::from(*x).powi(2)).sum::<f64>() + 1e-10).ln())
+        .collect();
+
+    let onsets: Vec<f64> = energies
+        .windows(2)
+        .map(|x| (x[1] - x[0]).max(0.0))
+        .collect();
+
+    let mean = onsets.iter().sum::<f64>() / onsets.len().max(1) as f64;
+    onsets.iter().map(|x| x - mean).collect()
+}
+
+/// Estimates the tempo of mono samples from the period at which their onsets repeat most, or
+/// None for audio without a beat to go by (e.g. silence)
+#[cfg(feature = "analysis")]
+pub fn estimate_bpm(samples: &[f32], sample_rate: u32) -> Option<f64> {
+    let envelope = onset_envelope(samples);
+    let blocks_per_second = f64::from(sample_rate) / HOP_SIZE as f64;
+
+    let min_lag = (blocks_per_second * 60.0 / MAX_BPM).floor() as usize;
+    let max_lag = (blocks_per_second * 60.0 / MIN_BPM).ceil() as usize;
+    if min_lag == 0 || envelope.len() <= max_lag + 1 {
+        return None;
+    }
+
+    let correlation = |lag: usize| -> f64 {
+        envelope
+            .iter()
+            .zip(&envelope[lag..])
+            .map(|(x, y)| x * y)
+            .sum()
+    };
+    let correlations: Vec<f64> = (min_lag..=max_lag + 1).map(correlation).collect();
+
+    // Weighted toward PREFERRED_BPM by how many octaves a tempo is from it
+    let weight = |lag: usize| {
+        let bpm = blocks_per_second * 60.0 / lag as f64;
+        (-0.5 * (bpm / PREFERRED_BPM).log2().powi(2)).exp()
+    };
+    let (best, _) = (min_lag..=max_lag)
+        .map(|lag| (lag, correlations[lag - min_lag] * weight(lag)))
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))?;
+    if correlations[best - min_lag] <= 0.0 {
+        return None;
+    }
+
+    // The peak between blocks, from a parabola through the correlations around it
+    let lag = if best > min_lag {
+        let (left, middle, right) = (
+            correlations[best - min_lag - 1],
+            correlations[best - min_lag],
+            correlations[best - min_lag + 1],
+        );
+        let curvature = left - 2.0 * middle + right;
+        if curvature < 0.0 {
+            best as f64 + 0.5 * (left - right) / curvature
+        } else {
+            best as f64
+        }
+    } else {
+        best as f64
+    };
+
+    Some(blocks_per_second * 60.0 / lag)
+}
+
+// K-weighting (ITU-R BS.1770-4): a high shelf modelling the head, then a high pass, given as
+// the analog parameters the filters for any sample rate are derived from
+#[cfg(feature = "analysis")]
+const SHELF_FREQUENCY: f64 = 1681.974450955533;
+#[cfg(feature = "analysis")]
+const SHELF_GAIN_DB: f64 = 3.999843853973347;
+#[cfg(feature = "analysis")]
+const SHELF_Q: f64 = 0.7071752369554196;
+#[cfg(feature = "analysis")]
+const HIGH_PASS_FREQUENCY: f64 = 38.13547087602444;
+#[cfg(feature = "analysis")]
+const HIGH_PASS_Q: f64 = 0.5003270373238773;
+
+// Loudness is measured over 400ms blocks starting every 100ms, built from 100ms segments
+#[cfg(feature = "analysis")]
+const SEGMENTS_PER_BLOCK: usize = 4;
+
+// Blocks quieter than the absolute gate, or than the relative gate below the loudness of the
+// blocks above the absolute gate, are left out (EBU R128)
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+// True peaks are found between samples by upsampling 4 times with a windowed sinc filter
+#[cfg(feature = "analysis")]
+const OVERSAMPLING: usize = 4;
+#[cfg(feature = "analysis")]
+const INTERPOLATION_TAPS: usize = 12;
+
+/// Loudness every ReplayGain 2.0 gain brings audio to, in LUFS
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+/// Loudness of audio measured per EBU R128, kept as its blocks so the loudness of several
+/// tracks together (an album) can be measured without decoding them again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Loudness {
+    // Mean square of each block, K-weighted and summed over the channels
+    blocks: Vec<f64>,
+    /// Highest true peak of any channel, 1.0 being full scale
+    pub true_peak: f64,
+}
+
+// Loudness in LUFS of a block's K-weighted mean square
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+impl Loudness {
+    /// Integrated loudness in LUFS, or None when every block is below the absolute gate
+    /// (e.g. silence)
+    pub fn integrated(&self) -> Option<f64> {
+        let mean = |threshold: f64| {
+            let gated: Vec<f64> = self
+                .blocks
+                .iter()
+                .copied()
+                .filter(|x| block_loudness(*x) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        let relative_gate = block_loudness(mean(ABSOLUTE_GATE_LUFS)?) + RELATIVE_GATE_LU;
+
+        mean(relative_gate.max(ABSOLUTE_GATE_LUFS)).map(block_loudness)
+    }
+
+    /// Loudness of tracks played one after the other, as measured for album gain
+    pub fn combine(tracks: &[Loudness]) -> Loudness {
+        Loudness {
+            blocks: tracks
+                .iter()
+                .flat_map(|x| x.blocks.iter().copied())
+                .collect(),
+            true_peak: tracks.iter().map(|x| x.true_peak).fold(0.0, f64::max),
+        }
+    }
+}
+
+/// ReplayGain 2.0 values of a track or album, stored in REPLAYGAIN_* TXXX frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayGain {
+    /// Decibels to bring the audio to `REFERENCE_LUFS` by
+    pub gain: f64,
+    /// Highest true peak, 1.0 being full scale
+    pub peak: f64,
+}
+
+impl ReplayGain {
+    /// Values bringing audio of this loudness to `REFERENCE_LUFS`, or None for silence
+    pub fn from_loudness(loudness: &Loudness) -> Option<ReplayGain> {
+        Some(ReplayGain {
+            gain: REFERENCE_LUFS - loudness.integrated()?,
+            peak: loudness.true_peak,
+        })
+    }
+
+    /// Gain as players read it, e.g. "-6.48 dB"
+    pub fn gain_text(&self) -> String {
+        format!("{:+.2} dB", self.gain)
+    }
+
+    /// Peak as players read it, e.g. "0.988553"
+    pub fn peak_text(&self) -> String {
+        format!("{:.6}", self.peak)
+    }
+}
+
+// A second order IIR filter, applied to one channel at a time
+#[cfg(feature = "analysis")]
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+#[cfg(feature = "analysis")]
+impl Biquad {
+    fn new(b: [f64; 3], a0: f64, a: [f64; 2]) -> Biquad {
+        Biquad {
+            b: b.map(|x| x / a0),
+            a: a.map(|x| x / a0),
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    // The stages of K-weighting for a sample rate, by bilinear transform
+    fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+        let rate = f64::from(sample_rate);
+
+        let k = (std::f64::consts::PI * SHELF_FREQUENCY / rate).tan();
+        let vh = 10f64.powf(SHELF_GAIN_DB / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let shelf = Biquad::new(
+            [
+                vh + vb * k / SHELF_Q + k * k,
+                2.0 * (k * k - vh),
+                vh - vb * k / SHELF_Q + k * k,
+            ],
+            1.0 + k / SHELF_Q + k * k,
+            [2.0 * (k * k - 1.0), 1.0 - k / SHELF_Q + k * k],
+        );
+
+        let k = (std::f64::consts::PI * HIGH_PASS_FREQUENCY / rate).tan();
+        let a0 = 1.0 + k / HIGH_PASS_Q + k * k;
+        let high_pass = Biquad::new(
+            [a0, -2.0 * a0, a0],
+            a0,
+            [2.0 * (k * k - 1.0), 1.0 - k / HIGH_PASS_Q + k * k],
+        );
+
+        [shelf, high_pass]
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+// Highest absolute value of a channel's samples and of the values between them
+#[cfg(feature = "analysis")]
+fn true_peak(channel: &[f32]) -> f64 {
+    let half = INTERPOLATION_TAPS as isize / 2;
+
+    // Coefficients of each position between two samples, Hann windowed
+    let phases: Vec<Vec<f64>> = (1..OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f64 / OVERSAMPLING as f64;
+            (-half + 1..=half)
+                .map(|tap| {
+                    let t = tap as f64 - offset;
+                    let sinc = (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t);
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * t / (half as f64 + 1.0)).cos();
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peak = channel
+        .iter()
+        .fold(0.0, |x: f64, y| x.max(f64::from(y.abs())));
+
+    for idx in 0..channel.len() {
+        for taps in &phases {
+            let value: f64 = taps
+                .iter()
+                .enumerate()
+                .map(|(tap, x)| {
+                    let sample = idx as isize + tap as isize - half + 1;
+                    usize::try_from(sample)
+                        .ok()
+                        .and_then(|x| channel.get(x))
+                        .map_or(0.0, |y| x * f64::from(*y))
+                })
+                .sum();
+            peak = peak.max(value.abs());
+        }
+    }
+
+    peak
+}
+
+/// Measures decoded audio per EBU R128. Every channel is weighted alike, which MPEG audio, at
+/// most stereo, calls for.
+#[cfg(feature = "analysis")]
+pub fn measure_loudness(decoded: &Decoded) -> Loudness {
+    let channels = decoded.channels.max(1);
+    let segment_size = (decoded.sample_rate / 10).max(1) as usize;
+
+    // K-weighted sum of squares of each 100ms segment, over all channels
+    let mut segments = vec![0.0; decoded.samples.len() / channels / segment_size];
+    let mut true_peak_value: f64 = 0.0;
+
+    for channel in 0..channels {
+        let samples: Vec<f32> = decoded
+            .samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect();
+        let mut filters = Biquad::k_weighting(decoded.sample_rate);
+
+        for (segment, x) in segments.iter_mut().zip(samples.chunks_exact(segment_size)) {
+            *segment += x
+                .iter()
+                .map(|x| {
+                    let y = filters
+                        .iter_mut()
+                        .fold(f64::from(*x), |x, filter| filter.process(x));
+                    y * y
+                })
+                .sum::<f64>();
+        }
+
+        true_peak_value = true_peak_value.max(true_peak(&samples));
+    }
+
+    let block_size = (segment_size * SEGMENTS_PER_BLOCK) as f64;
+
+    Loudness {
+        blocks: segments
+            .windows(SEGMENTS_PER_BLOCK)
+            .map(|x| x.iter().sum::<f64>() / block_size)
+            .collect(),
+        true_peak: true_peak_value,
+    }
+}
+
+/// Loudness of MPEG audio per EBU R128, for ReplayGain values
+pub fn scan_loudness(audio: &[u8]) -> Result<Loudness, String> {
+    #[cfg(feature = "analysis")]
+    {
+        Ok(measure_loudness(&decode(audio)?))
+    }
+
+    #[cfg(not(feature = "analysis"))]
+    Err(format!(
+        "unable to scan the loudness of {} bytes of audio (build with --features analysis to decode it)",
+        audio.len()
+    ))
+}
+
+/// Tempo of MPEG audio in beats per minute, detected from where its loudness rises
+pub fn detect_bpm(audio: &[u8]) -> Result<f64, String> {
+    #[cfg(feature = "analysis")]
+    {
+        let decoded = decode(audio)?;
+
+        estimate_bpm(&decoded.mono(), decoded.sample_rate)
+            .ok_or_else(|| "no beat found in the audio".to_string())
+    }
+
+    #[cfg(not(feature = "analysis"))]
+    Err(format!(
+        "unable to detect the BPM of {} bytes of audio (build with --features analysis to decode it)",
+        audio.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mean square of a block measuring `lufs`
+    fn power(lufs: f64) -> f64 {
+        10f64.powf((lufs + 0.691) / 10.0)
+    }
+
+    fn loudness(blocks: &[f64], true_peak: f64) -> Loudness {
+        Loudness {
+            blocks: blocks.iter().map(|x| power(*x)).collect(),
+            true_peak,
+        }
+    }
+
+    fn assert_lufs(loudness: &Loudness, lufs: f64) {
+        let integrated = loudness.integrated().unwrap();
+        assert!(
+            (integrated - lufs).abs() < 1e-9,
+            "{} != {}",
+            integrated,
+            lufs
+        );
+    }
+
+    #[test]
+    fn gates_quiet_blocks() {
+        assert_lufs(&loudness(&[-20.0; 4], 0.5), -20.0);
+
+        // Below the absolute gate
+        assert_lufs(&loudness(&[-20.0, -20.0, -75.0], 0.5), -20.0);
+        // Above the absolute gate, but more than 10 LU below the rest
+        assert_lufs(&loudness(&[-20.0, -20.0, -35.0], 0.5), -20.0);
+        // Within 10 LU, so averaged in as power
+        assert_lufs(
+            &loudness(&[-20.0, -25.0], 0.5),
+            block_loudness((power(-20.0) + power(-25.0)) / 2.0),
+        );
+
+        // Silence has no loudness
+        assert_eq!(loudness(&[-75.0, -90.0], 0.0).integrated(), None);
+        let silence = Loudness {
+            blocks: vec![0.0; 4],
+            true_peak: 0.0,
+        };
+        assert_eq!(silence.integrated(), None);
+        assert_eq!(Loudness::default().integrated(), None);
+    }
+
+    #[test]
+    fn combines_tracks_as_one() {
+        let loud = loudness(&[-20.0; 3], 0.9);
+        let quiet = loudness(&[-40.0], 0.1);
+        let album = Loudness::combine(&[loud.clone(), quiet.clone()]);
+
+        assert_eq!(album.blocks.len(), 4);
+        assert_eq!(album.true_peak, 0.9);
+        // The quiet track's block falls below the relative gate of the album
+        assert_lufs(&album, -20.0);
+        assert_lufs(&Loudness::combine(&[quiet, loud]), -20.0);
+
+        assert_eq!(Loudness::combine(&[]), Loudness::default());
+    }
+
+    #[test]
+    fn brings_gains_to_the_reference() {
+        let gain = ReplayGain::from_loudness(&loudness(&[-11.52; 2], 0.988553)).unwrap();
+
+        assert_eq!(gain.gain_text(), "-6.48 dB");
+        assert_eq!(gain.peak_text(), "0.988553");
+        assert_eq!(
+            ReplayGain::from_loudness(&loudness(&[-24.0], 0.1))
+                .unwrap()
+                .gain_text(),
+            "+6.00 dB"
+        );
+        assert_eq!(ReplayGain::from_loudness(&Loudness::default()), None);
+    }
+}
@@ -0,0 +1,670 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use alloy::{cache::TagCache, image_meta, parse, sanitize, storage, tag, utility};
+
+use crate::interrupt;
+
+// Checked in order, first match in an album directory wins
+const FOLDER_ART_NAMES: [&str; 4] = ["cover.jpg", "cover.png", "folder.jpg", "folder.png"];
+
+/// Embed cover art across a library without modifying any other frames
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct ArtArgs {
+    #[command(subcommand)]
+    command: Option<ArtCommand>,
+
+    /// Folder containing album directories (searched recursively), files are modified in place
+    #[arg(long)]
+    folder_input: Option<String>,
+
+    /// Embed each album directory's cover.jpg/folder.png into every track missing cover art
+    #[arg(long)]
+    from_folder_art: bool,
+
+    /// Description of cover art image
+    #[arg(short, long, default_value = "")]
+    description: String,
+
+    /// Record the tagging time (TDTG) and the alloy version (TSSE/TENC) in every file written
+    #[arg(long)]
+    stamp: bool,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ArtCommand {
+    /// Write the embedded cover art of a library to a folder, one image per distinct picture
+    Export(ExportArgs),
+    /// Compare the embedded front cover of every file in a folder against a reference image
+    Verify(VerifyArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ExportArgs {
+    /// Folder containing album directories (searched recursively)
+    #[arg(long)]
+    folder_input: String,
+
+    /// Folder to write the exported images to
+    #[arg(long)]
+    out: String,
+
+    /// Treat pictures that look the same as one and export its highest-resolution copy, e.g.
+    /// when some tracks carry a re-encoded or downscaled cover (needs --features convert-art)
+    #[arg(long)]
+    perceptual: bool,
+
+    /// Bits out of 64 the perceptual hashes may differ by and still be the same picture
+    #[arg(long, default_value_t = 5, requires = "perceptual")]
+    threshold: u32,
+
+    /// Only export from files whose tags changed since the last export to the same folder
+    #[arg(long)]
+    changed_since_last_run: bool,
+
+    /// Index file keeping the tag of each file at the last run, for --changed-since-last-run
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = ".alloy-index.json",
+        requires = "changed_since_last_run"
+    )]
+    index: String,
+}
+
+pub fn read_cover_art(path: &str, description: &str) -> Result<tag::Picture, String> {
+    let data = match fs::read(path) {
+        Ok(x) => x,
+        Err(x) => return Err(format!("unable to read cover art {}: {}", path, x)),
+    };
+
+    // The format is taken from the contents, so a mislabelled file isn't tagged with the wrong MIME type
+    let (mime, data) = match image_meta::detect_format(&data) {
+        Some("jpeg") => ("image/jpeg", data),
+        Some("png") => ("image/png", data),
+        #[cfg(feature = "convert-art")]
+        Some(x) if x != "heic" => {
+            println!("Converting {} cover art {} to JPEG", x, path);
+            ("image/jpeg", image_meta::convert_to_jpeg(&data)?)
+        }
+        Some(x) => {
+            return Err(format!(
+                "cover art {} is {}, but only JPEG and PNG are supported{}",
+                path,
+                x.to_uppercase(),
+                if cfg!(feature = "convert-art") || x == "heic" {
+                    ""
+                } else {
+                    " (build with --features convert-art to convert it to JPEG)"
+                }
+            ))
+        }
+        None => return Err(format!("cover art {} is not a JPEG or PNG image", path)),
+    };
+
+    Ok(tag::Picture::new(
+        mime.to_string(),
+        0x03,
+        description.to_string(),
+        data,
+    ))
+}
+
+/// Re-encodes cover art larger than `max_bytes` as a JPEG of lower quality until it fits
+pub fn fit_cover_art(picture: tag::Picture, max_bytes: usize) -> Result<tag::Picture, String> {
+    if picture.data().len() <= max_bytes {
+        return Ok(picture);
+    }
+
+    #[cfg(feature = "convert-art")]
+    {
+        let (quality, data) = image_meta::fit_jpeg(picture.data(), max_bytes)?;
+        println!(
+            "Re-encoded cover art at JPEG quality {}: {} bytes (was {})",
+            quality,
+            data.len(),
+            picture.data().len()
+        );
+
+        Ok(tag::Picture::new(
+            "image/jpeg".to_string(),
+            picture.picture_type(),
+            picture.description().to_string(),
+            data,
+        ))
+    }
+
+    #[cfg(not(feature = "convert-art"))]
+    Err(format!(
+        "cover art is {} bytes, over the budget of {} (build with --features convert-art to re-encode it)",
+        picture.data().len(),
+        max_bytes
+    ))
+}
+
+/// Makes a file icon picture from cover art by downscaling it to `size` x `size` pixels. The
+/// spec only allows 32x32 PNGs for the file icon type (0x01), other sizes use "Other file icon"
+/// (0x02).
+pub fn make_file_icon(picture: &tag::Picture, size: u32) -> Result<tag::Picture, String> {
+    #[cfg(feature = "convert-art")]
+    {
+        let picture_type = if size == 32 { 0x01 } else { 0x02 };
+
+        Ok(tag::Picture::new(
+            "image/png".to_string(),
+            picture_type,
+            String::new(),
+            image_meta::square_png(picture.data(), size)?,
+        ))
+    }
+
+    #[cfg(not(feature = "convert-art"))]
+    Err(format!(
+        "unable to make a {}x{} file icon from {} bytes of cover art (build with --features convert-art to generate icons)",
+        size,
+        size,
+        picture.data().len()
+    ))
+}
+
+fn find_folder_art(dir: &Path) -> Option<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|x| x.is_file())
+        .collect();
+
+    FOLDER_ART_NAMES.iter().find_map(|name| {
+        entries
+            .iter()
+            .find(|x| {
+                x.file_name()
+                    .and_then(OsStr::to_str)
+                    .is_some_and(|x| x.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+    })
+}
+
+pub fn is_mp3(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|x| x.eq_ignore_ascii_case("mp3"))
+}
+
+pub fn collect_mp3_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(x) => x.filter_map(|entry| entry.ok().map(|x| x.path())).collect(),
+        Err(x) => {
+            eprintln!("Unable to read directory {}: {}", dir.display(), x);
+            return;
+        }
+    };
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_mp3_files(&entry, files);
+        } else if is_mp3(&entry) {
+            files.push(entry);
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Image the embedded front covers should match
+    #[arg(long)]
+    reference: String,
+
+    /// Folder to check (searched recursively)
+    #[arg(long)]
+    folder: String,
+
+    /// Compare what the images look like rather than their bytes, so re-encoded or resized
+    /// copies of the reference still match (needs --features convert-art)
+    #[arg(long)]
+    perceptual: bool,
+
+    /// Bits out of 64 the perceptual hashes may differ by and still match
+    #[arg(long, default_value_t = 5, requires = "perceptual")]
+    threshold: u32,
+}
+
+fn image_extension(mime: &str) -> &str {
+    match mime {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/png" => "png",
+        _ => "bin",
+    }
+}
+
+// Returns whether art was embedded, or false if the file already had art
+fn embed_missing_art(
+    file: &Path,
+    cover_art_path: &Path,
+    description: &str,
+    stamp: bool,
+) -> Result<bool, String> {
+    let location = file.to_string_lossy();
+    let _locks = storage::lock(&[&location])?;
+    let (mut tag, audio_data) = parse::parse_file(file)?;
+
+    if tag.get_cover_art().is_some() {
+        return Ok(false);
+    }
+
+    let picture = read_cover_art(&cover_art_path.to_string_lossy(), description)?;
+    tag.set_cover_art(picture)?;
+
+    if stamp {
+        tag.stamp()?;
+    }
+
+    storage::write_verified(&location, &tag, &audio_data)?;
+
+    Ok(true)
+}
+
+fn process_album_directory(dir: &Path, description: &str, stamp: bool, tagged: &mut u32) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(x) => x.filter_map(|entry| entry.ok().map(|x| x.path())).collect(),
+        Err(x) => {
+            eprintln!("Unable to read directory {}: {}", dir.display(), x);
+            return;
+        }
+    };
+    entries.sort();
+
+    let tracks: Vec<&PathBuf> = entries
+        .iter()
+        .filter(|x| x.is_file() && is_mp3(x))
+        .collect();
+
+    if !tracks.is_empty() {
+        match find_folder_art(dir) {
+            Some(cover_art_path) => {
+                println!(
+                    "Processing album: {} (art: {})",
+                    dir.display(),
+                    cover_art_path.display()
+                );
+
+                for track in tracks {
+                    if interrupt::requested() {
+                        return;
+                    }
+
+                    match embed_missing_art(track, &cover_art_path, description, stamp) {
+                        Ok(true) => {
+                            *tagged += 1;
+                            println!("{} | Cover art embedded", track.display());
+                        }
+                        Ok(false) => {
+                            println!("{} | Already has cover art, skipping", track.display())
+                        }
+                        Err(x) => eprintln!("{} | {}", track.display(), x),
+                    }
+                }
+            }
+            None => println!("No folder art found in {}, skipping", dir.display()),
+        }
+    }
+
+    for subdir in entries.iter().filter(|x| x.is_dir()) {
+        if interrupt::requested() {
+            return;
+        }
+
+        process_album_directory(subdir, description, stamp, tagged);
+    }
+}
+
+// Pictures considered the same when exporting, exported once from the file with the largest copy
+struct ExportGroup {
+    hash: u64,
+    name: String,
+    pixels: u64,
+    source: PathBuf,
+}
+
+// Resolution of a picture for picking the best copy, by byte size for formats without a header
+// image_meta can read
+fn picture_pixels(data: &[u8]) -> u64 {
+    match image_meta::image_info(data) {
+        Some(x) => u64::from(x.width) * u64::from(x.height),
+        None => data.len() as u64,
+    }
+}
+
+fn picture_hash(data: &[u8], perceptual: bool) -> Result<u64, String> {
+    if !perceptual {
+        return Ok(utility::hash_bytes(data));
+    }
+
+    #[cfg(feature = "convert-art")]
+    {
+        image_meta::perceptual_hash(data)
+    }
+
+    #[cfg(not(feature = "convert-art"))]
+    Err(
+        "perceptual comparison needs image decoding (build with --features convert-art)"
+            .to_string(),
+    )
+}
+
+fn export_art(args: &ExportArgs) {
+    let now = Instant::now();
+
+    if args.perceptual && !cfg!(feature = "convert-art") {
+        eprintln!("Perceptual comparison needs image decoding (build with --features convert-art)");
+        return;
+    }
+
+    if let Err(x) = fs::create_dir_all(&args.out) {
+        eprintln!("Unable to create output folder {}: {}", args.out, x);
+        return;
+    }
+
+    let mut files = vec![];
+    collect_mp3_files(Path::new(&args.folder_input), &mut files);
+
+    let run = format!(
+        "art export {}",
+        fs::canonicalize(&args.out)
+            .unwrap_or(PathBuf::from(&args.out))
+            .display()
+    );
+    let cache = if args.changed_since_last_run {
+        match TagCache::load(Path::new(&args.index)) {
+            Ok(x) => Some(x),
+            Err(x) => {
+                eprintln!("{}", x);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    // Absolute paths, as the index keeps them
+    if let Some(cache) = &cache {
+        let before = files.len();
+        files = files
+            .into_iter()
+            .map(|x| fs::canonicalize(&x).unwrap_or(x))
+            .filter(|x| cache.changed_since_run(&run, x))
+            .collect();
+        println!(
+            "Skipping {} file(s) unchanged since the last export",
+            before - files.len()
+        );
+    }
+    let processed = files.clone();
+
+    // Exact copies match with a distance of 0, so both modes share the grouping
+    let threshold = if args.perceptual { args.threshold } else { 0 };
+
+    let mut groups: Vec<ExportGroup> = vec![];
+    let mut name_counts: HashMap<String, u32> = HashMap::new();
+
+    println!("Exporting cover art from library: {}", args.folder_input);
+
+    for file in files {
+        let tag = match parse::read_tag(&file) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{} | {}", file.display(), x);
+                continue;
+            }
+        };
+
+        let Some(picture) = tag.get_cover_art() else {
+            continue;
+        };
+
+        let hash = match picture_hash(picture.data(), args.perceptual) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{} | {}", file.display(), x);
+                continue;
+            }
+        };
+        let pixels = picture_pixels(picture.data());
+
+        if let Some(group) = groups
+            .iter_mut()
+            .find(|x| image_meta::hash_distance(x.hash, hash) <= threshold)
+        {
+            if pixels > group.pixels {
+                group.pixels = pixels;
+                group.source = file;
+            }
+            continue;
+        }
+
+        let artist = tag
+            .get_album_artist_name()
+            .or(tag.get_song_artist_name())
+            .unwrap_or("Unknown Artist".to_string());
+        let album = tag.get_album_title().unwrap_or("Unknown Album".to_string());
+
+        // Albums with differing art across tracks get numbered images instead of overwriting
+        let name = sanitize::sanitize_file_name(
+            &format!("{} - {}", artist, album),
+            &sanitize::SanitizeOptions::default(),
+        );
+        let count = name_counts.entry(name.clone()).or_insert(0);
+        *count += 1;
+        let name = match count {
+            1 => name,
+            x => format!("{} ({})", name, x),
+        };
+
+        groups.push(ExportGroup {
+            hash,
+            name,
+            pixels,
+            source: file,
+        });
+    }
+
+    // Only the best copy of each picture is kept in memory, at the end it is read again
+    for group in &groups {
+        let tag = match parse::read_tag(&group.source) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{} | {}", group.source.display(), x);
+                continue;
+            }
+        };
+        let Some(picture) = tag.get_cover_art() else {
+            eprintln!(
+                "{} | Cover art was removed during export",
+                group.source.display()
+            );
+            continue;
+        };
+
+        let output = Path::new(&args.out).join(format!(
+            "{}.{}",
+            group.name,
+            image_extension(picture.mime())
+        ));
+
+        match storage::write(&output.to_string_lossy(), picture.data()) {
+            Ok(()) => println!(
+                "{} | Exported to {}",
+                group.source.display(),
+                output.display()
+            ),
+            Err(x) => eprintln!("{} | {}", group.source.display(), x),
+        }
+    }
+
+    if let Some(cache) = &cache {
+        for file in &processed {
+            // Files that can't be read were already reported, and are tried again next run
+            let _ = cache.record_run(&run, file);
+        }
+
+        if let Err(x) = cache.save(Path::new(&args.index)) {
+            eprintln!("{}", x);
+        }
+    }
+
+    println!("Exported {} distinct images.", groups.len());
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
+
+// How the embedded art is compared against the reference
+enum ArtMatcher {
+    Content(u64),
+    #[cfg(feature = "convert-art")]
+    Perceptual(u64, u32),
+}
+
+impl ArtMatcher {
+    fn new(reference: &[u8], args: &VerifyArgs) -> Result<ArtMatcher, String> {
+        if !args.perceptual {
+            return Ok(ArtMatcher::Content(utility::hash_bytes(reference)));
+        }
+
+        #[cfg(feature = "convert-art")]
+        {
+            Ok(ArtMatcher::Perceptual(
+                image_meta::perceptual_hash(reference)?,
+                args.threshold,
+            ))
+        }
+
+        #[cfg(not(feature = "convert-art"))]
+        Err(
+            "perceptual comparison needs image decoding (build with --features convert-art)"
+                .to_string(),
+        )
+    }
+
+    // Returns a description of the difference, or None if the art matches
+    fn compare(&self, data: &[u8]) -> Result<Option<String>, String> {
+        match self {
+            ArtMatcher::Content(hash) => Ok((utility::hash_bytes(data) != *hash)
+                .then(|| format!("{} bytes, different content", data.len()))),
+            #[cfg(feature = "convert-art")]
+            ArtMatcher::Perceptual(hash, threshold) => {
+                let distance = image_meta::hash_distance(image_meta::perceptual_hash(data)?, *hash);
+                Ok((distance > *threshold)
+                    .then(|| format!("looks different ({} of 64 bits differ)", distance)))
+            }
+        }
+    }
+}
+
+fn verify_art(args: &VerifyArgs) {
+    let now = Instant::now();
+
+    let reference = match fs::read(&args.reference) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("Unable to read reference image {}: {}", args.reference, x);
+            return;
+        }
+    };
+
+    let matcher = match ArtMatcher::new(&reference, args) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let mut files = vec![];
+    collect_mp3_files(Path::new(&args.folder), &mut files);
+
+    let mut matching = 0;
+    let mut mismatches = vec![];
+
+    println!(
+        "Verifying cover art against {}: {}",
+        args.reference, args.folder
+    );
+
+    for file in &files {
+        if interrupt::requested() {
+            println!("Interrupted, the remaining files were not checked.");
+            break;
+        }
+
+        let problem = match parse::read_tag(file) {
+            Ok(tag) => match tag.get_front_cover() {
+                Some(picture) => matcher.compare(picture.data()),
+                None => Ok(Some("no cover art".to_string())),
+            },
+            Err(x) => Err(x),
+        };
+
+        match problem {
+            Ok(None) => matching += 1,
+            Ok(Some(x)) => {
+                println!("{} | Mismatch: {}", file.display(), x);
+                mismatches.push(file);
+            }
+            Err(x) => {
+                eprintln!("{} | {}", file.display(), x);
+                mismatches.push(file);
+            }
+        }
+    }
+
+    println!(
+        "{} of {} files match the reference, {} mismatched.",
+        matching,
+        files.len(),
+        mismatches.len()
+    );
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
+
+pub fn process_art(args: &ArtArgs) {
+    match &args.command {
+        Some(ArtCommand::Export(x)) => return export_art(x),
+        Some(ArtCommand::Verify(x)) => return verify_art(x),
+        None => {}
+    }
+
+    let Some(folder_path) = &args.folder_input else {
+        eprintln!("Must provide a folder to process: use --folder-input <PATH>");
+        return;
+    };
+
+    if !args.from_folder_art {
+        eprintln!("Must specify where to take art from: use --from-folder-art");
+        return;
+    }
+
+    let now = Instant::now();
+    let mut tagged = 0;
+
+    println!("Processing library: {}", folder_path);
+    process_album_directory(
+        Path::new(folder_path),
+        &args.description,
+        args.stamp,
+        &mut tagged,
+    );
+
+    if interrupt::requested() {
+        println!("Interrupted, the remaining files were not touched.");
+    }
+
+    println!("Cover art embedded into {} files.", tagged);
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
@@ -0,0 +1,175 @@
+use std::{fs, path::Path, time::Instant};
+
+use alloy::{
+    chapter::{self, Chapter},
+    mpeg, parse, registration, storage, utility,
+};
+
+/// Split an audiobook into chapters and set the frames audiobook players read
+#[derive(clap::Args, Debug)]
+pub struct AudiobookArgs {
+    /// Path to the MP3 file
+    #[arg(short, long)]
+    input_file: String,
+
+    /// Path to output tagged file
+    #[arg(short, long)]
+    output_file: String,
+
+    /// Chapter list file, one "[h:]mm:ss[.mmm] Title" line per chapter
+    #[arg(long)]
+    chapters: Option<String>,
+
+    /// Series the book belongs to (stored in TIT1)
+    #[arg(long)]
+    series: Option<String>,
+
+    /// Name of the narrator (stored in TCOM, as audiobook players expect)
+    #[arg(long)]
+    narrator: Option<String>,
+
+    /// Record the tagging time (TDTG) and the alloy version (TSSE/TENC) in the file written
+    #[arg(long)]
+    stamp: bool,
+
+    /// Write the file even if encrypted frames, or frames grouped with them, would be lost or altered
+    #[arg(long)]
+    force: bool,
+}
+
+// Each chapter ends where the next begins, the last one at the end of the audio
+fn read_chapter_list(text: &str, duration_ms: u32) -> Result<Vec<Chapter>, String> {
+    let mut starts: Vec<(u32, String)> = vec![];
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (time, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let start_ms = chapter::parse_time(time).map_err(|x| format!("line {}: {}", idx + 1, x))?;
+
+        if let Some((previous, _)) = starts.last() {
+            if start_ms <= *previous {
+                return Err(format!(
+                    "line {}: chapters must be listed in increasing order of start time",
+                    idx + 1
+                ));
+            }
+        }
+
+        if start_ms >= duration_ms {
+            return Err(format!(
+                "line {}: chapter starts at {} but the audio is only {} long",
+                idx + 1,
+                utility::format_milliseconds(start_ms),
+                utility::format_milliseconds(duration_ms)
+            ));
+        }
+
+        starts.push((start_ms, title.trim().to_string()));
+    }
+
+    let ends: Vec<u32> = starts
+        .iter()
+        .skip(1)
+        .map(|(x, _)| *x)
+        .chain([duration_ms])
+        .collect();
+
+    Ok(starts
+        .into_iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(idx, ((start_ms, title), end_ms))| Chapter {
+            element_id: format!("chp{}", idx),
+            start_ms,
+            end_ms,
+            title: if title.is_empty() { None } else { Some(title) },
+        })
+        .collect())
+}
+
+pub fn process_audiobook(args: &AudiobookArgs) {
+    let now = Instant::now();
+    println!("Processing file: {}", args.input_file);
+
+    let _locks = match storage::lock(&[&args.input_file, &args.output_file]) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let (mut tag, audio_data) = match parse::parse_file(Path::new(&args.input_file)) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    if let Some(path) = &args.chapters {
+        let text = match fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("Unable to read chapter list {}: {}", path, x);
+                return;
+            }
+        };
+
+        let duration_ms = u32::try_from(mpeg::duration_ms(&audio_data)).unwrap_or(u32::MAX);
+        println!(
+            "Audio length: {}",
+            utility::format_milliseconds(duration_ms)
+        );
+
+        let chapters = match read_chapter_list(&text, duration_ms) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{}: {}", path, x);
+                return;
+            }
+        };
+
+        for x in &chapters {
+            println!("{}", x.describe());
+        }
+
+        tag.set_chapters(&chapters);
+    }
+
+    if let Some(x) = &args.series {
+        tag.set_content_group(x.to_string() + "\0").unwrap();
+    }
+
+    if let Some(x) = &args.narrator {
+        tag.set_composer(x.to_string() + "\0").unwrap();
+    }
+
+    tag.set_content_type("Audiobook\0".to_string()).unwrap();
+
+    if args.stamp {
+        tag.stamp().unwrap();
+    }
+
+    if !args.force {
+        if let Err(x) = registration::verify_protected_frames(&protected_frames, &tag) {
+            eprintln!("{}", x);
+            return;
+        }
+    }
+
+    match storage::write_verified(&args.output_file, &tag, &audio_data) {
+        Ok(()) => println!(
+            "{:?} | File successfully tagged, saved to {}",
+            now.elapsed(),
+            args.output_file
+        ),
+        Err(x) => eprintln!("{}", x),
+    }
+}
@@ -0,0 +1,72 @@
+use std::{path::PathBuf, time::Instant};
+
+use alloy::{integrity, storage};
+
+use crate::art;
+
+/// Check the declared sizes in existing tags against their contents, without modifying anything
+#[derive(clap::Args, Debug)]
+pub struct AuditArgs {
+    /// MP3 files, or folders searched recursively for them
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// Write the path of every file with issues to this file, one per line
+    #[arg(long, value_name = "FILE")]
+    fix_list: Option<String>,
+}
+
+pub fn process_audit(args: &AuditArgs) {
+    let now = Instant::now();
+
+    let mut files = vec![];
+    for path in &args.paths {
+        let path = PathBuf::from(path);
+
+        if path.is_dir() {
+            art::collect_mp3_files(&path, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut flagged = vec![];
+
+    for file in &files {
+        let location = file.display().to_string();
+
+        let issues = match storage::read(&location).and_then(|x| integrity::audit_sizes(&x)) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{} | {}", location, x);
+                continue;
+            }
+        };
+
+        if issues.is_empty() {
+            continue;
+        }
+
+        println!("{} | {} issue(s)", location, issues.len());
+        for x in &issues {
+            println!("    {}", x.describe());
+        }
+
+        flagged.push(location);
+    }
+
+    if let Some(x) = &args.fix_list {
+        let list: String = flagged.iter().map(|x| x.clone() + "\n").collect();
+
+        if let Err(e) = storage::write(x, list.as_bytes()) {
+            eprintln!("{}", e);
+        }
+    }
+
+    println!(
+        "{} of {} files have size issues.",
+        flagged.len(),
+        files.len()
+    );
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
@@ -0,0 +1,768 @@
+//! A window for fixing the tags of MP3 files without the command line: open files or a folder
+//! (or drop them on the window), edit the fields of a file or apply them to every selected file,
+//! drop an image to make it the cover art, and save.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use alloy::{dj, image_meta, parse, registration, storage, tag};
+use eframe::egui;
+
+type Getter = fn(&tag::Id3v2Tag) -> Option<String>;
+type Setter = fn(&mut tag::Id3v2Tag, String) -> Result<(), String>;
+
+// Fields shown in the form, with the frame each is stored in
+const FIELDS: [(&str, [u8; 4], Getter, Setter); 7] = [
+    (
+        "Title",
+        *b"TIT2",
+        tag::Id3v2Tag::get_song_title,
+        tag::Id3v2Tag::set_song_title,
+    ),
+    (
+        "Artist",
+        *b"TPE1",
+        tag::Id3v2Tag::get_song_artist_name,
+        tag::Id3v2Tag::set_song_artist_name,
+    ),
+    (
+        "Album",
+        *b"TALB",
+        tag::Id3v2Tag::get_album_title,
+        tag::Id3v2Tag::set_album_title,
+    ),
+    (
+        "Album artist",
+        *b"TPE2",
+        tag::Id3v2Tag::get_album_artist_name,
+        tag::Id3v2Tag::set_album_artist_name,
+    ),
+    (
+        "Year",
+        *b"TDRC",
+        tag::Id3v2Tag::get_recording_time,
+        tag::Id3v2Tag::set_recording_time,
+    ),
+    (
+        "Track",
+        *b"TRCK",
+        tag::Id3v2Tag::get_track_number,
+        tag::Id3v2Tag::set_track_number,
+    ),
+    (
+        "Genre",
+        *b"TCON",
+        tag::Id3v2Tag::get_content_type,
+        tag::Id3v2Tag::set_content_type,
+    ),
+];
+
+// Fields every track of an album shares, edited once in the album view
+const ALBUM_FIELDS: [&str; 4] = ["Album", "Album artist", "Year", "Genre"];
+
+fn field_index(name: &str) -> usize {
+    FIELDS.iter().position(|x| x.0 == name).unwrap()
+}
+
+// Several values are shown separated by "; ", and only split again in frames holding a list
+fn display(value: Option<String>) -> String {
+    value
+        .unwrap_or_default()
+        .trim_end_matches('\0')
+        .replace('\0', "; ")
+}
+
+fn stored(frame_id: &[u8; 4], value: &str) -> String {
+    let id = String::from_utf8_lossy(frame_id);
+    if tag::LIST_FRAMES.contains(&id.as_ref()) {
+        value.replace("; ", "\0") + "\0"
+    } else {
+        value.to_string() + "\0"
+    }
+}
+
+fn is_mp3(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("mp3"))
+}
+
+// MP3 files in a folder and the folders in it, in name order
+fn collect_mp3_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|x| x.ok()).map(|x| x.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            collect_mp3_files(&path, files);
+        } else if is_mp3(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+// Cover art chosen but not saved yet, shared by every file it was set on
+#[derive(Clone)]
+struct Cover {
+    mime: &'static str,
+    data: Arc<[u8]>,
+}
+
+impl Cover {
+    // Only JPEG and PNG, the formats every player shows
+    fn read(path: &Path) -> Result<Cover, String> {
+        let data =
+            fs::read(path).map_err(|x| format!("unable to read {}: {}", path.display(), x))?;
+        let mime = match image_meta::detect_format(&data) {
+            Some("jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            _ => return Err(format!("{} is not a JPEG or PNG image", path.display())),
+        };
+
+        Ok(Cover {
+            mime,
+            data: data.into(),
+        })
+    }
+
+    fn picture(&self) -> tag::Picture {
+        tag::Picture::new(
+            self.mime.to_string(),
+            0x03,
+            String::new(),
+            Arc::clone(&self.data),
+        )
+    }
+}
+
+fn describe_art(mime: &str, data: &[u8]) -> String {
+    match image_meta::image_info(data) {
+        Some(x) => format!("{}, {} bytes", x.describe(), data.len()),
+        None => format!("{}, {} bytes", mime, data.len()),
+    }
+}
+
+struct Track {
+    path: PathBuf,
+    // As edited, and as in the file, in the order of FIELDS
+    values: Vec<String>,
+    saved: Vec<String>,
+    cover: Option<String>,
+    new_cover: Option<Cover>,
+    selected: bool,
+}
+
+impl Track {
+    fn open(path: PathBuf) -> Result<Track, String> {
+        let tag = parse::read_tag(&path)?;
+        let values: Vec<String> = FIELDS.iter().map(|x| display(x.2(&tag))).collect();
+
+        Ok(Track {
+            path,
+            saved: values.clone(),
+            values,
+            cover: tag
+                .get_front_cover()
+                .map(|x| describe_art(x.mime(), x.data())),
+            new_cover: None,
+            selected: true,
+        })
+    }
+
+    fn is_modified(&self) -> bool {
+        self.values != self.saved || self.new_cover.is_some()
+    }
+
+    fn revert(&mut self) {
+        self.values = self.saved.clone();
+        self.new_cover = None;
+    }
+
+    // The file is read again so only the fields edited change, whatever else its tag holds
+    fn save(&mut self) -> Result<(), String> {
+        let location = self.path.to_string_lossy().to_string();
+        let _locks = storage::lock(&[&location])?;
+        let (mut tag, audio_data) = parse::parse_file(&self.path)?;
+        let dj_frames = dj::dj_frame_bytes(&tag);
+        let protected_frames = registration::protected_frame_bytes(&tag);
+
+        for ((_, frame_id, _, set), (value, saved)) in
+            FIELDS.iter().zip(self.values.iter().zip(&self.saved))
+        {
+            if value == saved {
+                continue;
+            }
+
+            if value.trim().is_empty() {
+                tag.drop_frames(&[*frame_id]);
+            } else {
+                set(&mut tag, stored(frame_id, value.trim()))?;
+            }
+        }
+
+        if let Some(x) = &self.new_cover {
+            tag.set_cover_art(x.picture())?;
+        }
+
+        dj::verify_dj_frames(&dj_frames, &tag)?;
+        registration::verify_protected_frames(&protected_frames, &tag)?;
+        storage::write_verified(&location, &tag, &audio_data)?;
+
+        self.saved = self.values.clone();
+        self.cover = tag
+            .get_front_cover()
+            .map(|x| describe_art(x.mime(), x.data()));
+        self.new_cover = None;
+        Ok(())
+    }
+}
+
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|x| x.is_ascii_digit())
+        .collect();
+
+    digits.parse().ok()
+}
+
+// The most common non-empty value, the first seen on a tie, as --album-mode resolves a value
+// without prompting
+fn majority_value<'a>(values: impl Iterator<Item = &'a String>) -> String {
+    let mut counts: Vec<(&String, u32)> = vec![];
+    for value in values.filter(|x| !x.is_empty()) {
+        match counts.iter_mut().find(|(x, _)| *x == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+
+    let top = counts.iter().map(|(_, count)| *count).max();
+    counts
+        .iter()
+        .find(|(_, count)| Some(*count) == top)
+        .map(|(x, _)| x.to_string())
+        .unwrap_or_default()
+}
+
+// Tracks in the same folder with the same album title (TALB) as saved, so editing the title
+// doesn't split an album before it is saved
+struct Album {
+    folder: PathBuf,
+    title: String,
+    tracks: Vec<usize>,
+}
+
+impl Album {
+    fn key(&self) -> (PathBuf, String) {
+        (self.folder.clone(), self.title.clone())
+    }
+
+    fn name(&self) -> &str {
+        if self.title.is_empty() {
+            "No album"
+        } else {
+            &self.title
+        }
+    }
+}
+
+fn detect_albums(tracks: &[Track]) -> Vec<Album> {
+    let title = field_index("Album");
+    let mut albums: Vec<Album> = vec![];
+
+    for (idx, track) in tracks.iter().enumerate() {
+        let folder = track.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        match albums
+            .iter_mut()
+            .find(|x| x.folder == folder && x.title == track.saved[title])
+        {
+            Some(x) => x.tracks.push(idx),
+            None => albums.push(Album {
+                folder,
+                title: track.saved[title].clone(),
+                tracks: vec![idx],
+            }),
+        }
+    }
+
+    albums.sort_by_key(Album::key);
+    albums
+}
+
+#[derive(PartialEq)]
+enum View {
+    Files,
+    Albums,
+}
+
+struct App {
+    tracks: Vec<Track>,
+    current: Option<usize>,
+    // Fields applied to every selected file, left alone when empty
+    batch: Vec<String>,
+    view: View,
+    // Folder and title of the album shown, with its fields in the order of ALBUM_FIELDS
+    album: Option<(PathBuf, String)>,
+    album_values: Vec<String>,
+    number_tracks: bool,
+    status: String,
+}
+
+impl App {
+    fn new(paths: Vec<PathBuf>) -> App {
+        let mut app = App {
+            tracks: vec![],
+            current: None,
+            batch: vec![String::new(); FIELDS.len()],
+            view: View::Files,
+            album: None,
+            album_values: vec![String::new(); ALBUM_FIELDS.len()],
+            number_tracks: false,
+            status: "Open MP3 files or a folder, or drop them on the window".to_string(),
+        };
+        if !paths.is_empty() {
+            app.open(paths);
+        }
+
+        app
+    }
+
+    fn open(&mut self, paths: Vec<PathBuf>) {
+        let mut files = vec![];
+        for path in paths {
+            if path.is_dir() {
+                collect_mp3_files(&path, &mut files);
+            } else {
+                files.push(path);
+            }
+        }
+
+        let mut errors = vec![];
+        for file in files {
+            if self.tracks.iter().any(|x| x.path == file) {
+                continue;
+            }
+
+            match Track::open(file.clone()) {
+                Ok(x) => self.tracks.push(x),
+                Err(x) => errors.push(format!("{}: {}", file_name(&file), x)),
+            }
+        }
+
+        if self.current.is_none() && !self.tracks.is_empty() {
+            self.current = Some(0);
+        }
+        self.status = match errors.first() {
+            Some(x) => format!("{} file(s) couldn't be opened, e.g. {}", errors.len(), x),
+            None => format!("{} file(s) open", self.tracks.len()),
+        };
+    }
+
+    // Dropped images become the cover of the selected files, anything else is opened. A dropped
+    // folder is shown as albums, starting with the first found in it.
+    fn drop_files(&mut self, paths: Vec<PathBuf>) {
+        let (images, files): (Vec<PathBuf>, Vec<PathBuf>) = paths.into_iter().partition(|x| {
+            fs::read(x).is_ok_and(|data| image_meta::detect_format(&data).is_some())
+        });
+
+        if let Some(x) = images.first() {
+            self.set_cover(x);
+        }
+        if files.is_empty() {
+            return;
+        }
+
+        let folder = files.iter().find(|x| x.is_dir()).cloned();
+        self.open(files);
+
+        if let Some(folder) = folder {
+            self.view = View::Albums;
+            if let Some(x) = detect_albums(&self.tracks)
+                .iter()
+                .find(|x| x.folder.starts_with(&folder))
+            {
+                self.select_album(x);
+            }
+        }
+    }
+
+    // Selects the album's tracks, so cover art chosen applies to them, and fills in its fields
+    // with the values most of them have
+    fn select_album(&mut self, album: &Album) {
+        for (idx, track) in self.tracks.iter_mut().enumerate() {
+            track.selected = album.tracks.contains(&idx);
+        }
+
+        self.album_values = ALBUM_FIELDS
+            .iter()
+            .map(|x| {
+                let idx = field_index(x);
+                majority_value(album.tracks.iter().map(|x| &self.tracks[*x].values[idx]))
+            })
+            .collect();
+        self.album = Some(album.key());
+        self.current = album.tracks.first().copied();
+    }
+
+    // Album fields replace those of every track, and tracks are numbered in the order of their
+    // current numbers and then their paths, as --album-mode numbers them
+    fn apply_album(&mut self, album: &Album) {
+        for (name, value) in ALBUM_FIELDS.iter().zip(&self.album_values) {
+            let idx = field_index(name);
+            for x in &album.tracks {
+                self.tracks[*x].values[idx] = value.trim().to_string();
+            }
+        }
+
+        if self.number_tracks {
+            let number = field_index("Track");
+            let mut order = album.tracks.clone();
+            order.sort_by_key(|x| {
+                let track = &self.tracks[*x];
+                (
+                    leading_number(&track.values[number]).unwrap_or(u32::MAX),
+                    track.path.clone(),
+                )
+            });
+
+            for (position, x) in order.iter().enumerate() {
+                self.tracks[*x].values[number] = format!("{}/{}", position + 1, order.len());
+            }
+        }
+
+        self.status = format!(
+            "Applied to the {} track(s) of {}, save to write them",
+            album.tracks.len(),
+            album.name()
+        );
+    }
+
+    fn set_cover(&mut self, path: &Path) {
+        let cover = match Cover::read(path) {
+            Ok(x) => x,
+            Err(x) => {
+                self.status = x;
+                return;
+            }
+        };
+
+        let mut count = 0;
+        for track in self.tracks.iter_mut().filter(|x| x.selected) {
+            track.new_cover = Some(cover.clone());
+            count += 1;
+        }
+        self.status = format!(
+            "{} is the cover of {} file(s), save to write it",
+            file_name(path),
+            count
+        );
+    }
+
+    fn apply_batch(&mut self) {
+        let mut count = 0;
+        for track in self.tracks.iter_mut().filter(|x| x.selected) {
+            for (value, batch) in track.values.iter_mut().zip(&self.batch) {
+                if !batch.trim().is_empty() {
+                    *value = batch.trim().to_string();
+                }
+            }
+            count += 1;
+        }
+
+        self.status = format!("Applied to {} file(s), save to write them", count);
+    }
+
+    fn save_all(&mut self) {
+        let mut saved = 0;
+        let mut errors = vec![];
+        for track in self.tracks.iter_mut().filter(|x| x.is_modified()) {
+            match track.save() {
+                Ok(()) => saved += 1,
+                Err(x) => errors.push(format!("{}: {}", file_name(&track.path), x)),
+            }
+        }
+
+        self.status = match errors.first() {
+            Some(x) => format!(
+                "Saved {} file(s), {} failed, e.g. {}",
+                saved,
+                errors.len(),
+                x
+            ),
+            None => format!("Saved {} file(s)", saved),
+        };
+    }
+
+    fn toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Open files…").clicked() {
+                if let Some(x) = rfd::FileDialog::new()
+                    .add_filter("MP3", &["mp3"])
+                    .pick_files()
+                {
+                    self.open(x);
+                }
+            }
+
+            if ui.button("Open folder…").clicked() {
+                if let Some(x) = rfd::FileDialog::new().pick_folder() {
+                    self.open(vec![x]);
+                }
+            }
+
+            let modified = self.tracks.iter().filter(|x| x.is_modified()).count();
+            if ui
+                .add_enabled(
+                    modified > 0,
+                    egui::Button::new(format!("Save {} file(s)", modified)),
+                )
+                .clicked()
+            {
+                self.save_all();
+            }
+        });
+    }
+
+    fn album_list(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for album in detect_albums(&self.tracks) {
+                let modified = album.tracks.iter().any(|x| self.tracks[*x].is_modified());
+                let label = format!(
+                    "{}{} ({} tracks)\n{}",
+                    album.name(),
+                    if modified { " *" } else { "" },
+                    album.tracks.len(),
+                    file_name(&album.folder)
+                );
+
+                if ui
+                    .selectable_label(self.album == Some(album.key()), label)
+                    .clicked()
+                {
+                    self.select_album(&album);
+                }
+            }
+        });
+    }
+
+    fn album_editor(&mut self, ui: &mut egui::Ui) {
+        let Some(album) = detect_albums(&self.tracks)
+            .into_iter()
+            .find(|x| self.album == Some(x.key()))
+        else {
+            ui.label("Choose an album, or drop a folder on the window");
+            return;
+        };
+
+        ui.heading(album.name());
+        ui.label(format!(
+            "{} track(s) in {}",
+            album.tracks.len(),
+            album.folder.display()
+        ));
+
+        egui::Grid::new("album").num_columns(2).show(ui, |ui| {
+            for (name, value) in ALBUM_FIELDS.iter().zip(&mut self.album_values) {
+                ui.label(*name);
+                ui.text_edit_singleline(value);
+                ui.end_row();
+            }
+        });
+        ui.checkbox(
+            &mut self.number_tracks,
+            "Number the tracks 1/N to N/N in their current order",
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Apply to album").clicked() {
+                self.apply_album(&album);
+            }
+
+            if ui.button("Choose cover art…").clicked() {
+                if let Some(x) = rfd::FileDialog::new()
+                    .add_filter("Image", &["jpg", "jpeg", "png"])
+                    .pick_file()
+                {
+                    self.set_cover(&x);
+                }
+            }
+        });
+        ui.separator();
+
+        let (number, title) = (field_index("Track"), field_index("Title"));
+        egui::Grid::new("album tracks")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for x in &album.tracks {
+                    let track = &self.tracks[*x];
+                    ui.label(&track.values[number]);
+                    ui.label(&track.values[title]);
+                    ui.label(file_name(&track.path));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn file_list(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.view, View::Files, "Files");
+            ui.selectable_value(&mut self.view, View::Albums, "Albums");
+        });
+        ui.separator();
+
+        if self.view == View::Albums {
+            self.album_list(ui);
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Select");
+            if ui.small_button("all").clicked() {
+                self.tracks.iter_mut().for_each(|x| x.selected = true);
+            }
+            if ui.small_button("none").clicked() {
+                self.tracks.iter_mut().for_each(|x| x.selected = false);
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (idx, track) in self.tracks.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut track.selected, "");
+
+                    let name = if track.is_modified() {
+                        format!("{} *", file_name(&track.path))
+                    } else {
+                        file_name(&track.path)
+                    };
+                    if ui
+                        .selectable_label(self.current == Some(idx), name)
+                        .clicked()
+                    {
+                        self.current = Some(idx);
+                    }
+                });
+            }
+        });
+    }
+
+    fn editor(&mut self, ui: &mut egui::Ui) {
+        if let Some(track) = self.current.and_then(|x| self.tracks.get_mut(x)) {
+            ui.heading(file_name(&track.path));
+            field_grid(ui, "track", &mut track.values);
+
+            let cover = match (&track.new_cover, &track.cover) {
+                (Some(x), _) => format!("{} (not saved)", describe_art(x.mime, &x.data)),
+                (None, Some(x)) => x.clone(),
+                (None, None) => "none".to_string(),
+            };
+            ui.label(format!("Cover art: {}", cover));
+
+            if ui
+                .add_enabled(track.is_modified(), egui::Button::new("Revert"))
+                .clicked()
+            {
+                track.revert();
+            }
+            ui.separator();
+        }
+
+        let selected = self.tracks.iter().filter(|x| x.selected).count();
+        ui.heading(format!("{} selected file(s)", selected));
+        ui.label(
+            "Fields filled in here replace those of every selected file, empty ones are left alone",
+        );
+        field_grid(ui, "batch", &mut self.batch);
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(selected > 0, egui::Button::new("Apply to selected"))
+                .clicked()
+            {
+                self.apply_batch();
+            }
+
+            if ui
+                .add_enabled(selected > 0, egui::Button::new("Choose cover art…"))
+                .clicked()
+            {
+                if let Some(x) = rfd::FileDialog::new()
+                    .add_filter("Image", &["jpg", "jpeg", "png"])
+                    .pick_file()
+                {
+                    self.set_cover(&x);
+                }
+            }
+        });
+        ui.label(
+            "Drop a JPEG or PNG image on the window to make it the cover of the selected files",
+        );
+    }
+}
+
+fn field_grid(ui: &mut egui::Ui, id: &str, values: &mut [String]) {
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        for ((name, ..), value) in FIELDS.iter().zip(values) {
+            ui.label(*name);
+            ui.text_edit_singleline(value);
+            ui.end_row();
+        }
+    });
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|x| x.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            self.drop_files(dropped);
+        }
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| self.toolbar(ui));
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| ui.label(&self.status));
+        egui::SidePanel::left("files")
+            .resizable(true)
+            .show(ctx, |ui| self.file_list(ui));
+        egui::CentralPanel::default().show(ctx, |ui| match self.view {
+            View::Files => self.editor(ui),
+            View::Albums => self.album_editor(ui),
+        });
+    }
+}
+
+/// Files and folders given as arguments, e.g. by "Open with", are opened on start
+fn main() -> eframe::Result {
+    let paths = env::args_os().skip(1).map(PathBuf::from).collect();
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([960.0, 640.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "alloy",
+        options,
+        Box::new(|_| Ok(Box::new(App::new(paths)))),
+    )
+}
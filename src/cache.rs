@@ -0,0 +1,407 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    digest, fields,
+    json::{self, JsonValue},
+    mpeg, parse, storage, tag,
+};
+
+// Bumped whenever the summary changes, so old indexes are rebuilt rather than misread
+const INDEX_VERSION: f64 = 2.0;
+
+/// What scans over a whole library need from a file's tag, small enough to keep for every file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSummary {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<String>,
+    pub recording_time: Option<String>,
+    pub genre: Option<String>,
+    pub has_cover_art: bool,
+    /// Identifiers of every frame, in tag order
+    pub frame_ids: Vec<String>,
+    pub duration_ms: u64,
+    /// SHA-256 of the tag without padding, which only changes when its frames do
+    pub tag_hash: String,
+}
+
+impl TagSummary {
+    pub fn new(tag: &tag::Id3v2Tag, audio: &[u8]) -> TagSummary {
+        let text = |x: Option<String>| x.map(|x| x.trim_end_matches('\0').to_string());
+
+        TagSummary {
+            title: text(tag.get_song_title()),
+            artist: text(tag.get_song_artist_name()),
+            album: text(tag.get_album_title()),
+            album_artist: text(tag.get_album_artist_name()),
+            track_number: text(tag.get_track_number()),
+            recording_time: text(tag.get_recording_time()),
+            genre: text(tag.get_content_type()),
+            has_cover_art: tag.get_cover_art().is_some(),
+            frame_ids: tag.frames().iter().map(|x| x.id()).collect(),
+            duration_ms: mpeg::duration_ms(audio),
+            tag_hash: digest::to_hex(&digest::sha256(&tag.into_bytes())),
+        }
+    }
+
+    /// Value of a field as named in `--where` conditions, for the fields a summary keeps: title,
+    /// artist, album, album_artist, track, date, year, genre, cover and duration. Frame
+    /// identifiers such as "TXXX" give the identifier when the frame is present.
+    pub fn field(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(match fields::canonical(name) {
+            "title" => self.title.clone(),
+            "artist" => self.artist.clone(),
+            "album" => self.album.clone(),
+            "album_artist" => self.album_artist.clone(),
+            "track" => self.track_number.clone(),
+            "date" => self.recording_time.clone(),
+            "year" => self
+                .recording_time
+                .as_ref()
+                .map(|x| x.chars().take(4).collect()),
+            "genre" => self.genre.clone(),
+            "cover" => self.has_cover_art.then(|| "yes".to_string()),
+            "duration" => {
+                let seconds = self.duration_ms / 1000;
+                Some(format!("{}:{:02}", seconds / 60, seconds % 60))
+            }
+            x if x.len() == 4
+                && x.bytes()
+                    .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit()) =>
+            {
+                self.frame_ids.iter().find(|id| *id == x).cloned()
+            }
+            x => return Err(format!("field '{}' isn't kept in tag summaries", x)),
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(self.json_fields())
+    }
+
+    fn json_fields(&self) -> Vec<(String, JsonValue)> {
+        let text = |x: &Option<String>| match x {
+            Some(x) => JsonValue::String(x.clone()),
+            None => JsonValue::Null,
+        };
+
+        vec![
+            ("title".to_string(), text(&self.title)),
+            ("artist".to_string(), text(&self.artist)),
+            ("album".to_string(), text(&self.album)),
+            ("album_artist".to_string(), text(&self.album_artist)),
+            ("track_number".to_string(), text(&self.track_number)),
+            ("recording_time".to_string(), text(&self.recording_time)),
+            ("genre".to_string(), text(&self.genre)),
+            (
+                "has_cover_art".to_string(),
+                JsonValue::Bool(self.has_cover_art),
+            ),
+            (
+                "frame_ids".to_string(),
+                JsonValue::Array(
+                    self.frame_ids
+                        .iter()
+                        .map(|x| JsonValue::String(x.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "duration_ms".to_string(),
+                JsonValue::Number(self.duration_ms as f64),
+            ),
+            (
+                "tag_hash".to_string(),
+                JsonValue::String(self.tag_hash.clone()),
+            ),
+        ]
+    }
+
+    fn from_json(value: &JsonValue) -> Option<TagSummary> {
+        let text = |key: &str| {
+            value
+                .get(key)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+        };
+
+        Some(TagSummary {
+            title: text("title"),
+            artist: text("artist"),
+            album: text("album"),
+            album_artist: text("album_artist"),
+            track_number: text("track_number"),
+            recording_time: text("recording_time"),
+            genre: text("genre"),
+            has_cover_art: value.get("has_cover_art")?.as_bool()?,
+            frame_ids: value
+                .get("frame_ids")?
+                .as_array()?
+                .iter()
+                .map(|x| x.as_str().map(str::to_string))
+                .collect::<Option<Vec<String>>>()?,
+            duration_ms: value.get("duration_ms")?.as_f64()? as u64,
+            tag_hash: text("tag_hash")?,
+        })
+    }
+}
+
+// A file is re-read when its modification time or size changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    modified_secs: u64,
+    modified_nanos: u32,
+    size: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<FileStamp, String> {
+        let metadata =
+            fs::metadata(path).map_err(|x| format!("unable to read {}: {}", path.display(), x))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+
+        Ok(FileStamp {
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Summaries of parsed tags keyed by path, reused while a file's modification time and size are
+/// unchanged so repeated scans of a large library only parse what changed. It can be shared
+/// between threads, and saved to and loaded from a JSON index file between runs.
+///
+/// The index also remembers the tag hash each file had when a command last processed it (a
+/// "run", e.g. "check"), so the command can skip files whose tags haven't changed since.
+#[derive(Debug, Default)]
+pub struct TagCache {
+    entries: Mutex<HashMap<PathBuf, (FileStamp, TagSummary)>>,
+    runs: Mutex<HashMap<String, HashMap<PathBuf, String>>>,
+}
+
+impl TagCache {
+    pub fn new() -> TagCache {
+        TagCache::default()
+    }
+
+    /// Loads an index written by `save`. A missing index gives an empty cache, and one written by
+    /// a different version of alloy is ignored.
+    pub fn load(index: &Path) -> Result<TagCache, String> {
+        let text = match fs::read_to_string(index) {
+            Ok(x) => x,
+            Err(x) if x.kind() == std::io::ErrorKind::NotFound => return Ok(TagCache::new()),
+            Err(x) => return Err(format!("unable to read {}: {}", index.display(), x)),
+        };
+
+        let value = json::parse(&text).map_err(|x| format!("{}: {}", index.display(), x))?;
+        if value.get("version").and_then(JsonValue::as_f64) != Some(INDEX_VERSION) {
+            return Ok(TagCache::new());
+        }
+
+        let mut entries = HashMap::new();
+        for file in value
+            .get("files")
+            .and_then(JsonValue::as_array)
+            .unwrap_or_default()
+        {
+            let number = |key: &str| file.get(key).and_then(JsonValue::as_f64);
+            let (Some(path), Some(secs), Some(nanos), Some(size), Some(summary)) = (
+                file.get("path").and_then(JsonValue::as_str),
+                number("modified_secs"),
+                number("modified_nanos"),
+                number("size"),
+                TagSummary::from_json(file),
+            ) else {
+                continue;
+            };
+
+            let stamp = FileStamp {
+                modified_secs: secs as u64,
+                modified_nanos: nanos as u32,
+                size: size as u64,
+            };
+            entries.insert(PathBuf::from(path), (stamp, summary));
+        }
+
+        let mut runs: HashMap<String, HashMap<PathBuf, String>> = HashMap::new();
+        if let Some(JsonValue::Object(x)) = value.get("runs") {
+            for (run, files) in x {
+                let JsonValue::Object(files) = files else {
+                    continue;
+                };
+                runs.insert(
+                    run.clone(),
+                    files
+                        .iter()
+                        .filter_map(|(path, hash)| {
+                            Some((PathBuf::from(path), hash.as_str()?.to_string()))
+                        })
+                        .collect(),
+                );
+            }
+        }
+
+        Ok(TagCache {
+            entries: Mutex::new(entries),
+            runs: Mutex::new(runs),
+        })
+    }
+
+    /// Writes every summary to an index file, sorted by path so unchanged libraries give
+    /// identical files
+    pub fn save(&self, index: &Path) -> Result<(), String> {
+        let entries = self.entries.lock().unwrap();
+        let mut paths: Vec<&PathBuf> = entries.keys().collect();
+        paths.sort();
+
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let (stamp, summary) = &entries[path];
+                let mut fields = vec![
+                    (
+                        "path".to_string(),
+                        JsonValue::String(path.to_string_lossy().to_string()),
+                    ),
+                    (
+                        "modified_secs".to_string(),
+                        JsonValue::Number(stamp.modified_secs as f64),
+                    ),
+                    (
+                        "modified_nanos".to_string(),
+                        JsonValue::Number(f64::from(stamp.modified_nanos)),
+                    ),
+                    ("size".to_string(), JsonValue::Number(stamp.size as f64)),
+                ];
+                fields.extend(summary.json_fields());
+                JsonValue::Object(fields)
+            })
+            .collect();
+
+        let runs = self.runs.lock().unwrap();
+        let mut names: Vec<&String> = runs.keys().collect();
+        names.sort();
+
+        let runs = names
+            .into_iter()
+            .map(|name| {
+                let mut files: Vec<(&PathBuf, &String)> = runs[name].iter().collect();
+                files.sort();
+
+                let files = files
+                    .into_iter()
+                    .map(|(path, hash)| {
+                        (
+                            path.to_string_lossy().to_string(),
+                            JsonValue::String(hash.clone()),
+                        )
+                    })
+                    .collect();
+                (name.clone(), JsonValue::Object(files))
+            })
+            .collect();
+
+        let document = JsonValue::Object(vec![
+            ("version".to_string(), JsonValue::Number(INDEX_VERSION)),
+            ("files".to_string(), JsonValue::Array(files)),
+            ("runs".to_string(), JsonValue::Object(runs)),
+        ]);
+
+        storage::write(&index.to_string_lossy(), document.to_string().as_bytes())
+    }
+
+    /// Summary of the tag of the file at `path`, parsing it only if it isn't cached or has been
+    /// modified since
+    pub fn summary(&self, path: &Path) -> Result<TagSummary, String> {
+        let stamp = FileStamp::of(path)?;
+
+        if let Some((cached, summary)) = self.entries.lock().unwrap().get(path) {
+            if *cached == stamp {
+                return Ok(summary.clone());
+            }
+        }
+
+        // Parsed without holding the lock so other threads aren't kept waiting
+        let (tag, audio) = parse::parse_file(path)?;
+        let summary = TagSummary::new(&tag, &audio);
+
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (stamp, summary.clone()));
+
+        Ok(summary)
+    }
+
+    /// Forgets files that no longer exist, so a saved index doesn't grow forever
+    pub fn prune(&self) -> usize {
+        for files in self.runs.lock().unwrap().values_mut() {
+            files.retain(|path, _| path.is_file());
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|path, _| path.is_file());
+
+        before - entries.len()
+    }
+
+    /// Whether the tag of the file at `path` differs from when `run` last processed it, which is
+    /// also the case for files it hasn't seen and files that can't be read
+    pub fn changed_since_run(&self, run: &str, path: &Path) -> bool {
+        let Ok(summary) = self.summary(path) else {
+            return true;
+        };
+
+        self.runs.lock().unwrap().get(run).and_then(|x| x.get(path)) != Some(&summary.tag_hash)
+    }
+
+    /// Remembers the current tag of the file at `path` as processed by `run`
+    pub fn record_run(&self, run: &str, path: &Path) -> Result<(), String> {
+        let summary = self.summary(path)?;
+
+        self.runs
+            .lock()
+            .unwrap()
+            .entry(run.to_string())
+            .or_default()
+            .insert(path.to_path_buf(), summary.tag_hash);
+
+        Ok(())
+    }
+
+    /// Every cached file with its summary, sorted by path
+    pub fn entries(&self) -> Vec<(PathBuf, TagSummary)> {
+        let mut entries: Vec<(PathBuf, TagSummary)> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, (_, summary))| (path.clone(), summary.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,225 @@
+use std::env;
+
+use crate::{
+    json::{self, JsonValue},
+    remote, utility,
+};
+
+// Search results and artwork are small, the largest artwork is a few megabytes
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Public music catalogs alloy can look tracks up in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Catalog {
+    /// iTunes Search API, which needs no credentials
+    Apple,
+    /// Spotify Web API, with SPOTIFY_CLIENT_ID and SPOTIFY_CLIENT_SECRET
+    Spotify,
+}
+
+impl Catalog {
+    pub fn from_name(name: &str) -> Result<Catalog, String> {
+        match name {
+            "apple" => Ok(Catalog::Apple),
+            "spotify" => Ok(Catalog::Spotify),
+            x => Err(format!(
+                "unknown catalog '{}', expected \"apple\" or \"spotify\"",
+                x
+            )),
+        }
+    }
+}
+
+/// What a catalog knows about a track, missing fields are ones it doesn't provide
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CatalogMatch {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    /// yyyy-MM-dd, or just the year for releases the catalog has no exact date for
+    pub release_date: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_count: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_count: Option<u32>,
+    pub artwork_url: Option<String>,
+}
+
+fn number(value: Option<&JsonValue>) -> Option<u32> {
+    value
+        .and_then(JsonValue::as_f64)
+        .filter(|x| *x > 0.0)
+        .map(|x| x as u32)
+}
+
+fn text(value: Option<&JsonValue>) -> Option<String> {
+    value.and_then(JsonValue::as_str).map(|x| x.to_string())
+}
+
+fn get_json(url: &str, headers: &[(String, String)]) -> Result<JsonValue, String> {
+    let response = remote::request("GET", url, headers, &[], MAX_RESPONSE_SIZE)?;
+    let body = String::from_utf8_lossy(&response.body);
+
+    if response.status != 200 {
+        return Err(format!(
+            "{} returned {}: {}",
+            url,
+            response.status,
+            body.trim()
+        ));
+    }
+
+    json::parse(&body)
+}
+
+fn lookup_apple(artist: &str, title: &str) -> Result<Option<CatalogMatch>, String> {
+    let url = format!(
+        "https://itunes.apple.com/search?media=music&entity=song&limit=1&term={}",
+        utility::percent_encode(&format!("{} {}", artist, title), true)
+    );
+    let response = get_json(&url, &[])?;
+
+    let Some(result) = response
+        .get("results")
+        .and_then(JsonValue::as_array)
+        .and_then(|x| x.first())
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(CatalogMatch {
+        title: text(result.get("trackName")).unwrap_or_default(),
+        artist: text(result.get("artistName")).unwrap_or_default(),
+        album: text(result.get("collectionName")),
+        // e.g. "1991-09-24T07:00:00Z"
+        release_date: text(result.get("releaseDate")).map(|x| x.chars().take(10).collect()),
+        track_number: number(result.get("trackNumber")),
+        track_count: number(result.get("trackCount")),
+        disc_number: number(result.get("discNumber")),
+        disc_count: number(result.get("discCount")),
+        // Artwork is served at any size by changing the dimensions in the URL
+        artwork_url: text(result.get("artworkUrl100")).map(|x| x.replace("100x100", "1000x1000")),
+    }))
+}
+
+// Client credentials flow, the token is valid for an hour which outlasts any alloy run
+fn spotify_token() -> Result<String, String> {
+    let (Ok(client_id), Ok(client_secret)) = (
+        env::var("SPOTIFY_CLIENT_ID"),
+        env::var("SPOTIFY_CLIENT_SECRET"),
+    ) else {
+        return Err("Spotify lookups need SPOTIFY_CLIENT_ID and SPOTIFY_CLIENT_SECRET".to_string());
+    };
+
+    let credentials = utility::encode_base64(format!("{}:{}", client_id, client_secret).as_bytes());
+    let headers = [
+        (
+            "Authorization".to_string(),
+            format!("Basic {}", credentials),
+        ),
+        (
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        ),
+    ];
+
+    let url = "https://accounts.spotify.com/api/token";
+    let response = remote::request(
+        "POST",
+        url,
+        &headers,
+        b"grant_type=client_credentials",
+        MAX_RESPONSE_SIZE,
+    )?;
+    let body = String::from_utf8_lossy(&response.body);
+
+    if response.status != 200 {
+        return Err(format!(
+            "{} returned {}: {}",
+            url,
+            response.status,
+            body.trim()
+        ));
+    }
+
+    match json::parse(&body)?
+        .get("access_token")
+        .and_then(JsonValue::as_str)
+    {
+        Some(x) => Ok(x.to_string()),
+        None => Err("Spotify token response has no access_token".to_string()),
+    }
+}
+
+fn lookup_spotify(artist: &str, title: &str) -> Result<Option<CatalogMatch>, String> {
+    let token = spotify_token()?;
+    let url = format!(
+        "https://api.spotify.com/v1/search?type=track&limit=1&q={}",
+        utility::percent_encode(&format!("track:{} artist:{}", title, artist), true)
+    );
+    let response = get_json(
+        &url,
+        &[("Authorization".to_string(), format!("Bearer {}", token))],
+    )?;
+
+    let Some(track) = response
+        .get("tracks")
+        .and_then(|x| x.get("items"))
+        .and_then(JsonValue::as_array)
+        .and_then(|x| x.first())
+    else {
+        return Ok(None);
+    };
+
+    let album = track.get("album");
+    let artists: Vec<&str> = track
+        .get("artists")
+        .and_then(JsonValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|x| x.get("name").and_then(JsonValue::as_str))
+        .collect();
+
+    // Images are listed largest first
+    let artwork_url = album
+        .and_then(|x| x.get("images"))
+        .and_then(JsonValue::as_array)
+        .and_then(|x| x.first())
+        .and_then(|x| text(x.get("url")));
+
+    Ok(Some(CatalogMatch {
+        title: text(track.get("name")).unwrap_or_default(),
+        artist: artists.join(", "),
+        album: text(album.and_then(|x| x.get("name"))),
+        release_date: text(album.and_then(|x| x.get("release_date"))),
+        track_number: number(track.get("track_number")),
+        track_count: number(album.and_then(|x| x.get("total_tracks"))),
+        disc_number: number(track.get("disc_number")),
+        disc_count: None,
+        artwork_url,
+    }))
+}
+
+/// Searches a catalog for a track by artist and title, returning its best match if any
+pub fn lookup(catalog: Catalog, artist: &str, title: &str) -> Result<Option<CatalogMatch>, String> {
+    match catalog {
+        Catalog::Apple => lookup_apple(artist, title),
+        Catalog::Spotify => lookup_spotify(artist, title),
+    }
+}
+
+/// Downloads artwork, returning its MIME type and data
+pub fn fetch_artwork(url: &str) -> Result<(String, Vec<u8>), String> {
+    let response = remote::request("GET", url, &[], &[], MAX_RESPONSE_SIZE)?;
+
+    if response.status != 200 {
+        return Err(format!("{} returned {}", url, response.status));
+    }
+
+    let mime = match response.header("content-type") {
+        Some(x) if x.starts_with("image/png") => "image/png",
+        _ => "image/jpeg",
+    };
+
+    Ok((mime.to_string(), response.body))
+}
@@ -0,0 +1,140 @@
+use crate::utility;
+
+// CTOC flags: top-level table whose entries are ordered
+const TOC_FLAGS: u8 = 0x03;
+
+/// A titled section of the audio, stored in a CHAP frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub element_id: String,
+    pub start_ms: u32,
+    pub end_ms: u32,
+    pub title: Option<String>,
+}
+
+fn text_subframe(frame_id: &str, text: &str) -> Vec<u8> {
+    let data = [vec![0x03], text.as_bytes().to_vec(), vec![0x00]].concat();
+    let size = utility::convert_u32_to_safesynch(u32::try_from(data.len()).unwrap());
+
+    [
+        frame_id.as_bytes().to_vec(),
+        size.to_vec(),
+        vec![0x00, 0x00],
+        data,
+    ]
+    .concat()
+}
+
+fn split_element_id(bytes: &[u8]) -> Result<(String, &[u8]), String> {
+    match bytes.iter().position(|x| *x == 0x00) {
+        Some(end) => Ok((utility::decode_text(0x00, &bytes[..end]), &bytes[end + 1..])),
+        None => Err("element ID is not terminated".to_string()),
+    }
+}
+
+impl Chapter {
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        let title = match &self.title {
+            Some(x) => text_subframe("TIT2", x),
+            None => vec![],
+        };
+
+        [
+            self.element_id.as_bytes().to_vec(),
+            vec![0x00],
+            self.start_ms.to_be_bytes().to_vec(),
+            self.end_ms.to_be_bytes().to_vec(),
+            // Byte offsets are unused, times are authoritative
+            vec![0xFF; 8],
+            title,
+        ]
+        .concat()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Chapter, String> {
+        let (element_id, rest) = split_element_id(data)?;
+
+        if rest.len() < 16 {
+            return Err(format!("chapter '{}' is truncated", element_id));
+        }
+
+        let start_ms = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let end_ms = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]);
+
+        let mut title = None;
+        let mut idx = 16;
+        while idx + 10 <= rest.len() {
+            let size = utility::convert_safesynch_to_u32(
+                rest[idx + 4],
+                rest[idx + 5],
+                rest[idx + 6],
+                rest[idx + 7],
+            );
+            let end = (idx + 10 + usize::try_from(size).unwrap()).min(rest.len());
+
+            if &rest[idx..idx + 4] == b"TIT2" && end > idx + 10 {
+                title = Some(utility::decode_text(rest[idx + 10], &rest[idx + 11..end]));
+            }
+
+            idx = end;
+        }
+
+        Ok(Chapter {
+            element_id,
+            start_ms,
+            end_ms,
+            title,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} - {} '{}' ({})",
+            utility::format_milliseconds(self.start_ms),
+            utility::format_milliseconds(self.end_ms),
+            self.title.as_deref().unwrap_or(""),
+            self.element_id
+        )
+    }
+}
+
+/// Payload of the CTOC frame listing the given chapters in order
+pub fn table_of_contents_data(chapters: &[Chapter]) -> Vec<u8> {
+    let mut data = b"toc\0".to_vec();
+    data.push(TOC_FLAGS);
+    data.push(u8::try_from(chapters.len()).unwrap_or(u8::MAX));
+
+    for chapter in chapters.iter().take(usize::from(u8::MAX)) {
+        data.extend_from_slice(chapter.element_id.as_bytes());
+        data.push(0x00);
+    }
+
+    data
+}
+
+/// Parses "h:mm:ss.mmm", "mm:ss", or plain seconds into milliseconds
+pub fn parse_time(text: &str) -> Result<u32, String> {
+    let error = || format!("invalid time '{}', expected [h:]mm:ss[.mmm]", text);
+
+    let (whole, fraction) = match text.trim().split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (text.trim(), ""),
+    };
+
+    let mut seconds: u32 = 0;
+    for part in whole.split(':') {
+        let value: u32 = part.parse().map_err(|_| error())?;
+        seconds = seconds.checked_mul(60).ok_or_else(error)? + value;
+    }
+
+    let milliseconds: u32 = match fraction.len() {
+        0 => 0,
+        1..=3 => format!("{:0<3}", fraction).parse().map_err(|_| error())?,
+        _ => return Err(error()),
+    };
+
+    seconds
+        .checked_mul(1000)
+        .and_then(|x| x.checked_add(milliseconds))
+        .ok_or_else(error)
+}
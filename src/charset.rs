@@ -0,0 +1,81 @@
+use encoding_rs::{Encoding, KOI8_R, SHIFT_JIS, WINDOWS_1251, WINDOWS_1252};
+
+/// Legacy code pages text marked ISO-8859-1 is commonly really in: Western European, Cyrillic
+/// (Windows and KOI8-R) and Japanese (Shift_JIS, as Windows' CP932)
+pub fn candidates() -> [&'static Encoding; 4] {
+    [WINDOWS_1252, WINDOWS_1251, KOI8_R, SHIFT_JIS]
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, 'А'..='я' | 'Ё' | 'ё')
+}
+
+fn is_accented_latin(c: char) -> bool {
+    matches!(c, 'À'..='ÿ') && c != '×' && c != '÷'
+}
+
+// How typical a character is of real text, given the one before it. Decoding in the wrong code
+// page mostly gives symbols, box drawing, control and half-width katakana characters, runs of
+// accented Latin letters, Cyrillic letters inside Latin words, and capitals inside words (the
+// Cyrillic code pages put lowercase letters where the other has uppercase ones).
+fn char_score(c: char, previous: Option<char>) -> i32 {
+    let after_letter = previous.is_some_and(char::is_alphabetic);
+    let case_score = if c.is_lowercase() {
+        2
+    } else if after_letter {
+        -1
+    } else {
+        1
+    };
+
+    match c {
+        _ if c.is_ascii() => 0,
+        '\u{3040}'..='\u{30FF}' => 3,
+        '\u{4E00}'..='\u{9FFF}' | '\u{FF01}'..='\u{FF5E}' => 2,
+        '\u{FF61}'..='\u{FF9F}' => -1,
+        _ if is_cyrillic(c) && previous.is_some_and(|x| x.is_ascii_alphabetic()) => -2,
+        _ if is_cyrillic(c) => case_score,
+        _ if is_accented_latin(c) && previous.is_some_and(is_accented_latin) => -1,
+        _ if is_accented_latin(c) => case_score,
+        _ => -3,
+    }
+}
+
+fn text_score(text: &str) -> i32 {
+    let mut previous = None;
+    let mut score = 0;
+
+    for c in text.chars() {
+        score += char_score(c, previous);
+        previous = Some(c);
+    }
+
+    score
+}
+
+/// The candidate code page `bytes` read most like real text in, or None when they are ASCII or
+/// fit none of them
+pub fn detect(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.is_ascii() {
+        return None;
+    }
+
+    candidates()
+        .into_iter()
+        .filter_map(|x| {
+            let text = x.decode_without_bom_handling_and_without_replacement(bytes)?;
+            Some((x, text_score(&text)))
+        })
+        .filter(|(_, score)| *score > 0)
+        // The first candidate wins a tie, e.g. Windows-1252 for text with one accented letter
+        .reduce(|best, x| if x.1 > best.1 { x } else { best })
+        .map(|(x, _)| x)
+}
+
+/// Decodes `bytes` in the code page they are detected to be in, with its name
+pub fn decode(bytes: &[u8]) -> Option<(String, &'static str)> {
+    let encoding = detect(bytes)?;
+    let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+
+    Some((text.trim_end_matches('\0').to_string(), encoding.name()))
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use alloy::{cache::TagCache, digest, json::JsonValue, json_schema, parse, schema::Schema};
+
+use crate::art;
+
+/// Check the tags of files against a schema, e.g. as a release's QC gate
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    /// MP3 files, or folders searched recursively for them
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// TOML file listing the required frames, allowed genres and cover art requirements
+    #[arg(long, value_name = "FILE")]
+    schema: String,
+
+    /// Output format, the files failing with their issues or a JSON report of every file
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    format: String,
+
+    /// Only check files whose tags changed since they last passed a check against the same schema
+    #[arg(long)]
+    changed_since_last_run: bool,
+
+    /// Index file keeping the tag of each file at the last run, for --changed-since-last-run
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = ".alloy-index.json",
+        requires = "changed_since_last_run"
+    )]
+    index: String,
+}
+
+/// Exits with status 1 when any file fails or can't be read, so scripts can gate a release on it
+pub fn process_check(args: &CheckArgs) {
+    let text = match fs::read_to_string(&args.schema) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("unable to read schema {}: {}", args.schema, x);
+            process::exit(2);
+        }
+    };
+
+    let schema = match Schema::parse(&text).map_err(|e| format!("{}: {}", args.schema, e)) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            process::exit(2);
+        }
+    };
+
+    let mut files = vec![];
+    for path in &args.paths {
+        let path = PathBuf::from(path);
+
+        if path.is_dir() {
+            art::collect_mp3_files(&path, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    // Runs are told apart by the schema's contents, so changing it checks every file again
+    let run = format!(
+        "check {}",
+        &digest::to_hex(&digest::sha256(text.as_bytes()))[..16]
+    );
+    let cache = if args.changed_since_last_run {
+        match TagCache::load(Path::new(&args.index)) {
+            Ok(x) => Some(x),
+            Err(x) => {
+                eprintln!("{}", x);
+                process::exit(2);
+            }
+        }
+    } else {
+        None
+    };
+
+    // Absolute paths, as the index keeps them
+    let before = files.len();
+    if let Some(cache) = &cache {
+        files = files
+            .into_iter()
+            .map(|x| fs::canonicalize(&x).unwrap_or(x))
+            .filter(|x| cache.changed_since_run(&run, x))
+            .collect();
+    }
+    let unchanged = before - files.len();
+
+    // Unreadable files fail, with the reason as their only issue
+    let results: Vec<(String, Vec<String>)> = files
+        .iter()
+        .map(|file| {
+            let issues = match parse::read_tag(file) {
+                Ok(tag) => schema.check(&tag),
+                Err(x) => vec![format!("unable to read tag: {}", x)],
+            };
+            (file.display().to_string(), issues)
+        })
+        .collect();
+    let failed = results.iter().filter(|(_, x)| !x.is_empty()).count();
+
+    // Failing files are left out, so they are checked again until they pass
+    if let Some(cache) = &cache {
+        for (file, _) in files.iter().zip(&results).filter(|(_, x)| x.1.is_empty()) {
+            if let Err(x) = cache.record_run(&run, file) {
+                eprintln!("{} | {}", file.display(), x);
+            }
+        }
+
+        if let Err(x) = cache.save(Path::new(&args.index)) {
+            eprintln!("{}", x);
+        }
+    }
+
+    if args.format == "json" {
+        let files = results
+            .iter()
+            .map(|(file, issues)| {
+                JsonValue::Object(vec![
+                    ("path".to_string(), JsonValue::String(file.clone())),
+                    ("pass".to_string(), JsonValue::Bool(issues.is_empty())),
+                    (
+                        "issues".to_string(),
+                        JsonValue::Array(issues.iter().cloned().map(JsonValue::String).collect()),
+                    ),
+                ])
+            })
+            .collect();
+
+        println!(
+            "{}",
+            JsonValue::Object(vec![
+                json_schema::version_field(),
+                ("schema".to_string(), JsonValue::String(args.schema.clone())),
+                (
+                    "checked".to_string(),
+                    JsonValue::Number(results.len() as f64)
+                ),
+                ("failed".to_string(), JsonValue::Number(failed as f64)),
+                ("unchanged".to_string(), JsonValue::Number(unchanged as f64)),
+                ("files".to_string(), JsonValue::Array(files)),
+            ])
+        );
+    } else {
+        for (file, issues) in results.iter().filter(|(_, x)| !x.is_empty()) {
+            println!("{} | {} issue(s)", file, issues.len());
+            for x in issues {
+                println!("    {}", x);
+            }
+        }
+
+        println!(
+            "{} of {} file(s) pass {}",
+            results.len() - failed,
+            results.len(),
+            args.schema
+        );
+        if unchanged > 0 {
+            println!(
+                "{} file(s) unchanged since they last passed were skipped",
+                unchanged
+            );
+        }
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{self, IsTerminal, Write};
+
+use alloy::tag::Id3v2Tag;
+
+/// Values of a classical recording: the work (TIT1), movement name (MVNM) and number (MVIN),
+/// conductor (TPE3) and soloists (TMCL)
+#[derive(Debug, Default)]
+pub struct ClassicalValues {
+    pub work: Option<String>,
+    pub movement_name: Option<String>,
+    // number, total
+    pub movement: Option<(u32, Option<u32>)>,
+    pub conductor: Option<String>,
+    // instrument, name
+    pub soloists: Vec<(String, String)>,
+}
+
+impl ClassicalValues {
+    pub fn apply(&self, tag: &mut Id3v2Tag) -> Result<(), String> {
+        if let Some(x) = &self.work {
+            tag.set_content_group(x.to_string() + "\0")?;
+        }
+
+        if let Some(x) = &self.movement_name {
+            tag.set_movement_name(x.to_string() + "\0")?;
+        }
+
+        if let Some((number, total)) = self.movement {
+            tag.set_movement_number(number, total)?;
+        }
+
+        if let Some(x) = &self.conductor {
+            tag.set_conductor(x.to_string() + "\0")?;
+        }
+
+        for (instrument, name) in &self.soloists {
+            tag.add_musician_credit(instrument.to_string(), name.to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a movement given as "NUMBER" or "NUMBER/TOTAL", e.g. "2/4"
+pub fn parse_movement(text: &str) -> Result<(u32, Option<u32>), String> {
+    let invalid = || {
+        format!(
+            "invalid movement '{}', expected NUMBER or NUMBER/TOTAL",
+            text
+        )
+    };
+
+    let (number, total) = match text.split_once('/') {
+        Some((number, total)) => (number, Some(total)),
+        None => (text, None),
+    };
+
+    let number = number.trim().parse().map_err(|_| invalid())?;
+    let total = match total {
+        Some(x) => Some(x.trim().parse().map_err(|_| invalid())?),
+        None => None,
+    };
+
+    if number == 0 || total.is_some_and(|x| x < number) {
+        return Err(invalid());
+    }
+
+    Ok((number, total))
+}
+
+/// Parses a soloist given as "INSTRUMENT=NAME", e.g. "violin=Hilary Hahn"
+pub fn parse_soloist(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((instrument, name)) if !instrument.trim().is_empty() && !name.trim().is_empty() => {
+            Ok((instrument.trim().to_string(), name.trim().to_string()))
+        }
+        _ => Err(format!(
+            "invalid soloist '{}', expected INSTRUMENT=NAME",
+            text
+        )),
+    }
+}
+
+// Reads one answer, None for an empty line (keeping the current value) or the end of input
+fn prompt(label: &str, current: Option<String>) -> Result<Option<String>, String> {
+    match current.as_deref().map(|x| x.trim_end_matches('\0')) {
+        Some(x) if !x.is_empty() => print!("{} [{}]: ", label, x),
+        _ => print!("{}: ", label),
+    }
+    io::stdout().flush().map_err(|x| x.to_string())?;
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|x| format!("unable to read answer: {}", x))?;
+
+    let input = input.trim();
+    Ok((!input.is_empty()).then(|| input.to_string()))
+}
+
+// Asks again until the answer parses, or is empty
+fn prompt_parsed<T>(
+    label: &str,
+    current: Option<String>,
+    parse: fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    loop {
+        match prompt(label, current.clone())? {
+            Some(x) => match parse(&x) {
+                Ok(x) => return Ok(Some(x)),
+                Err(x) => println!("{}", x),
+            },
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Asks for the values not already given, for the --classical preset. The file's current values
+/// are shown and kept when the answer is left empty.
+pub fn prompt_missing(
+    file: &str,
+    tag: &Id3v2Tag,
+    values: &mut ClassicalValues,
+) -> Result<(), String> {
+    if !io::stdin().is_terminal() {
+        return Err("--classical asks for values, which needs a terminal".to_string());
+    }
+
+    println!("Classical values for {} (leave empty to keep):", file);
+
+    if values.work.is_none() {
+        values.work = prompt("Work", tag.get_content_group())?;
+    }
+
+    if values.movement_name.is_none() {
+        values.movement_name = prompt("Movement name", tag.get_movement_name())?;
+    }
+
+    if values.movement.is_none() {
+        values.movement = prompt_parsed(
+            "Movement (NUMBER/TOTAL)",
+            tag.get_movement_number(),
+            parse_movement,
+        )?;
+    }
+
+    if values.conductor.is_none() {
+        values.conductor = prompt("Conductor", tag.get_conductor())?;
+    }
+
+    if values.soloists.is_empty() {
+        let current = tag
+            .get_musician_credits()
+            .iter()
+            .map(|(instrument, name)| format!("{}={}", instrument, name))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        // One per answer, until an empty one
+        while let Some(x) = prompt_parsed(
+            "Soloist (INSTRUMENT=NAME)",
+            Some(current.clone()).filter(|_| values.soloists.is_empty()),
+            parse_soloist,
+        )? {
+            values.soloists.push(x);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::{cmp::Ordering, env};
+
+/// Compares text the way players list it: by the collation rules of a locale when built with
+/// `--features collation`, otherwise ignoring case
+pub struct Collator {
+    #[cfg(feature = "collation")]
+    inner: icu::collator::Collator,
+}
+
+impl Collator {
+    /// Collator for a BCP 47 locale such as "de-DE" or "sv", or for the system locale when None.
+    /// Without `--features collation` every locale compares the same.
+    pub fn new(locale: Option<&str>) -> Result<Collator, String> {
+        let locale = match locale {
+            Some(x) => Some(x.to_string()),
+            None => system_locale(),
+        };
+
+        #[cfg(feature = "collation")]
+        {
+            use icu::collator::CollatorOptions;
+            use icu::locid::Locale;
+
+            let tag = locale.unwrap_or_else(|| "und".to_string());
+            let parsed: Locale = tag
+                .parse()
+                .map_err(|_| format!("invalid locale '{}'", tag))?;
+            let inner = icu::collator::Collator::try_new(&(&parsed).into(), CollatorOptions::new())
+                .map_err(|x| format!("no collation for locale '{}': {}", tag, x))?;
+
+            Ok(Collator { inner })
+        }
+
+        #[cfg(not(feature = "collation"))]
+        {
+            let _ = locale;
+            Ok(Collator {})
+        }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        #[cfg(feature = "collation")]
+        {
+            self.inner.compare(a, b)
+        }
+
+        #[cfg(not(feature = "collation"))]
+        {
+            a.to_lowercase()
+                .cmp(&b.to_lowercase())
+                .then_with(|| a.cmp(b))
+        }
+    }
+
+    /// Compares field values for sorting listings: missing and empty values last, values
+    /// starting with a number by that number first (so track "2/12" comes before "10/12"),
+    /// then by collation
+    pub fn compare_values(&self, a: Option<&str>, b: Option<&str>) -> Ordering {
+        let a = a.filter(|x| !x.is_empty());
+        let b = b.filter(|x| !x.is_empty());
+
+        match (a, b) {
+            (Some(a), Some(b)) => match (leading_number(a), leading_number(b)) {
+                (Some(x), Some(y)) => x.cmp(&y).then_with(|| self.compare(a, b)),
+                _ => self.compare(a, b),
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+fn leading_number(value: &str) -> Option<u64> {
+    let digits: String = value
+        .trim_start()
+        .chars()
+        .take_while(|x| x.is_ascii_digit())
+        .collect();
+
+    digits.parse().ok()
+}
+
+/// The locale text is collated in by default, from LC_ALL, LC_COLLATE or LANG, e.g. "de_DE.UTF-8"
+/// as "de-DE". None when unset or the "C"/"POSIX" locale.
+pub fn system_locale() -> Option<String> {
+    let value = ["LC_ALL", "LC_COLLATE", "LANG"]
+        .iter()
+        .filter_map(|x| env::var(x).ok())
+        .find(|x| !x.is_empty())?;
+
+    let locale = value
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+
+    match locale.as_str() {
+        "" | "C" | "POSIX" => None,
+        _ => Some(locale),
+    }
+}
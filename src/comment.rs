@@ -0,0 +1,163 @@
+use crate::utility;
+
+// ISO 639-2 codes, both the bibliographic and terminology forms where they differ
+const LANGUAGE_CODES: [&str; 506] = [
+    "aar", "abk", "ace", "ach", "ada", "ady", "afa", "afh", "afr", "ain", "aka", "akk", "alb",
+    "ale", "alg", "alt", "amh", "ang", "anp", "apa", "ara", "arc", "arg", "arm", "arn", "arp",
+    "art", "arw", "asm", "ast", "ath", "aus", "ava", "ave", "awa", "aym", "aze", "bad", "bai",
+    "bak", "bal", "bam", "ban", "baq", "bas", "bat", "bej", "bel", "bem", "ben", "ber", "bho",
+    "bih", "bik", "bin", "bis", "bla", "bnt", "bod", "bos", "bra", "bre", "btk", "bua", "bug",
+    "bul", "bur", "byn", "cad", "cai", "car", "cat", "cau", "ceb", "cel", "ces", "cha", "chb",
+    "che", "chg", "chi", "chk", "chm", "chn", "cho", "chp", "chr", "chu", "chv", "chy", "cmc",
+    "cnr", "cop", "cor", "cos", "cpe", "cpf", "cpp", "cre", "crh", "crp", "csb", "cus", "cym",
+    "cze", "dak", "dan", "dar", "day", "del", "den", "deu", "dgr", "din", "div", "doi", "dra",
+    "dsb", "dua", "dum", "dut", "dyu", "dzo", "efi", "egy", "eka", "ell", "elx", "eng", "enm",
+    "epo", "est", "eus", "ewe", "ewo", "fan", "fao", "fas", "fat", "fij", "fil", "fin", "fiu",
+    "fon", "fra", "fre", "frm", "fro", "frr", "frs", "fry", "ful", "fur", "gaa", "gay", "gba",
+    "gem", "geo", "ger", "gez", "gil", "gla", "gle", "glg", "glv", "gmh", "goh", "gon", "gor",
+    "got", "grb", "grc", "gre", "grn", "gsw", "guj", "gwi", "hai", "hat", "hau", "haw", "heb",
+    "her", "hil", "him", "hin", "hit", "hmn", "hmo", "hrv", "hsb", "hun", "hup", "hye", "iba",
+    "ibo", "ice", "ido", "iii", "ijo", "iku", "ile", "ilo", "ina", "inc", "ind", "ine", "inh",
+    "ipk", "ira", "iro", "isl", "ita", "jav", "jbo", "jpn", "jpr", "jrb", "kaa", "kab", "kac",
+    "kal", "kam", "kan", "kar", "kas", "kat", "kau", "kaw", "kaz", "kbd", "kha", "khi", "khm",
+    "kho", "kik", "kin", "kir", "kmb", "kok", "kom", "kon", "kor", "kos", "kpe", "krc", "krl",
+    "kro", "kru", "kua", "kum", "kur", "kut", "lad", "lah", "lam", "lao", "lat", "lav", "lez",
+    "lim", "lin", "lit", "lol", "loz", "ltz", "lua", "lub", "lug", "lui", "lun", "luo", "lus",
+    "mac", "mad", "mag", "mah", "mai", "mak", "mal", "man", "mao", "map", "mar", "mas", "may",
+    "mdf", "mdr", "men", "mga", "mic", "min", "mis", "mkd", "mkh", "mlg", "mlt", "mnc", "mni",
+    "mno", "moh", "mon", "mos", "mri", "msa", "mul", "mun", "mus", "mwl", "mwr", "mya", "myn",
+    "myv", "nah", "nai", "nap", "nau", "nav", "nbl", "nde", "ndo", "nds", "nep", "new", "nia",
+    "nic", "niu", "nld", "nno", "nob", "nog", "non", "nor", "nqo", "nso", "nub", "nwc", "nya",
+    "nym", "nyn", "nyo", "nzi", "oci", "oji", "ori", "orm", "osa", "oss", "ota", "oto", "paa",
+    "pag", "pal", "pam", "pan", "pap", "pau", "peo", "per", "phi", "phn", "pli", "pol", "pon",
+    "por", "pra", "pro", "pus", "que", "raj", "rap", "rar", "roa", "roh", "rom", "ron", "rum",
+    "run", "rup", "rus", "sad", "sag", "sah", "sai", "sal", "sam", "san", "sas", "sat", "scn",
+    "sco", "sel", "sem", "sga", "sgn", "shn", "sid", "sin", "sio", "sit", "sla", "slk", "slo",
+    "slv", "sma", "sme", "smi", "smj", "smn", "smo", "sms", "sna", "snd", "snk", "sog", "som",
+    "son", "sot", "spa", "sqi", "srd", "srn", "srp", "srr", "ssa", "ssw", "suk", "sun", "sus",
+    "sux", "swa", "swe", "syc", "syr", "tah", "tai", "tam", "tat", "tel", "tem", "ter", "tet",
+    "tgk", "tgl", "tha", "tib", "tig", "tir", "tiv", "tkl", "tlh", "tli", "tmh", "tog", "ton",
+    "tpi", "tsi", "tsn", "tso", "tuk", "tum", "tup", "tur", "tut", "tvl", "twi", "tyv", "udm",
+    "uga", "uig", "ukr", "umb", "und", "urd", "uzb", "vai", "ven", "vie", "vol", "vot", "wak",
+    "wal", "war", "was", "wel", "wen", "wln", "wol", "xal", "xho", "yao", "yap", "yid", "yor",
+    "ypk", "zap", "zbl", "zen", "zgh", "zha", "zho", "znd", "zul", "zun", "zxx", "zza",
+];
+
+/// Language of comments and lyrics when none is given
+pub const DEFAULT_LANGUAGE: &str = "eng";
+
+// Used by taggers when the language isn't known
+const UNKNOWN_LANGUAGE: &str = "XXX";
+
+/// Checks a language code is one ID3 accepts (ISO 639-2, or "XXX"), returning it lowercased
+pub fn check_language(code: &str) -> Result<String, String> {
+    if code == UNKNOWN_LANGUAGE {
+        return Ok(code.to_string());
+    }
+
+    let lower = code.to_ascii_lowercase();
+
+    // qaa-qtz are reserved for local use
+    let local = lower.len() == 3 && lower.as_str() >= "qaa" && lower.as_str() <= "qtz";
+
+    if !local && LANGUAGE_CODES.binary_search(&lower.as_str()).is_err() {
+        return Err(format!(
+            "invalid language '{}', expected an ISO 639-2 code such as \"eng\"",
+            code
+        ));
+    }
+
+    Ok(lower)
+}
+
+/// Contents of a COMM or USLT frame, which share a layout: language, short description, text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub language: String,
+    pub description: String,
+    pub text: String,
+}
+
+impl Comment {
+    pub fn parse(data: &[u8]) -> Result<Comment, String> {
+        let (Some(encoding), Some(language)) = (data.first(), data.get(1..4)) else {
+            return Err("frame is truncated".to_string());
+        };
+
+        let Some((description, text)) = utility::split_terminated(&data[4..], *encoding) else {
+            return Err("description is not terminated".to_string());
+        };
+
+        Ok(Comment {
+            language: String::from_utf8_lossy(language).to_string(),
+            description: utility::decode_text(*encoding, description),
+            text: utility::decode_text(*encoding, text),
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        [
+            vec![0x03],
+            self.language.as_bytes().to_vec(),
+            self.description.as_bytes().to_vec(),
+            vec![0x00],
+            self.text.as_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.description.is_empty() {
+            format!("[{}] {}", self.language, self.text)
+        } else {
+            format!("[{}] {}: {}", self.language, self.description, self.text)
+        }
+    }
+}
+
+/// Contents of a USER frame: the terms of use of the file, in one language
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermsOfUse {
+    pub language: String,
+    pub text: String,
+}
+
+impl TermsOfUse {
+    pub fn parse(data: &[u8]) -> Result<TermsOfUse, String> {
+        let (Some(encoding), Some(language)) = (data.first(), data.get(1..4)) else {
+            return Err("frame is truncated".to_string());
+        };
+
+        Ok(TermsOfUse {
+            language: String::from_utf8_lossy(language).to_string(),
+            text: utility::decode_text(*encoding, &data[4..]),
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        [
+            vec![0x03],
+            self.language.as_bytes().to_vec(),
+            self.text.as_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        format!("[{}] {}", self.language, self.text)
+    }
+}
+
+/// Checks a copyright or produced notice (TCOP, TPRO), which the spec requires to start with the
+/// year and a space, e.g. "2024 Netlabel Records"
+pub fn check_notice(text: &str) -> Result<String, String> {
+    let year = text.get(..4).unwrap_or_default();
+    if !year.bytes().all(|x| x.is_ascii_digit()) || year.len() != 4 || text.get(4..5) != Some(" ") {
+        return Err(format!(
+            "invalid notice '{}', expected it to start with a year and a space, e.g. \"2024 {}\"",
+            text, text
+        ));
+    }
+
+    Ok(text.to_string())
+}
@@ -0,0 +1,267 @@
+use std::{fs, path::Path};
+
+use crate::{
+    json::{self, JsonValue},
+    utility,
+};
+
+// Meanings of the COMR "received as" byte
+const RECEIVED_AS: [&str; 9] = [
+    "other",
+    "standard CD album with other songs",
+    "compressed audio on CD",
+    "file over the Internet",
+    "stream over the Internet",
+    "as note sheets",
+    "as note sheets in a book with other sheets",
+    "music on other media",
+    "non-musical merchandise",
+];
+
+fn terminated(text: &str) -> Vec<u8> {
+    [text.as_bytes(), &[0x00]].concat()
+}
+
+fn split_text(bytes: &[u8], encoding: u8) -> Result<(String, &[u8]), String> {
+    match utility::split_terminated(bytes, encoding) {
+        Some((text, rest)) => Ok((utility::decode_text(encoding, text), rest)),
+        None => Err("string is not terminated".to_string()),
+    }
+}
+
+// Dates are stored as exactly 8 characters, YYYYMMDD
+fn check_date(date: &str) -> Result<(), String> {
+    if date.len() != 8 || !date.bytes().all(|x| x.is_ascii_digit()) {
+        return Err(format!("invalid date '{}', expected YYYYMMDD", date));
+    }
+
+    Ok(())
+}
+
+/// Contents of an OWNE frame: what was paid for the file, when, and to whom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ownership {
+    /// Currency code followed by the amount, e.g. "USD0.99"
+    pub price_paid: String,
+    /// YYYYMMDD
+    pub purchase_date: String,
+    pub seller: String,
+}
+
+impl Ownership {
+    pub fn parse(data: &[u8]) -> Result<Ownership, String> {
+        let Some(encoding) = data.first() else {
+            return Err("ownership frame is empty".to_string());
+        };
+
+        let (price_paid, rest) = split_text(&data[1..], 0x00)?;
+        let Some(purchase_date) = rest.get(..8) else {
+            return Err("ownership frame is truncated".to_string());
+        };
+
+        Ok(Ownership {
+            price_paid,
+            purchase_date: utility::decode_text(0x00, purchase_date),
+            seller: utility::decode_text(*encoding, &rest[8..]),
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        [
+            vec![0x03],
+            terminated(&self.price_paid),
+            self.purchase_date.as_bytes().to_vec(),
+            self.seller.as_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} paid on {} to '{}'",
+            self.price_paid, self.purchase_date, self.seller
+        )
+    }
+}
+
+/// Contents of a COMR frame: an offer to buy the file or related merchandise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commercial {
+    /// Currency code followed by the amount, several separated by '/', e.g. "USD0.99/EUR0.89"
+    pub prices: String,
+    /// YYYYMMDD
+    pub valid_until: String,
+    pub contact_url: String,
+    pub received_as: u8,
+    pub seller: String,
+    pub description: String,
+    /// MIME type and image data of the seller's logo
+    pub logo: Option<(String, Vec<u8>)>,
+}
+
+impl Commercial {
+    pub fn parse(data: &[u8]) -> Result<Commercial, String> {
+        let Some(encoding) = data.first() else {
+            return Err("commercial frame is empty".to_string());
+        };
+
+        let (prices, rest) = split_text(&data[1..], 0x00)?;
+        let Some(valid_until) = rest.get(..8) else {
+            return Err("commercial frame is truncated".to_string());
+        };
+        let (contact_url, rest) = split_text(&rest[8..], 0x00)?;
+        let Some((received_as, rest)) = rest.split_first() else {
+            return Err("commercial frame is truncated".to_string());
+        };
+        let (seller, rest) = split_text(rest, *encoding)?;
+        let (description, rest) = split_text(rest, *encoding)?;
+
+        // The logo is optional, in which case the frame ends after the description
+        let logo = match split_text(rest, 0x00) {
+            Ok((mime, data)) if !mime.is_empty() => Some((mime, data.to_vec())),
+            _ => None,
+        };
+
+        Ok(Commercial {
+            prices,
+            valid_until: utility::decode_text(0x00, valid_until),
+            contact_url,
+            received_as: *received_as,
+            seller,
+            description,
+            logo,
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        let logo = match &self.logo {
+            Some((mime, data)) => [terminated(mime), data.clone()].concat(),
+            None => vec![],
+        };
+
+        [
+            vec![0x03],
+            terminated(&self.prices),
+            self.valid_until.as_bytes().to_vec(),
+            terminated(&self.contact_url),
+            vec![self.received_as],
+            terminated(&self.seller),
+            terminated(&self.description),
+            logo,
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        let received_as = RECEIVED_AS
+            .get(usize::from(self.received_as))
+            .unwrap_or(&"unknown");
+
+        let mut result = format!(
+            "{} until {} from '{}' ({}), received as {}",
+            self.prices, self.valid_until, self.seller, self.contact_url, received_as
+        );
+
+        if !self.description.is_empty() {
+            result += &format!(", '{}'", self.description);
+        }
+
+        if let Some((mime, data)) = &self.logo {
+            result += &format!(", logo {} {} bytes", mime, data.len());
+        }
+
+        result
+    }
+}
+
+fn required_text(value: &JsonValue, key: &str) -> Result<String, String> {
+    match value.get(key).and_then(JsonValue::to_text) {
+        Some(x) => Ok(x),
+        None => Err(format!("'{}' is required", key)),
+    }
+}
+
+fn optional_text(value: &JsonValue, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(JsonValue::to_text)
+        .unwrap_or_default()
+}
+
+fn read_logo(path: &str) -> Result<(String, Vec<u8>), String> {
+    let mime = match Path::new(path)
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => return Err(format!("seller logo {} must be a .jpg or .png image", path)),
+    };
+
+    match fs::read(path) {
+        Ok(x) => Ok((mime.to_string(), x)),
+        Err(x) => Err(format!("unable to read seller logo {}: {}", path, x)),
+    }
+}
+
+/// Reads ownership and commercial frames from a JSON description:
+/// `{"ownership": {"price_paid": "USD0.99", "purchase_date": "20240131", "seller": "..."},
+/// "commercial": {"prices": "USD0.99", "valid_until": "20251231", "contact_url": "...",
+/// "received_as": 3, "seller": "...", "description": "...", "logo": "logo.png"}}`.
+/// Either object may be omitted, and a relative logo path is resolved against `base_dir`.
+pub fn parse_commercial_json(
+    text: &str,
+    base_dir: &Path,
+) -> Result<(Option<Ownership>, Option<Commercial>), String> {
+    let description = json::parse(text)?;
+
+    let ownership = match description.get("ownership") {
+        Some(x) => {
+            let error = |e: String| format!("ownership: {}", e);
+            let ownership = Ownership {
+                price_paid: required_text(x, "price_paid").map_err(error)?,
+                purchase_date: required_text(x, "purchase_date").map_err(error)?,
+                seller: optional_text(x, "seller"),
+            };
+            check_date(&ownership.purchase_date).map_err(error)?;
+
+            Some(ownership)
+        }
+        None => None,
+    };
+
+    let commercial = match description.get("commercial") {
+        Some(x) => {
+            let error = |e: String| format!("commercial: {}", e);
+
+            let received_as = match x.get("received_as").and_then(JsonValue::as_f64) {
+                None => 0,
+                Some(x) if (0.0..9.0).contains(&x) => x as u8,
+                Some(_) => return Err(error("'received_as' must be 0 to 8".to_string())),
+            };
+
+            let logo = match x.get("logo").and_then(JsonValue::as_str) {
+                Some(x) => Some(read_logo(&base_dir.join(x).to_string_lossy()).map_err(error)?),
+                None => None,
+            };
+
+            let commercial = Commercial {
+                prices: required_text(x, "prices").map_err(error)?,
+                valid_until: required_text(x, "valid_until").map_err(error)?,
+                contact_url: optional_text(x, "contact_url"),
+                received_as,
+                seller: optional_text(x, "seller"),
+                description: optional_text(x, "description"),
+                logo,
+            };
+            check_date(&commercial.valid_until).map_err(error)?;
+
+            Some(commercial)
+        }
+        None => None,
+    };
+
+    Ok((ownership, commercial))
+}
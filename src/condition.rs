@@ -0,0 +1,471 @@
+use crate::{tag::Id3v2Tag, template};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(String),
+    Operator(&'static str),
+}
+
+// Levels of parentheses, negations and chained comparisons a condition can nest, as each is
+// parsed and evaluated recursively
+const MAX_DEPTH: usize = 256;
+
+// Longest first so "<=" isn't read as "<"
+const OPERATORS: [&str; 12] = [
+    "==", "!=", "~=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(x) = OPERATORS.iter().find(|x| rest.starts_with(**x)) {
+            tokens.push(Token::Operator(x));
+            rest = &rest[x.len()..];
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, x)) => value.push(x),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some((idx, x)) if x == c => {
+                        rest = &rest[idx + 2..];
+                        break;
+                    }
+                    Some((_, x)) => value.push(x),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+
+            tokens.push(Token::Literal(value));
+        } else if c.is_alphanumeric() || "_.-:".contains(c) {
+            let end = rest
+                .find(|x: char| !(x.is_alphanumeric() || "_.-:".contains(x)))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+
+            // Numbers and durations such as "4:05" are values, anything else names a field
+            if c.is_ascii_digit() || c == '-' || c == '.' {
+                tokens.push(Token::Literal(word.to_string()));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Field(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Compare(Operand, &'static str, Operand),
+    Missing(String),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn accept(&mut self, operator: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Operator(x)) if *x == operator) {
+            self.position += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        if !self.accept(operator) {
+            return Err(format!("expected '{}'", operator));
+        }
+
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!(
+                "condition is nested more than {} levels deep",
+                MAX_DEPTH
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        let depth = self.depth;
+        let mut left = self.and()?;
+        while self.accept("||") {
+            self.enter()?;
+            left = Expression::Or(Box::new(left), Box::new(self.and()?));
+        }
+        self.depth = depth;
+
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        while self.accept("&&") {
+            self.enter()?;
+            left = Expression::And(Box::new(left), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.accept("!") {
+            self.enter()?;
+            let expression = Expression::Not(Box::new(self.unary()?));
+            self.depth -= 1;
+            return Ok(expression);
+        }
+
+        if self.accept("(") {
+            self.enter()?;
+            let expression = self.or()?;
+            self.expect(")")?;
+            self.depth -= 1;
+            return Ok(expression);
+        }
+
+        let left = match self.next() {
+            Some(Token::Name(x)) if matches!(x.as_str(), "missing" | "present") => {
+                self.expect("(")?;
+                let Some(Token::Name(field)) = self.next() else {
+                    return Err(format!("{}() takes a field name", x));
+                };
+                self.expect(")")?;
+
+                let missing = Expression::Missing(field);
+                return Ok(match x.as_str() {
+                    "missing" => missing,
+                    _ => Expression::Not(Box::new(missing)),
+                });
+            }
+            Some(Token::Name(x)) => Operand::Field(x),
+            Some(Token::Literal(x)) => Operand::Literal(x),
+            Some(Token::Operator(x)) => return Err(format!("unexpected '{}'", x)),
+            None => return Err("unexpected end of condition".to_string()),
+        };
+
+        let operator = match self.next() {
+            Some(Token::Operator(x))
+                if matches!(x, "==" | "!=" | "~=" | "<" | ">" | "<=" | ">=") =>
+            {
+                x
+            }
+            _ => return Err("expected a comparison (==, !=, ~=, <, >, <=, >=)".to_string()),
+        };
+
+        let right = match self.next() {
+            Some(Token::Name(x)) => Operand::Field(x),
+            Some(Token::Literal(x)) => Operand::Literal(x),
+            _ => return Err(format!("expected a value after '{}'", operator)),
+        };
+
+        Ok(Expression::Compare(left, operator, right))
+    }
+}
+
+// Numbers, the leading number of values like "3/12", and durations like "4:05" in seconds
+fn number(value: &str) -> Option<f64> {
+    let value = value.trim();
+
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        return Some(minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?);
+    }
+
+    let end = value
+        .find(|x: char| !(x.is_ascii_digit() || x == '.' || x == '-'))
+        .unwrap_or(value.len());
+
+    value[..end].parse().ok()
+}
+
+/// A `--where` condition deciding which files are modified, e.g.
+/// `genre == "Podcast" && missing(cover)`. Comparisons (`==`, `!=`, `~=` for a case-insensitive
+/// substring, and `<`, `>`, `<=`, `>=` on numbers) take field names or quoted values, and can be
+/// combined with `&&`, `||`, `!` and parentheses. `missing(field)` and `present(field)` test
+/// whether a field has a value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    expression: Expression,
+}
+
+// Fields are those of templates, "cover", raw frame IDs such as "TIT2", and file properties
+fn tag_field(
+    tag: &Id3v2Tag,
+    properties: &[(&str, String)],
+    name: &str,
+) -> Result<Option<String>, String> {
+    if let Some((_, x)) = properties.iter().find(|(x, _)| *x == name) {
+        return Ok(Some(x.clone()));
+    }
+
+    // The picture's MIME type, so a cover without a description still counts
+    if name == "cover" {
+        return Ok(tag.get_cover_art().map(|x| x.mime().to_string()));
+    }
+
+    if let Some(x) = template::tag_value(tag, name) {
+        return Ok(x);
+    }
+
+    if name.len() == 4
+        && name
+            .bytes()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit())
+    {
+        return Ok(tag.get_text_frame(name));
+    }
+
+    Err(format!("unknown field '{}'", name))
+}
+
+struct Context<'a> {
+    field: &'a dyn Fn(&str) -> Result<Option<String>, String>,
+}
+
+impl Context<'_> {
+    fn value(&self, name: &str) -> Result<Option<String>, String> {
+        (self.field)(name)
+    }
+
+    fn operand(&self, operand: &Operand) -> Result<String, String> {
+        match operand {
+            Operand::Field(x) => Ok(self.value(x)?.unwrap_or_default()),
+            Operand::Literal(x) => Ok(x.clone()),
+        }
+    }
+
+    fn evaluate(&self, expression: &Expression) -> Result<bool, String> {
+        match expression {
+            Expression::Or(a, b) => Ok(self.evaluate(a)? || self.evaluate(b)?),
+            Expression::And(a, b) => Ok(self.evaluate(a)? && self.evaluate(b)?),
+            Expression::Not(x) => Ok(!self.evaluate(x)?),
+            Expression::Missing(x) => Ok(self
+                .value(x)?
+                .is_none_or(|x| x.trim_end_matches('\0').is_empty())),
+            Expression::Compare(left, operator, right) => {
+                let left = self.operand(left)?;
+                let right = self.operand(right)?;
+
+                Ok(match *operator {
+                    "==" => left == right,
+                    "!=" => left != right,
+                    "~=" => left.to_lowercase().contains(&right.to_lowercase()),
+                    x => {
+                        let (Some(left), Some(right)) = (number(&left), number(&right)) else {
+                            return Ok(false);
+                        };
+
+                        match x {
+                            "<" => left < right,
+                            ">" => left > right,
+                            "<=" => left <= right,
+                            _ => left >= right,
+                        }
+                    }
+                })
+            }
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            depth: 0,
+        };
+
+        let expression = parser.or()?;
+        if let Some(x) = parser.peek() {
+            let x = match x {
+                Token::Name(x) | Token::Literal(x) => x,
+                Token::Operator(x) => *x,
+            };
+            return Err(format!("unexpected '{}' after the condition", x));
+        }
+
+        Ok(Condition { expression })
+    }
+
+    /// Evaluates the condition against a tag and file properties such as "filename"
+    pub fn matches(&self, tag: &Id3v2Tag, properties: &[(&str, String)]) -> Result<bool, String> {
+        self.matches_fields(&|x| tag_field(tag, properties, x))
+    }
+
+    /// Evaluates the condition with fields looked up by `field`, which returns None for a field
+    /// without a value and an error for an unknown field
+    pub fn matches_fields(
+        &self,
+        field: &dyn Fn(&str) -> Result<Option<String>, String>,
+    ) -> Result<bool, String> {
+        Context { field }.evaluate(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(condition: &str, fields: &[(&str, &str)]) -> Result<bool, String> {
+        Condition::parse(condition)?.matches_fields(&|name| match fields
+            .iter()
+            .find(|(x, _)| *x == name)
+        {
+            Some((_, x)) => Ok(Some(x.to_string())),
+            None if name == "unknown" => Err(format!("unknown field '{}'", name)),
+            None => Ok(None),
+        })
+    }
+
+    #[test]
+    fn tokenizes_operators_values_and_names() {
+        assert_eq!(
+            tokenize(r#"year<=1999 && !(title ~= 'it\'s')"#).unwrap(),
+            [
+                Token::Name("year".to_string()),
+                Token::Operator("<="),
+                Token::Literal("1999".to_string()),
+                Token::Operator("&&"),
+                Token::Operator("!"),
+                Token::Operator("("),
+                Token::Name("title".to_string()),
+                Token::Operator("~="),
+                Token::Literal("it's".to_string()),
+                Token::Operator(")"),
+            ]
+        );
+        assert_eq!(
+            tokenize("duration > 4:05").unwrap()[2],
+            Token::Literal("4:05".to_string())
+        );
+    }
+
+    #[test]
+    fn compares_text_and_numbers() {
+        let fields = [
+            ("genre", "Podcast"),
+            ("track", "3/12"),
+            ("duration", "4:05"),
+        ];
+
+        assert_eq!(matches(r#"genre == "Podcast""#, &fields), Ok(true));
+        assert_eq!(matches("genre != 'Podcast'", &fields), Ok(false));
+        assert_eq!(matches("genre ~= 'podc'", &fields), Ok(true));
+        assert_eq!(matches("track < 10", &fields), Ok(true));
+        assert_eq!(matches("track >= 3", &fields), Ok(true));
+        assert_eq!(matches("duration > 4:00", &fields), Ok(true));
+        assert_eq!(matches("duration <= 240", &fields), Ok(false));
+        // Values that aren't numbers never compare as numbers
+        assert_eq!(matches("genre > 1", &fields), Ok(false));
+        assert_eq!(matches("genre == track", &fields), Ok(false));
+    }
+
+    #[test]
+    fn combines_with_precedence() {
+        let fields = [("artist", "A"), ("album", "B")];
+
+        // && binds tighter than ||
+        assert_eq!(
+            matches("artist == 'X' && album == 'X' || album == 'B'", &fields),
+            Ok(true)
+        );
+        assert_eq!(
+            matches("artist == 'X' && (album == 'X' || album == 'B')", &fields),
+            Ok(false)
+        );
+        assert_eq!(
+            matches("!(artist == 'A') || !!(album == 'B')", &fields),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn tests_missing_and_present_fields() {
+        let fields = [("title", "Title"), ("comment", "\0")];
+
+        assert_eq!(matches("missing(cover)", &fields), Ok(true));
+        assert_eq!(matches("missing(comment)", &fields), Ok(true));
+        assert_eq!(
+            matches("present(title) && missing(album)", &fields),
+            Ok(true)
+        );
+        assert!(matches("missing('title')", &fields).is_err());
+        assert!(matches("unknown == 'x'", &fields).is_err());
+    }
+
+    #[test]
+    fn refuses_malformed_conditions() {
+        for condition in [
+            "",
+            "title",
+            "title ==",
+            "title = 'x'",
+            "== 'x'",
+            "'unterminated",
+            "(title == 'x'",
+            "title == 'x')",
+            "title == 'x' album",
+            "title == 'x' &&",
+            "title == 'x' $ 1",
+        ] {
+            assert!(Condition::parse(condition).is_err(), "{}", condition);
+        }
+    }
+
+    #[test]
+    fn refuses_deeply_nested_conditions() {
+        let nested = |depth| "(".repeat(depth) + "a == 1" + &")".repeat(depth);
+        assert!(Condition::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Condition::parse(&nested(MAX_DEPTH + 1)).is_err());
+
+        let error = Condition::parse(&"(".repeat(5000)).unwrap_err();
+        assert!(error.contains("nested more than"), "{}", error);
+        assert!(Condition::parse(&("!".repeat(5000) + "a == 1")).is_err());
+        assert!(Condition::parse(&vec!["a == 1"; 5000].join(" && ")).is_err());
+    }
+}
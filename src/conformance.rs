@@ -0,0 +1,616 @@
+use std::{fs, panic, path::Path, process};
+
+use alloy::{
+    digest, extract,
+    frame_handler::FrameHandlers,
+    json::{self, JsonValue},
+    json_schema, parse,
+    progress::{ProgressSink, Warning},
+    remote, storage,
+    tag::Id3v2Tag,
+    utility, zlib,
+};
+
+// Largest file a manifest can have downloaded
+const MAX_DOWNLOAD: usize = 64 * 1024 * 1024;
+
+/// Run the parser against reference tags and report whether each reads as expected
+#[derive(clap::Args, Debug)]
+pub struct ConformanceArgs {
+    /// Folder of reference files to run instead of the corpus built into alloy: each NAME.mp3 with a NAME.json holding its expected result
+    #[arg(long, value_name = "FOLDER")]
+    corpus: Option<String>,
+
+    /// JSON manifest of files written by other taggers to download and run instead of the built-in corpus, each with its name, URL, SHA-256 and expected result
+    #[arg(long, value_name = "FILE", conflicts_with = "corpus")]
+    manifest: Option<String>,
+
+    /// Folder downloaded manifest files are kept in, by SHA-256, so they are only fetched once
+    #[arg(
+        long,
+        value_name = "FOLDER",
+        default_value = ".alloy-conformance",
+        requires = "manifest"
+    )]
+    cache: String,
+
+    /// Write the built-in corpus to a folder as NAME.mp3 tags and NAME.json expected results, to check other tools against, instead of running it
+    #[arg(long, value_name = "FOLDER", conflicts_with_all = ["corpus", "manifest"])]
+    export: Option<String>,
+
+    /// Output format, the cases failing with their first difference or a JSON report of every case
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    format: String,
+}
+
+// A reference file, or why it couldn't be had, and the result reading it must give
+struct Case {
+    name: String,
+    bytes: Result<Vec<u8>, String>,
+    expected: JsonValue,
+}
+
+// Warning kinds raised while parsing
+#[derive(Default)]
+struct WarningKinds(Vec<&'static str>);
+
+impl ProgressSink for WarningKinds {
+    fn on_warning(&mut self, warning: &Warning) {
+        self.0.push(warning.kind);
+    }
+}
+
+fn frame(id: &str, data: &[u8], size: fn(u32) -> [u8; 4]) -> Vec<u8> {
+    flagged_frame(id, [0x00, 0x00], data, size)
+}
+
+fn flagged_frame(id: &str, flags: [u8; 2], data: &[u8], size: fn(u32) -> [u8; 4]) -> Vec<u8> {
+    let size = size(u32::try_from(data.len()).unwrap());
+    [id.as_bytes(), &size, &flags, data].concat()
+}
+
+fn synchsafe(size: u32) -> [u8; 4] {
+    utility::convert_u32_to_safesynch(size)
+}
+
+fn be32(size: u32) -> [u8; 4] {
+    size.to_be_bytes()
+}
+
+fn header(identifier: &[u8; 3], version: u8, flags: u8, size: usize) -> Vec<u8> {
+    let size = utility::convert_u32_to_safesynch(u32::try_from(size).unwrap());
+    [identifier.as_slice(), &[version, 0x00, flags], &size].concat()
+}
+
+fn tag(version: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+    [header(b"ID3", version, flags, body.len()), body.to_vec()].concat()
+}
+
+// Text frame data in UTF-8 (0x03), or in ISO-8859-1 (0x00) for text only using its characters
+fn text(encoding: u8, text: &str) -> Vec<u8> {
+    let bytes = match encoding {
+        0x00 => text.chars().map(|x| u8::try_from(x).unwrap()).collect(),
+        _ => text.as_bytes().to_vec(),
+    };
+    [vec![encoding], bytes].concat()
+}
+
+fn utf16(big_endian: bool, text: &str) -> Vec<u8> {
+    let units = text.encode_utf16();
+    if big_endian {
+        [vec![0x02], units.flat_map(u16::to_be_bytes).collect()].concat()
+    } else {
+        [
+            vec![0x01, 0xFF, 0xFE],
+            units.flat_map(u16::to_le_bytes).collect(),
+        ]
+        .concat()
+    }
+}
+
+// A 2x1 PNG, enough for its dimensions to be read
+fn png() -> Vec<u8> {
+    [
+        b"\x89PNG\r\n\x1a\n".as_slice(),
+        &13u32.to_be_bytes(),
+        b"IHDR",
+        &2u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        &[0x08, 0x02, 0x00, 0x00, 0x00],
+        &[0x00; 4],
+    ]
+    .concat()
+}
+
+fn builtin_cases() -> Vec<Case> {
+    let title = |x| frame("TIT2", &text(0x03, x), synchsafe);
+    let long_title = "x".repeat(300);
+    let year = text(0x00, "1999");
+    // ID3v2.3 puts the decompressed size, in plain bytes, before the compressed data
+    let v23_compressed = [
+        be32(u32::try_from(year.len()).unwrap()).as_slice(),
+        &zlib::compress(&year),
+    ]
+    .concat();
+    let cases = vec![
+        (
+            "v24_utf8",
+            tag(
+                4,
+                0x00,
+                &[
+                    title("Title"),
+                    frame("TPE1", &text(0x03, "Artist"), synchsafe),
+                    frame("TALB", &text(0x03, "Album"), synchsafe),
+                ]
+                .concat(),
+            ),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Title"]}, {"id": "TPE1", "values": ["Artist"]}, {"id": "TALB", "values": ["Album"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_latin1",
+            tag(4, 0x00, &frame("TIT2", &text(0x00, "Café"), synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Café"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_utf16_bom",
+            tag(4, 0x00, &frame("TIT2", &utf16(false, "Ünïcödé"), synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Ünïcödé"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_utf16be",
+            tag(4, 0x00, &frame("TIT2", &utf16(true, "Ünïcödé"), synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Ünïcödé"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_multiple_values",
+            tag(4, 0x00, &frame("TPE1", &text(0x03, "First\0Second"), synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "TPE1", "values": ["First", "Second"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_user_text",
+            tag(4, 0x00, &frame("TXXX", &text(0x03, "CATALOGNUMBER\0ABC-123"), synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "TXXX", "values": ["CATALOGNUMBER", "ABC-123"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_padding",
+            tag(4, 0x00, &[title("Title"), vec![0x00; 64]].concat()),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Title"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_footer",
+            [
+                tag(4, 0x10, &title("Title")),
+                header(b"3DI", 4, 0x10, title("Title").len()),
+            ]
+            .concat(),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Title"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_extended_header",
+            tag(
+                4,
+                0x40,
+                &[vec![0x00, 0x00, 0x00, 0x06, 0x01, 0x00], title("Title")].concat(),
+            ),
+            r#"{"version": "2.4.0", "frames": [{"id": "TIT2", "values": ["Title"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_picture",
+            tag(
+                4,
+                0x00,
+                &frame(
+                    "APIC",
+                    &[b"\x03image/png\0\x03Front\0".as_slice(), &png()].concat(),
+                    synchsafe,
+                ),
+            ),
+            r#"{"version": "2.4.0", "frames": [{"id": "APIC", "values": ["image/png, Front cover, 2x1, 'Front', 33 bytes"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v24_plain_sizes",
+            tag(4, 0x00, &frame("TIT2", &text(0x03, &long_title), be32)),
+            format!(r#"{{"version": "2.4.0", "frames": [{{"id": "TIT2", "values": ["{}"]}}], "warnings": ["frame_sizes"]}}"#, long_title),
+        ),
+        (
+            "v24_nonstandard_frame",
+            tag(4, 0x00, &frame("XABC", b"data", synchsafe)),
+            r#"{"version": "2.4.0", "frames": [{"id": "XABC", "values": ["4 bytes"]}], "warnings": ["nonstandard_frame"]}"#.to_string(),
+        ),
+        (
+            "v24_frame_past_end",
+            tag(4, 0x00, &title("Title")[..12]),
+            r#"{"error": true}"#.to_string(),
+        ),
+        (
+            "v23_text",
+            tag(
+                3,
+                0x00,
+                &[
+                    frame("TIT2", &text(0x00, "Title"), be32),
+                    frame("TYER", &text(0x00, "1999"), be32),
+                ]
+                .concat(),
+            ),
+            r#"{"version": "2.3.0", "frames": [{"id": "TIT2", "values": ["Title"]}, {"id": "TYER", "values": ["1999"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v23_extended_header",
+            tag(
+                3,
+                0x40,
+                &[
+                    vec![0x00, 0x00, 0x00, 0x0A, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00],
+                    vec![0x12, 0x34, 0x56, 0x78],
+                    frame("TIT2", &text(0x00, "Title"), be32),
+                ]
+                .concat(),
+            ),
+            r#"{"version": "2.3.0", "frames": [{"id": "TIT2", "values": ["Title"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v23_long_frame",
+            tag(3, 0x00, &frame("TIT2", &text(0x00, &long_title), be32)),
+            format!(r#"{{"version": "2.3.0", "frames": [{{"id": "TIT2", "values": ["{}"]}}], "warnings": []}}"#, long_title),
+        ),
+        (
+            "v23_utf16_bom",
+            tag(3, 0x00, &frame("TPE1", &utf16(false, "Ünïcödé"), be32)),
+            r#"{"version": "2.3.0", "frames": [{"id": "TPE1", "values": ["Ünïcödé"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v23_compressed_frame",
+            tag(
+                3,
+                0x00,
+                &[
+                    frame("TIT2", &text(0x00, "Title"), be32),
+                    flagged_frame("TYER", [0x00, 0x80], &v23_compressed, be32),
+                ]
+                .concat(),
+            ),
+            r#"{"version": "2.3.0", "frames": [{"id": "TIT2", "values": ["Title"]}, {"id": "TYER", "values": ["17 bytes"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "v23_encrypted_frame",
+            tag(
+                3,
+                0x00,
+                &[
+                    flagged_frame("TIT2", [0x00, 0x40], b"\x80\x12\x34\x56\x78", be32),
+                    frame("TPE1", &text(0x00, "Artist"), be32),
+                ]
+                .concat(),
+            ),
+            r#"{"version": "2.3.0", "frames": [{"id": "TIT2", "values": ["5 bytes"]}, {"id": "TPE1", "values": ["Artist"]}], "warnings": []}"#.to_string(),
+        ),
+        (
+            "not_id3v2",
+            b"TAG".iter().copied().chain([0x00; 125]).collect(),
+            r#"{"error": true}"#.to_string(),
+        ),
+    ];
+
+    cases
+        .into_iter()
+        .map(|(name, bytes, expected)| Case {
+            name: name.to_string(),
+            bytes: Ok(bytes),
+            expected: json::parse(&expected).unwrap(),
+        })
+        .collect()
+}
+
+fn read_corpus(folder: &str) -> Result<Vec<Case>, String> {
+    let entries = fs::read_dir(folder).map_err(|x| format!("unable to read {}: {}", folder, x))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.extension().is_some_and(|x| x.eq_ignore_ascii_case("mp3")))
+        .collect();
+    paths.sort();
+
+    let mut cases = vec![];
+    for path in paths {
+        let expected_path = path.with_extension("json");
+        let expected = fs::read_to_string(&expected_path)
+            .map_err(|x| format!("unable to read {}: {}", expected_path.display(), x))
+            .and_then(|x| {
+                json::parse(&x).map_err(|x| format!("{}: {}", expected_path.display(), x))
+            })?;
+
+        cases.push(Case {
+            name: path.file_stem().unwrap().to_string_lossy().to_string(),
+            bytes: fs::read(&path).map_err(|x| format!("unable to read {}: {}", path.display(), x)),
+            expected,
+        });
+    }
+
+    Ok(cases)
+}
+
+// The file at `url`, from the cache when it holds a copy with the right hash
+fn fetch_cached(url: &str, sha256: &str, cache: &str) -> Result<Vec<u8>, String> {
+    let path = Path::new(cache).join(sha256);
+    if let Ok(x) = fs::read(&path) {
+        if digest::to_hex(&digest::sha256(&x)) == sha256 {
+            return Ok(x);
+        }
+    }
+
+    let response = remote::request("GET", url, &[], &[], MAX_DOWNLOAD)?;
+    if response.status != 200 {
+        return Err(format!(
+            "{} responded with HTTP status {}",
+            url, response.status
+        ));
+    }
+
+    let actual = digest::to_hex(&digest::sha256(&response.body));
+    if actual != sha256 {
+        return Err(format!(
+            "{} has SHA-256 {}, the manifest pins {}",
+            url, actual, sha256
+        ));
+    }
+
+    fs::create_dir_all(cache).map_err(|x| format!("unable to create {}: {}", cache, x))?;
+    storage::write(&path.to_string_lossy(), &response.body)?;
+
+    Ok(response.body)
+}
+
+// Cases of a manifest: {"cases": [{"name": ..., "url": ..., "sha256": ..., "expected": {...}}]}.
+// A file that can't be downloaded fails its case rather than the run
+fn read_manifest(path: &str, cache: &str) -> Result<Vec<Case>, String> {
+    let text = fs::read_to_string(path).map_err(|x| format!("unable to read {}: {}", path, x))?;
+    let manifest = json::parse(&text).map_err(|x| format!("{}: {}", path, x))?;
+    let Some(entries) = manifest.get("cases").and_then(JsonValue::as_array) else {
+        return Err(format!(
+            "{}: expected an object with a \"cases\" array",
+            path
+        ));
+    };
+
+    let mut cases = vec![];
+    for entry in entries {
+        let field = |key: &str| {
+            entry
+                .get(key)
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| format!("{}: every case needs a \"{}\" string", path, key))
+        };
+        let (name, url, sha256) = (field("name")?, field("url")?, field("sha256")?);
+        let Some(expected) = entry.get("expected") else {
+            return Err(format!(
+                "{}: case '{}' has no \"expected\" result",
+                path, name
+            ));
+        };
+
+        cases.push(Case {
+            name: name.to_string(),
+            bytes: fetch_cached(url, &sha256.to_lowercase(), cache),
+            expected: expected.clone(),
+        });
+    }
+
+    Ok(cases)
+}
+
+fn tag_json(tag: &Id3v2Tag, warnings: Vec<&'static str>) -> JsonValue {
+    let version = tag.get_version();
+    let frames = tag
+        .frames()
+        .iter()
+        .map(|x| {
+            // User-defined text is compared by its key and value rather than its size
+            let description = match x.user_text() {
+                Some((key, value)) => format!("{}\0{}", key, value),
+                None => x.describe(),
+            };
+            let values = description
+                .split('\0')
+                .filter(|x| !x.is_empty())
+                .map(|x| JsonValue::String(x.to_string()))
+                .collect();
+
+            JsonValue::Object(vec![
+                ("id".to_string(), JsonValue::String(x.id())),
+                ("values".to_string(), JsonValue::Array(values)),
+            ])
+        })
+        .collect();
+
+    JsonValue::Object(vec![
+        (
+            "version".to_string(),
+            JsonValue::String(format!("2.{}.{}", version[0], version[1])),
+        ),
+        ("frames".to_string(), JsonValue::Array(frames)),
+        (
+            "warnings".to_string(),
+            JsonValue::Array(
+                warnings
+                    .into_iter()
+                    .map(|x| JsonValue::String(x.to_string()))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+// What reading a file gives: its version, frames and warning kinds, or an error
+fn read_result(bytes: &[u8]) -> JsonValue {
+    let mut warnings = WarningKinds::default();
+    let result = extract::extract_tag(&bytes.to_vec()).and_then(|(x, _)| {
+        parse::parse_tag_with_progress(
+            &x,
+            &FrameHandlers::default(),
+            parse::ParseOptions::default(),
+            &mut warnings,
+        )
+    });
+
+    match result {
+        Ok((tag, _)) => tag_json(&tag, warnings.0),
+        Err(_) => JsonValue::Object(vec![("error".to_string(), JsonValue::Bool(true))]),
+    }
+}
+
+// The first difference of `actual` from `expected` at `path`. Keys an expected object leaves
+// out aren't checked, so expected results can be kept to what matters to a case
+fn difference(path: &str, expected: &JsonValue, actual: &JsonValue) -> Option<String> {
+    match (expected, actual) {
+        (JsonValue::Object(expected), JsonValue::Object(actual)) => {
+            expected.iter().find_map(|(key, value)| {
+                let path = format!("{}.{}", path, key);
+                match actual.iter().find(|(x, _)| x == key) {
+                    Some((_, x)) => difference(&path, value, x),
+                    None => Some(format!("{}: expected {}, missing", path, value)),
+                }
+            })
+        }
+        (JsonValue::Array(expected), JsonValue::Array(actual)) => expected
+            .iter()
+            .zip(actual)
+            .enumerate()
+            .find_map(|(idx, (expected, actual))| {
+                difference(&format!("{}[{}]", path, idx), expected, actual)
+            })
+            .or_else(|| {
+                (expected.len() != actual.len()).then(|| {
+                    format!(
+                        "{}: expected {} item(s), got {}",
+                        path,
+                        expected.len(),
+                        actual.len()
+                    )
+                })
+            }),
+        _ if expected == actual => None,
+        _ => Some(format!("{}: expected {}, got {}", path, expected, actual)),
+    }
+}
+
+fn export(cases: &[Case], folder: &str) -> Result<(), String> {
+    fs::create_dir_all(folder).map_err(|x| format!("unable to create {}: {}", folder, x))?;
+
+    for case in cases {
+        let path = Path::new(folder).join(&case.name);
+        for (path, contents) in [
+            (path.with_extension("mp3"), case.bytes.clone()?),
+            (
+                path.with_extension("json"),
+                format!("{}\n", case.expected).into_bytes(),
+            ),
+        ] {
+            storage::write(&path.to_string_lossy(), &contents)?;
+        }
+    }
+
+    println!("Wrote {} case(s) to {}", cases.len(), folder);
+    Ok(())
+}
+
+/// Exits with status 1 when any case fails, so a build can gate on it
+pub fn process_conformance(args: &ConformanceArgs) {
+    let cases = match (&args.corpus, &args.manifest) {
+        (Some(x), _) => read_corpus(x),
+        (_, Some(x)) => read_manifest(x, &args.cache),
+        _ => Ok(builtin_cases()),
+    };
+    let cases = match cases {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            process::exit(2);
+        }
+    };
+
+    if let Some(x) = &args.export {
+        if let Err(x) = export(&cases, x) {
+            eprintln!("{}", x);
+            process::exit(2);
+        }
+        return;
+    }
+
+    // A case the parser panics on fails with the panic's message rather than ending the run
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let results: Vec<(&Case, Option<String>)> = cases
+        .iter()
+        .map(|x| {
+            let bytes = match &x.bytes {
+                Ok(x) => x,
+                Err(e) => return (x, Some(format!("unable to get the file: {}", e))),
+            };
+
+            match panic::catch_unwind(|| read_result(bytes)) {
+                Ok(actual) => (x, difference("$", &x.expected, &actual)),
+                Err(payload) => {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|x| x.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    (x, Some(format!("parser panicked: {}", message)))
+                }
+            }
+        })
+        .collect();
+    panic::set_hook(hook);
+    let failed = results.iter().filter(|(_, x)| x.is_some()).count();
+
+    if args.format == "json" {
+        let cases = results
+            .iter()
+            .map(|(case, difference)| {
+                JsonValue::Object(vec![
+                    ("name".to_string(), JsonValue::String(case.name.clone())),
+                    ("pass".to_string(), JsonValue::Bool(difference.is_none())),
+                    (
+                        "difference".to_string(),
+                        difference
+                            .clone()
+                            .map_or(JsonValue::Null, JsonValue::String),
+                    ),
+                ])
+            })
+            .collect();
+
+        println!(
+            "{}",
+            JsonValue::Object(vec![
+                json_schema::version_field(),
+                (
+                    "corpus".to_string(),
+                    JsonValue::String(args.corpus.clone().unwrap_or("built-in".to_string())),
+                ),
+                ("cases".to_string(), JsonValue::Number(results.len() as f64)),
+                ("failed".to_string(), JsonValue::Number(failed as f64)),
+                ("results".to_string(), JsonValue::Array(cases)),
+            ])
+        );
+    } else {
+        for (case, difference) in &results {
+            match difference {
+                Some(x) => println!("FAIL {} | {}", case.name, x),
+                None => println!("PASS {}", case.name),
+            }
+        }
+
+        println!(
+            "{} of {} case(s) pass",
+            results.len() - failed,
+            results.len()
+        );
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,209 @@
+use std::{fs, path::Path, time::Instant};
+
+use alloy::{
+    cue_sheet::{self, CueSheet},
+    json::JsonValue,
+    mpeg, parse, registration, storage, utility,
+};
+
+/// Import a cue sheet for a single-file album rip
+#[derive(clap::Args, Debug)]
+pub struct CueArgs {
+    /// Path to the .cue file
+    #[arg(long)]
+    cue: String,
+
+    /// Path to the MP3 file the cue sheet describes
+    #[arg(short, long, alias = "input")]
+    input_file: String,
+
+    /// Path to output tagged file
+    #[arg(short, long, required_unless_present = "split_metadata_only")]
+    output_file: Option<String>,
+
+    /// Print per-track tags (JSON, or CSV with --csv) for splitting tools instead of writing chapters
+    #[arg(long)]
+    split_metadata_only: bool,
+
+    /// Print per-track tags as CSV instead of JSON
+    #[arg(long, requires = "split_metadata_only")]
+    csv: bool,
+
+    /// Record the tagging time (TDTG) and the alloy version (TSSE/TENC) in the file written
+    #[arg(long)]
+    stamp: bool,
+
+    /// Write the file even if encrypted frames, or frames grouped with them, would be lost or altered
+    #[arg(long)]
+    force: bool,
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// Tags of each track, in the order they're printed
+fn track_metadata(sheet: &CueSheet, duration_ms: u32) -> Vec<Vec<(&'static str, String)>> {
+    let count = sheet.tracks.len();
+
+    sheet
+        .tracks
+        .iter()
+        .zip(sheet.track_ends(duration_ms))
+        .map(|(track, end_ms)| {
+            vec![
+                ("track_number", format!("{}/{}", track.number, count)),
+                ("title", track.title.clone().unwrap_or_default()),
+                (
+                    "artist",
+                    track
+                        .performer
+                        .clone()
+                        .or(sheet.performer.clone())
+                        .unwrap_or_default(),
+                ),
+                ("album", sheet.title.clone().unwrap_or_default()),
+                ("album_artist", sheet.performer.clone().unwrap_or_default()),
+                ("recording_time", sheet.date.clone().unwrap_or_default()),
+                ("genre", sheet.genre.clone().unwrap_or_default()),
+                ("start", utility::format_milliseconds(track.start_ms)),
+                ("end", utility::format_milliseconds(end_ms)),
+            ]
+        })
+        .collect()
+}
+
+fn print_metadata(sheet: &CueSheet, duration_ms: u32, csv: bool) {
+    let tracks = track_metadata(sheet, duration_ms);
+
+    if csv {
+        if let Some(x) = tracks.first() {
+            let header: Vec<&str> = x.iter().map(|(key, _)| *key).collect();
+            println!("{}", header.join(","));
+        }
+
+        for track in &tracks {
+            let values: Vec<String> = track.iter().map(|(_, value)| csv_field(value)).collect();
+            println!("{}", values.join(","));
+        }
+
+        return;
+    }
+
+    let tracks = tracks
+        .into_iter()
+        .map(|x| {
+            JsonValue::Object(
+                x.into_iter()
+                    .map(|(key, value)| (key.to_string(), JsonValue::String(value)))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    println!("{}", JsonValue::Array(tracks));
+}
+
+pub fn process_cue(args: &CueArgs) {
+    let now = Instant::now();
+
+    let sheet = match fs::read_to_string(&args.cue)
+        .map_err(|x| format!("unable to read cue sheet {}: {}", args.cue, x))
+        .and_then(|x| cue_sheet::parse_cue(&x).map_err(|e| format!("{}: {}", args.cue, e)))
+    {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let Some(output_file) = &args.output_file else {
+        let duration_ms = match storage::read(&args.input_file)
+            .and_then(|x| parse::parse_bytes(&x).map(|(_, audio_data)| audio_data))
+        {
+            Ok(x) => u32::try_from(mpeg::duration_ms(&x)).unwrap_or(u32::MAX),
+            Err(x) => {
+                eprintln!("{}", x);
+                return;
+            }
+        };
+
+        return print_metadata(&sheet, duration_ms, args.csv);
+    };
+
+    println!("Processing file: {}", args.input_file);
+
+    let _locks = match storage::lock(&[&args.input_file, output_file]) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let (mut tag, audio_data) = match parse::parse_file(Path::new(&args.input_file)) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    let duration_ms = u32::try_from(mpeg::duration_ms(&audio_data)).unwrap_or(u32::MAX);
+    let chapters = match sheet.chapters(duration_ms) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}: {}", args.cue, x);
+            return;
+        }
+    };
+
+    for x in &chapters {
+        println!("{}", x.describe());
+    }
+
+    tag.set_chapters(&chapters);
+
+    if let Some(x) = &sheet.title {
+        tag.set_album_title(x.to_string() + "\0").unwrap();
+    }
+
+    if let Some(x) = &sheet.performer {
+        tag.set_album_artist_name(x.to_string() + "\0").unwrap();
+    }
+
+    if let Some(x) = &sheet.date {
+        tag.set_recording_time(x.to_string() + "\0").unwrap();
+    }
+
+    if let Some(x) = &sheet.genre {
+        tag.set_content_type(x.to_string() + "\0").unwrap();
+    }
+
+    if args.stamp {
+        tag.stamp().unwrap();
+    }
+
+    if !args.force {
+        if let Err(x) = registration::verify_protected_frames(&protected_frames, &tag) {
+            eprintln!("{}", x);
+            return;
+        }
+    }
+
+    match storage::write_verified(output_file, &tag, &audio_data) {
+        Ok(()) => println!(
+            "{:?} | File successfully tagged, saved to {}",
+            now.elapsed(),
+            output_file
+        ),
+        Err(x) => eprintln!("{}", x),
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{chapter::Chapter, utility};
+
+// Cue sheet times are mm:ss:ff with 75 frames per second
+const FRAMES_PER_SECOND: u32 = 75;
+
+/// One TRACK entry of a cue sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Start of the track (INDEX 01) in the audio
+    pub start_ms: u32,
+}
+
+/// Album rip described by a cue sheet: one audio file split into tracks
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// REM DATE, usually the release year
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+// Values are either quoted (and may contain spaces) or a single word
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+
+    match text.strip_prefix('"') {
+        Some(x) => x.split('"').next().unwrap_or("").to_string(),
+        None => text.split_whitespace().next().unwrap_or("").to_string(),
+    }
+}
+
+fn parse_index_time(text: &str) -> Result<u32, String> {
+    let error = || format!("invalid index time '{}', expected mm:ss:ff", text);
+
+    let parts: Vec<u32> = text
+        .split(':')
+        .map(|x| x.parse().map_err(|_| error()))
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    let [minutes, seconds, frames] = parts[..] else {
+        return Err(error());
+    };
+
+    if seconds >= 60 || frames >= FRAMES_PER_SECOND {
+        return Err(error());
+    }
+
+    minutes
+        .checked_mul(60_000)
+        .and_then(|x| x.checked_add(seconds * 1000 + frames * 1000 / FRAMES_PER_SECOND))
+        .ok_or_else(|| format!("index time '{}' is too long", text))
+}
+
+/// Reads the tracks of a cue sheet for a single-file rip
+pub fn parse_cue(text: &str) -> Result<CueSheet, String> {
+    let mut sheet = CueSheet::default();
+    let mut files = 0;
+    // Tracks with whether their INDEX 01 has been seen
+    let mut tracks: Vec<(CueTrack, bool)> = vec![];
+
+    for (idx, line) in text.lines().enumerate() {
+        let error = |x: String| format!("line {}: {}", idx + 1, x);
+        let line = line.trim().trim_start_matches('\u{feff}');
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        match (command.to_ascii_uppercase().as_str(), tracks.last_mut()) {
+            ("FILE", _) => files += 1,
+            ("TRACK", _) => {
+                let number = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|x| x.parse().ok())
+                    .ok_or_else(|| error(format!("invalid track number '{}'", rest)))?;
+
+                let track = CueTrack {
+                    number,
+                    title: None,
+                    performer: None,
+                    start_ms: 0,
+                };
+                tracks.push((track, false));
+            }
+            ("TITLE", Some((track, _))) => track.title = Some(unquote(rest)),
+            ("TITLE", None) => sheet.title = Some(unquote(rest)),
+            ("PERFORMER", Some((track, _))) => track.performer = Some(unquote(rest)),
+            ("PERFORMER", None) => sheet.performer = Some(unquote(rest)),
+            ("INDEX", Some((track, indexed))) => {
+                let mut parts = rest.split_whitespace();
+                if parts.next().and_then(|x| x.parse::<u32>().ok()) == Some(1) {
+                    track.start_ms = parse_index_time(parts.next().unwrap_or("")).map_err(error)?;
+                    *indexed = true;
+                }
+            }
+            ("REM", None) => {
+                let (key, value) = rest
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((rest, ""));
+
+                match key.to_ascii_uppercase().as_str() {
+                    "DATE" => sheet.date = Some(unquote(value)),
+                    "GENRE" => sheet.genre = Some(unquote(value)),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if files > 1 {
+        return Err(format!(
+            "cue sheet references {} audio files, only single-file rips are supported",
+            files
+        ));
+    }
+
+    for (track, indexed) in tracks {
+        if !indexed {
+            return Err(format!("track {} has no INDEX 01", track.number));
+        }
+
+        sheet.tracks.push(track);
+    }
+
+    if sheet.tracks.is_empty() {
+        return Err("cue sheet has no tracks".to_string());
+    }
+
+    if sheet
+        .tracks
+        .windows(2)
+        .any(|x| x[1].start_ms <= x[0].start_ms)
+    {
+        return Err("cue sheet tracks must be in increasing order of start time".to_string());
+    }
+
+    Ok(sheet)
+}
+
+impl CueSheet {
+    /// End of each track: the start of the next, or the end of the audio for the last
+    pub fn track_ends(&self, duration_ms: u32) -> Vec<u32> {
+        self.tracks
+            .iter()
+            .skip(1)
+            .map(|x| x.start_ms)
+            .chain([duration_ms])
+            .collect()
+    }
+
+    /// One chapter per track, titled "Performer - Title" when the track names its own performer
+    pub fn chapters(&self, duration_ms: u32) -> Result<Vec<Chapter>, String> {
+        if let Some(x) = self.tracks.last().filter(|x| x.start_ms >= duration_ms) {
+            return Err(format!(
+                "track {} starts at {} but the audio is only {} long",
+                x.number,
+                utility::format_milliseconds(x.start_ms),
+                utility::format_milliseconds(duration_ms)
+            ));
+        }
+
+        Ok(self
+            .tracks
+            .iter()
+            .zip(self.track_ends(duration_ms))
+            .enumerate()
+            .map(|(idx, (track, end_ms))| Chapter {
+                element_id: format!("chp{}", idx),
+                start_ms: track.start_ms,
+                end_ms,
+                title: match (&track.performer, &track.title) {
+                    (Some(performer), Some(title))
+                        if Some(performer) != self.performer.as_ref() =>
+                    {
+                        Some(format!("{} - {}", performer, title))
+                    }
+                    (_, title) => title.clone(),
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUE: &str = "\u{feff}REM GENRE \"Electronic\"\r
+REM DATE 1999\r
+PERFORMER \"Various Artists\"\r
+TITLE \"Compilation\"\r
+FILE \"album.flac\" WAVE\r
+  TRACK 01 AUDIO\r
+    TITLE \"First\"\r
+    PERFORMER \"Various Artists\"\r
+    INDEX 01 00:00:00\r
+  track 02 audio\r
+    title \"Second \"\r
+    performer Someone\r
+    INDEX 00 03:58:00\r
+    INDEX 01 04:00:37\r
+";
+
+    #[test]
+    fn parses_a_single_file_rip() {
+        let sheet = parse_cue(CUE).unwrap();
+
+        assert_eq!(sheet.title.as_deref(), Some("Compilation"));
+        assert_eq!(sheet.performer.as_deref(), Some("Various Artists"));
+        assert_eq!(sheet.date.as_deref(), Some("1999"));
+        assert_eq!(sheet.genre.as_deref(), Some("Electronic"));
+        assert_eq!(
+            sheet
+                .tracks
+                .iter()
+                .map(|x| (x.number, x.title.as_deref(), x.start_ms))
+                .collect::<Vec<_>>(),
+            [(1, Some("First"), 0), (2, Some("Second "), 240493)]
+        );
+
+        let chapters = sheet.chapters(300_000).unwrap();
+        assert_eq!(
+            chapters
+                .iter()
+                .map(|x| (x.start_ms, x.end_ms, x.title.as_deref()))
+                .collect::<Vec<_>>(),
+            [
+                (0, 240493, Some("First")),
+                (240493, 300_000, Some("Someone - Second "))
+            ]
+        );
+        assert!(sheet.chapters(240493).is_err());
+    }
+
+    #[test]
+    fn refuses_malformed_sheets() {
+        let track = |index: &str| format!("TRACK 01 AUDIO\nINDEX 01 {}", index);
+
+        for (text, error) in [
+            ("".to_string(), "cue sheet has no tracks"),
+            (
+                "TITLE \"Only a title\"".to_string(),
+                "cue sheet has no tracks",
+            ),
+            ("TRACK".to_string(), "line 1: invalid track number ''"),
+            (
+                "TRACK one AUDIO".to_string(),
+                "line 1: invalid track number 'one AUDIO'",
+            ),
+            (
+                "TRACK 01 AUDIO\nINDEX 00 00:00:00".to_string(),
+                "track 1 has no INDEX 01",
+            ),
+            (
+                track(""),
+                "line 2: invalid index time '', expected mm:ss:ff",
+            ),
+            (track("00:00"), "invalid index time '00:00'"),
+            (track("00:00:00:00"), "invalid index time '00:00:00:00'"),
+            (track("-1:00:00"), "invalid index time '-1:00:00'"),
+            (track("00:60:00"), "invalid index time '00:60:00'"),
+            (track("00:00:75"), "invalid index time '00:00:75'"),
+            (
+                track("99999999:00:00"),
+                "index time '99999999:00:00' is too long",
+            ),
+            (
+                "FILE a.wav WAVE\nFILE b.wav WAVE\n".to_string() + &track("00:00:00"),
+                "references 2 audio files",
+            ),
+            (
+                track("01:00:00") + "\nTRACK 02 AUDIO\nINDEX 01 00:30:00",
+                "increasing order of start time",
+            ),
+        ] {
+            let result = parse_cue(&text).unwrap_err();
+            assert!(result.contains(error), "{:?}: {}", text, result);
+        }
+    }
+}
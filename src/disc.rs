@@ -0,0 +1,153 @@
+use crate::{digest, utility};
+
+// Leadin before the first track, which MusicBrainz counts as part of every track offset
+const LEADIN_SECTORS: u32 = 150;
+const SECTORS_PER_SECOND: u32 = 75;
+const LEAD_OUT_TRACK: u8 = 0xAA;
+
+/// Contents of an MCDI frame: the table of contents of the CD the file was ripped from
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableOfContents {
+    pub first_track: u8,
+    pub last_track: u8,
+    /// Start of each track from `first_track` on, in sectors (logical block addresses)
+    pub track_offsets: Vec<u32>,
+    /// End of the last track, in sectors
+    pub lead_out: u32,
+}
+
+impl TableOfContents {
+    /// Reads the binary TOC a CD drive returns (READ TOC format 0000b) with LBA addresses: a
+    /// 4-byte header followed by an 8-byte descriptor per track and one for the lead-out
+    pub fn parse(data: &[u8]) -> Result<TableOfContents, String> {
+        if data.len() < 4 {
+            return Err("table of contents is truncated".to_string());
+        }
+
+        let first_track = data[2];
+        let last_track = data[3];
+        let mut track_offsets = vec![];
+        let mut lead_out = None;
+
+        for descriptor in data[4..].chunks(8) {
+            if descriptor.len() < 8 {
+                return Err("table of contents is truncated".to_string());
+            }
+
+            let offset =
+                u32::from_be_bytes([descriptor[4], descriptor[5], descriptor[6], descriptor[7]]);
+
+            match descriptor[2] {
+                LEAD_OUT_TRACK => lead_out = Some(offset),
+                x if (first_track..=last_track).contains(&x) => track_offsets.push(offset),
+                x => return Err(format!("unexpected track number {}", x)),
+            }
+        }
+
+        let Some(lead_out) = lead_out else {
+            return Err("table of contents has no lead-out".to_string());
+        };
+
+        if track_offsets.is_empty()
+            || last_track < first_track
+            || track_offsets.len() != usize::from(last_track - first_track) + 1
+        {
+            return Err(format!(
+                "table of contents lists {} tracks for tracks {} to {}",
+                track_offsets.len(),
+                first_track,
+                last_track
+            ));
+        }
+
+        Ok(TableOfContents {
+            first_track,
+            last_track,
+            track_offsets,
+            lead_out,
+        })
+    }
+
+    /// MusicBrainz disc ID, used to look the CD up at https://musicbrainz.org/cdtoc/<id>
+    pub fn musicbrainz_disc_id(&self) -> String {
+        let mut text = format!(
+            "{:02X}{:02X}{:08X}",
+            self.first_track,
+            self.last_track,
+            self.lead_out + LEADIN_SECTORS
+        );
+
+        // Always the offsets of tracks 1 to 99, with 0 for tracks the disc doesn't have
+        for track in 1..=99u8 {
+            let offset = track
+                .checked_sub(self.first_track)
+                .and_then(|x| self.track_offsets.get(usize::from(x)))
+                .map_or(0, |x| x + LEADIN_SECTORS);
+            text += &format!("{:08X}", offset);
+        }
+
+        // Base64 with the URL-safe substitutions MusicBrainz uses
+        utility::encode_base64(&digest::sha1(text.as_bytes()))
+            .replace('+', ".")
+            .replace('/', "_")
+            .replace('=', "-")
+    }
+
+    pub fn describe(&self) -> String {
+        let length_ms = u64::from(self.lead_out.saturating_sub(self.track_offsets[0])) * 1000
+            / u64::from(SECTORS_PER_SECOND);
+
+        format!(
+            "tracks {}-{}, {} long, MusicBrainz disc ID {}",
+            self.first_track,
+            self.last_track,
+            utility::format_milliseconds(u32::try_from(length_ms).unwrap_or(u32::MAX)),
+            self.musicbrainz_disc_id()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example disc of https://musicbrainz.org/doc/Disc_ID_Calculation, whose offsets there
+    // include the leadin
+    fn example() -> TableOfContents {
+        TableOfContents {
+            first_track: 1,
+            last_track: 6,
+            track_offsets: [150, 15363, 32314, 46592, 63414, 80489]
+                .iter()
+                .map(|x| x - LEADIN_SECTORS)
+                .collect(),
+            lead_out: 95462 - LEADIN_SECTORS,
+        }
+    }
+
+    #[test]
+    fn computes_the_musicbrainz_disc_id() {
+        assert_eq!(
+            example().musicbrainz_disc_id(),
+            "49HHV7Eb8UKF3aQiNmu1GR8vKTY-"
+        );
+    }
+
+    #[test]
+    fn parses_a_drive_toc() {
+        let toc = example();
+        let mut data = vec![0, 0, toc.first_track, toc.last_track];
+        for (track, offset) in (1..)
+            .zip(&toc.track_offsets)
+            .chain([(LEAD_OUT_TRACK, &toc.lead_out)])
+        {
+            data.extend([0, 0x10, track, 0]);
+            data.extend(offset.to_be_bytes());
+        }
+
+        assert_eq!(TableOfContents::parse(&data), Ok(toc));
+        assert!(TableOfContents::parse(&data[..data.len() - 8]).is_err());
+        assert!(TableOfContents::parse(&data[..data.len() - 1]).is_err());
+        assert!(TableOfContents::parse(&data[..12]).is_err());
+    }
+}
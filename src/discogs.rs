@@ -0,0 +1,175 @@
+use std::env;
+
+use crate::{
+    chapter,
+    json::{self, JsonValue},
+    remote,
+};
+
+// Responses are a few hundred kilobytes at most, even for large box sets
+const MAX_RESPONSE_SIZE: usize = 8 * 1024 * 1024;
+
+/// One entry of a release's tracklist
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseTrack {
+    /// Position on the release, e.g. "A1" or "2-05"
+    pub position: String,
+    pub title: String,
+    /// Track artists, when they differ from the release artists
+    pub artists: Option<String>,
+    pub duration_ms: Option<u32>,
+}
+
+/// The parts of a Discogs release alloy maps onto tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub title: String,
+    pub artists: String,
+    pub year: Option<u32>,
+    pub labels: Vec<String>,
+    pub genres: Vec<String>,
+    /// Playable tracks only, headings and index entries are left out
+    pub tracks: Vec<ReleaseTrack>,
+}
+
+// Discogs disambiguates artists sharing a name with a numeric suffix, e.g. "Nirvana (2)"
+fn strip_disambiguation(name: &str) -> &str {
+    match name
+        .trim_end()
+        .strip_suffix(')')
+        .and_then(|x| x.rsplit_once(" ("))
+    {
+        Some((name, number)) if number.chars().all(|x| x.is_ascii_digit()) => name,
+        _ => name.trim_end(),
+    }
+}
+
+// Artist credits joined the way Discogs displays them, e.g. "A & B feat. C"
+fn artist_credit(value: Option<&JsonValue>) -> Option<String> {
+    let artists = value?.as_array()?;
+    let mut result = String::new();
+
+    for (idx, artist) in artists.iter().enumerate() {
+        // "anv" is the name variation credited on this release
+        let name = artist
+            .get("anv")
+            .and_then(JsonValue::as_str)
+            .filter(|x| !x.is_empty())
+            .or(artist.get("name").and_then(JsonValue::as_str))?;
+        result += strip_disambiguation(name);
+
+        let join = artist.get("join").and_then(JsonValue::as_str).unwrap_or("");
+        if idx + 1 < artists.len() {
+            match join.trim() {
+                "" | "," => result += ", ",
+                x => result += &format!(" {} ", x),
+            }
+        }
+    }
+
+    if result.is_empty() {
+        return None;
+    }
+
+    Some(result)
+}
+
+fn strings(value: Option<&JsonValue>, key: Option<&str>) -> Vec<String> {
+    value
+        .and_then(JsonValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|x| match key {
+            Some(key) => x.get(key).and_then(JsonValue::as_str),
+            None => x.as_str(),
+        })
+        .map(|x| strip_disambiguation(x).to_string())
+        .collect()
+}
+
+/// Reads a release in the format the Discogs API (and its JSON export) returns
+pub fn parse_release(text: &str) -> Result<Release, String> {
+    let release = json::parse(text)?;
+
+    let Some(title) = release.get("title").and_then(JsonValue::as_str) else {
+        return Err("release has no title".to_string());
+    };
+
+    let Some(tracklist) = release.get("tracklist").and_then(JsonValue::as_array) else {
+        return Err("release has no tracklist".to_string());
+    };
+
+    let tracks = tracklist
+        .iter()
+        .filter(|x| {
+            matches!(
+                x.get("type_").and_then(JsonValue::as_str),
+                None | Some("track")
+            )
+        })
+        .map(|x| ReleaseTrack {
+            position: x
+                .get("position")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("")
+                .to_string(),
+            title: x
+                .get("title")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("")
+                .to_string(),
+            artists: artist_credit(x.get("artists")),
+            duration_ms: x
+                .get("duration")
+                .and_then(JsonValue::as_str)
+                .and_then(|x| chapter::parse_time(x).ok()),
+        })
+        .collect();
+
+    let mut labels = strings(release.get("labels"), Some("name"));
+    labels.dedup();
+
+    let mut genres = strings(release.get("genres"), None);
+    genres.extend(strings(release.get("styles"), None));
+
+    Ok(Release {
+        title: title.to_string(),
+        artists: artist_credit(release.get("artists")).unwrap_or_default(),
+        year: release
+            .get("year")
+            .and_then(JsonValue::as_f64)
+            .filter(|x| *x > 0.0)
+            .map(|x| x as u32),
+        labels,
+        genres,
+        tracks,
+    })
+}
+
+/// Downloads a release from the Discogs API, authenticating with DISCOGS_TOKEN when it is set
+pub fn fetch_release(release_id: u64) -> Result<Release, String> {
+    let url = format!("https://api.discogs.com/releases/{}", release_id);
+
+    // Discogs rejects requests without a User-Agent identifying the application
+    let mut headers = vec![(
+        "User-Agent".to_string(),
+        format!("alloy/{}", env!("CARGO_PKG_VERSION")),
+    )];
+    if let Ok(x) = env::var("DISCOGS_TOKEN") {
+        headers.push(("Authorization".to_string(), format!("Discogs token={}", x)));
+    }
+
+    let response = remote::request("GET", &url, &headers, &[], MAX_RESPONSE_SIZE)?;
+    let text = String::from_utf8_lossy(&response.body);
+
+    if response.status != 200 {
+        return Err(format!(
+            "Discogs returned {} for release {}: {}",
+            response.status,
+            release_id,
+            text.trim()
+        ));
+    }
+
+    parse_release(&text)
+}
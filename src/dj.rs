@@ -0,0 +1,617 @@
+use core::fmt;
+use std::any::Any;
+
+use crate::frame_handler::{CustomFrameData, FrameHandler, FrameHandlers};
+use crate::json::{self, JsonValue};
+use crate::tag::{Frame, Id3v2Tag};
+use crate::utility;
+
+const SERATO_MARKERS2: &str = "Serato Markers2";
+
+// Written after a loop's positions, and the color Serato gives loops, as ARGB
+const SERATO_LOOP_PADDING: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const SERATO_LOOP_COLOR: [u8; 4] = [0x00, 0x27, 0xAA, 0xE1];
+
+// Serato breaks the base64 text into lines, and pads the object with nulls to a minimum size
+const SERATO_LINE_LENGTH: usize = 72;
+const SERATO_MIN_OBJECT_SIZE: usize = 470;
+
+/// GEOB and PRIV frames are where DJ software (Serato, Traktor, Rekordbox) keeps cue points,
+/// beatgrids and analysis data, so they must survive every rewrite untouched
+pub fn is_dj_frame(frame: &Frame) -> bool {
+    matches!(&frame.identifier(), b"GEOB" | b"PRIV")
+}
+
+/// Serialized DJ frames of a tag, used to check that a rewritten tag still contains all of them
+pub fn dj_frame_bytes(tag: &Id3v2Tag) -> Vec<Vec<u8>> {
+    tag.frames()
+        .iter()
+        .filter(|x| is_dj_frame(x))
+        .map(|x| x.into_bytes())
+        .collect()
+}
+
+/// Checks that every DJ frame from `original` is present byte-for-byte in `modified`
+pub fn verify_dj_frames(original: &[Vec<u8>], modified: &Id3v2Tag) -> Result<(), String> {
+    let remaining = dj_frame_bytes(modified);
+    let missing = original.iter().filter(|x| !remaining.contains(x)).count();
+
+    if missing > 0 {
+        return Err(format!(
+            "{} DJ metadata frame(s) (GEOB/PRIV) would be lost or altered, refusing to write (use --preserve-dj-data false to override)",
+            missing
+        ));
+    }
+
+    Ok(())
+}
+
+/// Handlers for the DJ frames alloy can decode
+pub fn handlers() -> FrameHandlers {
+    let mut handlers = FrameHandlers::new();
+    handlers.register(Box::new(SeratoMarkersHandler));
+
+    handlers
+}
+
+/// Description and object data of a GEOB (general encapsulated object) frame payload
+pub fn split_general_object(data: &[u8]) -> Option<(String, &[u8])> {
+    let encoding = *data.first()?;
+    let (_mime, rest) = utility::split_terminated(&data[1..], 0x00)?;
+    let (_filename, rest) = utility::split_terminated(rest, encoding)?;
+    let (description, object) = utility::split_terminated(rest, encoding)?;
+
+    Some((utility::decode_text(encoding, description), object))
+}
+
+#[derive(Debug, Clone)]
+pub struct SeratoCue {
+    pub index: u8,
+    pub position_ms: u32,
+    pub color: [u8; 3],
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeratoLoop {
+    pub index: u8,
+    pub start_ms: u32,
+    pub end_ms: u32,
+    pub locked: bool,
+    pub name: String,
+}
+
+/// Decoded "Serato Markers2" GEOB frame. The original payload is kept and written back as-is,
+/// so decoding can never alter what Serato reads.
+#[derive(Clone)]
+pub struct SeratoMarkers {
+    pub raw: Vec<u8>,
+    pub cues: Vec<SeratoCue>,
+    pub loops: Vec<SeratoLoop>,
+    /// Type and data of the other entries, e.g. the track color and BPM lock
+    pub other_entries: Vec<(String, Vec<u8>)>,
+}
+
+impl fmt::Debug for SeratoMarkers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SeratoMarkers [{} cues, {} loops, {} bytes]",
+            self.cues.len(),
+            self.loops.len(),
+            self.raw.len()
+        )
+    }
+}
+
+fn read_u32(bytes: &[u8], idx: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(idx..idx + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|x| *x == 0x00).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+impl SeratoMarkers {
+    fn parse(object: &[u8]) -> Result<SeratoMarkers, String> {
+        if !object.starts_with(&[0x01, 0x01]) {
+            return Err("unsupported Serato Markers2 version".to_string());
+        }
+
+        // Base64 text runs until the null padding Serato appends
+        let text_end = object[2..]
+            .iter()
+            .position(|x| *x == 0x00)
+            .map_or(object.len(), |x| x + 2);
+        let decoded = utility::decode_base64(&object[2..text_end])?;
+
+        if !decoded.starts_with(&[0x01, 0x01]) {
+            return Err("unsupported Serato Markers2 payload version".to_string());
+        }
+
+        let mut cues = vec![];
+        let mut loops = vec![];
+        let mut other_entries = vec![];
+        let mut idx = 2;
+
+        while idx < decoded.len() && decoded[idx] != 0x00 {
+            let name_end = match decoded[idx..].iter().position(|x| *x == 0x00) {
+                Some(x) => idx + x,
+                None => break,
+            };
+            let entry_type = String::from_utf8_lossy(&decoded[idx..name_end]).to_string();
+
+            let Some(length) = read_u32(&decoded, name_end + 1) else {
+                return Err(format!("truncated Serato {} entry", entry_type));
+            };
+            let start = name_end + 5;
+            let end = start + usize::try_from(length).unwrap();
+            let Some(entry) = decoded.get(start..end) else {
+                return Err(format!("truncated Serato {} entry", entry_type));
+            };
+
+            match entry_type.as_str() {
+                "CUE" if entry.len() >= 12 => cues.push(SeratoCue {
+                    index: entry[1],
+                    position_ms: read_u32(entry, 2).unwrap(),
+                    color: [entry[7], entry[8], entry[9]],
+                    name: read_name(&entry[12..]),
+                }),
+                "LOOP" if entry.len() >= 19 => loops.push(SeratoLoop {
+                    index: entry[1],
+                    start_ms: read_u32(entry, 2).unwrap(),
+                    end_ms: read_u32(entry, 6).unwrap(),
+                    locked: entry[18] != 0x00,
+                    name: read_name(&entry[19..]),
+                }),
+                // Colors, BPM locks and future entry types are kept as they are
+                _ => other_entries.push((entry_type, entry.to_vec())),
+            }
+
+            idx = end;
+        }
+
+        Ok(SeratoMarkers {
+            raw: vec![],
+            cues,
+            loops,
+            other_entries,
+        })
+    }
+
+    /// Markers holding these cues and loops, with their GEOB payload encoded the way Serato
+    /// writes it
+    pub fn new(
+        cues: Vec<SeratoCue>,
+        loops: Vec<SeratoLoop>,
+        other_entries: Vec<(String, Vec<u8>)>,
+    ) -> SeratoMarkers {
+        let mut markers = SeratoMarkers {
+            raw: vec![],
+            cues,
+            loops,
+            other_entries,
+        };
+        markers.raw = markers.encode();
+
+        markers
+    }
+
+    // GEOB payload: the object is "\x01\x01" and the base64 of the entries, in lines, padded
+    fn encode(&self) -> Vec<u8> {
+        let mut entries = vec![0x01, 0x01];
+        let mut push_entry = |entry_type: &str, data: &[u8]| {
+            entries.extend_from_slice(entry_type.as_bytes());
+            entries.push(0x00);
+            entries.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+            entries.extend_from_slice(data);
+        };
+
+        for (entry_type, data) in self.other_entries.iter().filter(|(x, _)| x == "COLOR") {
+            push_entry(entry_type, data);
+        }
+
+        for cue in &self.cues {
+            let mut data = vec![0x00, cue.index];
+            data.extend_from_slice(&cue.position_ms.to_be_bytes());
+            data.push(0x00);
+            data.extend_from_slice(&cue.color);
+            data.extend_from_slice(&[0x00, 0x00]);
+            data.extend_from_slice(cue.name.as_bytes());
+            data.push(0x00);
+            push_entry("CUE", &data);
+        }
+
+        for serato_loop in &self.loops {
+            let mut data = vec![0x00, serato_loop.index];
+            data.extend_from_slice(&serato_loop.start_ms.to_be_bytes());
+            data.extend_from_slice(&serato_loop.end_ms.to_be_bytes());
+            data.extend_from_slice(&SERATO_LOOP_PADDING);
+            data.extend_from_slice(&SERATO_LOOP_COLOR);
+            data.push(u8::from(serato_loop.locked));
+            data.extend_from_slice(serato_loop.name.as_bytes());
+            data.push(0x00);
+            push_entry("LOOP", &data);
+        }
+
+        for (entry_type, data) in self.other_entries.iter().filter(|(x, _)| x != "COLOR") {
+            push_entry(entry_type, data);
+        }
+        entries.push(0x00);
+
+        let text = utility::encode_base64(&entries);
+        let text = text.trim_end_matches('=').as_bytes();
+
+        let mut object = vec![0x01, 0x01];
+        for (idx, line) in text.chunks(SERATO_LINE_LENGTH).enumerate() {
+            if idx > 0 {
+                object.push(b'\n');
+            }
+            object.extend_from_slice(line);
+        }
+        object.resize(object.len().max(SERATO_MIN_OBJECT_SIZE), 0x00);
+
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(b"application/octet-stream\0\0");
+        payload.extend_from_slice(SERATO_MARKERS2.as_bytes());
+        payload.push(0x00);
+        payload.extend_from_slice(&object);
+
+        payload
+    }
+
+    /// Cue points and loops as JSON independent of Serato, read back by `parse_cues_json`
+    pub fn to_json(&self) -> JsonValue {
+        let number = |x: u32| JsonValue::Number(f64::from(x));
+
+        let cues = self
+            .cues
+            .iter()
+            .map(|x| {
+                JsonValue::Object(vec![
+                    ("index".to_string(), number(u32::from(x.index))),
+                    ("position_ms".to_string(), number(x.position_ms)),
+                    (
+                        "color".to_string(),
+                        JsonValue::String(format!(
+                            "#{:02X}{:02X}{:02X}",
+                            x.color[0], x.color[1], x.color[2]
+                        )),
+                    ),
+                    ("name".to_string(), JsonValue::String(x.name.clone())),
+                ])
+            })
+            .collect();
+
+        let loops = self
+            .loops
+            .iter()
+            .map(|x| {
+                JsonValue::Object(vec![
+                    ("index".to_string(), number(u32::from(x.index))),
+                    ("start_ms".to_string(), number(x.start_ms)),
+                    ("end_ms".to_string(), number(x.end_ms)),
+                    ("locked".to_string(), JsonValue::Bool(x.locked)),
+                    ("name".to_string(), JsonValue::String(x.name.clone())),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            ("cues".to_string(), JsonValue::Array(cues)),
+            ("loops".to_string(), JsonValue::Array(loops)),
+        ])
+    }
+}
+
+// Reads "#RRGGBB"
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').filter(|x| x.len() == 6)?;
+    let channel = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Reads cue points and loops from the JSON `SeratoMarkers::to_json` writes. Only positions are
+/// required: indexes default to their order, colors to Serato's red, names to none.
+pub fn parse_cues_json(text: &str) -> Result<(Vec<SeratoCue>, Vec<SeratoLoop>), String> {
+    let description = json::parse(text)?;
+
+    let list = |key: &str| match description.get(key) {
+        None => Ok(&[][..]),
+        Some(x) => x.as_array().ok_or(format!("'{}' must be an array", key)),
+    };
+    let number = |value: &JsonValue, key: &str| match value.get(key).and_then(JsonValue::as_f64) {
+        Some(x) if x >= 0.0 && x <= f64::from(u32::MAX) && x.fract() == 0.0 => Ok(Some(x as u32)),
+        Some(_) => Err(format!("'{}' must be a whole number of at least 0", key)),
+        None => Ok(None),
+    };
+    let index = |value: &JsonValue, idx: usize| match number(value, "index")? {
+        Some(x) => u8::try_from(x).map_err(|_| "'index' must be below 256".to_string()),
+        None => u8::try_from(idx).map_err(|_| "more than 256 entries".to_string()),
+    };
+    let name = |value: &JsonValue| {
+        value
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let mut cues = vec![];
+    for (idx, value) in list("cues")?.iter().enumerate() {
+        let error = |x: String| format!("cue {}: {}", idx + 1, x);
+
+        let color = match value.get("color").and_then(JsonValue::as_str) {
+            Some(x) => parse_color(x)
+                .ok_or_else(|| error(format!("invalid color '{}', expected #RRGGBB", x)))?,
+            None => [0xCC, 0x00, 0x00],
+        };
+
+        cues.push(SeratoCue {
+            index: index(value, idx).map_err(error)?,
+            position_ms: number(value, "position_ms")
+                .map_err(error)?
+                .ok_or_else(|| error("'position_ms' is required".to_string()))?,
+            color,
+            name: name(value),
+        });
+    }
+
+    let mut loops = vec![];
+    for (idx, value) in list("loops")?.iter().enumerate() {
+        let error = |x: String| format!("loop {}: {}", idx + 1, x);
+
+        let start_ms = number(value, "start_ms")
+            .map_err(error)?
+            .ok_or_else(|| error("'start_ms' is required".to_string()))?;
+        let end_ms = number(value, "end_ms")
+            .map_err(error)?
+            .ok_or_else(|| error("'end_ms' is required".to_string()))?;
+        if end_ms <= start_ms {
+            return Err(error("'end_ms' must be after 'start_ms'".to_string()));
+        }
+
+        loops.push(SeratoLoop {
+            index: index(value, idx).map_err(error)?,
+            start_ms,
+            end_ms,
+            locked: value
+                .get("locked")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false),
+            name: name(value),
+        });
+    }
+
+    Ok((cues, loops))
+}
+
+/// The decoded "Serato Markers2" frame of a tag, whether or not it was parsed with `handlers`
+pub fn serato_markers(tag: &Id3v2Tag) -> Option<SeratoMarkers> {
+    tag.frames().iter().find_map(|frame| match frame {
+        Frame::Custom(x) => x.data().as_any().downcast_ref::<SeratoMarkers>().cloned(),
+        Frame::Unknown(x) if &x.header.identifier == b"GEOB" => {
+            let (description, object) = split_general_object(&x.data)?;
+            if description != SERATO_MARKERS2 {
+                return None;
+            }
+
+            let mut markers = SeratoMarkers::parse(object).ok()?;
+            markers.raw = x.data.clone();
+            Some(markers)
+        }
+        _ => None,
+    })
+}
+
+/// Replaces the cue points and loops Serato reads from a tag, keeping its other Serato markers
+/// (e.g. the track color)
+pub fn set_serato_cues(tag: &mut Id3v2Tag, cues: Vec<SeratoCue>, loops: Vec<SeratoLoop>) {
+    let other_entries = serato_markers(tag)
+        .map(|x| x.other_entries)
+        .unwrap_or_default();
+
+    tag.set_general_object(SeratoMarkers::new(cues, loops, other_entries).raw);
+}
+
+impl CustomFrameData for SeratoMarkers {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.raw.clone()
+    }
+
+    fn describe(&self) -> String {
+        let mut lines = vec![format!(
+            "Serato markers: {} cue(s), {} loop(s)",
+            self.cues.len(),
+            self.loops.len()
+        )];
+
+        for cue in &self.cues {
+            lines.push(format!(
+                "  Cue {} at {} #{:02X}{:02X}{:02X} '{}'",
+                cue.index + 1,
+                utility::format_milliseconds(cue.position_ms),
+                cue.color[0],
+                cue.color[1],
+                cue.color[2],
+                cue.name
+            ));
+        }
+
+        for serato_loop in &self.loops {
+            lines.push(format!(
+                "  Loop {} from {} to {}{} '{}'",
+                serato_loop.index + 1,
+                utility::format_milliseconds(serato_loop.start_ms),
+                utility::format_milliseconds(serato_loop.end_ms),
+                if serato_loop.locked { " (locked)" } else { "" },
+                serato_loop.name
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Decodes cue points and loops from Serato's "Serato Markers2" GEOB frame
+pub struct SeratoMarkersHandler;
+
+impl FrameHandler for SeratoMarkersHandler {
+    fn frame_id(&self) -> [u8; 4] {
+        *b"GEOB"
+    }
+
+    fn matches(&self, data: &[u8]) -> bool {
+        split_general_object(data).is_some_and(|(description, _)| description == SERATO_MARKERS2)
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Box<dyn CustomFrameData>, String> {
+        let Some((_, object)) = split_general_object(data) else {
+            return Err("malformed GEOB frame".to_string());
+        };
+
+        let mut markers = SeratoMarkers::parse(object)?;
+        markers.raw = data.to_vec();
+
+        Ok(Box::new(markers))
+    }
+}
+
+/// One-line (or multi-line for decoded markers) description of a DJ frame for display
+pub fn describe_dj_frame(frame: &Frame) -> String {
+    match frame {
+        Frame::Unknown(x) if &x.header.identifier == b"GEOB" => {
+            match split_general_object(&x.data) {
+                Some((description, object)) => format!("'{}', {} bytes", description, object.len()),
+                None => format!("malformed, {} bytes", x.data.len()),
+            }
+        }
+        Frame::Unknown(x) if &x.header.identifier == b"PRIV" => {
+            let (owner, data) = utility::split_terminated(&x.data, 0x00).unwrap_or((&[], &x.data));
+            format!(
+                "owner '{}', {} bytes",
+                utility::decode_text(0x00, owner),
+                data.len()
+            )
+        }
+        x => x.describe(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use crate::tag::fixtures::{frame, tag_bytes};
+
+    // A Serato Markers2 object as Serato DJ Pro writes it: two cues, a locked loop, the track
+    // color and the BPM lock, base64 in lines of 72 and padded with nulls
+    const SERATO_MARKERS2_OBJECT: &str = "\
+AQFDT0xPUgAAAAAEAP///0NVRQAAAAASAAAAAB9AAMwAAAAASW50cm8AQ1VFAAAAABEAAwAB
+c5MAAMwAAABEcm9wAExPT1AAAAAAFAAAAAB9AAAAnED/////ACeq4QEAQlBNTE9DSwAAAAAB
+AAA";
+
+    fn geob_payload() -> Vec<u8> {
+        let mut object = vec![0x01, 0x01];
+        object.extend_from_slice(SERATO_MARKERS2_OBJECT.as_bytes());
+        object.resize(SERATO_MIN_OBJECT_SIZE, 0x00);
+
+        [
+            b"\x00application/octet-stream\0\0Serato Markers2\0".as_slice(),
+            &object,
+        ]
+        .concat()
+    }
+
+    fn tag_with_dj_frames() -> Vec<u8> {
+        tag_bytes(
+            4,
+            &[
+                frame(b"TIT2", [0x00, 0x00], b"\x03Title", 4),
+                frame(b"GEOB", [0x00, 0x00], &geob_payload(), 4),
+                frame(b"PRIV", [0x00, 0x00], b"TRAKTOR4\0\x01\x02\x03", 4),
+            ],
+        )
+    }
+
+    #[test]
+    fn decodes_serato_cues_and_loops() {
+        let tag = parse::parse_tag_with_handlers(&tag_with_dj_frames(), &handlers()).unwrap();
+        let markers = serato_markers(&tag).unwrap();
+
+        assert_eq!(markers.raw, geob_payload());
+
+        let cues: Vec<_> = markers
+            .cues
+            .iter()
+            .map(|x| (x.index, x.position_ms, x.color, x.name.as_str()))
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                (0, 8000, [0xCC, 0x00, 0x00], "Intro"),
+                (3, 95123, [0x00, 0xCC, 0x00], "Drop")
+            ]
+        );
+
+        let loops: Vec<_> = markers
+            .loops
+            .iter()
+            .map(|x| (x.index, x.start_ms, x.end_ms, x.locked, x.name.as_str()))
+            .collect();
+        assert_eq!(loops, [(0, 32000, 40000, true, "")]);
+
+        let other: Vec<_> = markers
+            .other_entries
+            .iter()
+            .map(|(x, _)| x.as_str())
+            .collect();
+        assert_eq!(other, ["COLOR", "BPMLOCK"]);
+    }
+
+    #[test]
+    fn decodes_serato_markers_without_handlers() {
+        let tag = parse::parse_tag(&tag_with_dj_frames()).unwrap();
+        let markers = serato_markers(&tag).unwrap();
+
+        assert_eq!(markers.cues.len(), 2);
+        assert_eq!(markers.loops.len(), 1);
+    }
+
+    #[test]
+    fn preserves_dj_frames_through_a_rewrite() {
+        for handlers in [handlers(), FrameHandlers::default()] {
+            let mut tag = parse::parse_tag_with_handlers(&tag_with_dj_frames(), &handlers).unwrap();
+            let dj_frames = dj_frame_bytes(&tag);
+            assert_eq!(dj_frames.len(), 2);
+
+            tag.set_song_title("Another title".to_string()).unwrap();
+            let rewritten = parse::parse_tag_with_handlers(&tag.into_bytes(), &handlers).unwrap();
+
+            verify_dj_frames(&dj_frames, &rewritten).unwrap();
+            assert_eq!(serato_markers(&rewritten).unwrap().raw, geob_payload());
+        }
+    }
+
+    #[test]
+    fn refuses_to_lose_dj_frames() {
+        let original = parse::parse_tag(&tag_with_dj_frames()).unwrap();
+        let dj_frames = dj_frame_bytes(&original);
+        let without_dj = parse::parse_tag(&tag_bytes(
+            4,
+            &[frame(b"TIT2", [0x00, 0x00], b"\x03Title", 4)],
+        ))
+        .unwrap();
+
+        assert!(verify_dj_frames(&dj_frames, &without_dj).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{env, fs};
+
+use crate::digest;
+
+/// Method data alloy registers in its ENCR frames, so frames encrypted by other software (whose
+/// methods alloy can't undo) are told apart from its own
+pub const METHOD_DATA: &[u8] = b"alloy-chacha20";
+
+const NONCE_SIZE: usize = 12;
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_SIZE]) -> [u8; 64] {
+    let word = |x: &[u8]| u32::from_le_bytes(x.try_into().unwrap());
+
+    // "expand 32-byte k"
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (x, y) in initial[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *x = word(y);
+    }
+    initial[12] = counter;
+    for (x, y) in initial[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *x = word(y);
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0; 64];
+    for (x, (y, z)) in block.chunks_exact_mut(4).zip(state.iter().zip(initial)) {
+        x.copy_from_slice(&y.wrapping_add(z).to_le_bytes());
+    }
+    block
+}
+
+/// XORs `data` with the ChaCha20 keystream (RFC 8439), starting from block 1
+pub fn chacha20(key: &[u8; 32], nonce: &[u8; NONCE_SIZE], data: &[u8]) -> Vec<u8> {
+    data.chunks(64)
+        .zip(1..)
+        .flat_map(|(chunk, counter)| {
+            let block = chacha20_block(key, counter, nonce);
+            chunk
+                .iter()
+                .zip(block)
+                .map(|(x, y)| x ^ y)
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
+
+/// Key for a passphrase, or for a key given as text in any other form
+pub fn derive_key(passphrase: &str) -> [u8; 32] {
+    digest::sha256(passphrase.as_bytes())
+}
+
+/// Key from the passphrase in `key_file`, or else in ALLOY_ENCRYPTION_KEY, so it never appears on
+/// a command line where other users and shell history can see it. None if neither is given.
+pub fn read_key(key_file: Option<&str>) -> Result<Option<[u8; 32]>, String> {
+    let passphrase = match key_file {
+        Some(x) => {
+            let text = fs::read_to_string(x)
+                .map_err(|e| format!("unable to read encryption key {}: {}", x, e))?;
+            match text.trim_end_matches(['\r', '\n']) {
+                "" => return Err(format!("encryption key file {} is empty", x)),
+                key => key.to_string(),
+            }
+        }
+        None => match env::var("ALLOY_ENCRYPTION_KEY") {
+            Ok(x) if !x.is_empty() => x,
+            _ => return Ok(None),
+        },
+    };
+
+    Ok(Some(derive_key(&passphrase)))
+}
+
+// Derived from the key and the contents, so encrypting the same frame twice gives the same
+// bytes (keeping --deterministic output stable) while different contents never share a nonce
+fn nonce(key: &[u8; 32], frame_id: [u8; 4], data: &[u8]) -> [u8; NONCE_SIZE] {
+    let mac = digest::hmac_sha256(key, &[&frame_id[..], data].concat());
+    mac[..NONCE_SIZE].try_into().unwrap()
+}
+
+/// Encrypts the data of a frame, returning the nonce followed by the ciphertext
+pub fn encrypt(key: &[u8; 32], frame_id: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let nonce = nonce(key, frame_id, data);
+    [nonce.to_vec(), chacha20(key, &nonce, data)].concat()
+}
+
+/// Decrypts what `encrypt` returned. The nonce doubles as a check of the result, so a wrong key
+/// is an error rather than garbage.
+pub fn decrypt(key: &[u8; 32], frame_id: [u8; 4], data: &[u8]) -> Result<Vec<u8>, String> {
+    let Some((nonce, ciphertext)) = data.split_first_chunk::<NONCE_SIZE>() else {
+        return Err("encrypted data is truncated".to_string());
+    };
+
+    let plain = chacha20(key, nonce, ciphertext);
+    if self::nonce(key, frame_id, &plain) != *nonce {
+        return Err("wrong key, or the encrypted data is corrupted".to_string());
+    }
+
+    Ok(plain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 8439, section 2.4.2
+    #[test]
+    fn encrypts_the_rfc_8439_example() {
+        let key: [u8; 32] = std::array::from_fn(|x| x as u8);
+        let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip \
+                      for the future, sunscreen would be it.";
+
+        let cipher = chacha20(&key, &nonce, plain);
+        assert_eq!(
+            digest::to_hex(&cipher),
+            "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b\
+             f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8\
+             07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736\
+             5af90bbf74a35be6b40b8eedf2785e42874d"
+        );
+        assert_eq!(chacha20(&key, &nonce, &cipher), plain);
+    }
+
+    #[test]
+    fn decrypts_what_it_encrypted() {
+        let key = derive_key("passphrase");
+        let plain = vec![7; 200];
+
+        let encrypted = encrypt(&key, *b"COMM", &plain);
+        assert_eq!(encrypted.len(), NONCE_SIZE + plain.len());
+        assert_eq!(decrypt(&key, *b"COMM", &encrypted), Ok(plain.clone()));
+
+        // The nonce comes from the key and contents, so encrypting again gives the same bytes,
+        // while other contents or frames get another
+        assert_eq!(encrypt(&key, *b"COMM", &plain), encrypted);
+        assert_ne!(
+            encrypt(&key, *b"COMM", &plain[1..])[..NONCE_SIZE],
+            encrypted[..NONCE_SIZE]
+        );
+        assert_ne!(
+            encrypt(&key, *b"USLT", &plain)[..NONCE_SIZE],
+            encrypted[..NONCE_SIZE]
+        );
+    }
+
+    #[test]
+    fn refuses_the_wrong_key() {
+        let encrypted = encrypt(&derive_key("right"), *b"TIT2", b"\x03Title");
+
+        assert!(decrypt(&derive_key("wrong"), *b"TIT2", &encrypted).is_err());
+        // Decrypting as another frame, or corrupted data, is refused the same way
+        assert!(decrypt(&derive_key("right"), *b"TALB", &encrypted).is_err());
+        let mut corrupted = encrypted.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&derive_key("right"), *b"TIT2", &corrupted).is_err());
+        assert!(decrypt(&derive_key("right"), *b"TIT2", &encrypted[..NONCE_SIZE - 1]).is_err());
+    }
+}
@@ -1,7 +1,16 @@
 use crate::tag;
 use crate::utility;
 
-pub fn extract_tag(bytes: &Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+/// Splits a file into its ID3v2 tag (header and footer included) and the audio after it
+pub fn extract_tag(bytes: &Vec<u8>) -> Result<(Vec<u8>, Vec<u8>), String> {
+    // Anything shorter can't even hold a tag header
+    if bytes.len() < 10 {
+        return Err(format!(
+            "file too small ({} bytes), not an MP3 with an ID3v2 tag",
+            bytes.len()
+        ));
+    }
+
     // add 10 to include header size
     let total_tag_size =
         utility::convert_safesynch_to_u32(bytes[6], bytes[7], bytes[8], bytes[9]) + 10;
@@ -18,58 +27,50 @@ pub fn extract_tag(bytes: &Vec<u8>) -> (Vec<u8>, Vec<u8>) {
         total_tag_size += 10;
     }
 
-    (
+    if total_tag_size > bytes.len() {
+        return Err(format!(
+            "tag declares {} bytes but the file only has {}",
+            total_tag_size,
+            bytes.len()
+        ));
+    }
+
+    Ok((
         bytes[..total_tag_size].to_vec(),
         bytes[total_tag_size..].to_vec(),
-    )
+    ))
 }
 
-pub fn extract_picture(bytes: &Vec<u8>) -> Result<tag::Picture, String> {
-    let mut encoding_byte: u8 = 0x03;
-    let mut mime_bytes: Vec<u8> = vec![];
-    let mut picture_type_byte = 0x03;
-    let mut description_bytes: Vec<u8> = vec![];
-    let mut data_bytes: Vec<u8> = vec![];
+pub fn extract_picture(bytes: &[u8]) -> Result<tag::Picture, String> {
+    let (encoding_byte, rest) = match bytes.split_first() {
+        Some((x, rest)) => (*x, rest),
+        None => (0x03, bytes),
+    };
 
-    let mut stage = "encoding";
-
-    for byte in bytes.iter() {
-        match stage {
-            "encoding" => {
-                encoding_byte = *byte;
-                stage = "mime";
-            }
-            "mime" => {
-                if *byte == 0x00 {
-                    stage = "type";
-                }
-
-                mime_bytes.push(*byte);
-            }
-            "type" => {
-                picture_type_byte = *byte;
-                stage = "description";
-            }
-            "description" => {
-                if *byte == 0x00 {
-                    stage = "data";
-                }
-
-                description_bytes.push(*byte);
-            }
-            "data" => {
-                data_bytes.push(*byte);
-            }
-            &_ => return Err("warning unknown stage while extracting picture".to_string()),
-        }
-    }
+    // The MIME type and description keep their terminators, the data is taken in one copy
+    let mime_end = rest
+        .iter()
+        .position(|x| *x == 0x00)
+        .map_or(rest.len(), |x| x + 1);
+    let (mime_bytes, rest) = rest.split_at(mime_end);
+    let (picture_type_byte, rest) = match rest.split_first() {
+        Some((x, rest)) => (*x, rest),
+        None => (0x03, rest),
+    };
+    let description_end = rest
+        .iter()
+        .position(|x| *x == 0x00)
+        .map_or(rest.len(), |x| x + 1);
+    let (description_bytes, data_bytes) = rest.split_at(description_end);
 
     Ok(tag::Picture {
         encoding: encoding_byte,
-        mime: String::from_utf8(mime_bytes).unwrap(),
+        mime: String::from_utf8(mime_bytes.to_vec())
+            .map_err(|_| "picture MIME type is not valid text".to_string())?,
         picture_type: picture_type_byte,
-        description: String::from_utf8(description_bytes).unwrap(),
-        data: data_bytes,
+        description: String::from_utf8(description_bytes.to_vec())
+            .map_err(|_| "picture description is not valid UTF-8".to_string())?,
+        data: data_bytes.into(),
     })
 }
 
@@ -0,0 +1,169 @@
+/// A tag field by its friendly name, as used in templates, `--where` conditions and wherever a
+/// frame identifier is taken on the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Field {
+    /// Name shown and documented, e.g. "album_artist"
+    pub name: &'static str,
+    /// Frame holding the field's value, e.g. "TPE2"
+    pub frame_id: &'static str,
+    /// Other names it is known by in players and other taggers, e.g. "tracknumber"
+    pub aliases: &'static [&'static str],
+}
+
+const fn field(
+    name: &'static str,
+    frame_id: &'static str,
+    aliases: &'static [&'static str],
+) -> Field {
+    Field {
+        name,
+        frame_id,
+        aliases,
+    }
+}
+
+/// Every field with a friendly name, each held by a text frame
+pub const FIELDS: [Field; 22] = [
+    field("title", "TIT2", &[]),
+    field("artist", "TPE1", &[]),
+    field("album", "TALB", &[]),
+    field("album_artist", "TPE2", &["band"]),
+    field("date", "TDRC", &["recording_date"]),
+    field("release_date", "TDRL", &[]),
+    field(
+        "original_date",
+        "TDOR",
+        &["original_year", "original_release_date"],
+    ),
+    field("track", "TRCK", &["track_number"]),
+    field("disc", "TPOS", &["disc_number"]),
+    field("genre", "TCON", &[]),
+    field("composer", "TCOM", &[]),
+    field("grouping", "TIT1", &["content_group"]),
+    field("publisher", "TPUB", &["label", "organization"]),
+    field("language", "TLAN", &[]),
+    field("media_type", "TMED", &["media"]),
+    field("original_artist", "TOPE", &[]),
+    field("original_album", "TOAL", &[]),
+    field("original_lyricist", "TOLY", &[]),
+    field("original_filename", "TOFN", &[]),
+    field("movement", "MVNM", &["movement_name"]),
+    field("conductor", "TPE3", &[]),
+    field("compilation", "TCMP", &["itunes_compilation"]),
+];
+
+/// ID3v2.4 frames and the ID3v2.3 frames they replaced. Fields are read from the ID3v2.3 frame
+/// when a tag has only that, and writing a field removes it, as tags are written as ID3v2.4.
+pub const VERSION_ALIASES: [(&str, &str); 3] =
+    [("TDRC", "TYER"), ("TDOR", "TORY"), ("TIPL", "IPLS")];
+
+/// The ID3v2.3 frame an ID3v2.4 frame replaced, e.g. "TYER" for "TDRC"
+pub fn legacy_frame_id(frame_id: &str) -> Option<&'static str> {
+    VERSION_ALIASES
+        .iter()
+        .find(|(x, _)| *x == frame_id)
+        .map(|(_, x)| *x)
+}
+
+// Names compare ignoring case, "_", "-" and spaces, so "albumartist" and "Album Artist" are
+// both "album_artist"
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|x| !matches!(x, '_' | '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The field with this name or alias, e.g. "album_artist", "albumartist" or "Album Artist"
+pub fn find(name: &str) -> Option<&'static Field> {
+    let name = normalize(name);
+
+    FIELDS.iter().find(|x| {
+        normalize(x.name) == name || x.aliases.iter().any(|alias| normalize(alias) == name)
+    })
+}
+
+/// The documented name of a field given by any of its names, or the name as given when it
+/// isn't one, e.g. a frame identifier
+pub fn canonical(name: &str) -> &str {
+    find(name).map_or(name, |x| x.name)
+}
+
+/// Frame identifier of the field with this name or alias
+pub fn frame_id(name: &str) -> Option<[u8; 4]> {
+    find(name).map(|x| *x.frame_id.as_bytes().first_chunk::<4>().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::fixtures::{frame, tag_bytes};
+    use crate::tag::{Id3v2Tag, V23_FLAG_COMPRESSION, V23_FLAG_ENCRYPTION};
+    use crate::{parse, zlib};
+
+    fn v23_tag(frames: &[Vec<u8>]) -> Id3v2Tag {
+        parse::parse_tag(&tag_bytes(3, frames)).unwrap()
+    }
+
+    fn field(tag: &Id3v2Tag, name: &str) -> Option<String> {
+        tag.get_text_frame(find(name)?.frame_id)
+    }
+
+    #[test]
+    fn finds_fields_by_name_and_alias() {
+        assert_eq!(canonical("Album Artist"), "album_artist");
+        assert_eq!(canonical("original_year"), "original_date");
+        assert_eq!(frame_id("recording_date"), Some(*b"TDRC"));
+        assert_eq!(canonical("TXXX"), "TXXX");
+        assert_eq!(legacy_frame_id("TDRC"), Some("TYER"));
+        assert_eq!(legacy_frame_id("TIT2"), None);
+    }
+
+    #[test]
+    fn reads_fields_from_v23_frames() {
+        let tag = v23_tag(&[
+            frame(b"TYER", [0x00, 0x00], b"\x001999", 3),
+            frame(b"TORY", [0x00, 0x00], b"\x001987", 3),
+            frame(b"IPLS", [0x00, 0x00], b"\x00producer\x00Name", 3),
+        ]);
+
+        assert_eq!(field(&tag, "date").as_deref(), Some("1999"));
+        assert_eq!(field(&tag, "original_year").as_deref(), Some("1987"));
+        assert_eq!(
+            tag.get_involved_people(),
+            [("producer".to_string(), "Name".to_string())]
+        );
+    }
+
+    #[test]
+    fn reads_compressed_v23_frames_once_decompressed() {
+        let data = b"\x001999";
+        let compressed = [
+            u32::try_from(data.len()).unwrap().to_be_bytes().as_slice(),
+            &zlib::compress(data),
+        ]
+        .concat();
+        let mut tag = v23_tag(&[frame(b"TYER", [0x00, V23_FLAG_COMPRESSION], &compressed, 3)]);
+
+        assert_eq!(field(&tag, "date"), None);
+        assert_eq!(tag.decompress_frames(), 1);
+        assert_eq!(field(&tag, "date").as_deref(), Some("1999"));
+    }
+
+    #[test]
+    fn leaves_encrypted_v23_frames_unread() {
+        let mut tag = v23_tag(&[
+            frame(b"TYER", [0x00, V23_FLAG_ENCRYPTION], b"\x80\x12\x34\x56", 3),
+            frame(b"IPLS", [0x00, V23_FLAG_ENCRYPTION], b"\x80\x12\x34\x56", 3),
+        ]);
+
+        assert_eq!(field(&tag, "date"), None);
+        assert_eq!(tag.get_involved_people(), []);
+
+        // Still the frames of their fields, so kept and dropped with them
+        tag.keep_frames(&[*b"TDRC", *b"TIPL"]);
+        assert_eq!(tag.frames().len(), 2);
+        tag.drop_frames(&[*b"TDRC"]);
+        assert_eq!(tag.frames()[0].id(), "IPLS");
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+
+use alloy::{
+    cache::{TagCache, TagSummary},
+    collation::Collator,
+    condition::Condition,
+    json::JsonValue,
+    json_schema,
+};
+
+use crate::art;
+
+/// Print the files in a folder whose tags match, e.g. `--artist Radiohead --missing cover`
+#[derive(clap::Args, Debug)]
+pub struct FindArgs {
+    /// Folder searched recursively for MP3 files
+    #[arg(long, required_unless_present = "index")]
+    folder: Option<String>,
+
+    /// Search the files in this index (see `alloy index`) instead of reading every file, limited to --folder if given
+    #[arg(long, value_name = "FILE")]
+    index: Option<String>,
+
+    /// Artist contains this text, ignoring case
+    #[arg(long)]
+    artist: Option<String>,
+
+    /// Album contains this text, ignoring case
+    #[arg(long)]
+    album: Option<String>,
+
+    /// Title contains this text, ignoring case
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Album artist contains this text, ignoring case
+    #[arg(long)]
+    album_artist: Option<String>,
+
+    /// Genre contains this text, ignoring case
+    #[arg(long)]
+    genre: Option<String>,
+
+    /// Field without a value, e.g. cover or year (repeatable)
+    #[arg(long, value_name = "FIELD")]
+    missing: Vec<String>,
+
+    /// Any other condition, in the --where syntax
+    #[arg(long = "where", value_parser = Condition::parse)]
+    condition: Option<Condition>,
+
+    /// Output format, one path per line or a JSON array of paths and tag summaries
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    format: String,
+
+    /// Sort by these fields, e.g. artist,album,track, collated as players list them (see --locale)
+    #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+    sort: Vec<String>,
+
+    /// Locale --sort collates text in, e.g. de-DE, instead of the system locale (needs --features collation)
+    #[arg(long, requires = "sort")]
+    locale: Option<String>,
+
+    /// End each path with a null byte instead of a newline, for `xargs -0`
+    #[arg(short = '0', long, conflicts_with = "format")]
+    null: bool,
+}
+
+fn summaries(args: &FindArgs) -> Result<Vec<(PathBuf, TagSummary)>, String> {
+    if let Some(x) = &args.index {
+        let folder = match &args.folder {
+            Some(x) => {
+                Some(std::fs::canonicalize(x).map_err(|e| format!("unable to read {}: {}", x, e))?)
+            }
+            None => None,
+        };
+
+        let mut entries = TagCache::load(Path::new(x))?.entries();
+        if let Some(folder) = folder {
+            entries.retain(|(path, _)| path.starts_with(&folder));
+        }
+
+        return Ok(entries);
+    }
+
+    let mut files = vec![];
+    art::collect_mp3_files(Path::new(args.folder.as_deref().unwrap_or(".")), &mut files);
+
+    let cache = TagCache::new();
+    let mut entries = vec![];
+    for file in files {
+        match cache.summary(&file) {
+            Ok(x) => entries.push((file, x)),
+            Err(x) => eprintln!("{} | {}", file.display(), x),
+        }
+    }
+
+    Ok(entries)
+}
+
+fn field(path: &Path, summary: &TagSummary, name: &str) -> Result<Option<String>, String> {
+    match name {
+        "path" => Ok(Some(path.display().to_string())),
+        "filename" => Ok(path.file_stem().map(|x| x.to_string_lossy().to_string())),
+        x => summary.field(x),
+    }
+}
+
+// Orders the files found by the --sort fields, each compared by `collator`
+fn sort(
+    args: &FindArgs,
+    found: Vec<(PathBuf, TagSummary)>,
+) -> Result<Vec<(PathBuf, TagSummary)>, String> {
+    let collator = Collator::new(args.locale.as_deref())?;
+
+    let mut keyed = found
+        .into_iter()
+        .map(|(path, summary)| {
+            let keys = args
+                .sort
+                .iter()
+                .map(|x| field(&path, &summary, x))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((keys, (path, summary)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    keyed.sort_by(|(a, _), (b, _)| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| collator.compare_values(x.as_deref(), y.as_deref()))
+            .find(|x| x.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Ok(keyed.into_iter().map(|(_, x)| x).collect())
+}
+
+fn matches(args: &FindArgs, path: &Path, summary: &TagSummary) -> Result<bool, String> {
+    let field = |name: &str| field(path, summary, name);
+
+    let contains = [
+        ("artist", &args.artist),
+        ("album", &args.album),
+        ("title", &args.title),
+        ("album_artist", &args.album_artist),
+        ("genre", &args.genre),
+    ];
+
+    for (name, text) in contains {
+        let Some(text) = text else {
+            continue;
+        };
+
+        let value = field(name)?.unwrap_or_default();
+        if !value.to_lowercase().contains(&text.to_lowercase()) {
+            return Ok(false);
+        }
+    }
+
+    for name in &args.missing {
+        if field(name)?.is_some_and(|x| !x.is_empty()) {
+            return Ok(false);
+        }
+    }
+
+    match &args.condition {
+        Some(x) => x.matches_fields(&field),
+        None => Ok(true),
+    }
+}
+
+pub fn process_find(args: &FindArgs) {
+    let entries = match summaries(args) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let mut found = vec![];
+    for (path, summary) in entries {
+        match matches(args, &path, &summary) {
+            Ok(true) => found.push((path, summary)),
+            Ok(false) => {}
+            Err(x) => {
+                eprintln!("{}", x);
+                return;
+            }
+        }
+    }
+
+    if !args.sort.is_empty() {
+        found = match sort(args, found) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{}", x);
+                return;
+            }
+        };
+    }
+
+    if args.format == "json" {
+        let files = found
+            .iter()
+            .map(|(path, summary)| {
+                let mut fields = vec![(
+                    "path".to_string(),
+                    JsonValue::String(path.display().to_string()),
+                )];
+                if let JsonValue::Object(x) = summary.to_json() {
+                    fields.extend(x);
+                }
+                JsonValue::Object(fields)
+            })
+            .collect();
+
+        println!(
+            "{}",
+            JsonValue::Object(vec![
+                json_schema::version_field(),
+                ("files".to_string(), JsonValue::Array(files)),
+            ])
+        );
+        return;
+    }
+
+    for (path, _) in found {
+        if args.null {
+            print!("{}\0", path.display());
+        } else {
+            println!("{}", path.display());
+        }
+    }
+}
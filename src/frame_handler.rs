@@ -0,0 +1,53 @@
+use core::fmt;
+use std::any::Any;
+
+/// Structured contents of a frame decoded by a [`FrameHandler`]
+pub trait CustomFrameData: fmt::Debug + Send + Sync {
+    /// Serializes the contents back into a frame payload (everything after the frame header)
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Human-readable summary used when displaying the frame
+    fn describe(&self) -> String;
+
+    /// Allows callers to downcast to the handler's concrete type
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Parser for a frame alloy has no built-in support for, such as proprietary GEOB or PRIV data.
+/// Frames matched by a registered handler are parsed into [`CustomFrameData`] instead of being
+/// kept as opaque bytes, and are serialized through it when the tag is written.
+pub trait FrameHandler: Send + Sync {
+    /// Identifier of the frames this handler understands, e.g. `*b"GEOB"`
+    fn frame_id(&self) -> [u8; 4];
+
+    /// Whether this handler should parse a given payload, used to share a frame ID with other
+    /// handlers (e.g. GEOB frames written by different applications)
+    fn matches(&self, _data: &[u8]) -> bool {
+        true
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Box<dyn CustomFrameData>, String>;
+}
+
+/// Set of handlers consulted, in registration order, while parsing frames
+#[derive(Default)]
+pub struct FrameHandlers {
+    handlers: Vec<Box<dyn FrameHandler>>,
+}
+
+impl FrameHandlers {
+    pub fn new() -> FrameHandlers {
+        FrameHandlers::default()
+    }
+
+    pub fn register(&mut self, handler: Box<dyn FrameHandler>) {
+        self.handlers.push(handler);
+    }
+
+    pub fn find(&self, identifier: [u8; 4], data: &[u8]) -> Option<&dyn FrameHandler> {
+        self.handlers
+            .iter()
+            .find(|x| x.frame_id() == identifier && x.matches(data))
+            .map(|x| x.as_ref())
+    }
+}
@@ -0,0 +1,21 @@
+use alloy::{digest, layout, storage};
+
+/// Print a SHA-256 of the audio in each file, leaving out its tags
+#[derive(clap::Args, Debug)]
+pub struct HashArgs {
+    /// Path to the MP3 file (repeatable)
+    #[arg(short, long, required = true)]
+    input_file: Vec<String>,
+}
+
+pub fn process_hash(args: &HashArgs) {
+    for file in &args.input_file {
+        match storage::read(file) {
+            Ok(bytes) => {
+                let audio = &bytes[layout::audio_range(&bytes)];
+                println!("{}  {}", digest::to_hex(&digest::sha256(audio)), file);
+            }
+            Err(x) => eprintln!("{} | {}", file, x),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const JPEG_SIGNATURE: [u8; 2] = [0xFF, 0xD8];
+
+/// What an image's header says about it, without decoding the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageInfo {
+    /// "jpeg", "png" or "gif"
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageInfo {
+    pub fn describe(&self) -> String {
+        format!("{}x{} {}", self.width, self.height, self.format)
+    }
+}
+
+// The IHDR chunk always comes first, right after the signature
+fn png_info(data: &[u8]) -> Option<ImageInfo> {
+    let ihdr = data.get(8..24)?;
+    if &ihdr[4..8] != b"IHDR" {
+        return None;
+    }
+
+    Some(ImageInfo {
+        format: "png",
+        width: u32::from_be_bytes(ihdr[8..12].try_into().unwrap()),
+        height: u32::from_be_bytes(ihdr[12..16].try_into().unwrap()),
+    })
+}
+
+// Walks the marker segments up to the start-of-frame segment, which holds the dimensions
+fn jpeg_info(data: &[u8]) -> Option<ImageInfo> {
+    let mut idx = 2;
+
+    loop {
+        // Markers may be preceded by any number of 0xFF fill bytes
+        while *data.get(idx)? == 0xFF && *data.get(idx + 1)? == 0xFF {
+            idx += 1;
+        }
+
+        if *data.get(idx)? != 0xFF {
+            return None;
+        }
+
+        let marker = *data.get(idx + 1)?;
+        let length = usize::from(u16::from_be_bytes([
+            *data.get(idx + 2)?,
+            *data.get(idx + 3)?,
+        ]));
+
+        match marker {
+            // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC) which share the range
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let segment = data.get(idx + 4..idx + 9)?;
+
+                return Some(ImageInfo {
+                    format: "jpeg",
+                    width: u32::from(u16::from_be_bytes([segment[3], segment[4]])),
+                    height: u32::from(u16::from_be_bytes([segment[1], segment[2]])),
+                });
+            }
+            // Start of scan, image data follows without a frame header having been found
+            0xDA => return None,
+            _ => idx += 2 + length,
+        }
+    }
+}
+
+fn gif_info(data: &[u8]) -> Option<ImageInfo> {
+    let size = data.get(6..10)?;
+
+    Some(ImageInfo {
+        format: "gif",
+        width: u32::from(u16::from_le_bytes([size[0], size[1]])),
+        height: u32::from(u16::from_le_bytes([size[2], size[3]])),
+    })
+}
+
+/// Identifies an image by its signature, returning the lowercase format name ("jpeg", "png",
+/// "gif", "webp", "bmp", "tiff" or "heic")
+pub fn detect_format(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&PNG_SIGNATURE) {
+        Some("png")
+    } else if data.starts_with(&JPEG_SIGNATURE) {
+        Some("jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        Some("webp")
+    } else if data.starts_with(b"BM") {
+        Some("bmp")
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        Some("tiff")
+    } else if data.get(4..8) == Some(b"ftyp")
+        && matches!(data.get(8..12), Some(b"heic" | b"heix" | b"mif1" | b"msf1"))
+    {
+        Some("heic")
+    } else {
+        None
+    }
+}
+
+/// Re-encodes an image in any format the `image` crate decodes as a JPEG
+#[cfg(feature = "convert-art")]
+pub fn convert_to_jpeg(data: &[u8]) -> Result<Vec<u8>, String> {
+    let image =
+        image::load_from_memory(data).map_err(|x| format!("unable to decode image: {}", x))?;
+
+    // JPEG has no alpha channel
+    let image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+    let mut bytes = std::io::Cursor::new(vec![]);
+    image
+        .write_to(&mut bytes, image::ImageFormat::Jpeg)
+        .map_err(|x| format!("unable to encode JPEG: {}", x))?;
+
+    Ok(bytes.into_inner())
+}
+
+/// Re-encodes an image as a JPEG at decreasing quality until it is at most `max_bytes`,
+/// returning the quality used and the encoded image
+#[cfg(feature = "convert-art")]
+pub fn fit_jpeg(data: &[u8], max_bytes: usize) -> Result<(u8, Vec<u8>), String> {
+    let image = image::load_from_memory(data)
+        .map_err(|x| format!("unable to decode image: {}", x))?
+        .to_rgb8();
+
+    let mut size = 0;
+    for quality in (10..=90).rev().step_by(10) {
+        let mut bytes = vec![];
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality)
+            .encode_image(&image)
+            .map_err(|x| format!("unable to encode JPEG: {}", x))?;
+
+        if bytes.len() <= max_bytes {
+            return Ok((quality, bytes));
+        }
+        size = bytes.len();
+    }
+
+    Err(format!(
+        "image is still {} bytes at JPEG quality 10, over the budget of {}",
+        size, max_bytes
+    ))
+}
+
+/// Scales and crops an image to a `size` x `size` PNG, as used for file icon pictures
+#[cfg(feature = "convert-art")]
+pub fn square_png(data: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let image =
+        image::load_from_memory(data).map_err(|x| format!("unable to decode image: {}", x))?;
+
+    let mut bytes = std::io::Cursor::new(vec![]);
+    image
+        .resize_to_fill(size, size, image::imageops::FilterType::Lanczos3)
+        .write_to(&mut bytes, image::ImageFormat::Png)
+        .map_err(|x| format!("unable to encode PNG: {}", x))?;
+
+    Ok(bytes.into_inner())
+}
+
+/// Side of the grayscale thumbnail a perceptual hash is computed from
+pub const PHASH_SIZE: usize = 32;
+
+/// Perceptual hash (pHash) of a `PHASH_SIZE` x `PHASH_SIZE` grayscale thumbnail, given row by
+/// row: the lowest 8x8 frequencies of its discrete cosine transform, one bit each, set when above
+/// their median. The DC term only carries the overall brightness, so its bit is always clear.
+pub fn phash_pixels(pixels: &[u8]) -> u64 {
+    assert_eq!(pixels.len(), PHASH_SIZE * PHASH_SIZE);
+
+    let basis: Vec<[f64; PHASH_SIZE]> = (0..8)
+        .map(|u| {
+            std::array::from_fn(|x| {
+                ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * PHASH_SIZE) as f64)
+                    .cos()
+            })
+        })
+        .collect();
+
+    // The DCT is separable: transform the rows, then the columns of the result
+    let rows: Vec<[f64; 8]> = pixels
+        .chunks_exact(PHASH_SIZE)
+        .map(|row| {
+            std::array::from_fn(|u| {
+                row.iter()
+                    .zip(basis[u])
+                    .map(|(x, c)| f64::from(*x) * c)
+                    .sum()
+            })
+        })
+        .collect();
+
+    let coefficients: Vec<f64> = (0..64)
+        .map(|i| {
+            let (v, u) = (i / 8, i % 8);
+            rows.iter().zip(basis[v]).map(|(row, c)| row[u] * c).sum()
+        })
+        .collect();
+
+    let mut ac = coefficients[1..].to_vec();
+    ac.sort_by(f64::total_cmp);
+    let median = ac[ac.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, x)| **x > median)
+        .fold(0, |hash, (i, _)| hash | (1 << i))
+}
+
+/// Perceptual hash of an image in any format the `image` crate decodes; re-encoded or resized
+/// copies of an image hash a few bits apart, see `phash_pixels`
+#[cfg(feature = "convert-art")]
+pub fn perceptual_hash(data: &[u8]) -> Result<u64, String> {
+    let size = PHASH_SIZE as u32;
+    let pixels = image::load_from_memory(data)
+        .map_err(|x| format!("unable to decode image: {}", x))?
+        .resize_exact(size, size, image::imageops::FilterType::Triangle)
+        .to_luma8()
+        .into_raw();
+
+    Ok(phash_pixels(&pixels))
+}
+
+/// Number of bits two perceptual hashes differ by, 0 for images that look the same
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Reads the format and dimensions of a JPEG, PNG or GIF image from its header, returning None
+/// for other formats or truncated data
+pub fn image_info(data: &[u8]) -> Option<ImageInfo> {
+    if data.starts_with(&PNG_SIGNATURE) {
+        png_info(data)
+    } else if data.starts_with(&JPEG_SIGNATURE) {
+        jpeg_info(data)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        gif_info(data)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,208 @@
+use std::{fs, time::Instant};
+
+use alloy::{
+    discogs::{self, Release, ReleaseTrack},
+    mpeg, parse, registration, storage, tag,
+    values::TrackNumber,
+};
+
+// Largest difference between a file's length and a tracklist duration still counted as a match
+const DURATION_TOLERANCE_MS: u32 = 5000;
+
+/// Apply a release's tracklist and details to the files in a folder
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Folder with the release's files (MP3 files only), modified in place unless --folder-output is given
+    #[arg(long)]
+    folder_input: String,
+
+    /// Folder to output the tagged files to
+    #[arg(long)]
+    folder_output: Option<String>,
+
+    /// Discogs release JSON, as returned by the API
+    #[arg(long, value_name = "FILE", required_unless_present = "discogs_release")]
+    discogs_json: Option<String>,
+
+    /// Discogs release ID to download (needs `--features https`, uses DISCOGS_TOKEN when set)
+    #[arg(long, value_name = "ID", conflicts_with = "discogs_json")]
+    discogs_release: Option<u64>,
+
+    /// Match files to tracks by their track number (TRCK) or by their length
+    #[arg(long = "match", default_value = "number", value_parser = ["number", "duration"])]
+    match_by: String,
+
+    /// Record the tagging time (TDTG) and the alloy version (TSSE/TENC) in every file written
+    #[arg(long)]
+    stamp: bool,
+
+    /// Write files even if encrypted frames, or frames grouped with them, would be lost or altered
+    #[arg(long)]
+    force: bool,
+}
+
+fn leading_number(value: &str) -> Option<usize> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|x| x.is_ascii_digit())
+        .collect();
+
+    digits.parse().ok()
+}
+
+// Index of the tracklist entry for a file, skipping entries already taken by another file
+fn match_track(
+    release: &Release,
+    tag: &tag::Id3v2Tag,
+    duration_ms: u32,
+    match_by: &str,
+    taken: &[usize],
+) -> Result<usize, String> {
+    if match_by == "duration" {
+        let closest = release
+            .tracks
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !taken.contains(idx))
+            .filter_map(|(idx, x)| Some((idx, x.duration_ms?.abs_diff(duration_ms))))
+            .min_by_key(|(_, difference)| *difference);
+
+        return match closest {
+            Some((idx, difference)) if difference <= DURATION_TOLERANCE_MS => Ok(idx),
+            _ => Err("no track in the release has a matching length".to_string()),
+        };
+    }
+
+    let Some(number) = tag.get_track_number().and_then(|x| leading_number(&x)) else {
+        return Err("no track number (TRCK) to match, try --match duration".to_string());
+    };
+
+    match number.checked_sub(1) {
+        Some(idx) if idx < release.tracks.len() => Ok(idx),
+        _ => Err(format!(
+            "track number {} is not on the release, which has {} tracks",
+            number,
+            release.tracks.len()
+        )),
+    }
+}
+
+fn apply_release(tag: &mut tag::Id3v2Tag, release: &Release, idx: usize) {
+    let track: &ReleaseTrack = &release.tracks[idx];
+
+    tag.set_song_title(track.title.clone() + "\0").unwrap();
+    tag.set_song_artist_name(track.artists.clone().unwrap_or(release.artists.clone()) + "\0")
+        .unwrap();
+    tag.set_album_title(release.title.clone() + "\0").unwrap();
+    tag.set_album_artist_name(release.artists.clone() + "\0")
+        .unwrap();
+    tag.set_track(TrackNumber {
+        number: u32::try_from(idx + 1).unwrap(),
+        total: Some(u32::try_from(release.tracks.len()).unwrap()),
+    })
+    .unwrap();
+
+    if let Some(x) = release.year {
+        tag.set_recording_time(format!("{}\0", x)).unwrap();
+    }
+
+    if !release.labels.is_empty() {
+        tag.set_publisher(release.labels.join(", ") + "\0").unwrap();
+    }
+
+    if !release.genres.is_empty() {
+        tag.set_content_type(release.genres.join(", ") + "\0")
+            .unwrap();
+    }
+}
+
+fn import_file(
+    args: &ImportArgs,
+    release: &Release,
+    input: &str,
+    output: &str,
+    taken: &mut Vec<usize>,
+) -> Result<String, String> {
+    let _locks = storage::lock(&[input, output])?;
+    let bytes = storage::read(input)?;
+    let (mut tag, audio_data) = parse::parse_bytes(&bytes)?;
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    let duration_ms = u32::try_from(mpeg::duration_ms(&audio_data)).unwrap_or(u32::MAX);
+    let idx = match_track(release, &tag, duration_ms, &args.match_by, taken)?;
+    taken.push(idx);
+
+    apply_release(&mut tag, release, idx);
+
+    if args.stamp {
+        tag.stamp().unwrap();
+    }
+
+    if !args.force {
+        registration::verify_protected_frames(&protected_frames, &tag)?;
+    }
+
+    storage::write_verified(output, &tag, &audio_data)?;
+
+    let track = &release.tracks[idx];
+    Ok(format!("{} {}", track.position, track.title))
+}
+
+pub fn process_import(args: &ImportArgs) {
+    let now = Instant::now();
+
+    let release = match (&args.discogs_json, args.discogs_release) {
+        (Some(path), _) => fs::read_to_string(path)
+            .map_err(|x| format!("unable to read release {}: {}", path, x))
+            .and_then(|x| discogs::parse_release(&x).map_err(|e| format!("{}: {}", path, e))),
+        (None, Some(id)) => discogs::fetch_release(id),
+        (None, None) => Err("Must provide --discogs-json or --discogs-release".to_string()),
+    };
+
+    let release = match release {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    println!(
+        "Release: {} - {} ({} tracks)",
+        release.artists,
+        release.title,
+        release.tracks.len()
+    );
+
+    let input_path = args.folder_input.trim_end_matches('/');
+    let listing = match storage::for_location(input_path).and_then(|x| x.list(input_path)) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+    for x in &listing.skipped {
+        eprintln!("{} | Skipped, file name is not valid UTF-8", x);
+    }
+    let mut files = listing.locations;
+    files.sort();
+
+    let mut taken = vec![];
+
+    for file in &files {
+        let file_name = storage::file_name(file);
+        let output = match &args.folder_output {
+            Some(x) => storage::join(x, &("tagged-".to_string() + file_name)),
+            None => file.clone(),
+        };
+
+        match import_file(args, &release, file, &output, &mut taken) {
+            Ok(x) => println!("{} | {}", file_name, x),
+            Err(x) => eprintln!("{} | {}", file_name, x),
+        }
+    }
+
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
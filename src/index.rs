@@ -0,0 +1,124 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use alloy::{cache::TagCache, condition::Condition, storage};
+
+use crate::art;
+
+/// Keep an index of the tags in a library for fast queries
+#[derive(clap::Args, Debug)]
+pub struct IndexArgs {
+    #[command(subcommand)]
+    command: IndexCommand,
+
+    /// Index file to build or query
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = ".alloy-index.json",
+        global = true
+    )]
+    index: String,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum IndexCommand {
+    /// Index every MP3 file in a folder (searched recursively), only parsing files that changed since the last build
+    Build { folder: String },
+
+    /// Print the path of every indexed file matching a condition, e.g. 'artist ~= "Boards" && missing(cover)'
+    Query {
+        #[arg(value_parser = Condition::parse)]
+        condition: Condition,
+    },
+}
+
+fn build(index: &Path, folder: &str) -> Result<(), String> {
+    let now = Instant::now();
+    let cache = TagCache::load(index)?;
+
+    // Absolute paths, so files written later from another directory update the same entries
+    let folder =
+        fs::canonicalize(folder).map_err(|x| format!("unable to read {}: {}", folder, x))?;
+    let mut files = vec![];
+    art::collect_mp3_files(&folder, &mut files);
+
+    let mut indexed = 0;
+    for file in &files {
+        match cache.summary(file) {
+            Ok(_) => indexed += 1,
+            Err(x) => eprintln!("{} | {}", file.display(), x),
+        }
+    }
+
+    let removed = cache.prune();
+    cache.save(index)?;
+
+    println!(
+        "Indexed {} of {} file(s) in {}, {} removed file(s) dropped",
+        indexed,
+        files.len(),
+        folder.display(),
+        removed
+    );
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+
+    Ok(())
+}
+
+fn query(index: &Path, condition: &Condition) -> Result<(), String> {
+    if !index.is_file() {
+        return Err(format!(
+            "no index at {}, create one with `alloy index build <FOLDER>`",
+            index.display()
+        ));
+    }
+
+    for (path, summary) in TagCache::load(index)?.entries() {
+        let location = path.display().to_string();
+        let matched = condition.matches_fields(&|name| match name {
+            "path" => Ok(Some(location.clone())),
+            "filename" => Ok(Some(
+                path.file_stem()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            )),
+            x => summary.field(x),
+        })?;
+
+        if matched {
+            println!("{}", location);
+        }
+    }
+
+    Ok(())
+}
+
+/// Refreshes the entry of a file just written in an existing index
+pub fn update_index(index: &str, file: &str) -> Result<(), String> {
+    if storage::is_object_storage(file) {
+        return Ok(());
+    }
+
+    let index = Path::new(index);
+    let cache = TagCache::load(index)?;
+    let file = fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file));
+    cache.summary(&file)?;
+    cache.save(index)
+}
+
+pub fn process_index(args: &IndexArgs) {
+    let index = Path::new(&args.index);
+
+    let result = match &args.command {
+        IndexCommand::Build { folder } => build(index, folder),
+        IndexCommand::Query { condition } => query(index, condition),
+    };
+
+    if let Err(x) = result {
+        eprintln!("{}", x);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::utility;
+
+const HEADER_SIZE: usize = 10;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+/// A size in a tag that a strict parser would reject or misread
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeIssue {
+    /// Position in the file of the header holding the size
+    pub offset: usize,
+    /// Frame identifier, or None for the tag header, extended header and padding
+    pub frame: Option<String>,
+    pub problem: String,
+}
+
+impl SizeIssue {
+    pub fn describe(&self) -> String {
+        format!(
+            "{:#010X} {}: {}",
+            self.offset,
+            self.frame.as_deref().unwrap_or("tag"),
+            self.problem
+        )
+    }
+}
+
+fn is_synchsafe(bytes: &[u8]) -> bool {
+    bytes.iter().all(|x| x & 0x80 == 0)
+}
+
+fn is_frame_identifier(bytes: &[u8]) -> bool {
+    bytes.len() == 4
+        && bytes[0].is_ascii_uppercase()
+        && bytes
+            .iter()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit())
+}
+
+// Whether a frame boundary can be at `idx`: the end of the frames, padding, or another frame
+fn is_boundary(frames: &[u8], idx: usize) -> bool {
+    idx == frames.len()
+        || (idx < frames.len()
+            && (frames[idx] == 0x00
+                || is_frame_identifier(&frames[idx..(idx + 4).min(frames.len())])))
+}
+
+/// Checks the declared sizes of a file's tag and of each frame in it against where their
+/// contents actually end, without modifying anything. Returns an error for files without an
+/// ID3v2 tag, and no issues for a tag strict parsers read as intended.
+pub fn audit_sizes(bytes: &[u8]) -> Result<Vec<SizeIssue>, String> {
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(b"ID3") {
+        return Err("no ID3v2 tag".to_string());
+    }
+
+    let mut issues = vec![];
+    let mut issue = |offset: usize, frame: Option<&str>, problem: String| {
+        issues.push(SizeIssue {
+            offset,
+            frame: frame.map(str::to_string),
+            problem,
+        })
+    };
+
+    let flags = bytes[5];
+    if !is_synchsafe(&bytes[6..10]) {
+        issue(
+            6,
+            None,
+            "tag size is not synchsafe, so parsers disagree on where the audio starts".to_string(),
+        );
+    }
+
+    let tag_size = usize::try_from(utility::convert_safesynch_to_u32(
+        bytes[6], bytes[7], bytes[8], bytes[9],
+    ))
+    .unwrap();
+    let footer_size = if flags & FLAG_FOOTER != 0 {
+        HEADER_SIZE
+    } else {
+        0
+    };
+
+    if HEADER_SIZE + tag_size + footer_size > bytes.len() {
+        issue(
+            6,
+            None,
+            format!(
+                "tag declares {} bytes but the file only has {} after the header",
+                tag_size + footer_size,
+                bytes.len() - HEADER_SIZE
+            ),
+        );
+    }
+
+    let tag_end = (HEADER_SIZE + tag_size).min(bytes.len());
+    let mut start = HEADER_SIZE;
+
+    if flags & FLAG_EXTENDED_HEADER != 0 && tag_end >= HEADER_SIZE + 4 {
+        let size_bytes = &bytes[HEADER_SIZE..HEADER_SIZE + 4];
+        let size = usize::try_from(utility::convert_safesynch_to_u32(
+            size_bytes[0],
+            size_bytes[1],
+            size_bytes[2],
+            size_bytes[3],
+        ))
+        .unwrap();
+
+        if !is_synchsafe(size_bytes) {
+            issue(
+                HEADER_SIZE,
+                None,
+                "extended header size is not synchsafe".to_string(),
+            );
+        }
+
+        if HEADER_SIZE + size > tag_end || size < 6 {
+            issue(
+                HEADER_SIZE,
+                None,
+                format!(
+                    "extended header declares {} bytes, which doesn't fit the tag",
+                    size
+                ),
+            );
+            return Ok(issues);
+        }
+
+        start += size;
+    }
+
+    let frames = &bytes[start..tag_end];
+    let mut idx = 0;
+
+    while idx < frames.len() {
+        let offset = start + idx;
+
+        if frames[idx] == 0x00 {
+            if frames[idx..].iter().any(|x| *x != 0x00) {
+                issue(
+                    offset,
+                    None,
+                    "padding contains non-zero bytes, often a frame written past its size"
+                        .to_string(),
+                );
+            }
+            break;
+        }
+
+        if frames.len() - idx < HEADER_SIZE || !is_frame_identifier(&frames[idx..idx + 4]) {
+            issue(
+                offset,
+                None,
+                format!(
+                    "{} bytes that are neither a frame nor padding",
+                    frames.len() - idx
+                ),
+            );
+            break;
+        }
+
+        let identifier = String::from_utf8_lossy(&frames[idx..idx + 4]).to_string();
+        let size_bytes = &frames[idx + 4..idx + 8];
+        let synchsafe_size = usize::try_from(utility::convert_safesynch_to_u32(
+            size_bytes[0],
+            size_bytes[1],
+            size_bytes[2],
+            size_bytes[3],
+        ))
+        .unwrap();
+        let plain_size = usize::try_from(u32::from_be_bytes([
+            size_bytes[0],
+            size_bytes[1],
+            size_bytes[2],
+            size_bytes[3],
+        ]))
+        .unwrap();
+        let available = frames.len() - idx - HEADER_SIZE;
+
+        let synchsafe_fits =
+            synchsafe_size <= available && is_boundary(frames, idx + HEADER_SIZE + synchsafe_size);
+        let plain_fits =
+            plain_size <= available && is_boundary(frames, idx + HEADER_SIZE + plain_size);
+
+        // ID3v2.3 sizes are plain integers, and some taggers keep writing them in v2.4 tags
+        let size = if !synchsafe_fits && plain_fits && synchsafe_size != plain_size {
+            issue(
+                offset,
+                Some(&identifier),
+                format!(
+                    "size is a plain integer ({} bytes) rather than synchsafe ({} bytes)",
+                    plain_size, synchsafe_size
+                ),
+            );
+            plain_size
+        } else if synchsafe_size > available {
+            issue(
+                offset,
+                Some(&identifier),
+                format!(
+                    "declares {} bytes but only {} remain in the tag",
+                    synchsafe_size, available
+                ),
+            );
+            break;
+        } else if !synchsafe_fits {
+            issue(
+                offset,
+                Some(&identifier),
+                format!(
+                    "declares {} bytes but no frame or padding follows them",
+                    synchsafe_size
+                ),
+            );
+            break;
+        } else {
+            if !is_synchsafe(size_bytes) {
+                issue(
+                    offset,
+                    Some(&identifier),
+                    "size is not synchsafe".to_string(),
+                );
+            }
+            synchsafe_size
+        };
+
+        idx += HEADER_SIZE + size;
+    }
+
+    Ok(issues)
+}
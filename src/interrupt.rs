@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::c_int;
+
+    pub const SIGINT: c_int = 2;
+    pub const SIGTERM: c_int = 15;
+    const SIG_DFL: usize = 0;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+    }
+
+    // Only async-signal-safe work here: record the request, and let a second signal end the
+    // process straight away
+    extern "C" fn on_signal(signum: c_int) {
+        super::INTERRUPTED.store(true, std::sync::atomic::Ordering::SeqCst);
+        unsafe {
+            signal(signum, SIG_DFL);
+        }
+    }
+
+    pub fn install(signum: c_int) {
+        unsafe {
+            signal(signum, on_signal as extern "C" fn(c_int) as usize);
+        }
+    }
+}
+
+/// Catches Ctrl-C and SIGTERM so the file being written is finished, and renamed into place,
+/// before a batch stops. A second signal ends the process as usual.
+pub fn install() {
+    #[cfg(unix)]
+    {
+        unix::install(unix::SIGINT);
+        unix::install(unix::SIGTERM);
+    }
+}
+
+/// Whether a batch should stop before its next file
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,412 @@
+use core::fmt;
+
+// Arrays and objects nested deeper than this are refused, as each level recurses
+const MAX_DEPTH: usize = 128;
+
+/// Minimal JSON document model used for manifests and machine-readable output
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    // Keeps insertion order so output is stable
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(x) => x.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Strings as-is, numbers without a trailing ".0" when integral
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            JsonValue::String(x) => Some(x.clone()),
+            JsonValue::Number(x) if x.fract() == 0.0 => Some(format!("{}", *x as i64)),
+            JsonValue::Number(x) => Some(x.to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for x in text.chars() {
+        match x {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            x if x.is_control() => write!(f, "\\u{:04x}", u32::from(x))?,
+            x => write!(f, "{}", x)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(x) => write!(f, "{}", x),
+            JsonValue::Number(x) if x.is_finite() => write!(f, "{}", x),
+            JsonValue::Number(_) => write!(f, "null"),
+            JsonValue::String(x) => write_escaped(f, x),
+            JsonValue::Array(x) => {
+                write!(f, "[")?;
+                for (idx, value) in x.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(x) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in x.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    idx: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid JSON at byte {}: {}", self.idx, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.idx < self.bytes.len() && self.bytes[self.idx].is_ascii_whitespace() {
+            self.idx += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.idx..].starts_with(literal.as_bytes()) {
+            self.idx += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", literal)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+
+        match self.bytes.get(self.idx) {
+            Some(b'n') => self.expect("null").map(|_| JsonValue::Null),
+            Some(b't') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b'[' | b'{') if self.depth == MAX_DEPTH => {
+                Err(self.error(&format!("nested more than {} levels deep", MAX_DEPTH)))
+            }
+            Some(b'[') => self.parse_nested(Self::parse_array),
+            Some(b'{') => self.parse_nested(Self::parse_object),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<JsonValue, String>,
+    ) -> Result<JsonValue, String> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.idx;
+        while self.idx < self.bytes.len()
+            && matches!(
+                self.bytes[self.idx],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.idx += 1;
+        }
+
+        let text = String::from_utf8_lossy(&self.bytes[start..self.idx]);
+        match text.parse::<f64>() {
+            Ok(x) => Ok(JsonValue::Number(x)),
+            Err(_) => Err(self.error("invalid number")),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.idx..self.idx + 4)
+            .ok_or(self.error("truncated unicode escape"))?;
+        let value = u32::from_str_radix(&String::from_utf8_lossy(digits), 16)
+            .map_err(|_| self.error("invalid unicode escape"))?;
+        self.idx += 4;
+
+        Ok(value)
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut result: Vec<u8> = vec![];
+
+        loop {
+            let Some(byte) = self.bytes.get(self.idx) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.idx += 1;
+
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.bytes.get(self.idx) else {
+                        return Err(self.error("unterminated escape"));
+                    };
+                    self.idx += 1;
+
+                    let x = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            // Surrogate pairs encode characters outside the basic plane
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect("\\u")?;
+                                let low = self.parse_hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            char::from_u32(code).unwrap_or('\u{FFFD}')
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+
+                    let mut buffer = [0; 4];
+                    result.extend_from_slice(x.encode_utf8(&mut buffer).as_bytes());
+                }
+                x => result.push(*x),
+            }
+        }
+
+        String::from_utf8(result).map_err(|_| self.error("string is not valid UTF-8"))
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect("[")?;
+        let mut values = vec![];
+
+        self.skip_whitespace();
+        if self.bytes.get(self.idx) == Some(&b']') {
+            self.idx += 1;
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.bytes.get(self.idx) {
+                Some(b',') => self.idx += 1,
+                Some(b']') => {
+                    self.idx += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect("{")?;
+        let mut entries = vec![];
+
+        self.skip_whitespace();
+        if self.bytes.get(self.idx) == Some(&b'}') {
+            self.idx += 1;
+            return Ok(JsonValue::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+
+            match self.bytes.get(self.idx) {
+                Some(b',') => self.idx += 1,
+                Some(b'}') => {
+                    self.idx += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        idx: 0,
+        depth: 0,
+    };
+
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+
+    if parser.idx != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let value = parse(
+            r#" {"a": [null, true, false, -1.5e3, 0], "b\u00e9": "\"\\\/\b\f\n\r\t",
+                "c": "\ud83c\udfb5", "d": {}, "e": []} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            JsonValue::Object(vec![
+                (
+                    "a".to_string(),
+                    JsonValue::Array(vec![
+                        JsonValue::Null,
+                        JsonValue::Bool(true),
+                        JsonValue::Bool(false),
+                        JsonValue::Number(-1500.0),
+                        JsonValue::Number(0.0),
+                    ])
+                ),
+                (
+                    "bé".to_string(),
+                    JsonValue::String("\"\\/\u{8}\u{c}\n\r\t".to_string())
+                ),
+                ("c".to_string(), JsonValue::String("🎵".to_string())),
+                ("d".to_string(), JsonValue::Object(vec![])),
+                ("e".to_string(), JsonValue::Array(vec![])),
+            ])
+        );
+        // What is written reads back the same
+        assert_eq!(parse(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn refuses_malformed_documents() {
+        for (text, error) in [
+            ("", "byte 0: unexpected end of input"),
+            ("nul", "byte 0: expected 'null'"),
+            ("True", "byte 0: unexpected character"),
+            ("'a'", "byte 0: unexpected character"),
+            ("-", "byte 1: invalid number"),
+            ("1.2.3", "byte 5: invalid number"),
+            ("1 2", "byte 2: trailing characters"),
+            ("[1 2]", "byte 3: expected ',' or ']'"),
+            ("[1,]", "byte 3: unexpected character"),
+            ("[1,", "byte 3: unexpected end of input"),
+            ("{\"a\" 1}", "byte 5: expected ':'"),
+            ("{\"a\":1,}", "byte 7: expected '\"'"),
+            ("{a:1}", "byte 1: expected '\"'"),
+            ("{\"a\":1", "byte 6: expected ',' or '}'"),
+            ("\"open", "byte 5: unterminated string"),
+            ("\"\\", "byte 2: unterminated escape"),
+            ("\"\\x\"", "byte 3: invalid escape"),
+            ("\"\\u12\"", "byte 3: truncated unicode escape"),
+            ("\"\\u12g4\"", "byte 3: invalid unicode escape"),
+            ("\"\\ud83c\"", "byte 7: expected '\\u'"),
+            ("\"\\ud83c\\u0041\"", "byte 13: invalid surrogate pair"),
+        ] {
+            let result = parse(text).unwrap_err();
+            assert!(result.ends_with(error), "{:?}: {}", text, result);
+        }
+
+        // Unpaired low surrogates have no character, so are replaced
+        assert_eq!(
+            parse("\"\\udc00\""),
+            Ok(JsonValue::String("\u{FFFD}".to_string()))
+        );
+    }
+
+    #[test]
+    fn refuses_deeply_nested_values() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+
+        let error = parse(&"[{\"a\":".repeat(60_000)).unwrap_err();
+        assert!(
+            error.contains("nested more than 128 levels deep"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use crate::json::JsonValue;
+
+/// Version of the `--format json` output, given in its `schema_version` field. Raised whenever a
+/// field is removed or renamed or its type changes; fields may be added without raising it.
+pub const SCHEMA_VERSION: f64 = 1.0;
+
+/// The `schema_version` field, first in every object printed with `--format json`
+pub fn version_field() -> (String, JsonValue) {
+    (
+        "schema_version".to_string(),
+        JsonValue::Number(SCHEMA_VERSION),
+    )
+}
+
+fn typed(name: &str) -> JsonValue {
+    JsonValue::Object(vec![(
+        "type".to_string(),
+        JsonValue::String(name.to_string()),
+    )])
+}
+
+fn nullable(name: &str) -> JsonValue {
+    JsonValue::Object(vec![(
+        "type".to_string(),
+        JsonValue::Array(vec![
+            JsonValue::String(name.to_string()),
+            JsonValue::String("null".to_string()),
+        ]),
+    )])
+}
+
+fn array(items: JsonValue) -> JsonValue {
+    JsonValue::Object(vec![
+        ("type".to_string(), JsonValue::String("array".to_string())),
+        ("items".to_string(), items),
+    ])
+}
+
+fn one_of(values: &[&str]) -> JsonValue {
+    JsonValue::Object(vec![
+        ("type".to_string(), JsonValue::String("string".to_string())),
+        (
+            "enum".to_string(),
+            JsonValue::Array(
+                values
+                    .iter()
+                    .map(|x| JsonValue::String(x.to_string()))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn reference(name: &str) -> JsonValue {
+    JsonValue::Object(vec![(
+        "$ref".to_string(),
+        JsonValue::String(format!("#/$defs/{}", name)),
+    )])
+}
+
+fn version() -> JsonValue {
+    JsonValue::Object(vec![(
+        "const".to_string(),
+        JsonValue::Number(SCHEMA_VERSION),
+    )])
+}
+
+// Every property is always present, null when it has no value
+fn object(description: &str, properties: Vec<(&str, JsonValue)>) -> JsonValue {
+    let required = properties
+        .iter()
+        .map(|(x, _)| JsonValue::String(x.to_string()))
+        .collect();
+
+    JsonValue::Object(vec![
+        ("type".to_string(), JsonValue::String("object".to_string())),
+        (
+            "description".to_string(),
+            JsonValue::String(description.to_string()),
+        ),
+        (
+            "properties".to_string(),
+            JsonValue::Object(
+                properties
+                    .into_iter()
+                    .map(|(x, value)| (x.to_string(), value))
+                    .collect(),
+            ),
+        ),
+        ("required".to_string(), JsonValue::Array(required)),
+    ])
+}
+
+fn warning_properties() -> Vec<(&'static str, JsonValue)> {
+    vec![
+        (
+            "kind",
+            one_of(&[
+                "handler_failed",
+                "unreadable_picture",
+                "nonstandard_frame",
+                "encoding_guessed",
+                "invalid_timestamp",
+                "padding_discarded",
+                "repaired",
+                "corrupt_audio",
+                "frame_sizes",
+            ]),
+        ),
+        ("offset", nullable("integer")),
+        ("frame", nullable("string")),
+        ("message", typed("string")),
+    ]
+}
+
+fn definitions() -> Vec<(String, JsonValue)> {
+    let count = || typed("integer");
+
+    let file_warning = [vec![("input", typed("string"))], warning_properties()].concat();
+    let tag_summary = vec![
+        ("path", typed("string")),
+        ("title", nullable("string")),
+        ("artist", nullable("string")),
+        ("album", nullable("string")),
+        ("album_artist", nullable("string")),
+        ("track_number", nullable("string")),
+        ("recording_time", nullable("string")),
+        ("genre", nullable("string")),
+        ("has_cover_art", typed("boolean")),
+        ("frame_ids", array(typed("string"))),
+        ("duration_ms", typed("integer")),
+        ("tag_hash", typed("string")),
+    ];
+
+    vec![
+        (
+            "warning",
+            object(
+                "A problem with a file that didn't stop it being read",
+                warning_properties(),
+            ),
+        ),
+        (
+            "change",
+            object(
+                "A frame added (old is null), replaced, or removed (new is null)",
+                vec![
+                    ("frame", typed("string")),
+                    ("old", nullable("string")),
+                    ("new", nullable("string")),
+                ],
+            ),
+        ),
+        (
+            "tag_report",
+            object(
+                "alloy tag: the outcome for one file, printed once per file in folder mode",
+                vec![
+                    ("schema_version", version()),
+                    ("input", typed("string")),
+                    ("output", nullable("string")),
+                    (
+                        "status",
+                        one_of(&["written", "dry_run", "unchanged", "skipped", "failed"]),
+                    ),
+                    ("message", nullable("string")),
+                    ("changes", array(reference("change"))),
+                    ("warnings", array(reference("warning"))),
+                ],
+            ),
+        ),
+        (
+            "batch_summary",
+            object(
+                "alloy tag in folder mode: printed last, after the report of every file",
+                vec![
+                    ("schema_version", version()),
+                    (
+                        "summary",
+                        object(
+                            "Counts of files by status, with every warning, the files whose audio looks damaged and the files an interruption left untouched",
+                            vec![
+                                ("files", count()),
+                                ("written", count()),
+                                ("dry_run", count()),
+                                ("unchanged", count()),
+                                ("skipped", count()),
+                                ("failed", count()),
+                                (
+                                    "warnings",
+                                    array(object("A warning raised for a file", file_warning)),
+                                ),
+                                ("possibly_corrupt", array(typed("string"))),
+                                ("untouched", array(typed("string"))),
+                            ],
+                        ),
+                    ),
+                ],
+            ),
+        ),
+        (
+            "check_report",
+            object(
+                "alloy check: every file checked against the schema, with its issues",
+                vec![
+                    ("schema_version", version()),
+                    ("schema", typed("string")),
+                    ("checked", count()),
+                    ("failed", count()),
+                    ("unchanged", count()),
+                    (
+                        "files",
+                        array(object(
+                            "A file checked",
+                            vec![
+                                ("path", typed("string")),
+                                ("pass", typed("boolean")),
+                                ("issues", array(typed("string"))),
+                            ],
+                        )),
+                    ),
+                ],
+            ),
+        ),
+        (
+            "conformance_report",
+            object(
+                "alloy conformance: every case of the corpus, with the first difference from its expected result",
+                vec![
+                    ("schema_version", version()),
+                    ("corpus", typed("string")),
+                    ("cases", count()),
+                    ("failed", count()),
+                    (
+                        "results",
+                        array(object(
+                            "A case run",
+                            vec![
+                                ("name", typed("string")),
+                                ("pass", typed("boolean")),
+                                ("difference", nullable("string")),
+                            ],
+                        )),
+                    ),
+                ],
+            ),
+        ),
+        (
+            "find_results",
+            object(
+                "alloy find: the files matching, with a summary of their tags",
+                vec![
+                    ("schema_version", version()),
+                    ("files", array(object("A file found", tag_summary))),
+                ],
+            ),
+        ),
+        (
+            "probe_results",
+            object(
+                "alloy probe: what the first few KB of each file tell about its tag",
+                vec![
+                    ("schema_version", version()),
+                    (
+                        "files",
+                        array(object(
+                            "A file probed, with null for what the bytes read didn't tell",
+                            vec![
+                                ("path", typed("string")),
+                                ("version", typed("string")),
+                                ("tag_size", count()),
+                                ("has_cover_art", nullable("boolean")),
+                                ("title", nullable("string")),
+                                ("artist", nullable("string")),
+                            ],
+                        )),
+                    ),
+                ],
+            ),
+        ),
+    ]
+    .into_iter()
+    .map(|(x, value)| (x.to_string(), value))
+    .collect()
+}
+
+/// JSON Schema (draft 2020-12) of everything printed with `--format json`, one definition per
+/// command's output, so tooling can validate it and tell breaking changes by `schema_version`
+pub fn output_schema() -> JsonValue {
+    let outputs = [
+        "tag_report",
+        "batch_summary",
+        "check_report",
+        "conformance_report",
+        "find_results",
+        "probe_results",
+    ];
+
+    JsonValue::Object(vec![
+        (
+            "$schema".to_string(),
+            JsonValue::String("https://json-schema.org/draft/2020-12/schema".to_string()),
+        ),
+        (
+            "title".to_string(),
+            JsonValue::String(format!(
+                "alloy --format json output, schema_version {}",
+                SCHEMA_VERSION
+            )),
+        ),
+        version_field(),
+        (
+            "anyOf".to_string(),
+            JsonValue::Array(outputs.iter().map(|x| reference(x)).collect()),
+        ),
+        ("$defs".to_string(), JsonValue::Object(definitions())),
+    ])
+}
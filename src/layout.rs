@@ -0,0 +1,212 @@
+use std::ops::Range;
+
+use crate::{mpeg, utility};
+
+const HEADER_SIZE: usize = 10;
+const ID3V1_SIZE: usize = 128;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+const APE_FOOTER_SIZE: usize = 32;
+// Set in an APE tag's flags when the tag also has a header before its items
+const APE_FLAG_HEADER: u32 = 0x8000_0000;
+
+/// Where a structural element of a tag is in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    /// Frame identifier, or a name such as "header"
+    pub name: String,
+    pub offset: usize,
+    /// Length including any header, as declared, so it may run past the end of the file
+    pub length: usize,
+}
+
+impl Element {
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+}
+
+fn is_frame_identifier(bytes: &[u8]) -> bool {
+    bytes.len() == 4
+        && bytes[0].is_ascii_uppercase()
+        && bytes
+            .iter()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit())
+}
+
+// Offset of the first frame and the end of the tag (excluding any footer) as declared
+fn tag_bounds(bytes: &[u8]) -> Result<(usize, usize), String> {
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(b"ID3") {
+        return Err("no ID3v2 tag".to_string());
+    }
+
+    let tag_end = HEADER_SIZE
+        + usize::try_from(utility::convert_safesynch_to_u32(
+            bytes[6], bytes[7], bytes[8], bytes[9],
+        ))
+        .unwrap();
+
+    let mut start = HEADER_SIZE;
+    // ID3v2.3 extended header sizes are plain integers leaving out their own 4 bytes
+    if bytes[5] & FLAG_EXTENDED_HEADER != 0 && bytes.len() >= HEADER_SIZE + 4 {
+        start += match bytes[3] {
+            3 => {
+                4 + usize::try_from(u32::from_be_bytes([
+                    bytes[10], bytes[11], bytes[12], bytes[13],
+                ]))
+                .unwrap()
+            }
+            _ => usize::try_from(utility::convert_safesynch_to_u32(
+                bytes[10], bytes[11], bytes[12], bytes[13],
+            ))
+            .unwrap(),
+        };
+    }
+
+    Ok((start, tag_end))
+}
+
+fn walk_frames(bytes: &[u8], start: usize, tag_end: usize) -> Vec<Element> {
+    let tag_end = tag_end.min(bytes.len());
+    let mut frames = vec![];
+    let mut idx = start;
+
+    while idx + HEADER_SIZE <= tag_end && is_frame_identifier(&bytes[idx..idx + 4]) {
+        let size = usize::try_from(utility::convert_safesynch_to_u32(
+            bytes[idx + 4],
+            bytes[idx + 5],
+            bytes[idx + 6],
+            bytes[idx + 7],
+        ))
+        .unwrap();
+
+        frames.push(Element {
+            name: String::from_utf8_lossy(&bytes[idx..idx + 4]).to_string(),
+            offset: idx,
+            length: HEADER_SIZE + size,
+        });
+        idx += HEADER_SIZE + size;
+    }
+
+    frames
+}
+
+/// Finds every frame in a file's tag by walking the declared sizes, without decoding anything,
+/// so it works on tags the parser rejects. Walking stops at padding or at anything that isn't a
+/// frame header.
+pub fn frames(bytes: &[u8]) -> Result<Vec<Element>, String> {
+    let (start, tag_end) = tag_bounds(bytes)?;
+
+    Ok(walk_frames(bytes, start, tag_end))
+}
+
+/// Maps a whole file in order: the tag header, extended header, each frame, padding (or data
+/// that is neither frames nor padding), footer, anything before the first MPEG frame, the
+/// audio, and an ID3v1 tag at the end
+pub fn layout(bytes: &[u8]) -> Result<Vec<Element>, String> {
+    let (start, tag_end) = tag_bounds(bytes)?;
+    let element = |name: &str, offset: usize, length: usize| Element {
+        name: name.to_string(),
+        offset,
+        length,
+    };
+
+    let mut elements = vec![element("header", 0, HEADER_SIZE)];
+    if start > HEADER_SIZE {
+        elements.push(element("extended header", HEADER_SIZE, start - HEADER_SIZE));
+    }
+
+    let frames = walk_frames(bytes, start, tag_end);
+    let frames_end = frames.last().map_or(start, Element::end);
+    elements.extend(frames);
+
+    if frames_end < tag_end {
+        let rest = &bytes[frames_end.min(bytes.len())..tag_end.min(bytes.len())];
+        let name = if rest.iter().all(|x| *x == 0x00) {
+            "padding"
+        } else {
+            "unrecognized data"
+        };
+        elements.push(element(name, frames_end, tag_end - frames_end));
+    }
+
+    let mut audio_start = tag_end.max(frames_end);
+    if bytes[5] & FLAG_FOOTER != 0 {
+        elements.push(element("footer", audio_start, HEADER_SIZE));
+        audio_start += HEADER_SIZE;
+    }
+
+    let audio_end = if bytes.len() >= audio_start + ID3V1_SIZE
+        && bytes[bytes.len() - ID3V1_SIZE..].starts_with(b"TAG")
+    {
+        bytes.len() - ID3V1_SIZE
+    } else {
+        bytes.len()
+    };
+
+    if audio_start < audio_end {
+        match mpeg::frames(&bytes[audio_start..audio_end]).next() {
+            Some((x, _)) => {
+                if x > 0 {
+                    elements.push(element("data before audio", audio_start, x));
+                }
+                elements.push(element(
+                    "audio",
+                    audio_start + x,
+                    audio_end - audio_start - x,
+                ));
+            }
+            None => elements.push(element(
+                "data (no MPEG frames)",
+                audio_start,
+                audio_end - audio_start,
+            )),
+        }
+    }
+
+    if audio_end < bytes.len() {
+        elements.push(element("ID3v1 tag", audio_end, ID3V1_SIZE));
+    }
+
+    Ok(elements)
+}
+
+// Start of an APEv2 tag ending at `end`, found by its footer
+fn ape_tag_start(bytes: &[u8], end: usize) -> Option<usize> {
+    let footer = bytes.get(end.checked_sub(APE_FOOTER_SIZE)?..end)?;
+    if !footer.starts_with(b"APETAGEX") {
+        return None;
+    }
+
+    // The size covers the items and the footer, but not the header
+    let size = u32::from_le_bytes(footer[12..16].try_into().unwrap());
+    let flags = u32::from_le_bytes(footer[20..24].try_into().unwrap());
+    let mut length = usize::try_from(size).unwrap();
+    if flags & APE_FLAG_HEADER != 0 {
+        length += APE_FOOTER_SIZE;
+    }
+
+    end.checked_sub(length)
+}
+
+/// Where the audio is in a file: after the ID3v2 tag and its footer, if any, and before an
+/// ID3v1 tag and an APEv2 tag at the end. Files that are retagged without touching the audio
+/// keep the same bytes in this range.
+pub fn audio_range(bytes: &[u8]) -> Range<usize> {
+    let start = match tag_bounds(bytes) {
+        Ok((_, tag_end)) if bytes[5] & FLAG_FOOTER != 0 => tag_end + HEADER_SIZE,
+        Ok((_, tag_end)) => tag_end,
+        Err(_) => 0,
+    };
+    let start = start.min(bytes.len());
+
+    let mut end = bytes.len();
+    if end >= start + ID3V1_SIZE && bytes[end - ID3V1_SIZE..].starts_with(b"TAG") {
+        end -= ID3V1_SIZE;
+    }
+    if let Some(x) = ape_tag_start(bytes, end).filter(|x| *x >= start) {
+        end = x;
+    }
+
+    start..end
+}
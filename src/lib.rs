@@ -0,0 +1,50 @@
+//! Parsing, modifying, and writing ID3v2 metadata in MP3 files.
+
+pub mod analysis;
+pub mod cache;
+#[cfg(feature = "lookup")]
+pub mod catalog;
+pub mod chapter;
+#[cfg(feature = "charset")]
+pub mod charset;
+pub mod collation;
+pub mod comment;
+pub mod commercial;
+pub mod condition;
+pub mod cue_sheet;
+pub mod digest;
+pub mod disc;
+pub mod discogs;
+pub mod dj;
+pub mod encryption;
+pub mod extract;
+pub mod fields;
+pub mod frame_handler;
+pub mod image_meta;
+pub mod integrity;
+pub mod json;
+pub mod json_schema;
+pub mod layout;
+pub mod link;
+pub mod media;
+pub mod mpeg;
+pub mod parse;
+pub mod probe;
+pub mod progress;
+pub mod registration;
+pub mod remote;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sanitize;
+pub mod schema;
+pub mod storage;
+pub mod tag;
+pub mod template;
+pub mod text_encoding;
+pub mod timestamp;
+pub mod timing;
+pub mod toml;
+pub mod transform;
+pub mod utility;
+pub mod values;
+pub mod zlib;
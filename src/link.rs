@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::{parse, remote, storage, tag::Id3v2Tag, utility};
+
+/// Contents of a LINK frame: a frame stored in the tag of another file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// Identifier of the linked frame, e.g. `*b"TPUB"`
+    pub frame_id: [u8; 4],
+    /// URL (or path) of the file whose tag holds the linked frame
+    pub url: String,
+    /// Data identifying the frame when the file has several with the same identifier
+    pub additional: String,
+}
+
+impl Link {
+    pub fn parse(data: &[u8]) -> Result<Link, String> {
+        let Some(frame_id) = data.first_chunk::<4>() else {
+            return Err("link frame is truncated".to_string());
+        };
+
+        let Some((url, rest)) = utility::split_terminated(&data[4..], 0x00) else {
+            return Err("link URL is not terminated".to_string());
+        };
+
+        Ok(Link {
+            frame_id: *frame_id,
+            url: utility::decode_text(0x00, url),
+            additional: utility::decode_text(0x00, rest),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let mut result = format!(
+            "{} from {}",
+            String::from_utf8_lossy(&self.frame_id),
+            self.url
+        );
+
+        if !self.additional.is_empty() {
+            result += &format!(", '{}'", self.additional);
+        }
+
+        result
+    }
+}
+
+fn fetch_tag(url: &str) -> Result<Id3v2Tag, String> {
+    let result = if remote::is_remote(url) {
+        remote::fetch_tag_bytes(url).and_then(|x| parse::parse_tag(&x))
+    } else {
+        storage::read(url)
+            .and_then(|x| parse::parse_bytes(&x))
+            .map(|(x, _)| x)
+    };
+
+    result.map_err(|x| format!("unable to resolve link to {}: {}", url, x))
+}
+
+/// Replaces every LINK frame in `tag` with a copy of the frame it references, fetching each
+/// linked file once. Returns the number of links resolved.
+pub fn resolve_links(tag: &mut Id3v2Tag) -> Result<usize, String> {
+    let mut linked_tags: HashMap<String, Id3v2Tag> = HashMap::new();
+    let mut resolved = 0;
+
+    for idx in 0..tag.frames.len() {
+        if &tag.frames[idx].identifier() != b"LINK" {
+            continue;
+        }
+
+        let Some(data) = tag.frames[idx].data() else {
+            continue;
+        };
+        let link = Link::parse(data)?;
+
+        if !linked_tags.contains_key(&link.url) {
+            linked_tags.insert(link.url.clone(), fetch_tag(&link.url)?);
+        }
+
+        let Some(position) = linked_tags[&link.url]
+            .frames
+            .iter()
+            .position(|x| x.identifier() == link.frame_id)
+        else {
+            return Err(format!(
+                "{} has no {} frame to resolve link",
+                link.url,
+                String::from_utf8_lossy(&link.frame_id)
+            ));
+        };
+
+        // Frames aren't Clone, so the linked frame is copied by reparsing its bytes
+        let bytes = linked_tags[&link.url].frames[position].into_bytes();
+        tag.put_frame(Some(idx), parse::parse_frame_bytes(&bytes)?);
+        resolved += 1;
+    }
+
+    Ok(resolved)
+}
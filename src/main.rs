@@ -1,18 +1,124 @@
-use clap::Parser;
-use std::{ffi::OsStr, fs, path, time::Instant};
+use alloy::{
+    analysis,
+    comment::{self, Comment, TermsOfUse},
+    commercial,
+    condition::Condition,
+    dj, encryption, extract,
+    frame_handler::FrameHandlers,
+    integrity,
+    json::JsonValue,
+    json_schema, layout, link, media, mpeg, parse,
+    progress::{ProgressSink, Warning},
+    registration::{self, EncryptionMethod, GroupRegistration},
+    remote, sanitize, storage, tag, template, timestamp, timing,
+    transform::{self, FrameCopy, TextLimit, TruncationPolicy},
+    utility,
+    values::TrackNumber,
+};
+use clap::{Parser, Subcommand};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path, process,
+    time::Instant,
+};
 
-mod extract;
-mod parse;
-mod tag;
-mod utility;
+mod album;
+mod art;
+mod audiobook;
+mod audit;
+mod check;
+mod classical;
+mod conformance;
+mod cue;
+mod find;
+mod hash;
+mod import;
+mod index;
+mod interrupt;
+mod merge;
+mod output_schema;
+mod podcast;
+mod probe_files;
+mod reencode;
+mod serve;
+mod show;
+
+// Frames --compress-frames compresses when none are listed: those that hold long text. GEOB and
+// PRIV data is left alone, as DJ software expects it byte for byte.
+const COMPRESSED_FRAMES: [&str; 3] = ["USLT", "SYLT", "COMM"];
 
 /// A tag editor for parsing, modifying, and writing ID3 metadata in MP3 files, written in Rust.
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Tag files, the same as running alloy without a subcommand
+    Tag(Box<Args>),
+
+    /// Embed cover art across a library
+    Art(art::ArtArgs),
+
+    /// Display the frames stored in a file's tag
+    Show(show::ShowArgs),
+
+    /// Split an audiobook into chapters and set series/narrator frames
+    Audiobook(audiobook::AudiobookArgs),
+
+    /// Check the frame sizes of existing files against their contents, listing files to repair
+    AuditSizes(audit::AuditArgs),
+
+    /// Write a single-file album rip's cue sheet as chapters, or print its per-track tags
+    Cue(cue::CueArgs),
+
+    /// Apply a Discogs release's tracklist, artists, year, label and genres to a folder of files
+    Import(import::ImportArgs),
+
+    /// Merge the tags of other files and JSON values into a file, e.g. ripper output, an online lookup and manual edits
+    Merge(merge::MergeArgs),
+
+    /// Serve tag reads and writes over a local HTTP API (GET/POST /tags)
+    Serve(serve::ServeArgs),
+
+    /// Build an index of the tags in a library, or query it
+    Index(index::IndexArgs),
+
+    /// Print the files in a folder whose tags match, e.g. --artist Radiohead --missing cover
+    Find(Box<find::FindArgs>),
+
+    /// Print a SHA-256 of only the audio in each file, so retagged copies hash the same
+    Hash(hash::HashArgs),
+
+    /// Check files against a schema of required frames, allowed genres and cover art, for release QC
+    Check(check::CheckArgs),
+
+    /// Run the parser against a corpus of reference ID3v2.3 and ID3v2.4 tags, built in or from a folder, and report which read as expected
+    Conformance(conformance::ConformanceArgs),
+
+    /// Print the version, size, cover art, title and artist of each file's tag from its first few KB, for previews scanning many files
+    Probe(probe_files::ProbeArgs),
+
+    /// Rewrite every text frame in one encoding, decoding frames whose encoding byte is wrong
+    Reencode(reencode::ReencodeArgs),
+
+    /// Print the versioned JSON Schema of the --format json output (alloy schema print)
+    Schema(output_schema::SchemaArgs),
+}
+
+#[derive(clap::Args, Debug)]
 struct Args {
-    /// Title of the song
-    #[arg(short, long)]
-    track: Option<String>,
+    /// Title of the song, or with files given as arguments one title per file in the same order
+    #[arg(short = 't', long = "track", value_name = "TRACK")]
+    tracks: Vec<String>,
 
     /// Name of the song's artist
     #[arg(short, long)]
@@ -26,10 +132,66 @@ struct Args {
     #[arg(short, long)]
     main_artist: Option<String>,
 
+    /// Track number (TRCK), e.g. 3 or 3/12 with the number of tracks
+    #[arg(long, value_name = "NUMBER[/TOTAL]", value_parser = TrackNumber::parse)]
+    track_number: Option<TrackNumber>,
+
+    /// Disc number (TPOS), e.g. 1 or 1/2 with the number of discs
+    #[arg(long, value_name = "NUMBER[/TOTAL]", value_parser = TrackNumber::parse)]
+    disc_number: Option<TrackNumber>,
+
+    /// Detect the tempo of the audio and write it to TBPM (needs --features analysis)
+    #[arg(long)]
+    detect_bpm: bool,
+
+    /// Measure loudness per EBU R128 and write ReplayGain TXXX frames for each track, or with "album" also for the tracks in each folder together (needs --features analysis)
+    #[arg(long, value_name = "MODE", num_args = 0..=1, default_missing_value = "track", value_parser = ["track", "album"])]
+    scan_loudness: Option<String>,
+
+    /// Recording time (TDRC), e.g. 2003, 2003-05 or 2003-05-17T21:30
+    #[arg(long, value_name = "DATE", value_parser = timestamp::normalize)]
+    recording_time: Option<String>,
+
+    /// Release time (TDRL), in the same formats as --recording-time
+    #[arg(long, value_name = "DATE", value_parser = timestamp::normalize)]
+    release_time: Option<String>,
+
+    /// Original release time (TDOR), e.g. of the album a remaster or cover comes from
+    #[arg(long, value_name = "DATE", value_parser = timestamp::normalize)]
+    original_release_time: Option<String>,
+
+    /// Encoding time (TDEN)
+    #[arg(long, value_name = "DATE", value_parser = timestamp::normalize)]
+    encoding_time: Option<String>,
+
+    /// Artist of the original recording, for covers (TOPE)
+    #[arg(long)]
+    original_artist: Option<String>,
+
+    /// Album the original recording was released on (TOAL)
+    #[arg(long)]
+    original_album: Option<String>,
+
+    /// Lyricist of the original recording (TOLY)
+    #[arg(long)]
+    original_lyricist: Option<String>,
+
+    /// File name of the original recording (TOFN)
+    #[arg(long)]
+    original_filename: Option<String>,
+
     /// Path to the cover art image of the song
     #[arg(short, long)]
     cover_art_path: Option<String>,
 
+    /// Largest cover art to embed, e.g. 300k; larger images are re-encoded at lower JPEG quality (needs --features convert-art)
+    #[arg(long, value_name = "SIZE", value_parser = utility::parse_byte_size)]
+    art_max_bytes: Option<usize>,
+
+    /// Also embed a file icon downscaled from the cover art, 32x32 by default (needs --features convert-art)
+    #[arg(long, value_name = "PIXELS", num_args = 0..=1, default_missing_value = "32", requires = "cover_art_path")]
+    art_icon: Option<u32>,
+
     /// Description of cover art image
     #[arg(short, long)]
     description: Option<String>,
@@ -42,6 +204,14 @@ struct Args {
     #[arg(short, long)]
     output_file: Option<String>,
 
+    /// MP3 files to tag (e.g. a shell glob), each written as "tagged-<name>" next to it or into --output-dir
+    #[arg(conflicts_with_all = ["input_file", "folder_input"])]
+    files: Vec<String>,
+
+    /// Folder to write the files given as arguments to, instead of next to each input
+    #[arg(long, conflicts_with_all = ["input_file", "folder_input"])]
+    output_dir: Option<String>,
+
     /// Folder with files to tag (MP3 files only), will override --input_file (-i) and --output_file (-o)
     #[arg(long)]
     folder_input: Option<String>,
@@ -50,184 +220,1864 @@ struct Args {
     #[arg(long)]
     folder_output: Option<String>,
 
+    /// Also tag the files in the subfolders of --folder-input (local folders only)
+    #[arg(long, requires = "folder_input")]
+    recursive: bool,
+
+    /// With --recursive, write every file directly into --folder-output
+    #[arg(long, requires = "recursive", conflicts_with = "mirror")]
+    flatten: bool,
+
+    /// With --recursive, recreate the subfolders of --folder-input in --folder-output (the default)
+    #[arg(long, requires = "recursive")]
+    mirror: bool,
+
+    /// Subfolder of the output folder to write each file to, filled from its tag once tagged, e.g. "{album_artist}/{album}"
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["output_file", "flatten", "mirror"])]
+    output_template: Option<String>,
+
     /// Reuse the filename as the title of the track (ignores -t and --track)
     #[arg(long)]
     reuse: bool,
+
+    /// Treat --folder-input as one album: apply the majority TALB/TPE2/TDRC to every track and number tracks consistently
+    #[arg(long)]
+    album_mode: bool,
+
+    /// Find compilations, folders where no one artist (TPE1) is on more than half the tracks, and mark their tracks as one with the album artist (TPE2) set to --various-artists-name and TCMP set
+    #[arg(long)]
+    various_artists: bool,
+
+    /// Album artist (TPE2) of the compilations --various-artists finds, unless --main-artist is given
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = "Various Artists",
+        requires = "various_artists"
+    )]
+    various_artists_name: String,
+
+    /// Title (TIT2) of compilation tracks, e.g. "{artist} - {title}", left alone on titles that already have this form
+    #[arg(long, value_name = "TEMPLATE", requires = "various_artists")]
+    compilation_title: Option<String>,
+
+    /// JSON episode manifest to apply as a podcast preset (podcast frames, chapters, and episode metadata), other flags override its values
+    #[arg(long, value_name = "MANIFEST")]
+    podcast: Option<String>,
+
+    /// JSON description of event timing codes (ETCO) and tempo codes (SYTC) to store, e.g. cues for light shows
+    #[arg(long, value_name = "FILE")]
+    timing: Option<String>,
+
+    /// JSON description of ownership (OWNE) and commercial (COMR) frames: price, seller, purchase date, and seller logo
+    #[arg(long, value_name = "FILE")]
+    owne: Option<String>,
+
+    /// Refuse to write a file if any DJ software data (GEOB/PRIV frames such as Serato cue points) would be lost or altered
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    preserve_dj_data: bool,
+
+    /// JSON cue points and loops, as printed by alloy show --serato-cues --format json, to store for Serato in place of its current ones
+    #[arg(long, value_name = "FILE")]
+    import_cues: Option<String>,
+
+    /// Write files even if encrypted frames, or frames grouped with them, would be lost or altered
+    #[arg(long)]
+    force: bool,
+
+    /// File holding the key (any passphrase) to decrypt the frames alloy encrypted when reading a file, which are encrypted again when it is written. ALLOY_ENCRYPTION_KEY is used if not given.
+    #[arg(long, value_name = "FILE")]
+    encryption_key_file: Option<String>,
+
+    /// Encrypt these frames with the key from --encryption-key-file or ALLOY_ENCRYPTION_KEY, e.g. --encrypt-frames COMM (GEOB and PRIV also need --preserve-dj-data false)
+    #[arg(long, value_delimiter = ',', value_name = "FRAMES")]
+    encrypt_frames: Vec<String>,
+
+    /// Owner (URL or email) to register the encryption method (ENCR) under, needed the first time frames of a file are encrypted
+    #[arg(long, value_name = "OWNER", requires = "encrypt_frames")]
+    encryption_owner: Option<String>,
+
+    /// Symbol of the encryption method, 0x80 to 0xF0
+    #[arg(long, value_name = "SYMBOL", default_value = "0x80", value_parser = registration::parse_symbol)]
+    encryption_method: u8,
+
+    /// Compress these frames with zlib when writing, or lyrics (USLT/SYLT) and comments (COMM) if none are listed
+    #[arg(long, value_delimiter = ',', value_name = "FRAMES", num_args = 0..)]
+    compress_frames: Option<Vec<String>>,
+
+    /// Smallest frame in bytes that --compress-frames compresses
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 1024,
+        requires = "compress_frames"
+    )]
+    compress_min_size: usize,
+
+    /// Put these frames in the group --group-symbol, e.g. to mark the frames a pipeline step added (after compressing and encrypting them)
+    #[arg(long, value_delimiter = ',', value_name = "FRAMES")]
+    group_frames: Vec<String>,
+
+    /// Owner (URL or email) to register the group (GRID) under, needed the first time frames of a file are put in it
+    #[arg(long, value_name = "OWNER", requires = "group_frames")]
+    group_owner: Option<String>,
+
+    /// Symbol of the group for --group-frames, 0x80 to 0xF0
+    #[arg(long, value_name = "SYMBOL", default_value = "0x80", value_parser = registration::parse_symbol)]
+    group_symbol: u8,
+
+    /// Remove every frame in this group, with its registration and signatures
+    #[arg(long, value_name = "SYMBOL", value_parser = registration::parse_symbol)]
+    drop_group: Option<u8>,
+
+    /// Shell command to run after each file is tagged, with tag values exported as ALLOY_* environment variables
+    #[arg(long)]
+    exec: Option<String>,
+
+    /// Guarantee byte-identical output for identical inputs and arguments: frames are written in a fixed order, folders are processed in sorted order, and album mode never prompts
+    #[arg(long, conflicts_with = "stamp")]
+    deterministic: bool,
+
+    /// ID3v2 version written, whatever the version of the existing tag; only 2.4 is supported
+    #[arg(long, value_name = "VERSION", default_value = "2.4", value_parser = tag::parse_write_version)]
+    write_version: [u8; 2],
+
+    /// Check the declared sizes of the tag and each of its frames against what is written, failing the file rather than writing a tag parsers would misread
+    #[arg(long)]
+    verify: bool,
+
+    /// Walk the MPEG frames of the audio being copied and warn about runs of damaged data or a last frame cut short, listing the files after a batch as possibly corrupt
+    #[arg(long)]
+    check_audio: bool,
+
+    /// Record the tagging time (TDTG) and the alloy version (TSSE/TENC) in every file written
+    #[arg(long)]
+    stamp: bool,
+
+    /// Update the entry of each file written in this index (see `alloy index`)
+    #[arg(long, value_name = "FILE")]
+    update_index: Option<String>,
+
+    /// Show the changes that would be made to each file without writing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// List the frames added, changed and removed in each file written
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Output format: messages as files are tagged, or a JSON object per file with its status, changes and warnings, followed by a summary for a batch
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    format: String,
+
+    /// Salvage malformed tags with a warning instead of refusing the file: frames running past the end of the tag are truncated and malformed data between frames is skipped
+    #[arg(long)]
+    lenient: bool,
+
+    /// Read frame sizes as synchsafe or plain 32-bit (be32) integers whatever the tag's version says, for sources known to write them wrong, rather than detecting it, and report how they were read
+    #[arg(long, value_name = "FORMAT", value_parser = parse::FrameSizeFormat::parse)]
+    frame_size_format: Option<parse::FrameSizeFormat>,
+
+    /// Drop every existing frame before applying the provided values
+    #[arg(long)]
+    clear: bool,
+
+    /// Keep attached pictures (APIC) when using --clear
+    #[arg(long, requires = "clear")]
+    keep_art: bool,
+
+    /// Only keep these frames when writing, e.g. --keep-frames TIT2,TPE1,TALB,APIC
+    #[arg(long, value_delimiter = ',', value_name = "FRAMES")]
+    keep_frames: Vec<String>,
+
+    /// Remove these frames when writing, e.g. --drop-frames PRIV,COMM
+    #[arg(long, value_delimiter = ',', value_name = "FRAMES")]
+    drop_frames: Vec<String>,
+
+    /// Remove user-defined text frames (TXXX) with this key, where * and ? match any text or character, ignoring case, e.g. --remove-txxx "MUSICBRAINZ_*" (repeatable)
+    #[arg(long, value_name = "KEY")]
+    remove_txxx: Vec<String>,
+
+    /// Add a role to the involved people list (TIPL), e.g. --credit "producer=Rick Rubin" (repeatable)
+    #[arg(long, value_name = "ROLE=NAME")]
+    credit: Vec<String>,
+
+    /// Work a classical track is part of (TIT1, which iTunes shows as the work), e.g. "Symphony No. 5 in C minor, Op. 67"
+    #[arg(long)]
+    work: Option<String>,
+
+    /// Name of the movement (MVNM), e.g. "Allegro con brio"
+    #[arg(long, value_name = "NAME")]
+    movement_name: Option<String>,
+
+    /// Number of the movement within the work, and optionally how many it has (MVIN), e.g. 1/4
+    #[arg(long, value_name = "NUMBER[/TOTAL]", value_parser = classical::parse_movement)]
+    movement: Option<(u32, Option<u32>)>,
+
+    /// Conductor (TPE3)
+    #[arg(long)]
+    conductor: Option<String>,
+
+    /// Add a soloist to the musician credits list (TMCL), e.g. --soloist "violin=Hilary Hahn" (repeatable)
+    #[arg(long, value_name = "INSTRUMENT=NAME", value_parser = classical::parse_soloist)]
+    soloist: Vec<(String, String)>,
+
+    /// Ask for the work, movement, conductor and soloists of each file, for those not given above
+    #[arg(long)]
+    classical: bool,
+
+    /// Language of the audio as an ISO 639-2 code (TLAN), also used for --comment, --lyrics and --terms-of-use
+    #[arg(long, value_name = "CODE", value_parser = comment::check_language)]
+    language: Option<String>,
+
+    /// Media the audio was ripped from (TMED), e.g. CD, vinyl/7, DAT or radio/FM
+    #[arg(long, value_name = "TYPE", value_parser = media::media_type)]
+    media_type: Option<String>,
+
+    /// Type of the audio file (TFLT), e.g. mp3 or MPG/AAC
+    #[arg(long, value_name = "TYPE", value_parser = media::file_type)]
+    file_type: Option<String>,
+
+    /// Comment (COMM), in the --language given or "eng"
+    #[arg(long)]
+    comment: Option<String>,
+
+    /// Longest value, in characters, text frames may hold when writing, for devices that crash on long titles
+    #[arg(long, value_name = "N")]
+    max_field_len: Option<usize>,
+
+    /// What happens to longer values: truncate them, truncate them ending with "…" (ellipsis), or refuse to write the file (error)
+    #[arg(long, value_name = "POLICY", default_value = "truncate", value_parser = TruncationPolicy::parse, requires = "max_field_len")]
+    field_len_policy: TruncationPolicy,
+
+    /// Add to the end of the comment (COMM) in the --language given or "eng" after a space, keeping its text
+    #[arg(long, value_name = "TEXT")]
+    append_comment: Option<String>,
+
+    /// Add to the end of a text frame's value after a space, e.g. --append "TIT2=(Remastered)"; frames holding several values, and genres (TCON) and languages (TLAN), get it as another value (repeatable)
+    #[arg(long, value_name = "FRAME=TEXT", value_parser = utility::parse_frame_text)]
+    append: Vec<([u8; 4], String)>,
+
+    /// Add to the start of a text frame's value before a space, the same way as --append (repeatable)
+    #[arg(long, value_name = "FRAME=TEXT", value_parser = utility::parse_frame_text)]
+    prepend: Vec<([u8; 4], String)>,
+
+    /// Text file of unsynchronised lyrics (USLT), in the --language given or "eng"
+    #[arg(long, value_name = "FILE")]
+    lyrics: Option<String>,
+
+    /// Name of the internet radio station (TRSN)
+    #[arg(long, value_name = "NAME")]
+    station_name: Option<String>,
+
+    /// Owner of the internet radio station (TRSO)
+    #[arg(long, value_name = "NAME")]
+    station_owner: Option<String>,
+
+    /// Homepage of the internet radio station (WORS)
+    #[arg(long, value_name = "URL")]
+    station_url: Option<String>,
+
+    /// Copyright message (TCOP), starting with the year, e.g. "2024 Netlabel Records"
+    #[arg(long, value_name = "NOTICE", value_parser = comment::check_notice)]
+    copyright: Option<String>,
+
+    /// Produced notice for the sound recording (TPRO), starting with the year
+    #[arg(long, value_name = "NOTICE", value_parser = comment::check_notice)]
+    produced_notice: Option<String>,
+
+    /// Terms of use (USER), in the --language given or "eng", e.g. a Creative Commons license
+    #[arg(long, value_name = "TEXT")]
+    terms_of_use: Option<String>,
+
+    /// Decode HTML entities (&amp;, &#39;) and replace smart quotes and dashes in text frames
+    #[arg(long)]
+    clean_text: bool,
+
+    /// Only clean these frames with --clean-text, e.g. --clean-frames TIT2,TPE1,TALB
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "FRAMES",
+        requires = "clean_text"
+    )]
+    clean_frames: Vec<String>,
+
+    /// Copy a text frame's value into another as FROM:TO, optionally changed with a transform (first-artist-only, first-value or clean), e.g. TPE1:TPE2:first-artist-only (repeatable)
+    #[arg(long, value_name = "FROM:TO[:TRANSFORM]", value_parser = FrameCopy::parse)]
+    copy_frame: Vec<FrameCopy>,
+
+    /// Repair text that went through the wrong encoding, e.g. "CafÃ©" or Cyrillic read as "Êèíî"
+    #[arg(long)]
+    fix_mojibake: bool,
+
+    /// Only modify files matching a condition, e.g. --where 'genre == "Podcast" && missing(cover)'
+    #[arg(long = "where", value_name = "CONDITION", value_parser = Condition::parse)]
+    condition: Option<Condition>,
+
+    /// Replace LINK frames with the frames they reference, fetched from the linked files or URLs
+    #[arg(long)]
+    resolve_links: bool,
+
+    /// Fill album, release date, track/disc numbers and cover art from a catalog ("apple" or "spotify")
+    #[cfg(feature = "lookup")]
+    #[arg(long, value_name = "CATALOG", value_parser = ["apple", "spotify"])]
+    lookup: Option<String>,
+
+    #[arg(skip)]
+    track: Option<String>,
+
+    // Whether the file being tagged is in a compilation --various-artists found
+    #[arg(skip)]
+    compilation: bool,
+
+    // Scanned before tagging with --scan-loudness album, so tracks aren't decoded twice
+    #[arg(skip)]
+    loudness: Option<analysis::Loudness>,
+    #[arg(skip)]
+    album_gain: Option<analysis::ReplayGain>,
 }
 
-fn process_folder(args: &mut Args) {
+// The folder in `output_folder` matching the subfolder of `input_root` that holds `file`
+fn mirrored_folder(output_folder: &str, input_root: Option<&str>, file: &str) -> String {
+    let subfolder = input_root
+        .and_then(|x| storage::parent(file).strip_prefix(x))
+        .map_or("", |x| x.trim_start_matches('/'));
+
+    if subfolder.is_empty() {
+        output_folder.to_string()
+    } else {
+        storage::join(output_folder, subfolder)
+    }
+}
+
+// Each component of the template is expanded and sanitized on its own, so a value such as
+// "AC/DC" names one folder rather than two
+fn template_folder(
+    template: &str,
+    tag: &tag::Id3v2Tag,
+    properties: &[(&str, String)],
+) -> Result<String, String> {
+    let mut folders = vec![];
+    for component in template.split('/').filter(|x| !x.is_empty()) {
+        let name = template::expand(component, tag, properties)?;
+        folders.push(sanitize::sanitize_file_name(
+            &name,
+            &sanitize::SanitizeOptions::default(),
+        ));
+    }
+
+    Ok(folders.join("/"))
+}
+
+// Tags each file in turn, writing "tagged-<name>" into `output_folder`, or next to the input
+// when there is none. Files under `input_root` go into the matching subfolder of
+// `output_folder`.
+fn process_files(
+    args: &mut Args,
+    files: &[String],
+    output_folder: Option<&str>,
+    input_root: Option<&str>,
+) {
     let now = Instant::now();
-    if let Some(folder_path) = &args.folder_input {
-        println!("Processing folder: {}", folder_path);
 
-        if let Some(output_folder) = &args.folder_output {
-            let input_path = match folder_path.strip_suffix("/") {
-                Some(x) => x,
-                None => folder_path,
-            };
+    // Several titles are paired with the files positionally
+    let titles = if args.tracks.len() > 1 {
+        if args.tracks.len() != files.len() {
+            eprintln!(
+                "{} titles given with --track for {} files, expected one per file",
+                args.tracks.len(),
+                files.len()
+            );
+            return;
+        }
 
-            let output_path = match output_folder.strip_suffix("/") {
-                Some(x) => x,
-                None => output_folder,
-            };
+        args.tracks.clone()
+    } else {
+        vec![]
+    };
 
-            fs::create_dir_all(output_path).unwrap();
+    let given_main_artist = args.main_artist.clone();
 
-            for file in
-                fs::read_dir(folder_path).expect("directory must be readable and accessible")
-            {
-                let file = file.expect("file must be valid and readable");
+    let album_values = if args.album_mode {
+        let values = album::resolve_album(
+            files,
+            album::AlbumValues {
+                album: args.album.clone(),
+                album_artist: args.main_artist.clone(),
+                recording_time: args.recording_time.clone(),
+                ..Default::default()
+            },
+            !args.deterministic,
+        );
 
-                println!(
-                    "{}",
-                    file.file_name()
-                        .into_string()
-                        .expect("must be readable file name")
-                );
+        args.album = values.album.clone();
+        args.main_artist = values.album_artist.clone();
+        args.recording_time = values.recording_time.clone();
 
-                args.input_file = Some(format!(
-                    "{}/{}",
-                    input_path,
-                    file.file_name()
-                        .into_string()
-                        .expect("must be readable file name"),
-                ));
-
-                args.output_file = Some(
-                    output_path.to_string()
-                        + "/tagged-"
-                        + &file
-                            .file_name()
-                            .into_string()
-                            .expect("must be readable file name"),
-                );
+        Some(values)
+    } else {
+        None
+    };
+
+    let compilations = if args.various_artists {
+        album::find_compilations(files)
+    } else {
+        HashSet::new()
+    };
+    if args.format == "text" {
+        let mut folders: Vec<&String> = compilations.iter().collect();
+        folders.sort();
+        for x in folders {
+            println!("Various artists: {} is a compilation", x);
+        }
+    }
+    let main_artist = args.main_artist.clone();
 
-                if args.reuse {
-                    let mut filename = file
-                        .file_name()
-                        .into_string()
-                        .expect("must be readable username");
+    let mut album_loudness = if args.scan_loudness.as_deref() == Some("album") {
+        Some(album::scan_album_loudness(files))
+    } else {
+        None
+    };
 
-                    if let Some((left, _)) = filename.split_once(".") {
-                        filename = left.to_string();
-                    }
+    let json = args.format == "json";
+    let mut reports = vec![];
+    let mut untouched: &[String] = &[];
 
-                    println!(
-                        "Reusing filename as track title: {} (-r)",
-                        file.file_name()
-                            .into_string()
-                            .expect("must be readable file name")
-                    );
+    for (idx, file) in files.iter().enumerate() {
+        if interrupt::requested() {
+            untouched = &files[idx..];
+            break;
+        }
 
-                    args.track = Some(filename);
-                }
+        let file_name = storage::file_name(file);
+
+        if !json {
+            println!("{}", file_name);
+        }
 
-                process_single_file(args);
+        args.input_file = Some(file.clone());
+
+        let output_folder = match output_folder {
+            Some(x) => mirrored_folder(x, input_root, file),
+            None => storage::parent(file).to_string(),
+        };
+        args.output_file = Some(storage::join(
+            &output_folder,
+            &("tagged-".to_string() + file_name),
+        ));
+
+        if let Some(x) = titles.get(idx) {
+            args.track = Some(x.clone());
+        }
+
+        if args.reuse {
+            let mut filename = file_name.to_string();
+
+            if let Some((left, _)) = filename.split_once(".") {
+                filename = left.to_string();
             }
 
-            println!("All files successfully tagged.");
-            println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+            if !json {
+                println!("Reusing filename as track title: {} (-r)", file_name);
+            }
 
-            return;
+            args.track = Some(filename);
         }
 
-        eprintln!("If attempting to tag all files in a folder, please include an output folder using --folder-output <PATH>");
+        if let Some(values) = &album_values {
+            args.track_number = values.track_numbers.get(file).copied();
+        }
+
+        if args.various_artists {
+            args.compilation = compilations.contains(storage::parent(file));
+            args.main_artist = match args.compilation {
+                true => given_main_artist
+                    .clone()
+                    .or_else(|| Some(args.various_artists_name.clone())),
+                false => main_artist.clone(),
+            };
+        }
+
+        if let Some(x) = &mut album_loudness {
+            args.loudness = x.tracks.remove(file);
+            args.album_gain = x.albums.get(storage::parent(file)).copied();
+        }
+
+        reports.push(process_single_file(args));
+    }
+
+    if json {
+        println!("{}", batch_summary(&reports, untouched));
+        return;
+    }
+
+    let warned: Vec<&FileReport> = reports.iter().filter(|x| !x.warnings.is_empty()).collect();
+    if !warned.is_empty() {
+        println!(
+            "{} warning(s) in {} file(s):",
+            warned.iter().map(|x| x.warnings.len()).sum::<usize>(),
+            warned.len()
+        );
+        for report in warned {
+            for warning in &report.warnings {
+                println!("  {} | {}", report.input, warning.describe());
+            }
+        }
+    }
+
+    let corrupt = possibly_corrupt(&reports);
+    if !corrupt.is_empty() {
+        println!("Possibly corrupt audio in {} file(s):", corrupt.len());
+        for x in corrupt {
+            println!("  {}", x);
+        }
+    }
+
+    if !untouched.is_empty() {
+        println!("Interrupted, {} file(s) were not touched:", untouched.len());
+        for x in untouched {
+            println!("  {}", x);
+        }
+        return;
     }
+
+    println!("All files successfully tagged.");
+    println!("Total time elapsed: {}ms", now.elapsed().as_millis());
+}
+
+// Files whose audio --check-audio found damaged
+fn possibly_corrupt(reports: &[FileReport]) -> Vec<String> {
+    reports
+        .iter()
+        .filter(|x| x.warnings.iter().any(|x| x.kind == "corrupt_audio"))
+        .map(|x| x.input.clone())
+        .collect()
 }
 
-fn process_single_file(args: &Args) {
-    if let Some(input) = &args.input_file {
-        if let Some(output) = &args.output_file {
-            println!("Processing file: {}", input);
+// Counts of each status, every warning with the file it was raised for, the files whose audio
+// looks damaged, and the files an interruption left untouched
+fn batch_summary(reports: &[FileReport], untouched: &[String]) -> JsonValue {
+    let count = |status: &str| {
+        let count = reports.iter().filter(|x| x.status() == status).count();
+        (status.to_string(), JsonValue::Number(count as f64))
+    };
 
-            let now = Instant::now();
+    let warnings = reports
+        .iter()
+        .flat_map(|report| {
+            report.warnings.iter().map(|x| {
+                let mut fields =
+                    vec![("input".to_string(), JsonValue::String(report.input.clone()))];
+                if let JsonValue::Object(x) = x.to_json() {
+                    fields.extend(x);
+                }
+                JsonValue::Object(fields)
+            })
+        })
+        .collect();
+
+    JsonValue::Object(vec![
+        json_schema::version_field(),
+        (
+            "summary".to_string(),
+            JsonValue::Object(vec![
+                (
+                    "files".to_string(),
+                    JsonValue::Number((reports.len() + untouched.len()) as f64),
+                ),
+                count("written"),
+                count("dry_run"),
+                count("unchanged"),
+                count("skipped"),
+                count("failed"),
+                ("warnings".to_string(), JsonValue::Array(warnings)),
+                (
+                    "possibly_corrupt".to_string(),
+                    JsonValue::Array(
+                        possibly_corrupt(reports)
+                            .into_iter()
+                            .map(JsonValue::String)
+                            .collect(),
+                    ),
+                ),
+                (
+                    "untouched".to_string(),
+                    JsonValue::Array(untouched.iter().cloned().map(JsonValue::String).collect()),
+                ),
+            ]),
+        ),
+    ])
+}
+
+// Files in a folder, and with `recursive` in its subfolders too
+fn list_folder(folder: &str, recursive: bool) -> Result<storage::Listing, String> {
+    let storage = storage::for_location(folder)?;
+    if !recursive {
+        return storage.list(folder);
+    }
+
+    if storage::is_object_storage(folder) {
+        return Err(format!(
+            "unable to list {}: --recursive is only supported for local folders",
+            folder
+        ));
+    }
+
+    let mut listing = storage.list(folder)?;
+    let mut files = vec![];
+    for location in listing.locations {
+        if path::Path::new(&location).is_dir() {
+            let subfolder = list_folder(&location, true)?;
+            files.extend(subfolder.locations);
+            listing.skipped.extend(subfolder.skipped);
+        } else {
+            files.push(location);
+        }
+    }
+    listing.locations = files;
+
+    Ok(listing)
+}
 
-            let bytes = fs::read(input.to_string()).expect("must be readable file");
+fn process_folder(args: &mut Args) {
+    if let Some(folder_path) = &args.folder_input {
+        if args.format == "text" {
+            println!("Processing folder: {}", folder_path);
+        }
 
-            let (id3v2_bytes, audio_data) = extract::extract_tag(&bytes);
+        if let Some(output_folder) = &args.folder_output {
+            let input_path = match folder_path.strip_suffix("/") {
+                Some(x) => x,
+                None => folder_path,
+            };
 
-            // println!("First Music Byte: {:#04X?}", audio_data[0]);
+            let output_path = match output_folder.strip_suffix("/") {
+                Some(x) => x,
+                None => output_folder,
+            }
+            .to_string();
 
-            let mut tag: tag::Id3v2Tag = match parse::parse_tag(&id3v2_bytes) {
+            let listing = match list_folder(input_path, args.recursive) {
                 Ok(x) => x,
                 Err(x) => {
                     eprintln!("{}", x);
                     return;
                 }
             };
-
-            if let Some(x) = &args.cover_art_path {
-                if let Some(y) = &args.description {
-                    let cover_art_bytes = fs::read(x).expect("must be readable file");
-
-                    let file_extension = match path::Path::new(x)
-                        .extension()
-                        .and_then(OsStr::to_str)
-                        .unwrap()
-                    {
-                        "jpg" => "jpg",
-                        "png" => "png",
-                        _ => {
-                            eprintln!("cover art picture must be either a .jpg or .png file.");
-                            return;
-                        }
-                    };
-
-                    tag.set_cover_art(tag::Picture {
-                        encoding: 0x03,
-                        mime: "image/".to_owned() + file_extension + "\0",
-                        picture_type: 0x03,
-                        description: y.to_string() + "\0",
-                        data: cover_art_bytes,
-                    })
-                    .unwrap();
-                } else {
-                    eprintln!("Must provide a description to embed an image");
-                    return;
-                }
+            for x in &listing.skipped {
+                eprintln!("{} | Skipped, file name is not valid UTF-8", x);
             }
-            // println!("cover art bytes size: {:?}", cover_art_bytes.len());
+            let mut files = listing.locations;
 
-            if let Some(x) = &args.track {
-                tag.set_song_title(x.to_string() + "\0").unwrap();
+            if args.deterministic {
+                files.sort();
             }
 
-            if let Some(x) = &args.name {
-                tag.set_song_artist_name(x.to_string() + "\0").unwrap();
+            if args.tracks.len() > 1 {
+                eprintln!("One --track per file is only supported for files given as arguments, the order of files in a folder is not known in advance");
+                return;
             }
 
-            if let Some(x) = &args.album {
-                tag.set_album_title(x.to_string() + "\0").unwrap();
-            }
+            let input_root = (args.recursive && !args.flatten && args.output_template.is_none())
+                .then(|| input_path.to_string());
+            process_files(args, &files, Some(&output_path), input_root.as_deref());
 
-            if let Some(x) = &args.main_artist {
-                tag.set_album_artist_name(x.to_string() + "\0").unwrap();
-            }
+            return;
+        }
 
-            let _ = fs::write(output.clone(), [tag.into_bytes(), audio_data].concat());
+        eprintln!("If attempting to tag all files in a folder, please include an output folder using --folder-output <PATH>");
+    }
+}
 
-            println!(
-                "{:?} | File successfully tagged, saved to {}",
-                now.elapsed(),
-                output
-            );
+fn run_exec_hook(command: &str, tag: &tag::Id3v2Tag, input: &str, output: &str) {
+    let mut shell = if cfg!(windows) {
+        let mut x = process::Command::new("cmd");
+        x.arg("/C");
+        x
+    } else {
+        let mut x = process::Command::new("sh");
+        x.arg("-c");
+        x
+    };
 
-            return;
-        }
+    let env = [
+        ("ALLOY_TITLE", tag.get_song_title()),
+        ("ALLOY_ARTIST", tag.get_song_artist_name()),
+        ("ALLOY_ALBUM", tag.get_album_title()),
+        ("ALLOY_ALBUM_ARTIST", tag.get_album_artist_name()),
+        ("ALLOY_RECORDING_TIME", tag.get_recording_time()),
+        ("ALLOY_TRACK_NUMBER", tag.get_track_number()),
+        ("ALLOY_INPUT_PATH", Some(input.to_string())),
+        ("ALLOY_OUTPUT_PATH", Some(output.to_string())),
+    ];
 
-        eprintln!("Must provide an output file to process: use -o <FILE> or --output-file <FILE>");
-        return;
+    for (key, value) in env {
+        shell.env(key, value.unwrap_or_default());
     }
 
-    eprintln!("Must provide an input file to process");
+    match shell.arg(command).status() {
+        Ok(x) if x.success() => {}
+        Ok(x) => eprintln!("--exec command exited with {} for {}", x, output),
+        Err(x) => eprintln!("Unable to run --exec command for {}: {}", output, x),
+    }
 }
 
-fn main() {
-    let mut args = Args::parse();
+// Runs before the field setters so values given on the command line win over the catalog's
+#[cfg(feature = "lookup")]
+fn apply_catalog_lookup(args: &Args, catalog: &str, tag: &mut tag::Id3v2Tag) -> Result<(), String> {
+    let catalog = alloy::catalog::Catalog::from_name(catalog)?;
 
-    if let Some(_) = args.folder_input {
-        process_folder(&mut args);
-    } else {
-        process_single_file(&args);
+    let (Some(artist), Some(title)) = (
+        args.name.clone().or(tag.get_song_artist_name()),
+        args.track.clone().or(tag.get_song_title()),
+    ) else {
+        return Err(
+            "--lookup needs an artist (-n) and a title (-t), or a tag that has them".to_string(),
+        );
+    };
+    let artist = artist.trim_end_matches('\0');
+    let title = title.trim_end_matches('\0');
+
+    let Some(found) = alloy::catalog::lookup(catalog, artist, title)? else {
+        return Err(format!("no catalog match for {} - {}", artist, title));
+    };
+
+    if args.format == "text" {
+        println!("Catalog match: {} - {}", found.artist, found.title);
+    }
+
+    if let Some(x) = &found.album {
+        tag.set_album_title(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &found.release_date {
+        tag.set_release_time(x.to_string() + "\0")?;
+    }
+
+    // Written as the catalog has them, even when its counts look wrong
+    if let Some(number) = found.track_number {
+        tag.set_track(TrackNumber {
+            number,
+            total: found.track_count,
+        })?;
+    }
+
+    if let Some(number) = found.disc_number {
+        tag.set_disc(TrackNumber {
+            number,
+            total: found.disc_count,
+        })?;
+    }
+
+    if let (Some(x), None) = (&found.artwork_url, &args.cover_art_path) {
+        let (mime, data) = alloy::catalog::fetch_artwork(x)?;
+        let mut picture = tag::Picture::new(mime, 0x03, "Cover".to_string(), data);
+
+        if let Some(max_bytes) = args.art_max_bytes {
+            picture = art::fit_cover_art(picture, max_bytes)?;
+        }
+
+        tag.set_cover_art(picture)?;
+    }
+
+    Ok(())
+}
+
+// What the CLI prints as a file is tagged, warnings are also kept for the batch summary
+struct CliProgress {
+    started: Instant,
+    json: bool,
+    warnings: Vec<Warning>,
+}
+
+impl ProgressSink for CliProgress {
+    fn on_file_start(&mut self, path: &str) {
+        if !self.json {
+            println!("Processing file: {}", path);
+        }
+    }
+
+    fn on_warning(&mut self, warning: &Warning) {
+        if !self.json {
+            println!("[warning] {}", warning.describe());
+        }
+        self.warnings.push(warning.clone());
+    }
+
+    fn on_file_written(&mut self, path: &str) {
+        if !self.json {
+            println!(
+                "{:?} | File successfully tagged, saved to {}",
+                self.started.elapsed(),
+                path
+            );
+        }
+    }
+}
+
+// The outcome of tagging one file and the warnings raised on the way
+struct FileReport {
+    input: String,
+    outcome: Result<Outcome, String>,
+    warnings: Vec<Warning>,
+}
+
+impl FileReport {
+    fn status(&self) -> &'static str {
+        match &self.outcome {
+            Ok(Outcome::Written(..)) => "written",
+            Ok(Outcome::DryRun(..)) => "dry_run",
+            Ok(Outcome::Unchanged(_)) => "unchanged",
+            Ok(Outcome::Skipped(_)) => "skipped",
+            Err(_) => "failed",
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let text = |x: &Option<String>| x.clone().map_or(JsonValue::Null, JsonValue::String);
+        let (output, changes, message) = match &self.outcome {
+            Ok(Outcome::Written(output, changes) | Outcome::DryRun(output, changes)) => {
+                (Some(output.clone()), changes.as_slice(), None)
+            }
+            Ok(Outcome::Unchanged(output)) => (Some(output.clone()), [].as_slice(), None),
+            Ok(Outcome::Skipped(x)) | Err(x) => (None, [].as_slice(), Some(x.clone())),
+        };
+
+        let changes = changes
+            .iter()
+            .map(|x| {
+                JsonValue::Object(vec![
+                    ("frame".to_string(), JsonValue::String(x.frame.clone())),
+                    ("old".to_string(), text(&x.old)),
+                    ("new".to_string(), text(&x.new)),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            json_schema::version_field(),
+            ("input".to_string(), JsonValue::String(self.input.clone())),
+            ("output".to_string(), text(&output)),
+            (
+                "status".to_string(),
+                JsonValue::String(self.status().to_string()),
+            ),
+            ("message".to_string(), text(&message)),
+            ("changes".to_string(), JsonValue::Array(changes)),
+            (
+                "warnings".to_string(),
+                JsonValue::Array(self.warnings.iter().map(Warning::to_json).collect()),
+            ),
+        ])
+    }
+}
+
+fn print_changes(tag: &tag::Id3v2Tag) {
+    for change in tag.changes() {
+        println!("  {}", change.describe());
+    }
+}
+
+// What became of a file, with the changes made to its tag
+enum Outcome {
+    Written(String, Vec<tag::Change>),
+    DryRun(String, Vec<tag::Change>),
+    // The output already holds exactly what would be written
+    Unchanged(String),
+    Skipped(String),
+}
+
+// The title of a compilation track expanded from `template`, or None when the title already has
+// that form, so tagging a compilation again doesn't add the artist twice
+fn compilation_title(
+    template: &str,
+    tag: &tag::Id3v2Tag,
+    properties: &[(&str, String)],
+) -> Result<Option<String>, String> {
+    const MARKER: &str = "\u{1}";
+
+    let title = tag.get_song_title().unwrap_or_default();
+    let title = title.trim_end_matches('\0');
+
+    let mut marked = properties.to_vec();
+    marked.push(("title", MARKER.to_string()));
+    let form = template::expand(template, tag, &marked)?;
+
+    if let Some((prefix, suffix)) = form.split_once(MARKER) {
+        if title.len() > prefix.len() + suffix.len()
+            && title.starts_with(prefix)
+            && title.ends_with(suffix)
+        {
+            return Ok(None);
+        }
+    }
+
+    template::expand(template, tag, properties).map(Some)
+}
+
+// Damage `mpeg::check_frames` finds in the audio of a file, as warnings at offsets in the file
+fn audio_warnings(bytes: &[u8]) -> Vec<Warning> {
+    let range = layout::audio_range(bytes);
+    let damage = mpeg::check_frames(&bytes[range.clone()]);
+    let warning = |offset: Option<usize>, message: String| Warning {
+        kind: "corrupt_audio",
+        offset: offset.map(|x| range.start + x),
+        frame: None,
+        message,
+    };
+
+    if damage.frames == 0 {
+        return vec![warning(None, "no MPEG audio frames found".to_string())];
+    }
+
+    let mut warnings: Vec<Warning> = damage
+        .invalid_regions
+        .iter()
+        .map(|(offset, length)| {
+            warning(
+                Some(*offset),
+                format!("{} bytes of audio aren't MPEG frames", length),
+            )
+        })
+        .collect();
+
+    if let Some(x) = damage.truncated_at {
+        warnings.push(warning(
+            Some(x),
+            "audio ends partway through a frame".to_string(),
+        ));
+    }
+
+    warnings
+}
+
+// Compares what is written with bytes already there, failing at the first difference
+struct Comparison<'a>(&'a [u8]);
+
+impl Write for Comparison<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.strip_prefix(buf) {
+            Some(rest) => {
+                self.0 = rest;
+                Ok(buf.len())
+            }
+            None => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Whether `existing` is byte-for-byte the padded tag followed by the audio, compared as the tag
+// is written rather than serialized first
+fn is_unchanged(existing: &[u8], tag: &tag::Id3v2Tag, padding: usize, audio: &[u8]) -> bool {
+    let tag_size = tag.written_size(padding);
+
+    existing.len() == tag_size + audio.len()
+        && existing[tag_size..] == *audio
+        && tag
+            .write_padded_to(Comparison(&existing[..tag_size]), padding)
+            .is_ok()
+}
+
+fn tag_file(
+    args: &Args,
+    input: &str,
+    output: &str,
+    progress: &mut CliProgress,
+) -> Result<Outcome, String> {
+    if remote::is_remote(input) {
+        return Err(format!(
+            "Remote files are read-only, use `alloy show -i {}` to view the tag",
+            input
+        ));
+    }
+
+    // Held until the output is written so concurrent runs cannot interleave. With
+    // --output-template the output isn't known until the tag is, so it's locked then.
+    let locations = match args.output_template {
+        Some(_) => vec![input],
+        None => vec![input, output],
+    };
+    let _locks = storage::lock(&locations)?;
+
+    let bytes = storage::read(input)?;
+
+    // Skipped rather than aborting, so one stray file doesn't stop a batch
+    let (id3v2_bytes, audio_data) = match extract::extract_tag(&bytes) {
+        Ok(x) => x,
+        Err(x) => return Ok(Outcome::Skipped(x)),
+    };
+
+    // println!("First Music Byte: {:#04X?}", audio_data[0]);
+
+    let options = parse::ParseOptions {
+        lenient: args.lenient,
+        frame_size_format: args.frame_size_format.unwrap_or_default(),
+    };
+
+    let (mut tag, repairs) =
+        parse::parse_tag_with_progress(&id3v2_bytes, &FrameHandlers::default(), options, progress)?;
+    progress
+        .warnings
+        .extend(repairs.iter().map(parse::Repair::to_warning));
+
+    // Detecting plain sizes in an ID3v2.4 tag already raised a warning saying so
+    if let Some(x) = args.frame_size_format {
+        if !progress.warnings.iter().any(|x| x.kind == "frame_sizes") {
+            progress.on_warning(&Warning {
+                kind: "frame_sizes",
+                offset: None,
+                frame: None,
+                message: format!(
+                    "frame sizes read as {} ({})",
+                    tag.frame_size_format().name(),
+                    if x == parse::FrameSizeFormat::Auto {
+                        "detected"
+                    } else {
+                        "forced"
+                    }
+                ),
+            });
+        }
+    }
+
+    if args.resolve_links {
+        let count = link::resolve_links(&mut tag)?;
+        if !progress.json {
+            println!("Resolved {} linked frame(s)", count);
+        }
+    }
+
+    // File properties available to --where conditions and value templates
+    let file_name = storage::file_name(input);
+    let seconds = mpeg::duration_ms(&audio_data) / 1000;
+    let properties = [
+        (
+            "filename",
+            file_name
+                .rsplit_once('.')
+                .map_or(file_name, |(stem, _)| stem)
+                .to_string(),
+        ),
+        ("duration", format!("{}:{:02}", seconds / 60, seconds % 60)),
+    ];
+
+    if let Some(x) = &args.condition {
+        match x.matches(&tag, &properties) {
+            Ok(true) => {}
+            Ok(false) => return Ok(Outcome::Skipped("does not match --where".to_string())),
+            Err(x) => return Err(format!("--where: {}", x)),
+        }
+    }
+
+    // Imported before the DJ frames are kept, so the new cues are what must survive writing
+    if let Some(x) = &args.import_cues {
+        let (cues, loops) = fs::read_to_string(x)
+            .map_err(|e| format!("unable to read cue points {}: {}", x, e))
+            .and_then(|x| dj::parse_cues_json(&x))
+            .map_err(|e| format!("{}: {}", x, e))?;
+        dj::set_serato_cues(&mut tag, cues, loops);
+    }
+
+    // Kept as they are stored, before decrypting or decompressing, as they must be written
+    let mut dj_frames = dj::dj_frame_bytes(&tag);
+
+    // Frames alloy encrypted are edited in the clear and encrypted again before writing
+    let key = encryption::read_key(args.encryption_key_file.as_deref())?;
+    if key.is_none() && !args.encrypt_frames.is_empty() {
+        return Err(
+            "--encrypt-frames needs a key, from --encryption-key-file or ALLOY_ENCRYPTION_KEY"
+                .to_string(),
+        );
+    }
+    let decrypted = match &key {
+        Some(x) => tag.decrypt_frames(x)?,
+        None => vec![],
+    };
+
+    if args.compress_frames.is_some() {
+        tag.decompress_frames();
+    }
+
+    // Respected by every value set below, and applied to the rest before writing
+    tag.set_text_limit(args.max_field_len.map(|x| TextLimit {
+        max_chars: x,
+        policy: args.field_len_policy,
+    }));
+
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    if args.clear {
+        tag.clear_frames(args.keep_art);
+    }
+
+    if let Some(x) = &args.podcast {
+        podcast::apply_podcast_manifest(&mut tag, x)?;
+    }
+
+    if let Some(x) = &args.timing {
+        let codes = fs::read_to_string(x)
+            .map_err(|e| format!("unable to read timing description {}: {}", x, e))
+            .and_then(|x| timing::parse_timing_json(&x));
+
+        match codes {
+            Ok((events, tempos)) => {
+                if let Some(x) = events {
+                    tag.set_event_timing_codes(&x);
+                }
+
+                if let Some(x) = tempos {
+                    tag.set_tempo_codes(&x);
+                }
+            }
+            Err(e) => return Err(format!("{}: {}", x, e)),
+        }
+    }
+
+    if let Some(x) = &args.owne {
+        let base_dir = path::Path::new(x).parent().unwrap_or(path::Path::new(""));
+        let frames = fs::read_to_string(x)
+            .map_err(|e| format!("unable to read commercial description {}: {}", x, e))
+            .and_then(|x| commercial::parse_commercial_json(&x, base_dir));
+
+        match frames {
+            Ok((ownership, offer)) => {
+                if let Some(x) = ownership {
+                    tag.set_ownership(&x);
+                }
+
+                if let Some(x) = offer {
+                    tag.set_commercial(&x);
+                }
+            }
+            Err(e) => return Err(format!("{}: {}", x, e)),
+        }
+    }
+
+    #[cfg(feature = "lookup")]
+    if let Some(x) = &args.lookup {
+        apply_catalog_lookup(args, x, &mut tag)?;
+    }
+
+    if let Some(x) = &args.cover_art_path {
+        if let Some(y) = &args.description {
+            let mut picture = art::read_cover_art(x, y)?;
+
+            if let Some(max_bytes) = args.art_max_bytes {
+                picture = art::fit_cover_art(picture, max_bytes)?;
+            }
+
+            // Made before the cover is moved into the tag, added after so the cover stays first
+            let icon = args
+                .art_icon
+                .map(|x| art::make_file_icon(&picture, x))
+                .transpose()?;
+
+            tag.set_cover_art(picture)?;
+            if let Some(x) = icon {
+                tag.set_file_icon(x);
+            }
+        } else {
+            return Err("Must provide a description to embed an image".to_string());
+        }
+    }
+    // println!("cover art bytes size: {:?}", cover_art_bytes.len());
+
+    // Placeholders such as "{artist}" are filled from the tag before any values are set
+    let values = [
+        &args.track,
+        &args.name,
+        &args.album,
+        &args.main_artist,
+        &args.comment,
+    ]
+    .map(|x| {
+        x.as_deref()
+            .map(|x| template::expand(x, &tag, &properties))
+            .transpose()
+    });
+
+    let [track, name, album, main_artist, comment] = match values {
+        [Ok(a), Ok(b), Ok(c), Ok(d), Ok(e)] => [a, b, c, d, e],
+        values => {
+            let errors: Vec<String> = values.into_iter().filter_map(|x| x.err()).collect();
+            return Err(errors.join("\n"));
+        }
+    };
+
+    if let Some(x) = track {
+        tag.set_song_title(x + "\0")?;
+    }
+
+    if let Some(x) = name {
+        tag.set_song_artist_name(x + "\0")?;
+    }
+
+    if let Some(x) = album {
+        tag.set_album_title(x + "\0")?;
+    }
+
+    if let Some(x) = main_artist {
+        tag.set_album_artist_name(x + "\0")?;
+    }
+
+    if args.compilation {
+        tag.set_compilation(true)?;
+
+        if let Some(x) = &args.compilation_title {
+            if let Some(title) = compilation_title(x, &tag, &properties)? {
+                tag.set_song_title(title + "\0")?;
+            }
+        }
+    }
+
+    if let Some(x) = &args.recording_time {
+        tag.set_recording_time(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.release_time {
+        tag.set_release_time(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.original_release_time {
+        tag.set_original_release_time(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.encoding_time {
+        tag.set_encoding_time(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.original_artist {
+        tag.set_original_artist(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.original_album {
+        tag.set_original_album(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.original_lyricist {
+        tag.set_original_lyricist(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.original_filename {
+        tag.set_original_filename(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = args.track_number {
+        tag.set_track(x)?;
+    }
+
+    if let Some(x) = args.disc_number {
+        tag.set_disc(x)?;
+    }
+
+    if args.detect_bpm {
+        let bpm = analysis::detect_bpm(&audio_data)?.round();
+        if !progress.json {
+            println!("Detected {} BPM", bpm);
+        }
+        tag.set_bpm(format!("{}\0", bpm))?;
+    }
+
+    if args.scan_loudness.is_some() {
+        let loudness = match &args.loudness {
+            Some(x) => x.clone(),
+            None => analysis::scan_loudness(&audio_data)?,
+        };
+        let track = analysis::ReplayGain::from_loudness(&loudness)
+            .ok_or("the audio is silent, its loudness can't be measured")?;
+        if !progress.json {
+            println!(
+                "Track gain {}, peak {}",
+                track.gain_text(),
+                track.peak_text()
+            );
+        }
+        tag.set_track_gain(&track);
+
+        if let Some(album) = &args.album_gain {
+            if !progress.json {
+                println!(
+                    "Album gain {}, peak {}",
+                    album.gain_text(),
+                    album.peak_text()
+                );
+            }
+            tag.set_album_gain(album);
+        }
+    }
+
+    for x in &args.credit {
+        let Some((role, name)) = x.split_once('=') else {
+            return Err(format!("Invalid --credit '{}', expected ROLE=NAME", x));
+        };
+
+        tag.add_involved_person(role.trim().to_string(), name.trim().to_string())?;
+    }
+
+    let mut classical = classical::ClassicalValues {
+        work: args.work.clone(),
+        movement_name: args.movement_name.clone(),
+        movement: args.movement,
+        conductor: args.conductor.clone(),
+        soloists: args.soloist.clone(),
+    };
+    if args.classical {
+        classical::prompt_missing(input, &tag, &mut classical)?;
+    }
+    classical.apply(&mut tag)?;
+
+    if let Some(x) = &args.language {
+        tag.set_language(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.media_type {
+        tag.set_media_type(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.file_type {
+        tag.set_file_type(x.to_string() + "\0")?;
+    }
+
+    let language = args
+        .language
+        .clone()
+        .unwrap_or(comment::DEFAULT_LANGUAGE.to_string());
+
+    if let Some(x) = comment {
+        tag.set_comment(&Comment {
+            language: language.clone(),
+            description: String::new(),
+            text: x,
+        });
+    }
+
+    // After the values above, so they are extended rather than replaced
+    if let Some(x) = &args.append_comment {
+        tag.append_comment(&language, x);
+    }
+
+    for (frame_id, text) in &args.append {
+        tag.append_text(*frame_id, text)
+            .map_err(|x| format!("--append: {}", x))?;
+    }
+
+    for (frame_id, text) in &args.prepend {
+        tag.prepend_text(*frame_id, text)
+            .map_err(|x| format!("--prepend: {}", x))?;
+    }
+
+    if let Some(x) = &args.lyrics {
+        let text =
+            fs::read_to_string(x).map_err(|e| format!("unable to read lyrics {}: {}", x, e))?;
+
+        tag.set_lyrics(&Comment {
+            language: language.clone(),
+            description: String::new(),
+            text,
+        });
+    }
+
+    if let Some(x) = &args.station_name {
+        tag.set_radio_station_name(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.station_owner {
+        tag.set_radio_station_owner(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.station_url {
+        tag.set_radio_station_url(x)?;
+    }
+
+    if let Some(x) = &args.copyright {
+        tag.set_copyright(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.produced_notice {
+        tag.set_produced_notice(x.to_string() + "\0")?;
+    }
+
+    if let Some(x) = &args.terms_of_use {
+        tag.set_terms_of_use(&TermsOfUse {
+            language,
+            text: x.to_string(),
+        });
+    }
+
+    let frame_ids = |ids: &[String]| {
+        ids.iter()
+            .map(|x| utility::parse_frame_id(x))
+            .collect::<Result<Vec<[u8; 4]>, String>>()
+    };
+
+    for copy in &args.copy_frame {
+        tag.copy_text_frame(copy.from, copy.to, |x| copy.transform.apply(x))?;
+    }
+
+    if args.clean_text {
+        match frame_ids(&args.clean_frames) {
+            Ok(x) => {
+                let count = tag.transform_text_frames(&x, transform::clean_text);
+                if !progress.json {
+                    println!("Cleaned text in {} frame(s)", count);
+                }
+            }
+            Err(x) => return Err(format!("--clean-frames: {}", x)),
+        }
+    }
+
+    if args.fix_mojibake {
+        let count = tag.transform_text_frames(&[], transform::fix_mojibake);
+        if !progress.json {
+            println!("Repaired mojibake in {} frame(s)", count);
+        }
+    }
+
+    // DJ frames dropped by name were meant to go, so they no longer have to survive writing
+    if !args.keep_frames.is_empty() {
+        match frame_ids(&args.keep_frames) {
+            Ok(x) => {
+                tag.keep_frames(&x);
+                dj_frames.retain(|frame| x.iter().any(|id| frame.starts_with(id)));
+            }
+            Err(x) => return Err(format!("--keep-frames: {}", x)),
+        }
+    }
+
+    if !args.drop_frames.is_empty() {
+        match frame_ids(&args.drop_frames) {
+            Ok(x) => {
+                tag.drop_frames(&x);
+                dj_frames.retain(|frame| !x.iter().any(|id| frame.starts_with(id)));
+            }
+            Err(x) => return Err(format!("--drop-frames: {}", x)),
+        }
+    }
+
+    if !args.remove_txxx.is_empty() {
+        let count = tag.remove_user_text_frames(&args.remove_txxx);
+        if !progress.json {
+            println!("Removed {} TXXX frame(s)", count);
+        }
+    }
+
+    if let Some(x) = args.drop_group {
+        tag.drop_group(x);
+    }
+
+    if args.max_field_len.is_some() {
+        tag.apply_text_limit()?;
+    }
+
+    if args.stamp {
+        tag.stamp()?;
+    }
+
+    if args.deterministic {
+        tag.sort_frames();
+    }
+
+    if let Some(key) = &key {
+        for (frame_id, method_symbol) in &decrypted {
+            tag.encrypt_frames(&[*frame_id], *method_symbol, key)?;
+        }
+
+        if let Some(x) = &args.encryption_owner {
+            tag.register_encryption_method(&EncryptionMethod {
+                owner: x.to_string(),
+                method_symbol: args.encryption_method,
+                data: encryption::METHOD_DATA.to_vec(),
+            });
+        }
+
+        if !args.encrypt_frames.is_empty() {
+            tag.encrypt_frames(
+                &frame_ids(&args.encrypt_frames)?,
+                args.encryption_method,
+                key,
+            )
+            .map_err(|x| format!("{} (register it with --encryption-owner)", x))?;
+        }
+    }
+
+    // After encryption, which compressed frames can't go through
+    if let Some(x) = &args.compress_frames {
+        let ids = if x.is_empty() {
+            frame_ids(&COMPRESSED_FRAMES.map(String::from))?
+        } else {
+            frame_ids(x)?
+        };
+        tag.compress_frames(&ids, args.compress_min_size);
+    }
+
+    // Last, as the group symbol goes in front of everything else
+    if let Some(x) = &args.group_owner {
+        tag.register_group(&GroupRegistration {
+            owner: x.to_string(),
+            group_symbol: args.group_symbol,
+            data: vec![],
+        });
+    }
+
+    if !args.group_frames.is_empty() {
+        tag.group_frames(&frame_ids(&args.group_frames)?, args.group_symbol)
+            .map_err(|x| format!("{} (register it with --group-owner)", x))?;
+    }
+
+    // Checked once every frame is stored as it will be written, so encrypting, compressing or
+    // grouping a DJ or protected frame is refused like any other change to it
+    if args.preserve_dj_data {
+        dj::verify_dj_frames(&dj_frames, &tag)?;
+    }
+
+    if !args.force {
+        registration::verify_protected_frames(&protected_frames, &tag)?;
+    }
+
+    let version = tag.get_version();
+    if version != args.write_version && !progress.json {
+        println!(
+            "Writing ID3v2.{}.{}, the existing tag is ID3v2.{}.{}",
+            args.write_version[0], args.write_version[1], version[0], version[1]
+        );
+    }
+
+    let cleared = tag.cleared_header_flags();
+    if !cleared.is_empty() && !progress.json {
+        println!(
+            "Clearing the {} header flag(s), which aren't supported when writing",
+            cleared.join(", ")
+        );
+    }
+
+    let output = match &args.output_template {
+        Some(x) => match template_folder(x, &tag, &properties) {
+            Ok(folder) => storage::join(
+                &storage::join(storage::parent(output), &folder),
+                storage::file_name(output),
+            ),
+            Err(x) => return Err(format!("--output-template: {}", x)),
+        },
+        None => output.to_string(),
+    };
+    let output = output.as_str();
+
+    let _output_lock = match args.output_template {
+        Some(_) => storage::lock(&[output])?,
+        None => vec![],
+    };
+
+    if args.dry_run {
+        if !progress.json {
+            if tag.changes().is_empty() {
+                println!("Dry run, no changes to {}", input);
+            } else {
+                println!("Dry run, {} would be saved to {} with:", input, output);
+                print_changes(&tag);
+            }
+        }
+        return Ok(Outcome::DryRun(output.to_string(), tag.changes().to_vec()));
+    }
+
+    if args.check_audio {
+        for warning in audio_warnings(&bytes) {
+            progress.on_warning(&warning);
+        }
+    }
+
+    // Padded so the audio can be shared with the input rather than copied
+    tag.recompute_sizes();
+    let audio_offset = bytes.len() - audio_data.len();
+    let tag_size = 10 + tag.body_size();
+    let padding = storage::alignment_padding(tag_size, audio_offset, audio_data.len());
+
+    if args.verify {
+        let issues = integrity::audit_sizes(&tag.into_bytes_with_padding(padding))?;
+        if !issues.is_empty() {
+            let issues: Vec<String> = issues.iter().map(|x| x.describe()).collect();
+            return Err(format!("tag sizes don't match: {}", issues.join("; ")));
+        }
+    }
+
+    // Nothing is written when the output wouldn't change, so re-running a job is a no-op
+    let unchanged = if output == input {
+        is_unchanged(&bytes, &tag, padding, &audio_data)
+    } else {
+        storage::read(output).is_ok_and(|x| is_unchanged(&x, &tag, padding, &audio_data))
+    };
+    if unchanged {
+        if !progress.json {
+            println!("Unchanged, {} already has this tag", output);
+        }
+        return Ok(Outcome::Unchanged(output.to_string()));
+    }
+
+    storage::write_tagged(output, &tag, padding, input, audio_offset, &audio_data)?;
+
+    progress.on_file_written(output);
+
+    if let Some(path) = &args.update_index {
+        if let Err(x) = index::update_index(path, output) {
+            eprintln!("Unable to update index {}: {}", path, x);
+        }
+    }
+
+    if args.verbose && !progress.json {
+        print_changes(&tag);
+    }
+
+    if !repairs.is_empty() && !progress.json {
+        println!("Repaired {} problem(s) in the original tag:", repairs.len());
+        for repair in &repairs {
+            println!("  {}", repair.describe());
+        }
+    }
+
+    if let Some(x) = &args.exec {
+        run_exec_hook(x, &tag, input, output);
+    }
+
+    Ok(Outcome::Written(output.to_string(), tag.changes().to_vec()))
+}
+
+// Tags the file given by --input-file and --output-file, reporting the outcome as text or JSON
+fn process_single_file(args: &Args) -> FileReport {
+    let json = args.format == "json";
+    let mut progress = CliProgress {
+        started: Instant::now(),
+        json,
+        warnings: vec![],
+    };
+
+    let outcome = match (&args.input_file, &args.output_file) {
+        (Some(input), Some(output)) => {
+            progress.on_file_start(input);
+            tag_file(args, input, output, &mut progress)
+        }
+        (Some(_), None) => Err(
+            "Must provide an output file to process: use -o <FILE> or --output-file <FILE>"
+                .to_string(),
+        ),
+        (None, _) => Err("Must provide an input file to process".to_string()),
+    };
+
+    let report = FileReport {
+        input: args.input_file.clone().unwrap_or_default(),
+        outcome,
+        warnings: progress.warnings,
+    };
+
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        match &report.outcome {
+            Ok(Outcome::Skipped(x)) => eprintln!("{} | Skipped, {}", report.input, x),
+            Err(x) => eprintln!("{}", x),
+            Ok(_) => {}
+        }
+    }
+
+    report
+}
+
+fn main() {
+    let cli = Cli::parse();
+    interrupt::install();
+
+    match &cli.command {
+        Some(Command::Art(x)) => return art::process_art(x),
+        Some(Command::Show(x)) => return show::process_show(x),
+        Some(Command::Audiobook(x)) => return audiobook::process_audiobook(x),
+        Some(Command::AuditSizes(x)) => return audit::process_audit(x),
+        Some(Command::Cue(x)) => return cue::process_cue(x),
+        Some(Command::Import(x)) => return import::process_import(x),
+        Some(Command::Merge(x)) => return merge::process_merge(x),
+        Some(Command::Serve(x)) => return serve::process_serve(x),
+        Some(Command::Index(x)) => return index::process_index(x),
+        Some(Command::Find(x)) => return find::process_find(x),
+        Some(Command::Hash(x)) => return hash::process_hash(x),
+        Some(Command::Check(x)) => return check::process_check(x),
+        Some(Command::Conformance(x)) => return conformance::process_conformance(x),
+        Some(Command::Probe(x)) => return probe_files::process_probe(x),
+        Some(Command::Reencode(x)) => return reencode::process_reencode(x),
+        Some(Command::Schema(x)) => return output_schema::process_schema(x),
+        Some(Command::Tag(_)) | None => {}
+    }
+
+    let mut args = match cli.command {
+        Some(Command::Tag(x)) => *x,
+        _ => cli.args,
+    };
+
+    args.track = match args.tracks.as_slice() {
+        [x] => Some(x.clone()),
+        _ => None,
+    };
+
+    if !args.files.is_empty() {
+        let files = args.files.clone();
+        let output_dir = args.output_dir.clone();
+        process_files(&mut args, &files, output_dir.as_deref(), None);
+    } else if let Some(_) = args.folder_input {
+        process_folder(&mut args);
+    } else if args.tracks.len() > 1 {
+        eprintln!("--track can only be given once when tagging a single file");
+    } else {
+        process_single_file(&args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file whose tag holds a title and a GEOB object large enough to be compressed
+    fn write_dj_file(name: &str) -> String {
+        let mut tag = parse::parse_tag(&b"ID3\x04\x00\x00\x00\x00\x00\x00".to_vec()).unwrap();
+        tag.set_song_title("Title".to_string()).unwrap();
+        tag.set_general_object(
+            [
+                b"\x00application/octet-stream\0\0Serato Overview\0".as_slice(),
+                &[0x01; 2048],
+            ]
+            .concat(),
+        );
+
+        let path = std::env::temp_dir().join(format!("alloy-{}-{}.mp3", process::id(), name));
+        fs::write(&path, [tag.into_bytes(), vec![0x00; 1024]].concat()).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn write_with(input: &str, output: &str, flags: &[&str]) -> Result<Outcome, String> {
+        let argv = ["alloy", "-i", input, "-o", output];
+        let args = Cli::try_parse_from(argv.iter().chain(flags)).unwrap().args;
+        let mut progress = CliProgress {
+            started: Instant::now(),
+            json: true,
+            warnings: vec![],
+        };
+
+        tag_file(&args, input, output, &mut progress)
+    }
+
+    fn tag_with(path: &str, flags: &[&str]) -> Result<Outcome, String> {
+        write_with(path, path, &[flags, &["--dry-run"]].concat())
+    }
+
+    #[test]
+    fn refuses_to_compress_dj_frames() {
+        let path = write_dj_file("compress");
+        let compress = ["--compress-frames", "GEOB,TIT2", "--compress-min-size", "0"];
+
+        let result = tag_with(&path, &compress);
+        assert!(result.is_err_and(|x| x.contains("DJ metadata")));
+
+        let overridden = [compress.as_slice(), &["--preserve-dj-data", "false"]].concat();
+        assert!(tag_with(&path, &overridden).is_ok());
+
+        // The defaults leave DJ data alone
+        assert!(tag_with(&path, &["--compress-frames", "--compress-min-size", "0"]).is_ok());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_encrypt_dj_frames() {
+        let path = write_dj_file("encrypt");
+        let key_file = format!("{}.key", path);
+        fs::write(&key_file, "passphrase\n").unwrap();
+        let encrypt = [
+            "--encryption-key-file",
+            &key_file,
+            "--encrypt-frames",
+            "GEOB",
+            "--encryption-owner",
+            "mailto:drm@example.com",
+        ];
+
+        let result = tag_with(&path, &encrypt);
+        assert!(result.is_err_and(|x| x.contains("DJ metadata")));
+
+        let overridden = [encrypt.as_slice(), &["--preserve-dj-data", "false"]].concat();
+        assert!(tag_with(&path, &overridden).is_ok());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(key_file).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_group_or_ungroup_dj_frames() {
+        let path = write_dj_file("group");
+        let grouped = format!("{}.grouped.mp3", path);
+        let group = [
+            "--group-frames",
+            "GEOB",
+            "--group-owner",
+            "https://example.com/pipeline",
+        ];
+
+        let result = tag_with(&path, &group);
+        assert!(result.is_err_and(|x| x.contains("DJ metadata")));
+
+        let overridden = [group.as_slice(), &["--preserve-dj-data", "false"]].concat();
+        assert!(write_with(&path, &grouped, &overridden).is_ok());
+
+        // Dropping the group loses the frame, which is refused the same way
+        let result = tag_with(&grouped, &["--drop-group", "0x80"]);
+        assert!(result.is_err_and(|x| x.contains("DJ metadata")));
+        assert!(tag_with(
+            &grouped,
+            &["--drop-group", "0x80", "--preserve-dj-data", "false"]
+        )
+        .is_ok());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(grouped).unwrap();
+    }
+
+    #[test]
+    fn drops_dj_frames_named_explicitly() {
+        let path = write_dj_file("drop");
+
+        assert!(tag_with(&path, &["--drop-frames", "GEOB,COMM"]).is_ok());
+        assert!(tag_with(&path, &["--keep-frames", "TIT2"]).is_ok());
+        assert!(tag_with(
+            &path,
+            &["--keep-frames", "TIT2,GEOB", "--drop-frames", "TIT2"]
+        )
+        .is_ok());
+
+        // Dropped along with everything else rather than by name
+        let result = tag_with(&path, &["--clear"]);
+        assert!(result.is_err_and(|x| x.contains("DJ metadata")));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn writes_nothing_when_the_tag_is_unchanged() {
+        let path = write_dj_file("unchanged");
+        let output = format!("{}.tagged.mp3", path);
+
+        let result = write_with(&path, &output, &["--album", "Album"]);
+        assert!(matches!(result, Ok(Outcome::Written(..))));
+        let written = parse::parse_bytes(&fs::read(&output).unwrap()).unwrap().0;
+        assert_eq!(
+            written.get_album_title().unwrap().trim_end_matches('\0'),
+            "Album"
+        );
+
+        let result = write_with(&output, &output, &["--album", "Album"]);
+        assert!(matches!(result, Ok(Outcome::Unchanged(_))));
+        let result = write_with(&output, &output, &["--album", "Another album"]);
+        assert!(matches!(result, Ok(Outcome::Written(..))));
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output).unwrap();
     }
 }
@@ -0,0 +1,134 @@
+/// Media types from the ID3v2.4 spec (TMED): code, friendly aliases and the refinements that may
+/// follow it after a "/"
+const MEDIA_TYPES: [(&str, &[&str], &[&str]); 15] = [
+    ("DIG", &["digital"], &["A"]),
+    ("ANA", &["analog", "analogue"], &["WAC", "8CA"]),
+    ("CD", &["compact-disc"], &["A", "DD", "AD", "AA"]),
+    ("LD", &["laserdisc"], &["A"]),
+    (
+        "TT",
+        &["vin", "vinyl", "record", "turntable"],
+        &["33", "45", "71", "76", "78", "80"],
+    ),
+    ("MD", &["minidisc"], &["A"]),
+    ("DAT", &[], &["A", "1", "2", "3", "4", "5", "6"]),
+    ("DCC", &[], &["A"]),
+    ("DVD", &[], &["A"]),
+    ("TV", &["television"], &["PAL", "NTSC", "SECAM"]),
+    (
+        "VID",
+        &["video"],
+        &["PAL", "NTSC", "SECAM", "VHS", "SVHS", "BETA"],
+    ),
+    ("RAD", &["radio"], &["FM", "AM", "LW", "MW"]),
+    ("TEL", &["telephone", "phone"], &["I"]),
+    (
+        "MC",
+        &["cassette", "tape"],
+        &["4", "9", "I", "II", "III", "IV"],
+    ),
+    (
+        "REE",
+        &["reel", "reel-to-reel"],
+        &["9", "19", "38", "76", "I", "II", "III", "IV"],
+    ),
+];
+
+/// Record sizes people use for vinyl, mapped to the speed the spec refines turntables by
+const RECORD_SIZES: [(&str, &str); 3] = [("7", "45"), ("10", "78"), ("12", "33")];
+
+/// File types from the ID3v2.4 spec (TFLT), with the same layout as `MEDIA_TYPES`
+const FILE_TYPES: [(&str, &[&str], &[&str]); 4] = [
+    ("MIME", &[], &[]),
+    ("MPG", &["mpeg"], &["1", "2", "3", "2.5", "AAC"]),
+    ("VQF", &[], &[]),
+    ("PCM", &["wav"], &[]),
+];
+
+/// Shorthands for common MPEG file types
+const FILE_TYPE_SHORTHANDS: [(&str, &str); 4] = [
+    ("mp1", "MPG/1"),
+    ("mp2", "MPG/2"),
+    ("mp3", "MPG/3"),
+    ("aac", "MPG/AAC"),
+];
+
+fn find_type<'a>(
+    types: &'a [(&'a str, &'a [&'a str], &'a [&'a str])],
+    name: &str,
+) -> Option<&'a (&'a str, &'a [&'a str], &'a [&'a str])> {
+    types.iter().find(|(code, aliases, _)| {
+        code.eq_ignore_ascii_case(name) || aliases.iter().any(|x| x.eq_ignore_ascii_case(name))
+    })
+}
+
+fn codes(types: &[(&str, &[&str], &[&str])]) -> String {
+    types
+        .iter()
+        .map(|(code, _, _)| *code)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Checks "TYPE[/REFINEMENT...]" against the given list, returning it with the spec's codes
+fn encode(types: &[(&str, &[&str], &[&str])], kind: &str, text: &str) -> Result<String, String> {
+    let mut parts = text.trim().split('/');
+    let name = parts.next().unwrap_or_default();
+
+    let Some((code, _, refinements)) = find_type(types, name) else {
+        return Err(format!(
+            "unknown {} '{}', expected one of {}",
+            kind,
+            name,
+            codes(types)
+        ));
+    };
+
+    let mut encoded = code.to_string();
+    for part in parts {
+        let refinement = match RECORD_SIZES.iter().find(|(size, _)| *size == part) {
+            Some((_, speed)) if *code == "TT" => *speed,
+            _ => part,
+        };
+
+        let Some(refinement) = refinements
+            .iter()
+            .find(|x| x.eq_ignore_ascii_case(refinement))
+        else {
+            return Err(format!(
+                "unknown refinement '{}' for {} {}, expected one of {}",
+                part,
+                kind,
+                code,
+                if refinements.is_empty() {
+                    "none".to_string()
+                } else {
+                    refinements.join(", ")
+                }
+            ));
+        };
+
+        encoded.push('/');
+        encoded.push_str(refinement);
+    }
+
+    Ok(encoded)
+}
+
+/// Checks a media type (TMED) such as "CD", "vinyl/7", "DAT" or "radio/FM", returning it the way
+/// the spec encodes it ("CD", "TT/45", "DAT", "RAD/FM")
+pub fn media_type(text: &str) -> Result<String, String> {
+    encode(&MEDIA_TYPES, "media type", text)
+}
+
+/// Checks a file type (TFLT) such as "mp3", "MPG/AAC" or "PCM", returning it the way the spec
+/// encodes it ("MPG/3", "MPG/AAC", "PCM")
+pub fn file_type(text: &str) -> Result<String, String> {
+    match FILE_TYPE_SHORTHANDS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(text.trim()))
+    {
+        Some((_, encoded)) => Ok(encoded.to_string()),
+        None => encode(&FILE_TYPES, "file type", text),
+    }
+}
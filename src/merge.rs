@@ -0,0 +1,192 @@
+use std::{collections::HashSet, fs};
+
+use alloy::{
+    json::{self, JsonValue},
+    parse, registration, storage,
+    tag::Id3v2Tag,
+    utility,
+};
+
+/// Combine the tags of several partial sources into one file
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// MP3 file whose audio and tag the others are merged into
+    #[arg(long, value_name = "FILE")]
+    base: String,
+
+    /// MP3 file, or JSON object of field names or frame identifiers to values, e.g. {"title": "...", "TXXX:CATALOGNUMBER": "..."}, whose frames replace those of the sources before it (repeatable)
+    #[arg(long, value_name = "FILE", required = true)]
+    overlay: Vec<String>,
+
+    /// Take a field from one source whenever it has it, whatever the order, e.g. --prefer title=base or --prefer TPE1=lookup.json (repeatable)
+    #[arg(long, value_name = "FIELD=SOURCE", value_parser = parse_preference)]
+    prefer: Vec<(String, String)>,
+
+    /// File to write the merged tag and the base's audio to, which may be the base itself
+    #[arg(short, long, value_name = "FILE")]
+    output_file: String,
+
+    /// Print the changes to the base's tag without writing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Write the file even if encrypted frames, or frames grouped with them, would be lost or altered
+    #[arg(long)]
+    force: bool,
+}
+
+// Merge key of a field name, frame identifier or "TXXX:KEY"
+fn merge_key(field: &str) -> Result<String, String> {
+    match field.split_once(':') {
+        Some((id, key)) if id.eq_ignore_ascii_case("TXXX") && !key.is_empty() => {
+            Ok(format!("TXXX:{}", key.to_uppercase()))
+        }
+        _ => utility::parse_frame_id(field).map(|x| String::from_utf8_lossy(&x).to_string()),
+    }
+}
+
+fn parse_preference(text: &str) -> Result<(String, String), String> {
+    let Some((field, source)) = text.split_once('=') else {
+        return Err(format!("invalid '{}', expected FIELD=SOURCE", text));
+    };
+
+    Ok((merge_key(field.trim())?, source.trim().to_string()))
+}
+
+// What a source holds: a whole tag, or text values by merge key
+enum Source {
+    Tag(Id3v2Tag),
+    Values(Vec<(String, String)>),
+}
+
+impl Source {
+    fn keys(&self) -> HashSet<String> {
+        match self {
+            Source::Tag(x) => x.frames().iter().map(|x| x.merge_key()).collect(),
+            Source::Values(x) => x.iter().map(|(key, _)| key.clone()).collect(),
+        }
+    }
+}
+
+fn read_values(path: &str) -> Result<Vec<(String, String)>, String> {
+    let text = fs::read_to_string(path).map_err(|x| x.to_string())?;
+    let JsonValue::Object(fields) = json::parse(&text)? else {
+        return Err("expected an object of fields to values".to_string());
+    };
+
+    let mut values = vec![];
+    for (field, value) in fields {
+        let text = match &value {
+            JsonValue::Array(x) => x
+                .iter()
+                .map(JsonValue::to_text)
+                .collect::<Option<Vec<String>>>()
+                .map(|x| x.join("\0")),
+            x => x.to_text(),
+        };
+        let Some(text) = text else {
+            return Err(format!(
+                "'{}' must be a string, a number or an array of them",
+                field
+            ));
+        };
+
+        values.push((
+            merge_key(&field).map_err(|x| format!("'{}': {}", field, x))?,
+            text,
+        ));
+    }
+
+    Ok(values)
+}
+
+fn read_source(path: &str) -> Result<Source, String> {
+    if path.to_lowercase().ends_with(".json") {
+        return read_values(path).map(Source::Values);
+    }
+
+    storage::read(path)
+        .and_then(|x| parse::parse_bytes(&x))
+        .map(|(x, _)| Source::Tag(x))
+}
+
+fn merge(args: &MergeArgs) -> Result<(), String> {
+    let _locks = storage::lock(&[&args.base, &args.output_file])?;
+    let bytes = storage::read(&args.base)?;
+    let (mut tag, audio_data) = parse::parse_bytes(&bytes)?;
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    let mut names = vec!["base"];
+    let mut sources = vec![];
+    for path in &args.overlay {
+        sources.push(read_source(path).map_err(|x| format!("{}: {}", path, x))?);
+        names.push(path);
+    }
+
+    // Keys of the base, then of each overlay
+    let keys: Vec<HashSet<String>> = [tag.frames().iter().map(|x| x.merge_key()).collect()]
+        .into_iter()
+        .chain(sources.iter().map(Source::keys))
+        .collect();
+
+    let mut preferred = vec![];
+    for (key, source) in &args.prefer {
+        let Some(idx) = names.iter().position(|x| x == source) else {
+            return Err(format!(
+                "--prefer {}={}: no source '{}', expected base or an --overlay as given",
+                key, source, source
+            ));
+        };
+        preferred.push((key.as_str(), idx));
+    }
+
+    // Later sources win, except over a preferred source holding the key
+    let take = |key: &str, idx: usize| match preferred.iter().find(|(x, _)| *x == key) {
+        Some((_, preferred)) if keys[*preferred].contains(key) => idx == *preferred,
+        _ => true,
+    };
+
+    for (idx, source) in sources.into_iter().enumerate() {
+        let idx = idx + 1;
+
+        match source {
+            Source::Tag(x) => {
+                tag.merge_frames(x, |key| take(key, idx));
+            }
+            Source::Values(values) => {
+                for (key, value) in values.iter().filter(|(key, _)| take(key, idx)) {
+                    match key.strip_prefix("TXXX:") {
+                        Some(x) => tag.set_user_text(x, value),
+                        None => tag.set_text(*key.as_bytes().first_chunk::<4>().unwrap(), value)?,
+                    }
+                }
+            }
+        }
+    }
+
+    if tag.changes().is_empty() {
+        println!("No changes to the tag of {}", args.base);
+    }
+    for change in tag.changes() {
+        println!("  {}", change.describe());
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    if !args.force {
+        registration::verify_protected_frames(&protected_frames, &tag)?;
+    }
+
+    storage::write_verified(&args.output_file, &tag, &audio_data)?;
+    println!("Merged into {}", args.output_file);
+
+    Ok(())
+}
+
+pub fn process_merge(args: &MergeArgs) {
+    if let Err(x) = merge(args) {
+        eprintln!("{}", x);
+    }
+}
@@ -0,0 +1,195 @@
+// Bitrates in kbps indexed by [version][layer][bitrate index], version 0 is MPEG-1 and 1 is MPEG-2/2.5
+const BITRATES: [[[u32; 16]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0,
+        ],
+        [
+            0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+        ],
+        [
+            0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+        ],
+    ],
+];
+
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// Header fields of a single MPEG audio frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MpegFrameHeader {
+    pub sample_rate: u32,
+    pub bitrate: u32,
+    pub samples: u32,
+    pub length: usize,
+}
+
+pub fn parse_frame_header(bytes: &[u8]) -> Option<MpegFrameHeader> {
+    if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    // 0b11 MPEG-1, 0b10 MPEG-2, 0b00 MPEG-2.5, 0b01 reserved
+    let version_bits = (bytes[1] >> 3) & 0x03;
+    // 0b11 Layer I, 0b10 Layer II, 0b01 Layer III
+    let layer_bits = (bytes[1] >> 1) & 0x03;
+    let bitrate_index = usize::from(bytes[2] >> 4);
+    let sample_rate_index = usize::from((bytes[2] >> 2) & 0x03);
+    let padding = u32::from((bytes[2] >> 1) & 0x01);
+
+    if version_bits == 0b01 || layer_bits == 0b00 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version_bits == 0b11;
+    let layer = usize::from(3 - layer_bits);
+    let bitrate = BITRATES[usize::from(!mpeg1)][layer][bitrate_index] * 1000;
+
+    // Free-format and invalid bitrates cannot be measured
+    if bitrate == 0 {
+        return None;
+    }
+
+    let sample_rate = match version_bits {
+        0b11 => SAMPLE_RATES[sample_rate_index],
+        0b10 => SAMPLE_RATES[sample_rate_index] / 2,
+        _ => SAMPLE_RATES[sample_rate_index] / 4,
+    };
+
+    let (samples, length) = match layer {
+        0 => (384, (12 * bitrate / sample_rate + padding) * 4),
+        1 => (1152, 144 * bitrate / sample_rate + padding),
+        _ if mpeg1 => (1152, 144 * bitrate / sample_rate + padding),
+        _ => (576, 72 * bitrate / sample_rate + padding),
+    };
+
+    Some(MpegFrameHeader {
+        sample_rate,
+        bitrate,
+        samples,
+        length: usize::try_from(length).unwrap(),
+    })
+}
+
+/// Iterates over the MPEG frames in audio data, skipping bytes that are not part of a frame
+pub fn frames(audio: &[u8]) -> impl Iterator<Item = (usize, MpegFrameHeader)> + '_ {
+    let mut idx = 0;
+
+    std::iter::from_fn(move || {
+        while idx + 4 <= audio.len() {
+            match parse_frame_header(&audio[idx..]) {
+                Some(header) if header.length > 0 && idx + header.length <= audio.len() => {
+                    let start = idx;
+                    idx += header.length;
+                    return Some((start, header));
+                }
+                _ => idx += 1,
+            }
+        }
+
+        None
+    })
+}
+
+/// Playing time of the audio in milliseconds, measured by walking every frame so VBR files are
+/// timed accurately
+pub fn duration_ms(audio: &[u8]) -> u64 {
+    let mut total: f64 = 0.0;
+
+    for (_, header) in frames(audio) {
+        total += f64::from(header.samples) / f64::from(header.sample_rate);
+    }
+
+    (total * 1000.0).round() as u64
+}
+
+/// Shortest run of bytes between frames reported as damage, about three frames at 128 kbps.
+/// Shorter runs are usually padding or junk left by encoders.
+pub const MIN_INVALID_REGION: usize = 1024;
+
+/// Damage found by `check_frames`, with offsets into the audio it was given
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameDamage {
+    /// Complete frames found
+    pub frames: usize,
+    /// Offset and length of each run of at least `MIN_INVALID_REGION` bytes that aren't frames
+    pub invalid_regions: Vec<(usize, usize)>,
+    /// Offset of a last frame cut short, whose header promises more bytes than are left
+    pub truncated_at: Option<usize>,
+}
+
+impl FrameDamage {
+    pub fn is_damaged(&self) -> bool {
+        self.frames == 0 || !self.invalid_regions.is_empty() || self.truncated_at.is_some()
+    }
+}
+
+/// Walks audio frame by frame, as a player would, to find where it isn't a continuous run of
+/// frames. After bytes that aren't a frame, a header only counts when the next frame follows
+/// it, so sync bits in damaged data aren't taken for frames.
+pub fn check_frames(audio: &[u8]) -> FrameDamage {
+    let mut damage = FrameDamage::default();
+    let mut gap_start: Option<usize> = None;
+    let mut idx = 0;
+
+    // A frame that fits, and when resyncing one that the next frame or the end follows
+    let frame_at = |idx: usize, resync: bool| {
+        let header = parse_frame_header(&audio[idx..]).filter(|x| x.length > 0)?;
+        let end = idx + header.length;
+        let followed = end == audio.len()
+            || audio
+                .get(end..)
+                .and_then(parse_frame_header)
+                .is_some_and(|x| x.length > 0);
+
+        (end <= audio.len() && (!resync || followed)).then_some(header)
+    };
+
+    while idx + 4 <= audio.len() {
+        match frame_at(idx, gap_start.is_some() || damage.frames == 0) {
+            Some(header) => {
+                if let Some(start) = gap_start.take() {
+                    if idx - start >= MIN_INVALID_REGION {
+                        damage.invalid_regions.push((start, idx - start));
+                    }
+                }
+                damage.frames += 1;
+                idx += header.length;
+            }
+            None => {
+                // A header right after the last frame that runs past the end
+                if gap_start.is_none()
+                    && damage.frames > 0
+                    && parse_frame_header(&audio[idx..]).is_some()
+                {
+                    damage.truncated_at = Some(idx);
+                    return damage;
+                }
+
+                gap_start.get_or_insert(idx);
+                idx += 1;
+            }
+        }
+    }
+
+    let start = gap_start.unwrap_or(idx);
+    if audio.len() - start >= MIN_INVALID_REGION {
+        damage.invalid_regions.push((start, audio.len() - start));
+    } else if gap_start.is_none() && damage.frames > 0 && audio.get(idx) == Some(&0xFF) {
+        // Fewer bytes than a header are left, the start of a frame
+        damage.truncated_at = Some(idx);
+    }
+
+    damage
+}
@@ -0,0 +1,20 @@
+use alloy::json_schema;
+
+/// Describe the output of --format json
+#[derive(clap::Args, Debug)]
+pub struct SchemaArgs {
+    #[command(subcommand)]
+    command: SchemaCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum SchemaCommand {
+    /// Print the JSON Schema of every --format json output, at the current schema_version
+    Print,
+}
+
+pub fn process_schema(args: &SchemaArgs) {
+    match args.command {
+        SchemaCommand::Print => println!("{}", json_schema::output_schema()),
+    }
+}
@@ -1,8 +1,218 @@
+use std::{fs, path::Path};
+
 use crate::extract;
+use crate::frame_handler::FrameHandlers;
+use crate::progress::{PrintWarnings, ProgressSink, Warning};
 use crate::tag;
+use crate::timestamp::{self, Timestamp};
 use crate::utility;
 
+/// How strictly malformed tags are handled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Frames declaring more data than remains in the tag are truncated, and malformed data
+    /// between frames is skipped up to the next intact frame, with a warning instead of failing
+    /// the whole tag
+    pub lenient: bool,
+    /// How frame sizes are read
+    pub frame_size_format: FrameSizeFormat,
+}
+
+/// How the 4 size bytes of a frame header are read. ID3v2.4 sizes are synchsafe and ID3v2.3 sizes
+/// plain 32-bit big-endian integers, but some taggers write the ID3v2.3 layout into ID3v2.4 tags
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FrameSizeFormat {
+    /// The layout of the tag's version, unless only the other one lines the frames up
+    #[default]
+    Auto,
+    Synchsafe,
+    Be32,
+}
+
+impl FrameSizeFormat {
+    pub fn parse(text: &str) -> Result<FrameSizeFormat, String> {
+        match text {
+            "auto" => Ok(FrameSizeFormat::Auto),
+            "synchsafe" => Ok(FrameSizeFormat::Synchsafe),
+            "be32" => Ok(FrameSizeFormat::Be32),
+            x => Err(format!(
+                "unknown frame size format '{}', expected synchsafe, be32 or auto",
+                x
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrameSizeFormat::Auto => "auto",
+            FrameSizeFormat::Synchsafe => "synchsafe",
+            FrameSizeFormat::Be32 => "be32",
+        }
+    }
+
+    // Size declared by a frame header's size bytes, synchsafe unless plain
+    fn read(&self, bytes: &[u8]) -> usize {
+        let size = match self {
+            FrameSizeFormat::Be32 => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            _ => utility::convert_safesynch_to_u32(bytes[0], bytes[1], bytes[2], bytes[3]),
+        };
+
+        usize::try_from(size).unwrap()
+    }
+
+    // The format frames in a tag of `version` are read with: the version's own layout, or the
+    // other one when only it runs the frames exactly to the end or into the padding
+    fn detect(self, version: u8, frame_bytes: &[u8]) -> FrameSizeFormat {
+        if self != FrameSizeFormat::Auto {
+            return self;
+        }
+
+        let (native, other) = match version {
+            3 => (FrameSizeFormat::Be32, FrameSizeFormat::Synchsafe),
+            _ => (FrameSizeFormat::Synchsafe, FrameSizeFormat::Be32),
+        };
+
+        if !is_frame_chain(frame_bytes, native) && is_frame_chain(frame_bytes, other) {
+            other
+        } else {
+            native
+        }
+    }
+}
+
+/// A change lenient parsing made to salvage a malformed tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+    /// Position in the tag, which is also the position in the file
+    pub offset: usize,
+    /// Frame identifier, or None for data that isn't a frame
+    pub frame: Option<String>,
+    pub action: String,
+}
+
+impl Repair {
+    pub fn describe(&self) -> String {
+        format!(
+            "{:#010X} {}: {}",
+            self.offset,
+            self.frame.as_deref().unwrap_or("data"),
+            self.action
+        )
+    }
+
+    pub fn to_warning(&self) -> Warning {
+        Warning {
+            kind: "repaired",
+            offset: Some(self.offset),
+            frame: self.frame.clone(),
+            message: self.action.clone(),
+        }
+    }
+}
+
+/// Reads an MP3 file, returning its parsed tag and the audio data that follows it
+pub fn parse_file(path: &Path) -> Result<(tag::Id3v2Tag, Vec<u8>), String> {
+    parse_file_with_handlers(path, &FrameHandlers::default())
+}
+
+pub fn parse_file_with_handlers(
+    path: &Path,
+    handlers: &FrameHandlers,
+) -> Result<(tag::Id3v2Tag, Vec<u8>), String> {
+    let bytes = match fs::read(path) {
+        Ok(x) => x,
+        Err(x) => return Err(format!("unable to read {}: {}", path.display(), x)),
+    };
+
+    parse_bytes_with_handlers(&bytes, handlers)
+}
+
+/// Reads only the tag of an MP3 file, for callers that don't need its audio
+pub fn read_tag(path: &Path) -> Result<tag::Id3v2Tag, String> {
+    let file = match fs::File::open(path) {
+        Ok(x) => x,
+        Err(x) => return Err(format!("unable to read {}: {}", path.display(), x)),
+    };
+
+    tag::Id3v2Tag::read_from(file)
+}
+
+/// Parses the contents of an MP3 file, returning its tag and the audio data that follows it
+pub fn parse_bytes(bytes: &[u8]) -> Result<(tag::Id3v2Tag, Vec<u8>), String> {
+    parse_bytes_with_handlers(bytes, &FrameHandlers::default())
+}
+
+pub fn parse_bytes_with_handlers(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+) -> Result<(tag::Id3v2Tag, Vec<u8>), String> {
+    parse_bytes_with_options(bytes, handlers, ParseOptions::default())
+}
+
+pub fn parse_bytes_with_options(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+    options: ParseOptions,
+) -> Result<(tag::Id3v2Tag, Vec<u8>), String> {
+    let (id3v2_bytes, audio_data) = extract::extract_tag(&bytes.to_vec())?;
+
+    Ok((
+        parse_tag_with_options(&id3v2_bytes, handlers, options)?,
+        audio_data,
+    ))
+}
+
 pub fn parse_tag(bytes: &Vec<u8>) -> Result<tag::Id3v2Tag, String> {
+    parse_tag_with_handlers(bytes, &FrameHandlers::default())
+}
+
+/// Parses a tag, decoding frames matched by one of `handlers` into custom frames
+pub fn parse_tag_with_handlers(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+) -> Result<tag::Id3v2Tag, String> {
+    parse_tag_with_options(bytes, handlers, ParseOptions::default())
+}
+
+pub fn parse_tag_with_options(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+    options: ParseOptions,
+) -> Result<tag::Id3v2Tag, String> {
+    let (tag, repairs) = parse_tag_with_repairs(bytes, handlers, options)?;
+
+    for repair in repairs {
+        PrintWarnings.on_warning(&repair.to_warning());
+    }
+
+    Ok(tag)
+}
+
+/// Parses a tag like `parse_tag_with_options`, returning the repairs made to salvage it instead
+/// of printing them
+pub fn parse_tag_with_repairs(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+    options: ParseOptions,
+) -> Result<(tag::Id3v2Tag, Vec<Repair>), String> {
+    parse_tag_with_progress(bytes, handlers, options, &mut PrintWarnings)
+}
+
+/// Parses a tag like `parse_tag_with_repairs`, reporting each frame parsed and any warnings to
+/// `progress` instead of printing them
+pub fn parse_tag_with_progress(
+    bytes: &[u8],
+    handlers: &FrameHandlers,
+    options: ParseOptions,
+    progress: &mut dyn ProgressSink,
+) -> Result<(tag::Id3v2Tag, Vec<Repair>), String> {
+    if bytes.len() < 10 {
+        return Err(format!(
+            "too small ({} bytes) to be an ID3v2 tag",
+            bytes.len()
+        ));
+    }
+
     if bytes[0] != 0x49 || bytes[1] != 0x44 || bytes[2] != 0x33 {
         // Not an ID3v2 tag
         return Err(format!(
@@ -17,13 +227,19 @@ pub fn parse_tag(bytes: &Vec<u8>) -> Result<tag::Id3v2Tag, String> {
 
     let header_bytes = &bytes[..10];
     let header = parse_header(&header_bytes.to_vec());
-    let extended_header = if header.flags & 0b01000000 != 0 {
-        let total_extended_header_size =
-            utility::convert_safesynch_to_u32(bytes[10], bytes[11], bytes[12], bytes[13]);
 
-        let total_extended_header_size = usize::try_from(total_extended_header_size).unwrap();
-        let extended_header_bytes = &bytes[10..total_extended_header_size + 10].to_vec();
-        Some(parse_extended_header(extended_header_bytes))
+    // ID3v2.3 unsynchronises the whole tag after the header rather than each frame, extended
+    // header included, so it is reversed before anything else is read and the tag is held (and
+    // written) without it
+    let resynchronised;
+    let bytes = if header.version[0] < 4 && header.flags & tag::HEADER_UNSYNCHRONISATION != 0 {
+        resynchronised = [header_bytes, &utility::resynchronise(&bytes[10..])].concat();
+        &resynchronised[..]
+    } else {
+        bytes
+    };
+    let extended_header = if header.flags & tag::HEADER_EXTENDED != 0 {
+        Some(parse_extended_header(&bytes[10..], header.version[0])?)
     } else {
         None
     };
@@ -35,89 +251,158 @@ pub fn parse_tag(bytes: &Vec<u8>) -> Result<tag::Id3v2Tag, String> {
     // header is always 10 bytes
     // extended header might or might not be present
     // frames start after extended up to footer
-    let frames_start = if extended_header.is_some() {
-        usize::try_from(extended_header.as_ref().unwrap().size + 10).unwrap()
-    } else {
-        10
-    };
+    let frames_start = 10
+        + extended_header
+            .as_ref()
+            .map_or(0, |x| usize::try_from(x.size).unwrap());
 
     let frames_end = if footer_present {
         bytes.len() - 10
     } else {
         bytes.len()
     };
+    if frames_start > frames_end {
+        return Err(format!(
+            "extended header declares {} bytes, more than the tag holds",
+            frames_start - 10
+        ));
+    }
 
     let frame_bytes = &bytes[frames_start..frames_end].to_vec();
-    let frames = parse_frames(frame_bytes);
+    let frame_sizes = options
+        .frame_size_format
+        .detect(header.version[0], frame_bytes);
+    if options.frame_size_format == FrameSizeFormat::Auto
+        && frame_sizes == FrameSizeFormat::Be32
+        && header.version[0] != 3
+    {
+        progress.on_warning(&Warning {
+            kind: "frame_sizes",
+            offset: None,
+            frame: None,
+            message: format!(
+                "frame sizes are plain integers, as in ID3v2.3, rather than synchsafe in this ID3v2.{} tag, read as be32",
+                header.version[0]
+            ),
+        });
+    }
+    let (frames, repairs) = parse_frames(
+        frame_bytes,
+        frames_start,
+        header.version[0],
+        handlers,
+        ParseOptions {
+            frame_size_format: frame_sizes,
+            ..options
+        },
+        progress,
+    )?;
     let footer: Option<tag::Id3v2Header> = if footer_present {
         Some(parse_header(&bytes.last_chunk::<10>().unwrap().to_vec()))
     } else {
         None
     };
 
-    let result = Ok(tag::Id3v2Tag {
-        header,
-        extended_header,
-        frames,
-        footer,
-    });
+    let result = Ok((
+        tag::Id3v2Tag {
+            header,
+            extended_header,
+            frames,
+            footer,
+            changes: vec![],
+            text_limit: None,
+            frame_sizes,
+        },
+        repairs,
+    ));
 
     result
 }
 
-fn parse_extended_header(bytes: &Vec<u8>) -> tag::Id3v2ExtendedHeader {
-    let size: u32 = utility::convert_safesynch_to_u32(bytes[0], bytes[1], bytes[2], bytes[3]);
+// The extended header at the start of `bytes`. ID3v2.4 extended header sizes are synchsafe and
+// count the whole extended header, followed by flags whose data comes after them in order.
+// ID3v2.3 sizes are plain integers leaving out their own 4 bytes, followed by 2 flag bytes, the
+// size of the padding and the CRC if its flag is set.
+fn parse_extended_header(bytes: &[u8], version: u8) -> Result<tag::Id3v2ExtendedHeader, String> {
+    let too_short = || {
+        format!(
+            "extended header runs past the end of the tag ({} bytes)",
+            bytes.len()
+        )
+    };
+    let size_bytes = bytes.get(..4).ok_or_else(too_short)?;
+
+    if version == 3 {
+        let size = u32::from_be_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]]);
+        if size != 6 && size != 10 {
+            return Err(format!(
+                "extended header declares {} bytes, expected 6 or 10",
+                size
+            ));
+        }
+        let data = bytes.get(4..4 + size as usize).ok_or_else(too_short)?;
+
+        // The CRC is the only ID3v2.3 flag, and ID3v2.4 has it too
+        return Ok(tag::Id3v2ExtendedHeader {
+            size: size + 4,
+            flags: if data[0] & 0x80 != 0 {
+                tag::EXTENDED_CRC
+            } else {
+                0x00
+            },
+            restrictions: None,
+        });
+    }
 
-    assert!(
-        bytes.len() == usize::try_from(size).unwrap() + 10,
-        "parse_extended_header() must be given a bytes vector with a length of {}, length = {}",
-        size + 10,
-        bytes.len()
+    let size = utility::convert_safesynch_to_u32(
+        size_bytes[0],
+        size_bytes[1],
+        size_bytes[2],
+        size_bytes[3],
     );
+    if size < 6 {
+        return Err(format!(
+            "extended header declares {} bytes, at least 6 expected",
+            size
+        ));
+    }
+    let bytes = bytes.get(..size as usize).ok_or_else(too_short)?;
 
-    let number_of_flag_bytes = bytes[4];
+    let number_of_flag_bytes = usize::from(bytes[4]);
     let flags = bytes[5];
+    let mut idx = 5 + number_of_flag_bytes;
+
+    let mut restrictions = None;
+    for flag in [
+        tag::EXTENDED_UPDATE,
+        tag::EXTENDED_CRC,
+        tag::EXTENDED_RESTRICTIONS,
+    ] {
+        if flags & flag == 0 {
+            continue;
+        }
 
-    let b_flag_length = bytes[6];
-    let c_flag_length = bytes[7];
-
-    let mut total_frame_crc: Option<u64> = None;
-
-    // CRC data flag is set
-    if flags & 0b0010000 != 0 {
-        total_frame_crc = Some(utility::convert_safesynch_to_u64(
-            bytes[8], bytes[9], bytes[10], bytes[11], bytes[12],
-        ))
+        let length = usize::from(*bytes.get(idx).ok_or_else(too_short)?);
+        let data = bytes.get(idx + 1..idx + 1 + length).ok_or_else(too_short)?;
+        idx += 1 + length;
+
+        match (flag, data) {
+            (tag::EXTENDED_UPDATE, []) | (tag::EXTENDED_CRC, [_, _, _, _, _]) => {}
+            (tag::EXTENDED_RESTRICTIONS, [x]) => restrictions = Some(*x),
+            _ => {
+                return Err(format!(
+                    "extended header flag {:#04X} has {} bytes of data",
+                    flag, length
+                ))
+            }
+        }
     }
 
-    // positions change based on if c flag was set and its data followed the c flag length byte
-    let d_flag_length = if flags & 0b0010000 != 0 {
-        bytes[13]
-    } else {
-        bytes[8]
-    };
-    let mut restrictions: Option<u8> = if flags & 0b0010000 != 0 {
-        Some(bytes[14])
-    } else {
-        Some(bytes[9])
-    };
-
-    restrictions = if flags & 0b00010000 != 0 {
-        restrictions
-    } else {
-        None
-    };
-
-    tag::Id3v2ExtendedHeader {
+    Ok(tag::Id3v2ExtendedHeader {
         size,
-        number_of_flag_bytes,
         flags,
-        b_flag_length,
-        c_flag_length,
-        total_frame_crc,
-        d_flag_length,
         restrictions,
-    }
+    })
 }
 
 fn parse_header(bytes: &Vec<u8>) -> tag::Id3v2Header {
@@ -140,61 +425,287 @@ fn parse_header(bytes: &Vec<u8>) -> tag::Id3v2Header {
     }
 }
 
-fn parse_frame(bytes: &Vec<u8>) -> Result<tag::Frame, String> {
-    let identifier = [bytes[0], bytes[1], bytes[2], bytes[3]];
-    let size = utility::convert_safesynch_to_u32(bytes[4], bytes[5], bytes[6], bytes[7]);
-    let flags = [bytes[8], bytes[9]];
+/// Parses a single serialized frame, header included
+pub(crate) fn parse_frame_bytes(bytes: &[u8]) -> Result<tag::Frame, String> {
+    parse_frame(
+        &bytes.to_vec(),
+        4,
+        &FrameHandlers::default(),
+        &mut PrintWarnings,
+    )
+}
 
-    let header = tag::Id3v2FrameHeader {
-        identifier,
-        size,
-        flags,
+// The flags of an ID3v2.3 frame and the data they add in front of it, laid out as ID3v2.4 lays
+// them out, so frames are held (and written) the same whatever version they were read from.
+// ID3v2.3 status flags sit one bit higher, and its format flags add the decompressed size as a
+// plain integer, the encryption method and the group symbol, in that order, where ID3v2.4 has the
+// group symbol, the encryption method and a synchsafe data length indicator.
+fn upgrade_v23_frame(flags: [u8; 2], data: Vec<u8>) -> Result<([u8; 2], Vec<u8>), String> {
+    let status = (flags[0] & tag::V23_STATUS_FLAGS) >> 1;
+    let compressed = flags[1] & tag::V23_FLAG_COMPRESSION != 0;
+    let encrypted = flags[1] & tag::V23_FLAG_ENCRYPTION != 0;
+    let grouped = flags[1] & tag::V23_FLAG_GROUPING != 0;
+
+    let extra = 4 * usize::from(compressed) + usize::from(encrypted) + usize::from(grouped);
+    let Some((extra, rest)) = data.split_at_checked(extra) else {
+        return Err(format!(
+            "{} bytes of frame data, too few for its flags",
+            data.len()
+        ));
+    };
+
+    let mut format = 0x00;
+    let mut upgraded = Vec::with_capacity(data.len());
+    if grouped {
+        format |= tag::FLAG_GROUPING;
+        upgraded.push(extra[extra.len() - 1]);
+    }
+    if encrypted {
+        format |= tag::FLAG_ENCRYPTION;
+        upgraded.push(extra[4 * usize::from(compressed)]);
+    }
+    if compressed {
+        let size = u32::from_be_bytes([extra[0], extra[1], extra[2], extra[3]]);
+        if size > 0x0FFF_FFFF {
+            return Err(format!(
+                "decompressed size of {} bytes can't be held by an ID3v2.4 frame",
+                size
+            ));
+        }
+
+        format |= tag::FLAG_COMPRESSION | tag::FLAG_DATA_LENGTH;
+        upgraded.extend_from_slice(&utility::convert_u32_to_safesynch(size));
+    }
+    upgraded.extend_from_slice(rest);
+
+    Ok(([status, format], upgraded))
+}
+
+fn parse_frame(
+    bytes: &Vec<u8>,
+    version: u8,
+    handlers: &FrameHandlers,
+    progress: &mut dyn ProgressSink,
+) -> Result<tag::Frame, String> {
+    let identifier = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    let (flags, data) = match version {
+        3 => upgrade_v23_frame([bytes[8], bytes[9]], bytes[10..].to_vec())
+            .map_err(|x| format!("frame {}: {}", String::from_utf8_lossy(&identifier), x))?,
+        _ => ([bytes[8], bytes[9]], bytes[10..].to_vec()),
     };
 
-    let data = bytes[10..].to_vec();
+    let header = tag::Id3v2FrameHeader { identifier, flags };
+
+    // Grouped, compressed, encrypted, or unsynchronised data is kept byte-for-byte, since it
+    // can't be interpreted (or safely rewritten) without undoing those first
+    let format_flags = tag::FLAG_GROUPING
+        | tag::FLAG_COMPRESSION
+        | tag::FLAG_ENCRYPTION
+        | tag::FLAG_UNSYNCHRONISATION
+        | tag::FLAG_DATA_LENGTH;
+    if flags[1] & format_flags != 0 {
+        return Ok(tag::Frame::Unknown(tag::Id3v2UnknownFrame { header, data }));
+    }
+
+    // A handler that fails leaves the frame to the built-in parsing so its data is not lost
+    if let Some(handler) = handlers.find(identifier, &data) {
+        match handler.parse(&data) {
+            Ok(x) => {
+                return Ok(tag::Frame::Custom(tag::Id3v2CustomFrame {
+                    header,
+                    data: x,
+                }))
+            }
+            Err(x) => progress.on_warning(&Warning {
+                kind: "handler_failed",
+                offset: None,
+                frame: Some(String::from_utf8_lossy(&identifier).to_string()),
+                message: format!("custom handler failed: {}", x),
+            }),
+        }
+    }
+
     let binding = String::from_utf8(identifier.to_vec()).unwrap();
     let ascii_id = binding.as_str();
 
     match ascii_id {
-        "TIT2" | "TALB" | "TPE1" | "TPE2" | "TSSE" => Ok(tag::Frame::Text(tag::Id3v2TextFrame {
-            header,
-            info: tag::TextInformation {
+        // All text information frames share one layout (TXXX adds a description and is kept as-is),
+        // as do the iTunes podcast feed and movement frames
+        id if !data.is_empty()
+            && ((id.starts_with('T') && id != "TXXX")
+                || matches!(id, "WFED" | "MVNM" | "MVIN")) =>
+        {
+            // Read as UTF-8 by the text getters, which is what taggers misusing the byte mean
+            if data[0] > 0x03 {
+                progress.on_warning(&Warning {
+                    kind: "encoding_guessed",
+                    offset: None,
+                    frame: Some(id.to_string()),
+                    message: format!("unknown text encoding {:#04X}, read as UTF-8", data[0]),
+                });
+            }
+
+            let info = tag::TextInformation {
                 encoding: data[0],
                 data: data[1..].to_vec(),
-            },
-        })),
+            };
+
+            // Kept as stored, but flagged so it can be fixed, e.g. year 0000 or month 13
+            if timestamp::TIMESTAMP_FRAMES.contains(&id) {
+                if let Err(x) = Timestamp::parse(&info.text()) {
+                    progress.on_warning(&Warning {
+                        kind: "invalid_timestamp",
+                        offset: None,
+                        frame: Some(id.to_string()),
+                        message: x,
+                    });
+                }
+            }
+
+            Ok(tag::Frame::Text(tag::Id3v2TextFrame { header, info }))
+        }
         "APIC" => {
-            let extracted_picture = extract::extract_picture(&data).unwrap();
+            // Pictures that can't be read are kept as-is rather than lost
+            let extracted_picture = match extract::extract_picture(&data) {
+                Ok(x) => x,
+                Err(x) => {
+                    progress.on_warning(&Warning {
+                        kind: "unreadable_picture",
+                        offset: None,
+                        frame: Some("APIC".to_string()),
+                        message: format!("unreadable picture kept unchanged: {}", x),
+                    });
+                    return Ok(tag::Frame::Unknown(tag::Id3v2UnknownFrame { header, data }));
+                }
+            };
 
             Ok(tag::Frame::Picture(tag::Id3v2PictureFrame {
                 header,
-                picture: tag::Picture {
-                    encoding: extracted_picture.encoding,
-                    mime: extracted_picture.mime,
-                    picture_type: extracted_picture.picture_type,
-                    description: extracted_picture.description,
-                    data: extracted_picture.data,
-                },
+                picture: extracted_picture,
             }))
         }
-        _ => Err(format!("Unknown frame id {}", ascii_id)),
+        // Frames without dedicated support are carried through untouched
+        id => {
+            if id != "TXXX"
+                && id != "WXXX"
+                && utility::get_field_name(identifier) == "Unknown frame"
+            {
+                progress.on_warning(&Warning {
+                    kind: "nonstandard_frame",
+                    offset: None,
+                    frame: Some(id.to_string()),
+                    message: "not an ID3v2.4 frame, kept unchanged".to_string(),
+                });
+            }
+
+            Ok(tag::Frame::Unknown(tag::Id3v2UnknownFrame { header, data }))
+        }
+    }
+}
+
+fn is_frame_identifier(bytes: &[u8]) -> bool {
+    bytes.len() == 4
+        && bytes[0].is_ascii_uppercase()
+        && bytes
+            .iter()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit())
+}
+
+// Whether well-formed frames starting at `bytes` run exactly to the end or into padding
+fn is_frame_chain(bytes: &[u8], sizes: FrameSizeFormat) -> bool {
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx..].iter().take(4).all(|x| *x == 0x00) {
+            return true;
+        }
+
+        if bytes.len() - idx < 10 || !is_frame_identifier(&bytes[idx..idx + 4]) {
+            return false;
+        }
+
+        idx += 10 + sizes.read(&bytes[idx + 4..idx + 8]);
     }
+
+    idx == bytes.len()
+}
+
+// A frame declaring more data than remains keeps everything up to the first point where the
+// rest of the tag parses as intact frames, so the frames after it survive
+fn lenient_frame_size(bytes: &[u8], sizes: FrameSizeFormat) -> usize {
+    let available = bytes.len() - 10;
+
+    (1..available)
+        .find(|x| {
+            is_frame_identifier(&bytes[10 + x..(14 + x).min(bytes.len())])
+                && is_frame_chain(&bytes[10 + x..], sizes)
+        })
+        .unwrap_or(available)
+}
+
+// Unused flag bits are always clear in frames written by a conforming tagger. ID3v2.3 has three
+// status and three format flags in the top bits of each byte, ID3v2.4 moved them around.
+fn has_plausible_flags(flags: &[u8], version: u8) -> bool {
+    match version {
+        3 => flags[0] & 0b00011111 == 0 && flags[1] & 0b00011111 == 0,
+        _ => flags[0] & 0b10001111 == 0 && flags[1] & 0b10110000 == 0,
+    }
+}
+
+/// Scans malformed frame data for the next offset (at least 1) where a plausible frame header
+/// starts: a `[A-Z0-9]{4}` identifier with a size read as `sizes` (which must be synchsafe unless
+/// read as be32) that fits in `bytes`, valid flags for an ID3v2.`version` frame, and padding,
+/// another identifier or the end of the data right after it
+pub fn resync(bytes: &[u8], sizes: FrameSizeFormat, version: u8) -> Option<usize> {
+    (1..bytes.len().saturating_sub(9)).find(|x| {
+        let header = &bytes[*x..x + 10];
+        if !is_frame_identifier(&header[..4])
+            || (sizes != FrameSizeFormat::Be32 && header[4..8].iter().any(|x| x & 0x80 != 0))
+            || !has_plausible_flags(&header[8..], version)
+        {
+            return false;
+        }
+
+        let end = x + 10 + sizes.read(&header[4..8]);
+
+        end == bytes.len()
+            || (end < bytes.len()
+                && (bytes[end] == 0x00
+                    || is_frame_identifier(&bytes[end..(end + 4).min(bytes.len())])))
+    })
 }
 
-fn parse_frames(bytes: &Vec<u8>) -> Vec<tag::Frame> {
+// `start` is the position of the frames in the tag, so repairs give positions in the file
+fn parse_frames(
+    bytes: &Vec<u8>,
+    start: usize,
+    version: u8,
+    handlers: &FrameHandlers,
+    options: ParseOptions,
+    progress: &mut dyn ProgressSink,
+) -> Result<(Vec<tag::Frame>, Vec<Repair>), String> {
     let frame_bytes = bytes.clone();
 
     let mut idx = 0;
     let mut frames: Vec<tag::Frame> = vec![];
+    let mut repairs: Vec<Repair> = vec![];
 
     while idx < frame_bytes.len() {
         // There are no frame identifiers with 0x00 0x00 0x00 0x00
         // therefore it is padding and end of frame bytes
-        if frame_bytes[idx] == 0x00
-            && frame_bytes[idx + 1] == 0x00
-            && frame_bytes[idx + 2] == 0x00
-            && frame_bytes[idx + 3] == 0x00
-        {
+        if frame_bytes[idx..].iter().take(4).all(|x| *x == 0x00) {
+            let data = frame_bytes[idx..].iter().filter(|x| **x != 0x00).count();
+            if data > 0 {
+                progress.on_warning(&Warning {
+                    kind: "padding_discarded",
+                    offset: Some(start + idx),
+                    frame: None,
+                    message: format!(
+                        "{} non-zero byte(s) in the padding, discarded when the tag is written",
+                        data
+                    ),
+                });
+            }
             break;
         }
 
@@ -202,20 +713,223 @@ fn parse_frames(bytes: &Vec<u8>) -> Vec<tag::Frame> {
         // not fulfilling this likely means a frame was encoded into bytes
         // incorrectly
         if frame_bytes[idx..].len() < 11 {
-            println!(
-                "[warning] unexpected misshaped final frame: {}",
-                String::from_utf8(frame_bytes[idx..].to_vec()).unwrap()
-            );
-            return frames;
+            repairs.push(Repair {
+                offset: start + idx,
+                frame: None,
+                action: format!(
+                    "dropped misshaped final frame: {}",
+                    String::from_utf8_lossy(&frame_bytes[idx..])
+                ),
+            });
+            return Ok((frames, repairs));
         }
 
-        // println!("{:?}", frame_bytes[idx..].to_vec());
+        // Usually the remains of a frame whose declared size is too small
+        if !is_frame_identifier(&frame_bytes[idx..idx + 4]) {
+            if !options.lenient {
+                return Err(format!(
+                    "expected a frame at offset {:#X}, found {:02X?}",
+                    start + idx,
+                    &frame_bytes[idx..idx + 4]
+                ));
+            }
+
+            match resync(&frame_bytes[idx..], options.frame_size_format, version) {
+                Some(x) => {
+                    repairs.push(Repair {
+                        offset: start + idx,
+                        frame: None,
+                        action: format!(
+                            "skipped {} bytes of malformed data, resynchronized at {:#X}",
+                            x,
+                            start + idx + x
+                        ),
+                    });
+                    idx += x;
+                    continue;
+                }
+                None => {
+                    repairs.push(Repair {
+                        offset: start + idx,
+                        frame: None,
+                        action: format!(
+                            "dropped {} bytes of malformed data with no frame after them, keeping the {} frame(s) before",
+                            frame_bytes.len() - idx,
+                            frames.len()
+                        ),
+                    });
+                    return Ok((frames, repairs));
+                }
+            }
+        }
 
-        let (unparsed_frame_bytes, end) = extract::extract_frame(idx, &frame_bytes);
+        // println!("{:?}", frame_bytes[idx..].to_vec());
 
-        frames.push(parse_frame(&unparsed_frame_bytes).unwrap());
+        let identifier = String::from_utf8_lossy(&frame_bytes[idx..idx + 4]).to_string();
+        let declared_size = options
+            .frame_size_format
+            .read(&frame_bytes[idx + 4..idx + 8]);
+        let available = frame_bytes.len() - idx - 10;
+
+        let size = if declared_size <= available {
+            declared_size
+        } else if options.lenient {
+            let size = lenient_frame_size(&frame_bytes[idx..], options.frame_size_format);
+            repairs.push(Repair {
+                offset: start + idx,
+                frame: Some(identifier),
+                action: format!(
+                    "declares {} bytes but only {} remain in the tag, truncated to {}",
+                    declared_size, available, size
+                ),
+            });
+            size
+        } else {
+            return Err(format!(
+                "frame {} declares {} bytes but only {} remain in the tag",
+                identifier, declared_size, available
+            ));
+        };
+
+        // The frame's size is derived from the data kept when it is written back
+        let end = idx + 10 + size;
+        let unparsed_frame_bytes = frame_bytes[idx..end].to_vec();
+
+        let frame = parse_frame(&unparsed_frame_bytes, version, handlers, progress)?;
+        progress.on_frame_parsed(&frame);
+        frames.push(frame);
         idx = end;
     }
 
-    frames
+    Ok((frames, repairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::fixtures::{frame, tag_bytes};
+    use crate::zlib;
+
+    // ID3v2.3 compressed data: the decompressed size as a plain integer, then the zlib stream
+    fn v23_compressed(data: &[u8]) -> Vec<u8> {
+        let size = u32::try_from(data.len()).unwrap().to_be_bytes();
+        [size.as_slice(), &zlib::compress(data)].concat()
+    }
+
+    #[test]
+    fn reads_v23_compressed_frames_as_compressed() {
+        let bytes = tag_bytes(
+            3,
+            &[frame(
+                b"TIT2",
+                [0x00, tag::V23_FLAG_COMPRESSION],
+                &v23_compressed(b"\x00Title"),
+                3,
+            )],
+        );
+        let mut tag = parse_tag(&bytes).unwrap();
+
+        assert!(matches!(tag.frames()[0], tag::Frame::Unknown(_)));
+        assert_eq!(tag.get_song_title(), None);
+
+        assert_eq!(tag.decompress_frames(), 1);
+        assert_eq!(tag.get_song_title().as_deref(), Some("Title"));
+    }
+
+    #[test]
+    fn reads_v23_encrypted_frames_as_encrypted() {
+        let bytes = tag_bytes(
+            3,
+            &[
+                frame(
+                    b"TIT2",
+                    [0x00, tag::V23_FLAG_ENCRYPTION],
+                    b"\x80\x12\x34\x56",
+                    3,
+                ),
+                // Compressed, encrypted and grouped: size, method and group symbol in that order
+                frame(
+                    b"PRIV",
+                    [0x00, 0xE0],
+                    &[b"\x00\x00\x00\x05\x81\x07".as_slice(), b"\x12\x34"].concat(),
+                    3,
+                ),
+            ],
+        );
+        let tag = parse_tag(&bytes).unwrap();
+
+        let frames = tag.frames();
+        assert!(matches!(frames[0], tag::Frame::Unknown(_)));
+        assert_eq!(frames[0].encryption_method(), Some(0x80));
+        assert_eq!(frames[0].group_symbol(), None);
+        assert_eq!(tag.get_song_title(), None);
+
+        assert_eq!(frames[1].group_symbol(), Some(0x07));
+        assert_eq!(frames[1].encryption_method(), Some(0x81));
+        assert_eq!(
+            frames[1].data(),
+            Some(b"\x07\x81\x00\x00\x00\x05\x12\x34".as_slice())
+        );
+    }
+
+    #[test]
+    fn refuses_v23_frames_too_short_for_their_flags() {
+        let bytes = tag_bytes(
+            3,
+            &[frame(
+                b"TIT2",
+                [0x00, tag::V23_FLAG_COMPRESSION],
+                b"\x00\x01",
+                3,
+            )],
+        );
+
+        assert!(parse_tag(&bytes).is_err());
+    }
+
+    #[test]
+    fn resyncs_on_frame_flags_of_the_tags_version() {
+        // Tag alter preservation and compression, both in the top bits in ID3v2.3
+        let compressed = frame(
+            b"TIT2",
+            [0x80, tag::V23_FLAG_COMPRESSION],
+            &v23_compressed(b"\x00Title"),
+            3,
+        );
+        let bytes = [b"junk".as_slice(), &compressed].concat();
+
+        assert_eq!(resync(&bytes, FrameSizeFormat::Be32, 3), Some(4));
+        assert_eq!(resync(&bytes, FrameSizeFormat::Be32, 4), None);
+
+        // ID3v2.4's data length indicator is an unused bit in ID3v2.3
+        let flagged = frame(b"TIT2", [0x00, tag::FLAG_DATA_LENGTH], b"\x00Title", 3);
+        let bytes = [b"junk".as_slice(), &flagged].concat();
+        assert_eq!(resync(&bytes, FrameSizeFormat::Be32, 3), None);
+        assert_eq!(resync(&bytes, FrameSizeFormat::Be32, 4), Some(4));
+    }
+
+    #[test]
+    fn lenient_v23_parsing_skips_to_flagged_frames() {
+        let frames = [
+            frame(b"TPE1", [0x00, 0x00], b"\x00Artist", 3),
+            b"junk".to_vec(),
+            frame(
+                b"TIT2",
+                [0x80, tag::V23_FLAG_COMPRESSION],
+                &v23_compressed(b"\x00Title"),
+                3,
+            ),
+        ];
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let (tag, repairs) =
+            parse_tag_with_repairs(&tag_bytes(3, &frames), &FrameHandlers::default(), options)
+                .unwrap();
+
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(tag.frames().len(), 2);
+        assert_eq!(tag.frames()[1].id(), "TIT2");
+    }
 }
@@ -0,0 +1,131 @@
+use std::fs;
+
+use alloy::{
+    chapter::{self, Chapter},
+    json::{self, JsonValue},
+    tag,
+};
+
+fn chapter_time(chapter: &JsonValue, key: &str) -> Result<Option<u32>, String> {
+    if let Some(x) = chapter
+        .get(&format!("{}_ms", key))
+        .and_then(JsonValue::as_f64)
+    {
+        return Ok(Some(x as u32));
+    }
+
+    match chapter.get(key) {
+        Some(JsonValue::String(x)) => chapter::parse_time(x).map(Some),
+        Some(JsonValue::Number(x)) => Ok(Some((x * 1000.0) as u32)),
+        Some(_) => Err(format!(
+            "chapter '{}' must be a time string or seconds",
+            key
+        )),
+        None => Ok(None),
+    }
+}
+
+// Chapters without an end run until the next chapter starts
+fn read_chapters(values: &[JsonValue]) -> Result<Vec<Chapter>, String> {
+    let mut chapters: Vec<Chapter> = vec![];
+    let mut ends: Vec<Option<u32>> = vec![];
+
+    for (idx, value) in values.iter().enumerate() {
+        let Some(start_ms) = chapter_time(value, "start")? else {
+            return Err(format!("chapter {} is missing a start time", idx + 1));
+        };
+
+        ends.push(chapter_time(value, "end")?);
+        chapters.push(Chapter {
+            element_id: format!("chp{}", idx),
+            start_ms,
+            end_ms: 0,
+            title: value.get("title").and_then(JsonValue::to_text),
+        });
+    }
+
+    for idx in 0..chapters.len() {
+        chapters[idx].end_ms = match (ends[idx], chapters.get(idx + 1)) {
+            (Some(x), _) => x,
+            (None, Some(next)) => next.start_ms,
+            (None, None) => return Err("the last chapter must have an end time".to_string()),
+        };
+    }
+
+    Ok(chapters)
+}
+
+/// Applies an episode manifest (JSON) to a tag, setting the podcast frames, chapters, and
+/// episode metadata. Returns an error describing the first invalid field.
+pub fn apply_podcast_manifest(tag: &mut tag::Id3v2Tag, path: &str) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(x) => return Err(format!("unable to read podcast manifest {}: {}", path, x)),
+    };
+    let manifest = json::parse(&text)?;
+
+    let text_field = |key: &str| manifest.get(key).and_then(JsonValue::to_text);
+
+    if let Some(x) = text_field("title") {
+        tag.set_song_title(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("author") {
+        tag.set_song_artist_name(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("podcast") {
+        tag.set_album_title(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("description") {
+        tag.set_podcast_description(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("category") {
+        tag.set_podcast_category(x + "\0")?;
+    }
+
+    let keywords = match manifest.get("keywords") {
+        Some(JsonValue::Array(x)) => Some(
+            x.iter()
+                .filter_map(JsonValue::to_text)
+                .collect::<Vec<String>>()
+                .join(","),
+        ),
+        Some(x) => x.to_text(),
+        None => None,
+    };
+    if let Some(x) = keywords {
+        tag.set_podcast_keywords(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("feed_url") {
+        tag.set_podcast_feed_url(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("guid") {
+        tag.set_podcast_id(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("episode") {
+        tag.set_track_number(x + "\0")?;
+    }
+
+    if let Some(x) = text_field("release_date") {
+        tag.set_release_time(x + "\0")?;
+    }
+
+    if let Some(x) = manifest.get("chapters") {
+        let Some(values) = x.as_array() else {
+            return Err("podcast manifest 'chapters' must be an array".to_string());
+        };
+
+        tag.set_chapters(&read_chapters(values)?);
+    }
+
+    tag.set_content_type("Podcast\0".to_string())?;
+    tag.set_podcast_flag();
+
+    Ok(())
+}
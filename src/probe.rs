@@ -0,0 +1,162 @@
+use std::{fs, io::Read, path::Path};
+
+use crate::json::JsonValue;
+use crate::utility;
+
+/// Bytes read from the start of a file by `probe`, enough for the header and the text frames
+/// taggers write before any cover art
+pub const PROBE_WINDOW: usize = 8192;
+
+/// What the first `PROBE_WINDOW` bytes of a file tell about its tag, without parsing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    /// Major version and revision, e.g. [4, 0] for ID3v2.4.0
+    pub version: [u8; 2],
+    /// Size of the tag, header and footer included
+    pub tag_size: usize,
+    /// None when the window ended before the last frame without an APIC frame in it
+    pub has_cover_art: Option<bool>,
+    /// None when there is no TIT2 frame, or it doesn't end within the window
+    pub title: Option<String>,
+    /// None when there is no TPE1 frame, or it doesn't end within the window
+    pub artist: Option<String>,
+}
+
+impl Probe {
+    pub fn to_json(&self) -> JsonValue {
+        let optional = |x: &Option<String>| match x {
+            Some(x) => JsonValue::String(x.clone()),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            (
+                "version".to_string(),
+                JsonValue::String(format!("2.{}.{}", self.version[0], self.version[1])),
+            ),
+            (
+                "tag_size".to_string(),
+                JsonValue::Number(self.tag_size as f64),
+            ),
+            (
+                "has_cover_art".to_string(),
+                match self.has_cover_art {
+                    Some(x) => JsonValue::Bool(x),
+                    None => JsonValue::Null,
+                },
+            ),
+            ("title".to_string(), optional(&self.title)),
+            ("artist".to_string(), optional(&self.artist)),
+        ])
+    }
+}
+
+/// Probes the tag of the file at `path`, reading no more than `PROBE_WINDOW` bytes of it
+pub fn probe(path: &Path) -> Result<Probe, String> {
+    let mut bytes = Vec::with_capacity(PROBE_WINDOW);
+
+    fs::File::open(path)
+        .and_then(|x| x.take(PROBE_WINDOW as u64).read_to_end(&mut bytes))
+        .map_err(|x| format!("unable to read {}: {}", path.display(), x))?;
+
+    probe_bytes(&bytes)
+}
+
+// First value of a text frame's data, or None for frames compressed, encrypted or
+// unsynchronised, which can't be read without parsing them
+fn frame_text(major: u8, format_flags: u8, data: &[u8]) -> Option<String> {
+    let (unreadable, prefix) = if major == 4 {
+        (
+            0x08 | 0x04 | 0x02,
+            usize::from(format_flags & 0x40 != 0) + 4 * usize::from(format_flags & 0x01 != 0),
+        )
+    } else {
+        (0x80 | 0x40, usize::from(format_flags & 0x20 != 0))
+    };
+    if format_flags & unreadable != 0 {
+        return None;
+    }
+
+    let (encoding, text) = data.get(prefix..)?.split_first()?;
+    let text = utility::decode_text(*encoding, text);
+
+    Some(text.split('\0').next().unwrap_or_default().to_string())
+}
+
+/// Probes a tag from the start of a file, which may be cut short: the header gives the version
+/// and size, and frames are walked by their headers as far as `bytes` goes
+pub fn probe_bytes(bytes: &[u8]) -> Result<Probe, String> {
+    if bytes.len() < 10 || !bytes.starts_with(b"ID3") {
+        return Err("no ID3v2 tag".to_string());
+    }
+
+    let major = bytes[3];
+    let flags = bytes[5];
+    let size = usize::try_from(utility::convert_safesynch_to_u32(
+        bytes[6], bytes[7], bytes[8], bytes[9],
+    ))
+    .unwrap();
+    let footer_size = if flags & 0x10 != 0 { 10 } else { 0 };
+
+    let mut result = Probe {
+        version: [bytes[3], bytes[4]],
+        tag_size: 10 + size + footer_size,
+        has_cover_art: None,
+        title: None,
+        artist: None,
+    };
+
+    // ID3v2.2 frames have 3 character identifiers, and are left unread
+    if major != 3 && major != 4 {
+        return Ok(result);
+    }
+
+    // ID3v2.3 sizes are plain integers, ID3v2.4 sizes synchsafe
+    let read_size = |x: &[u8]| {
+        let size = match major {
+            4 => utility::convert_safesynch_to_u32(x[0], x[1], x[2], x[3]),
+            _ => u32::from_be_bytes([x[0], x[1], x[2], x[3]]),
+        };
+        usize::try_from(size).unwrap()
+    };
+
+    let tag_end = 10 + size;
+    let mut idx = 10;
+
+    if flags & 0x40 != 0 {
+        let Some(x) = bytes.get(10..14) else {
+            return Ok(result);
+        };
+        // The ID3v2.3 extended header size leaves out its own 4 bytes
+        idx += read_size(x) + if major == 3 { 4 } else { 0 };
+    }
+
+    let mut has_cover_art = false;
+    let complete = loop {
+        if idx >= tag_end || bytes.get(idx) == Some(&0x00) {
+            break true;
+        }
+        let Some(header) = bytes.get(idx..idx + 10) else {
+            break false;
+        };
+
+        let data_start = idx + 10;
+        let data_end = data_start + read_size(&header[4..8]);
+        let data = bytes.get(data_start..data_end);
+
+        match (&header[..4], data) {
+            (b"APIC", _) => has_cover_art = true,
+            (b"TIT2", Some(x)) => result.title = frame_text(major, header[9], x),
+            (b"TPE1", Some(x)) => result.artist = frame_text(major, header[9], x),
+            _ => {}
+        }
+
+        idx = data_end;
+    };
+
+    if complete || has_cover_art {
+        result.has_cover_art = Some(has_cover_art);
+    }
+
+    Ok(result)
+}
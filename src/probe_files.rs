@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use alloy::{json::JsonValue, json_schema, probe};
+
+use crate::art;
+
+/// Print what the first few KB of each file tell about its tag, without parsing it
+#[derive(clap::Args, Debug)]
+pub struct ProbeArgs {
+    /// MP3 files, or folders searched recursively for them
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// Output format, one line per file or a JSON array of probes
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    format: String,
+}
+
+fn describe(result: &probe::Probe) -> String {
+    let cover_art = match result.has_cover_art {
+        Some(true) => "cover art",
+        Some(false) => "no cover art",
+        None => "cover art unknown",
+    };
+    let mut fields = vec![
+        format!("ID3v2.{}.{}", result.version[0], result.version[1]),
+        format!("{} bytes", result.tag_size),
+        cover_art.to_string(),
+    ];
+
+    match (&result.artist, &result.title) {
+        (Some(artist), Some(title)) => fields.push(format!("{} - {}", artist, title)),
+        (None, Some(x)) | (Some(x), None) => fields.push(x.clone()),
+        (None, None) => {}
+    }
+
+    fields.join(" | ")
+}
+
+pub fn process_probe(args: &ProbeArgs) {
+    let mut files = vec![];
+    for path in &args.paths {
+        let path = PathBuf::from(path);
+
+        if path.is_dir() {
+            art::collect_mp3_files(&path, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut probes = vec![];
+
+    for file in &files {
+        let result = match probe::probe(file) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{} | {}", file.display(), x);
+                continue;
+            }
+        };
+
+        if args.format == "json" {
+            probes.push((file, result));
+        } else {
+            println!("{} | {}", file.display(), describe(&result));
+        }
+    }
+
+    if args.format == "json" {
+        let files = probes
+            .iter()
+            .map(|(path, result)| {
+                let mut fields = vec![(
+                    "path".to_string(),
+                    JsonValue::String(path.display().to_string()),
+                )];
+                if let JsonValue::Object(x) = result.to_json() {
+                    fields.extend(x);
+                }
+                JsonValue::Object(fields)
+            })
+            .collect();
+
+        println!(
+            "{}",
+            JsonValue::Object(vec![
+                json_schema::version_field(),
+                ("files".to_string(), JsonValue::Array(files)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{json::JsonValue, tag::Frame};
+
+/// A problem with a file that didn't stop it being read, e.g. a frame kept unparsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Stable name for scripts: "handler_failed", "unreadable_picture", "nonstandard_frame",
+    /// "encoding_guessed", "invalid_timestamp", "padding_discarded", "repaired",
+    /// "corrupt_audio" or "frame_sizes"
+    pub kind: &'static str,
+    /// Position in the file, when known
+    pub offset: Option<usize>,
+    /// Frame identifier, or None for problems outside a frame
+    pub frame: Option<String>,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        if let Some(x) = self.offset {
+            text += &format!("{:#010X} ", x);
+        }
+        if let Some(x) = &self.frame {
+            text += &format!("{}: ", x);
+        }
+
+        text + &self.message
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("kind".to_string(), JsonValue::String(self.kind.to_string())),
+            (
+                "offset".to_string(),
+                self.offset
+                    .map_or(JsonValue::Null, |x| JsonValue::Number(x as f64)),
+            ),
+            (
+                "frame".to_string(),
+                self.frame
+                    .clone()
+                    .map_or(JsonValue::Null, JsonValue::String),
+            ),
+            (
+                "message".to_string(),
+                JsonValue::String(self.message.clone()),
+            ),
+        ])
+    }
+}
+
+/// Receives events while files are read and written, so a frontend embedding the library can
+/// drive progress bars and warning panels rather than reading stdout. Every method does nothing
+/// by default.
+pub trait ProgressSink {
+    fn on_file_start(&mut self, _path: &str) {}
+
+    fn on_frame_parsed(&mut self, _frame: &Frame) {}
+
+    /// Something was wrong with the file but it could still be read, e.g. a frame kept unparsed
+    fn on_warning(&mut self, _warning: &Warning) {}
+
+    fn on_file_written(&mut self, _path: &str) {}
+}
+
+/// Prints warnings to stdout and ignores every other event, what the parsing functions that
+/// don't take a sink use
+pub struct PrintWarnings;
+
+impl ProgressSink for PrintWarnings {
+    fn on_warning(&mut self, warning: &Warning) {
+        println!("[warning] {}", warning.describe());
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use alloy::{parse, storage, text_encoding::LegacyCharset};
+
+use crate::{art, interrupt};
+
+/// Rewrite the text of every frame in one encoding, decoding each from the encoding its bytes
+/// are actually in
+#[derive(clap::Args, Debug)]
+pub struct ReencodeArgs {
+    /// MP3 files, or folders searched recursively for them, modified in place
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// Encoding to rewrite the text in
+    #[arg(long, default_value = "utf8", value_parser = ["utf8"])]
+    to: String,
+
+    /// List the frames that would be rewritten, with their text before and after, without writing
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Decode text marked ISO-8859-1 in the legacy code page it looks like, e.g. Windows-1251,
+    /// KOI8-R or Shift_JIS
+    #[cfg(feature = "charset")]
+    #[arg(long)]
+    detect_charset: bool,
+}
+
+impl ReencodeArgs {
+    fn legacy_charset(&self) -> LegacyCharset {
+        #[cfg(feature = "charset")]
+        if self.detect_charset {
+            return LegacyCharset::Detect;
+        }
+
+        LegacyCharset::Latin1
+    }
+}
+
+// Returns the identifiers of the frames rewritten
+fn reencode_file(file: &Path, legacy: LegacyCharset, dry_run: bool) -> Result<Vec<String>, String> {
+    let location = file.to_string_lossy();
+    let _locks = storage::lock(&[&location])?;
+    let (mut tag, audio_data) = parse::parse_file(file)?;
+
+    let reencoded = tag.reencode_utf8(legacy);
+    if dry_run {
+        for change in tag.changes() {
+            println!("  {}", change.describe());
+        }
+    } else if !reencoded.is_empty() {
+        storage::write_verified(&location, &tag, &audio_data)?;
+    }
+
+    Ok(reencoded)
+}
+
+pub fn process_reencode(args: &ReencodeArgs) {
+    let mut files = vec![];
+    for path in &args.paths {
+        let path = PathBuf::from(path);
+
+        if path.is_dir() {
+            art::collect_mp3_files(&path, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut rewritten = 0;
+    for file in &files {
+        if interrupt::requested() {
+            println!("Interrupted, the remaining files were not touched");
+            break;
+        }
+
+        match reencode_file(file, args.legacy_charset(), args.dry_run) {
+            Ok(x) if x.is_empty() => {}
+            Ok(x) => {
+                rewritten += 1;
+                println!(
+                    "{} | {} {} frame(s): {}",
+                    file.display(),
+                    if args.dry_run {
+                        "Would re-encode"
+                    } else {
+                        "Re-encoded"
+                    },
+                    x.len(),
+                    x.join(", ")
+                );
+            }
+            Err(x) => eprintln!("{} | {}", file.display(), x),
+        }
+    }
+
+    println!(
+        "{} of {} file(s) {} as {}",
+        rewritten,
+        files.len(),
+        if args.dry_run {
+            "would be rewritten"
+        } else {
+            "rewritten"
+        },
+        args.to
+    );
+}
@@ -0,0 +1,226 @@
+use crate::{
+    tag::{Frame, Id3v2Tag},
+    utility,
+};
+
+/// Lowest and highest group and method symbols the spec allows
+pub const SYMBOLS: (u8, u8) = (0x80, 0xF0);
+
+/// Parses a group or method symbol given as hex ("0x80") or decimal ("128"), checking the spec's
+/// range
+pub fn parse_symbol(text: &str) -> Result<u8, String> {
+    let symbol = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(x) => u8::from_str_radix(x, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("invalid symbol '{}'", text))?;
+
+    if symbol < SYMBOLS.0 || symbol > SYMBOLS.1 {
+        return Err(format!(
+            "symbol {:#04X} is out of range, expected {:#04X} to {:#04X}",
+            symbol, SYMBOLS.0, SYMBOLS.1
+        ));
+    }
+
+    Ok(symbol)
+}
+
+fn split_owner(data: &[u8]) -> Result<(String, &[u8]), String> {
+    match utility::split_terminated(data, 0x00) {
+        Some((owner, rest)) => Ok((utility::decode_text(0x00, owner), rest)),
+        None => Err("owner identifier is not terminated".to_string()),
+    }
+}
+
+/// Contents of an ENCR frame: registers the method symbol frames encrypted with it carry
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptionMethod {
+    /// URL or email of the organisation responsible for the method
+    pub owner: String,
+    pub method_symbol: u8,
+    pub data: Vec<u8>,
+}
+
+impl EncryptionMethod {
+    pub fn parse(data: &[u8]) -> Result<EncryptionMethod, String> {
+        let (owner, rest) = split_owner(data)?;
+        let Some((method_symbol, rest)) = rest.split_first() else {
+            return Err("encryption registration is truncated".to_string());
+        };
+
+        Ok(EncryptionMethod {
+            owner,
+            method_symbol: *method_symbol,
+            data: rest.to_vec(),
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        [
+            self.owner.as_bytes(),
+            &[0x00, self.method_symbol],
+            &self.data,
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "method {:#04X?} owned by '{}', {} bytes of data",
+            self.method_symbol,
+            self.owner,
+            self.data.len()
+        )
+    }
+}
+
+/// Contents of a GRID frame: registers the symbol frames belonging to a group carry
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRegistration {
+    pub owner: String,
+    pub group_symbol: u8,
+    pub data: Vec<u8>,
+}
+
+impl GroupRegistration {
+    pub fn parse(data: &[u8]) -> Result<GroupRegistration, String> {
+        let (owner, rest) = split_owner(data)?;
+        let Some((group_symbol, rest)) = rest.split_first() else {
+            return Err("group registration is truncated".to_string());
+        };
+
+        Ok(GroupRegistration {
+            owner,
+            group_symbol: *group_symbol,
+            data: rest.to_vec(),
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        [
+            self.owner.as_bytes(),
+            &[0x00, self.group_symbol],
+            &self.data,
+        ]
+        .concat()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "group {:#04X?} owned by '{}', {} bytes of data",
+            self.group_symbol,
+            self.owner,
+            self.data.len()
+        )
+    }
+}
+
+/// Contents of a SIGN frame: a signature over the frames of one group
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub group_symbol: u8,
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn parse(data: &[u8]) -> Result<Signature, String> {
+        let Some((group_symbol, rest)) = data.split_first() else {
+            return Err("signature frame is empty".to_string());
+        };
+
+        Ok(Signature {
+            group_symbol: *group_symbol,
+            signature: rest.to_vec(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "group {:#04X?}, {} byte signature",
+            self.group_symbol,
+            self.signature.len()
+        )
+    }
+}
+
+fn registrations<T>(
+    tag: &Id3v2Tag,
+    frame_id: &[u8; 4],
+    parse: fn(&[u8]) -> Result<T, String>,
+) -> Vec<T> {
+    tag.frames()
+        .iter()
+        .filter(|x| &x.identifier() == frame_id)
+        .filter_map(|x| parse(x.data()?).ok())
+        .collect()
+}
+
+/// Group and encryption method of a frame with the owners registering them, e.g.
+/// "group 0x81 ('https://example.com'), encrypted with method 0x80 ('https://example.com')"
+pub fn describe_protection(tag: &Id3v2Tag, frame: &Frame) -> Option<String> {
+    let mut parts = vec![];
+
+    if let Some(symbol) = frame.group_symbol() {
+        let owner = registrations(tag, b"GRID", GroupRegistration::parse)
+            .into_iter()
+            .find(|x| x.group_symbol == symbol)
+            .map_or("unregistered".to_string(), |x| format!("'{}'", x.owner));
+        parts.push(format!("group {:#04X?} ({})", symbol, owner));
+    }
+
+    if let Some(symbol) = frame.encryption_method() {
+        let owner = registrations(tag, b"ENCR", EncryptionMethod::parse)
+            .into_iter()
+            .find(|x| x.method_symbol == symbol)
+            .map_or("unregistered".to_string(), |x| format!("'{}'", x.owner));
+        parts.push(format!(
+            "encrypted with method {:#04X?} ({})",
+            symbol, owner
+        ));
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+
+    Some(parts.join(", "))
+}
+
+/// Frames alloy must not rewrite: encrypted frames, and every frame (including signatures)
+/// belonging to a group that contains an encrypted frame
+pub fn protected_frame_bytes(tag: &Id3v2Tag) -> Vec<Vec<u8>> {
+    let encrypted_groups: Vec<u8> = tag
+        .frames()
+        .iter()
+        .filter(|x| x.encryption_method().is_some())
+        .filter_map(|x| x.group_symbol())
+        .collect();
+
+    tag.frames()
+        .iter()
+        .filter(|x| {
+            let group = match &x.identifier() {
+                b"SIGN" => x.data().and_then(|x| x.first().copied()),
+                _ => x.group_symbol(),
+            };
+
+            x.encryption_method().is_some() || group.is_some_and(|x| encrypted_groups.contains(&x))
+        })
+        .map(|x| x.into_bytes())
+        .collect()
+}
+
+/// Checks that every protected frame from `original` is present byte-for-byte in `modified`
+pub fn verify_protected_frames(original: &[Vec<u8>], modified: &Id3v2Tag) -> Result<(), String> {
+    let remaining = protected_frame_bytes(modified);
+    let missing = original.iter().filter(|x| !remaining.contains(x)).count();
+
+    if missing > 0 {
+        return Err(format!(
+            "{} encrypted or encrypted-group frame(s) would be lost or altered, refusing to write (use --force to override)",
+            missing
+        ));
+    }
+
+    Ok(())
+}
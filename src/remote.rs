@@ -0,0 +1,310 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use crate::utility;
+
+// Enough for the header and the frames of most tags without embedded art
+const INITIAL_RANGE: usize = 64 * 1024;
+const MAX_REDIRECTS: u32 = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Fetches only the ID3v2 tag at the start of a remote file using HTTP range requests
+pub fn fetch_tag_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = fetch_range(url, 0, INITIAL_RANGE)?;
+
+    if bytes.len() < 10 || &bytes[..3] != b"ID3" {
+        return Err(format!("{} does not start with an ID3v2 tag", url));
+    }
+
+    let mut total_tag_size = usize::try_from(utility::convert_safesynch_to_u32(
+        bytes[6], bytes[7], bytes[8], bytes[9],
+    ))
+    .unwrap()
+        + 10;
+
+    // Footer present flag
+    if bytes[5] & 0b00010000 != 0 {
+        total_tag_size += 10;
+    }
+
+    if bytes.len() < total_tag_size {
+        let mut rest = fetch_range(url, bytes.len(), total_tag_size)?;
+        bytes.append(&mut rest);
+    }
+
+    if bytes.len() < total_tag_size {
+        return Err(format!(
+            "{} ended after {} bytes, before the end of its {} byte tag",
+            url,
+            bytes.len(),
+            total_tag_size
+        ));
+    }
+
+    bytes.truncate(total_tag_size);
+
+    Ok(bytes)
+}
+
+/// Bytes `start..end` of a remote file, or fewer if the file is shorter
+pub fn fetch_range(url: &str, start: usize, end: usize) -> Result<Vec<u8>, String> {
+    let headers = [("Range".to_string(), format!("bytes={}-{}", start, end - 1))];
+    let response = request("GET", url, &headers, &[], end)?;
+
+    match response.status {
+        // Requested range starts past the end of the file
+        416 => return Ok(vec![]),
+        200 | 206 => {}
+        x => return Err(format!("{} responded with HTTP status {}", url, x)),
+    }
+
+    // A server ignoring the range sends the whole file from the start
+    let skip = if response.status == 206 { 0 } else { start };
+    let body = response.body.get(skip..).unwrap_or_default();
+
+    Ok(body[..body.len().min(end - start)].to_vec())
+}
+
+/// Status, headers, and body of an HTTP response
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u32,
+    // Names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Sends a request, following redirects and reading at most `limit` bytes of the response body.
+/// The Host header is set from the URL.
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+    limit: usize,
+) -> Result<HttpResponse, String> {
+    let mut url = url.to_string();
+
+    for _ in 0..MAX_REDIRECTS {
+        if url.starts_with("https://") {
+            return request_https(method, &url, headers, body, limit);
+        }
+
+        let response = request_http(method, &url, headers, body, limit)?;
+
+        match (response.status, response.header("location")) {
+            (301 | 302 | 303 | 307 | 308, Some(location)) if location.starts_with('/') => {
+                url = format!("http://{}{}", split_url(&url).0, location);
+            }
+            (301 | 302 | 303 | 307 | 308, Some(location)) => url = location.to_string(),
+            _ => return Ok(response),
+        }
+    }
+
+    Err(format!("too many redirects fetching {}", url))
+}
+
+/// Splits "http(s)://host[:port]/path?query" into its authority and path (with query)
+pub fn split_url(url: &str) -> (&str, &str) {
+    let rest = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .unwrap_or(url);
+
+    match rest.find('/') {
+        Some(x) => (&rest[..x], &rest[x..]),
+        None => (rest, "/"),
+    }
+}
+
+#[cfg(feature = "https")]
+fn request_https(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+    limit: usize,
+) -> Result<HttpResponse, String> {
+    let mut request = ureq::request(method, url)
+        .timeout(TIMEOUT)
+        .set("User-Agent", concat!("alloy/", env!("CARGO_PKG_VERSION")));
+
+    for (key, value) in headers {
+        request = request.set(key, value);
+    }
+
+    let response = match request.send_bytes(body) {
+        Ok(x) => x,
+        // Error statuses are returned to the caller like any other response
+        Err(ureq::Error::Status(_, x)) => x,
+        Err(x) => return Err(format!("unable to fetch {}: {}", url, x)),
+    };
+
+    let status = u32::from(response.status());
+    let headers = response
+        .headers_names()
+        .into_iter()
+        .filter_map(|x| {
+            let value = response.header(&x)?.to_string();
+            Some((x.to_ascii_lowercase(), value))
+        })
+        .collect();
+
+    let mut body = vec![];
+    response
+        .into_reader()
+        .take(u64::try_from(limit).unwrap_or(u64::MAX))
+        .read_to_end(&mut body)
+        .map_err(|x| format!("unable to read {}: {}", url, x))?;
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+#[cfg(not(feature = "https"))]
+fn request_https(
+    _method: &str,
+    url: &str,
+    _headers: &[(String, String)],
+    _body: &[u8],
+    _limit: usize,
+) -> Result<HttpResponse, String> {
+    Err(format!(
+        "unable to fetch {}: HTTPS support requires building alloy with `--features https`",
+        url
+    ))
+}
+
+fn request_http(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+    limit: usize,
+) -> Result<HttpResponse, String> {
+    let (authority, path) = split_url(url);
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let mut stream = TcpStream::connect(&address)
+        .map_err(|x| format!("unable to connect to {}: {}", address, x))?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok();
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: alloy/{}\r\nConnection: close\r\n",
+        method,
+        path,
+        authority,
+        env!("CARGO_PKG_VERSION")
+    );
+    for (key, value) in headers {
+        request += &format!("{}: {}\r\n", key, value);
+    }
+    if !body.is_empty() || method != "GET" {
+        request += &format!("Content-Length: {}\r\n", body.len());
+    }
+    request += "\r\n";
+
+    stream
+        .write_all(&[request.as_bytes(), body].concat())
+        .map_err(|x| format!("unable to send request to {}: {}", address, x))?;
+
+    // A server ignoring a range sends the whole file, so stop reading once enough has arrived
+    let mut response = vec![];
+    stream
+        .take(u64::try_from(limit.saturating_add(INITIAL_RANGE)).unwrap_or(u64::MAX))
+        .read_to_end(&mut response)
+        .map_err(|x| format!("unable to read response from {}: {}", address, x))?;
+
+    let Some(header_end) = response.windows(4).position(|x| x == b"\r\n\r\n") else {
+        return Err(format!("malformed HTTP response from {}", url));
+    };
+    let head = String::from_utf8_lossy(&response[..header_end]).to_string();
+    let mut body = response[header_end + 4..].to_vec();
+
+    let mut lines = head.lines();
+    let status: u32 = lines
+        .next()
+        .and_then(|x| x.split_whitespace().nth(1))
+        .and_then(|x| x.parse().ok())
+        .ok_or(format!("malformed HTTP status line from {}", url))?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|x| x.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: vec![],
+    };
+
+    if response
+        .header("transfer-encoding")
+        .is_some_and(|x| x.eq_ignore_ascii_case("chunked"))
+    {
+        body = decode_chunked(&body);
+    }
+
+    if let Some(length) = response
+        .header("content-length")
+        .and_then(|x| x.parse::<usize>().ok())
+    {
+        body.truncate(length);
+    }
+
+    body.truncate(limit);
+    response.body = body;
+
+    Ok(response)
+}
+
+fn decode_chunked(bytes: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    let mut idx = 0;
+
+    while let Some(line_end) = bytes[idx..].windows(2).position(|x| x == b"\r\n") {
+        let size_text = String::from_utf8_lossy(&bytes[idx..idx + line_end]).to_string();
+        let size_text = size_text.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_text, 16) else {
+            break;
+        };
+
+        let start = idx + line_end + 2;
+        if size == 0 || start >= bytes.len() {
+            break;
+        }
+
+        let end = (start + size).min(bytes.len());
+        result.extend_from_slice(&bytes[start..end]);
+        idx = end + 2;
+
+        if idx >= bytes.len() {
+            break;
+        }
+    }
+
+    result
+}
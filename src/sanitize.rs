@@ -0,0 +1,285 @@
+// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Characters that aren't allowed in file names on Windows, including both path separators
+const INVALID_CHARACTERS: [char; 9] = ['/', '\\', '<', '>', ':', '"', '|', '?', '*'];
+
+// Extensions longer than this are treated as part of the name when truncating
+const MAX_EXTENSION_LENGTH: usize = 16;
+
+// ASCII replacements for the letters and punctuation most common in tags
+const TRANSLITERATIONS: [(&str, &str); 37] = [
+    ("ÀÁÂÃÄÅĀĂĄ", "A"),
+    ("àáâãäåāăą", "a"),
+    ("ÇĆĈĊČ", "C"),
+    ("çćĉċč", "c"),
+    ("ĎĐ", "D"),
+    ("ďđ", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"),
+    ("èéêëēĕėęě", "e"),
+    ("ĜĞĠĢ", "G"),
+    ("ĝğġģ", "g"),
+    ("ÌÍÎÏĨĪĬĮİ", "I"),
+    ("ìíîïĩīĭįı", "i"),
+    ("ŁĹĻĽ", "L"),
+    ("łĺļľ", "l"),
+    ("ÑŃŅŇ", "N"),
+    ("ñńņň", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"),
+    ("òóôõöøōŏő", "o"),
+    ("ŔŖŘ", "R"),
+    ("ŕŗř", "r"),
+    ("ŚŜŞŠ", "S"),
+    ("śŝşš", "s"),
+    ("ŢŤ", "T"),
+    ("ţť", "t"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"),
+    ("ùúûüũūŭůűų", "u"),
+    ("ÝŸ", "Y"),
+    ("ýÿ", "y"),
+    ("ŹŻŽ", "Z"),
+    ("źżž", "z"),
+    ("Æ", "AE"),
+    ("æ", "ae"),
+    ("Œ", "OE"),
+    ("œ", "oe"),
+    ("ß", "ss"),
+    ("Þþ", "th"),
+    // Double quotes aren't allowed on Windows
+    ("‘’‚′“”„″", "'"),
+];
+
+// Dashes and other punctuation that have a close ASCII equivalent
+fn transliterate_punctuation(c: char) -> Option<&'static str> {
+    match c {
+        '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' => Some("-"),
+        '…' => Some("..."),
+        '\u{A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{3000}' => Some(" "),
+        _ => None,
+    }
+}
+
+fn transliterate(text: &str, replacement: char) -> String {
+    let mut result = String::new();
+
+    for c in text.chars() {
+        if c.is_ascii() {
+            result.push(c);
+        } else if let Some(x) = transliterate_punctuation(c) {
+            result += x;
+        } else if let Some((_, x)) = TRANSLITERATIONS.iter().find(|(from, _)| from.contains(c)) {
+            result += x;
+        } else {
+            result.push(replacement);
+        }
+    }
+
+    result
+}
+
+/// How `sanitize_file_name` turns text from tags into a file name
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizeOptions {
+    /// Stands in for characters that can't appear in a file name
+    pub replacement: char,
+    /// Longest name in bytes, 255 on most filesystems
+    pub max_length: usize,
+    /// Replace accented letters and typographic punctuation with ASCII, and anything else
+    /// non-ASCII with `replacement`
+    pub transliterate: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> SanitizeOptions {
+        SanitizeOptions {
+            replacement: '_',
+            max_length: 255,
+            transliterate: false,
+        }
+    }
+}
+
+// Windows drops trailing dots and spaces, so names ending in them can't be opened again
+fn trim_name(name: &str) -> &str {
+    name.trim_start().trim_end_matches([' ', '.'])
+}
+
+// Cuts a string to at most `max_length` bytes without splitting a character
+fn truncate(text: &str, max_length: usize) -> &str {
+    let mut end = max_length.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &text[..end]
+}
+
+/// Makes a single file name (not a path) from arbitrary text, e.g. a title, safe to create on
+/// Linux, macOS and Windows: path separators, characters Windows rejects and control characters
+/// are replaced, reserved device names are suffixed, and long names are truncated keeping the
+/// extension
+pub fn sanitize_file_name(name: &str, options: &SanitizeOptions) -> String {
+    let name = if options.transliterate {
+        transliterate(name, options.replacement)
+    } else {
+        name.to_string()
+    };
+
+    let name: String = name
+        .chars()
+        .map(|x| {
+            if x.is_control() || INVALID_CHARACTERS.contains(&x) {
+                options.replacement
+            } else {
+                x
+            }
+        })
+        .collect();
+
+    let mut name = trim_name(&name).to_string();
+
+    // "CON.mp3" is as unusable as "CON"
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+    if RESERVED_NAMES.iter().any(|x| x.eq_ignore_ascii_case(stem)) {
+        name.insert(stem.len(), options.replacement);
+    }
+
+    if name.len() > options.max_length {
+        let extension = match name.rfind('.') {
+            Some(idx) if idx > 0 && name.len() - idx <= MAX_EXTENSION_LENGTH => &name[idx..],
+            _ => "",
+        };
+
+        let stem = &name[..name.len() - extension.len()];
+        let stem = trim_name(truncate(
+            stem,
+            options.max_length.saturating_sub(extension.len()),
+        ));
+
+        name = truncate(&(stem.to_string() + extension), options.max_length).to_string();
+    }
+
+    if trim_name(&name).is_empty() {
+        return options.replacement.to_string();
+    }
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitize(name: &str) -> String {
+        sanitize_file_name(name, &SanitizeOptions::default())
+    }
+
+    #[test]
+    fn replaces_path_separators() {
+        assert_eq!(sanitize("AC/DC"), "AC_DC");
+        assert_eq!(sanitize("Live\\Unplugged"), "Live_Unplugged");
+        assert_eq!(sanitize("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(
+            sanitize("What? <Why>: \"Now\" | *"),
+            "What_ _Why__ _Now_ _ _"
+        );
+    }
+
+    #[test]
+    fn suffixes_reserved_windows_names() {
+        assert_eq!(sanitize("CON"), "CON_");
+        assert_eq!(sanitize("nul"), "nul_");
+        assert_eq!(sanitize("COM1"), "COM1_");
+        assert_eq!(sanitize("LPT9"), "LPT9_");
+        assert_eq!(sanitize("con.mp3"), "con_.mp3");
+        assert_eq!(sanitize("Aux.tar.gz"), "Aux_.tar.gz");
+        assert_eq!(sanitize("PRN .mp3"), "PRN_ .mp3");
+    }
+
+    #[test]
+    fn keeps_names_that_only_start_like_reserved_ones() {
+        assert_eq!(sanitize("CONSOLE.mp3"), "CONSOLE.mp3");
+        assert_eq!(sanitize("COM10"), "COM10");
+        assert_eq!(sanitize("Nullify"), "Nullify");
+    }
+
+    #[test]
+    fn replaces_control_characters() {
+        assert_eq!(sanitize("a\tb\nc\0d\u{7F}e\u{9B}f"), "a_b_c_d_e_f");
+        assert_eq!(
+            sanitize_file_name(
+                "a\u{1}b",
+                &SanitizeOptions {
+                    replacement: '-',
+                    ..SanitizeOptions::default()
+                }
+            ),
+            "a-b"
+        );
+    }
+
+    #[test]
+    fn trims_trailing_dots_and_spaces() {
+        assert_eq!(sanitize("Song. . "), "Song");
+        assert_eq!(sanitize("  Intro.mp3. "), "Intro.mp3");
+        assert_eq!(sanitize("..."), "_");
+        assert_eq!(sanitize(" "), "_");
+        assert_eq!(sanitize(""), "_");
+    }
+
+    #[test]
+    fn truncates_keeping_the_extension() {
+        let options = SanitizeOptions {
+            max_length: 12,
+            ..SanitizeOptions::default()
+        };
+
+        assert_eq!(
+            sanitize_file_name("A long title.mp3", &options),
+            "A long t.mp3"
+        );
+        // The stem is trimmed again once cut, so the name doesn't end in a space
+        assert_eq!(
+            sanitize_file_name("A long  title.mp3", &options),
+            "A long.mp3"
+        );
+        assert_eq!(sanitize_file_name("Short.mp3", &options), "Short.mp3");
+    }
+
+    #[test]
+    fn truncates_on_a_char_boundary() {
+        let name = "é".repeat(200) + ".mp3";
+        let sanitized = sanitize(&name);
+        assert_eq!(sanitized, "é".repeat(125) + ".mp3");
+        assert!(sanitized.len() <= 255);
+
+        // Two-byte letters can't fill an odd length, so one byte is left unused
+        for max_length in 1..12 {
+            let options = SanitizeOptions {
+                max_length,
+                ..SanitizeOptions::default()
+            };
+            let sanitized = sanitize_file_name("日本語の曲", &options);
+
+            assert!(sanitized.len() <= max_length.max(1), "{}", sanitized);
+            assert!("日本語の曲".starts_with(&sanitized) || sanitized == "_");
+        }
+    }
+
+    #[test]
+    fn transliterates_to_ascii() {
+        let options = SanitizeOptions {
+            transliterate: true,
+            ..SanitizeOptions::default()
+        };
+
+        assert_eq!(
+            sanitize_file_name("Björk – Jóga… Live", &options),
+            "Bjork - Joga... Live"
+        );
+        assert_eq!(sanitize_file_name("日本", &options), "__");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    image_meta,
+    json::JsonValue,
+    tag::{Frame, Id3v2Tag},
+    toml, utility,
+};
+
+/// Cover art a schema requires. The front cover is checked for dimensions, every picture for
+/// its size.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ArtRequirements {
+    pub required: bool,
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub square: bool,
+    pub max_bytes: Option<usize>,
+}
+
+/// What a label requires of its releases' tags: frames that must be present, the genres allowed
+/// and the cover art
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Schema {
+    pub required_frames: Vec<[u8; 4]>,
+    pub allowed_genres: Vec<String>,
+    pub art: ArtRequirements,
+}
+
+// Unknown keys are errors, so a typo doesn't silently drop a requirement
+fn check_keys(table: &[(String, JsonValue)], name: &str, known: &[&str]) -> Result<(), String> {
+    match table.iter().find(|(key, _)| !known.contains(&key.as_str())) {
+        Some((key, _)) => Err(format!(
+            "unknown key '{}' in {}, expected one of {}",
+            key,
+            name,
+            known.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+fn strings(value: Option<&JsonValue>, key: &str) -> Result<Vec<String>, String> {
+    let Some(value) = value else {
+        return Ok(vec![]);
+    };
+
+    value
+        .as_array()
+        .and_then(|x| x.iter().map(|x| x.as_str().map(String::from)).collect())
+        .ok_or(format!("{} must be an array of strings", key))
+}
+
+fn flag(value: Option<&JsonValue>, key: &str) -> Result<bool, String> {
+    match value {
+        Some(x) => x.as_bool().ok_or(format!("{} must be true or false", key)),
+        None => Ok(false),
+    }
+}
+
+fn pixels(value: Option<&JsonValue>, key: &str) -> Result<Option<u32>, String> {
+    match value.map(|x| x.as_f64()) {
+        Some(Some(x)) if x >= 0.0 && x.fract() == 0.0 && x <= f64::from(u32::MAX) => {
+            Ok(Some(x as u32))
+        }
+        Some(_) => Err(format!("{} must be a number of pixels", key)),
+        None => Ok(None),
+    }
+}
+
+// A number of bytes, or a size such as "2M" as --art-max-bytes takes
+fn byte_size(value: Option<&JsonValue>, key: &str) -> Result<Option<usize>, String> {
+    match value {
+        Some(JsonValue::Number(x)) if *x >= 0.0 && x.fract() == 0.0 => Ok(Some(*x as usize)),
+        Some(JsonValue::String(x)) => utility::parse_byte_size(x)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", key, e)),
+        Some(_) => Err(format!("{} must be a size, e.g. 2097152 or \"2M\"", key)),
+        None => Ok(None),
+    }
+}
+
+impl Schema {
+    /// Reads a schema from TOML, e.g.
+    ///
+    /// ```toml
+    /// required_frames = ["TIT2", "TPE1", "TALB", "TDRC", "APIC"]
+    /// allowed_genres = ["Ambient", "Techno"]
+    ///
+    /// [art]
+    /// min_width = 1400
+    /// min_height = 1400
+    /// square = true
+    /// max_bytes = "2M"
+    /// ```
+    pub fn parse(text: &str) -> Result<Schema, String> {
+        let document = toml::parse(text)?;
+        let JsonValue::Object(root) = &document else {
+            unreachable!()
+        };
+        check_keys(
+            root,
+            "the schema",
+            &["required_frames", "allowed_genres", "art"],
+        )?;
+
+        let required_frames = strings(document.get("required_frames"), "required_frames")?
+            .iter()
+            .map(|x| utility::parse_frame_id(x))
+            .collect::<Result<Vec<[u8; 4]>, String>>()
+            .map_err(|x| format!("required_frames: {}", x))?;
+
+        let art = match document.get("art") {
+            Some(JsonValue::Object(table)) => {
+                check_keys(
+                    table,
+                    "[art]",
+                    &["required", "min_width", "min_height", "square", "max_bytes"],
+                )?;
+
+                let art = |key: &str| table.iter().find(|(x, _)| x == key).map(|(_, x)| x);
+                ArtRequirements {
+                    required: flag(art("required"), "art.required")?,
+                    min_width: pixels(art("min_width"), "art.min_width")?,
+                    min_height: pixels(art("min_height"), "art.min_height")?,
+                    square: flag(art("square"), "art.square")?,
+                    max_bytes: byte_size(art("max_bytes"), "art.max_bytes")?,
+                }
+            }
+            Some(_) => return Err("art must be a table ([art])".to_string()),
+            None => ArtRequirements::default(),
+        };
+
+        Ok(Schema {
+            required_frames,
+            allowed_genres: strings(document.get("allowed_genres"), "allowed_genres")?,
+            art,
+        })
+    }
+
+    /// Every way the tag falls short of the schema, empty if it passes
+    pub fn check(&self, tag: &Id3v2Tag) -> Vec<String> {
+        let mut issues = vec![];
+
+        for frame_id in &self.required_frames {
+            if !tag.frames().iter().any(|x| x.identifier() == *frame_id) {
+                issues.push(format!(
+                    "missing {} ({})",
+                    String::from_utf8_lossy(frame_id),
+                    utility::get_field_name(*frame_id)
+                ));
+            }
+        }
+
+        // TCON holds several genres separated by null characters
+        if !self.allowed_genres.is_empty() {
+            for genre in tag.get_content_type().unwrap_or_default().split('\0') {
+                let genre = genre.trim();
+                if !genre.is_empty()
+                    && !self
+                        .allowed_genres
+                        .iter()
+                        .any(|x| x.eq_ignore_ascii_case(genre))
+                {
+                    issues.push(format!("genre '{}' is not allowed", genre));
+                }
+            }
+        }
+
+        issues.extend(self.check_art(tag));
+
+        issues
+    }
+
+    fn check_art(&self, tag: &Id3v2Tag) -> Vec<String> {
+        let mut issues = vec![];
+        let art = &self.art;
+
+        if let Some(max_bytes) = art.max_bytes {
+            for frame in tag.frames() {
+                if let Frame::Picture(x) = frame {
+                    let picture = &x.picture;
+                    if picture.data().len() > max_bytes {
+                        issues.push(format!(
+                            "{} is {} bytes, over the {} byte limit",
+                            picture.picture_type_name().to_lowercase(),
+                            picture.data().len(),
+                            max_bytes
+                        ));
+                    }
+                }
+            }
+        }
+
+        let Some(cover) = tag.get_front_cover() else {
+            // Already reported when APIC is a required frame
+            if art.required && !self.required_frames.contains(b"APIC") {
+                issues.push("no cover art".to_string());
+            }
+            return issues;
+        };
+
+        if art.min_width.is_none() && art.min_height.is_none() && !art.square {
+            return issues;
+        }
+
+        let Some(info) = image_meta::image_info(cover.data()) else {
+            issues.push(format!(
+                "cover art dimensions can't be read ({})",
+                cover.mime()
+            ));
+            return issues;
+        };
+
+        if info.width < art.min_width.unwrap_or(0) || info.height < art.min_height.unwrap_or(0) {
+            issues.push(format!(
+                "cover art is {}x{}, smaller than {}x{}",
+                info.width,
+                info.height,
+                art.min_width.unwrap_or(0),
+                art.min_height.unwrap_or(0)
+            ));
+        }
+
+        if art.square && info.width != info.height {
+            issues.push(format!(
+                "cover art is {}x{}, not square",
+                info.width, info.height
+            ));
+        }
+
+        issues
+    }
+}
@@ -0,0 +1,531 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    dj,
+    json::{self, JsonValue},
+    parse, registration, storage, tag,
+    timestamp::{self, Timestamp},
+    utility,
+};
+
+// Request bodies only carry field values, never audio
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// Request line and headers, far more than any client sends
+const MAX_HEADER_SIZE: u64 = 16 * 1024;
+
+// A whole request must arrive within this, as requests are handled one at a time and a client
+// sending slowly would hold up every other
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Setter = fn(&mut tag::Id3v2Tag, String) -> Result<(), String>;
+
+/// Serve tag reads and writes over a local HTTP API
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on. Only connections from this machine are served, whatever the address
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    bind: String,
+
+    /// Folder whose files can be read and written, can be repeated. Paths outside every root are refused
+    #[arg(long, value_name = "FOLDER", required = true)]
+    root: Vec<String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut result = vec![];
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => result.push(b' '),
+            b'%' if idx + 2 < bytes.len() => {
+                match u8::from_str_radix(&String::from_utf8_lossy(&bytes[idx + 1..idx + 3]), 16) {
+                    Ok(x) => {
+                        result.push(x);
+                        idx += 2;
+                    }
+                    Err(_) => result.push(b'%'),
+                }
+            }
+            x => result.push(x),
+        }
+        idx += 1;
+    }
+
+    String::from_utf8_lossy(&result).to_string()
+}
+
+// Reads a connection until a deadline for the whole request rather than for each read, so a
+// client can't keep it open by sending a byte at a time
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn read_error(error: io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            format!(
+                "no complete request within {} seconds",
+                READ_TIMEOUT.as_secs()
+            )
+        }
+        _ => error.to_string(),
+    }
+}
+
+// A line of the request head, refused if it runs past the size left for the head
+fn read_head_line(head: &mut io::Take<impl BufRead>, line: &mut String) -> Result<usize, String> {
+    let size = head.read_line(line).map_err(read_error)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(format!(
+            "request headers are larger than {} bytes",
+            MAX_HEADER_SIZE
+        ));
+    }
+
+    Ok(size)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(DeadlineReader {
+        stream,
+        deadline: Instant::now() + READ_TIMEOUT,
+    });
+    let mut head = (&mut reader).take(MAX_HEADER_SIZE);
+
+    let mut request_line = String::new();
+    read_head_line(&mut head, &mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".to_string());
+    };
+
+    let mut content_length = 0;
+    let (mut host, mut origin, mut content_type) = (None, None, None);
+    loop {
+        let mut line = String::new();
+        if read_head_line(&mut head, &mut line)? == 0 {
+            break;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {
+                    content_length = value
+                        .parse()
+                        .map_err(|_| "invalid Content-Length".to_string())?;
+                }
+                "host" => host = Some(value.to_string()),
+                "origin" => origin = Some(value.to_string()),
+                "content-type" => content_type = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err(format!(
+            "request body is larger than {} bytes",
+            MAX_BODY_SIZE
+        ));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (key, value) = x.split_once('=').unwrap_or((x, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        host,
+        origin,
+        content_type,
+        body,
+    })
+}
+
+// Browsers send an Origin header with cross-origin requests, and a page reaching the server
+// through DNS rebinding still sends its own host name, so only requests without an Origin and
+// addressed to a loopback host are served
+fn check_sender(request: &Request) -> Result<(), String> {
+    if request.origin.is_some() {
+        return Err("requests from web pages are refused".to_string());
+    }
+
+    let Some(host) = &request.host else {
+        return Err("missing Host header".to_string());
+    };
+    // "localhost:7878", "127.0.0.1" or "[::1]:7878"
+    let name = match host.strip_prefix('[') {
+        Some(x) => x.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    if !matches!(name, "localhost" | "127.0.0.1" | "::1") {
+        return Err(format!("Host '{}' is not a loopback address", host));
+    }
+
+    Ok(())
+}
+
+// Folders files can be read and written in, resolved so that paths are compared without links
+fn resolve_roots(roots: &[String]) -> Result<Vec<PathBuf>, String> {
+    roots
+        .iter()
+        .map(|x| fs::canonicalize(x).map_err(|e| format!("unable to resolve root {}: {}", x, e)))
+        .collect()
+}
+
+// `location` resolved, if it is a file under one of `roots`. A file that doesn't exist yet, e.g.
+// an output, is resolved through its folder.
+fn confine(location: &str, roots: &[PathBuf]) -> Result<String, String> {
+    let path = Path::new(location);
+    let resolved = match fs::canonicalize(path) {
+        Ok(x) => x,
+        Err(_) => {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(format!("{} is not a file path", location));
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+
+            fs::canonicalize(parent)
+                .map_err(|x| format!("unable to resolve {}: {}", location, x))?
+                .join(name)
+        }
+    };
+
+    if !roots.iter().any(|x| resolved.starts_with(x)) {
+        return Err(format!("{} is outside of every --root folder", location));
+    }
+
+    Ok(resolved.to_string_lossy().to_string())
+}
+
+fn respond(mut stream: &TcpStream, status: u32, body: &JsonValue) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+
+    // The client may already be gone, nothing else to do about it
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn error_json(message: &str) -> JsonValue {
+    JsonValue::Object(vec![(
+        "error".to_string(),
+        JsonValue::String(message.to_string()),
+    )])
+}
+
+// Role (or instrument) to name, with the names of a repeated role joined by ", "
+fn people_json(people: &[(String, String)]) -> JsonValue {
+    let mut map: Vec<(String, JsonValue)> = vec![];
+
+    for (role, name) in people {
+        match map.iter_mut().find(|(x, _)| x == role) {
+            Some((_, JsonValue::String(x))) => *x += &format!(", {}", name),
+            _ => map.push((role.clone(), JsonValue::String(name.clone()))),
+        }
+    }
+
+    JsonValue::Object(map)
+}
+
+fn optional_text(value: Option<String>) -> JsonValue {
+    match value {
+        Some(x) => JsonValue::String(x),
+        None => JsonValue::Null,
+    }
+}
+
+// The stored text of a timestamp frame, with the normalized timestamp and how long ago it was, or
+// why it is invalid
+fn timestamp_json(text: &str, now: &Timestamp) -> JsonValue {
+    let raw = (
+        "raw".to_string(),
+        JsonValue::String(text.trim_end_matches('\0').to_string()),
+    );
+
+    match Timestamp::parse(text) {
+        Ok(x) => JsonValue::Object(vec![
+            raw,
+            ("formatted".to_string(), JsonValue::String(x.to_string())),
+            (
+                "relative".to_string(),
+                JsonValue::String(x.relative_to(now)),
+            ),
+        ]),
+        Err(x) => JsonValue::Object(vec![raw, ("error".to_string(), JsonValue::String(x))]),
+    }
+}
+
+/// The fields POST /tags accepts, with their current values, followed by every frame in the tag
+fn tag_json(path: &str, tag: &tag::Id3v2Tag) -> JsonValue {
+    let version = tag.get_version();
+
+    let now = Timestamp::now();
+    let frames = tag
+        .frames()
+        .iter()
+        .map(|x| {
+            let mut frame = vec![
+                ("id".to_string(), JsonValue::String(x.id())),
+                (
+                    "name".to_string(),
+                    JsonValue::String(utility::get_field_name(x.identifier())),
+                ),
+                ("value".to_string(), JsonValue::String(x.describe())),
+            ];
+
+            if matches!(x, tag::Frame::Text(_))
+                && timestamp::TIMESTAMP_FRAMES.contains(&x.id().as_str())
+            {
+                frame.push(("timestamp".to_string(), timestamp_json(&x.describe(), &now)));
+            }
+
+            JsonValue::Object(frame)
+        })
+        .collect();
+
+    JsonValue::Object(vec![
+        ("path".to_string(), JsonValue::String(path.to_string())),
+        (
+            "version".to_string(),
+            JsonValue::String(format!("2.{}.{}", version[0], version[1])),
+        ),
+        ("title".to_string(), optional_text(tag.get_song_title())),
+        (
+            "artist".to_string(),
+            optional_text(tag.get_song_artist_name()),
+        ),
+        ("album".to_string(), optional_text(tag.get_album_title())),
+        (
+            "album_artist".to_string(),
+            optional_text(tag.get_album_artist_name()),
+        ),
+        (
+            "recording_time".to_string(),
+            optional_text(tag.get_recording_time()),
+        ),
+        (
+            "track_number".to_string(),
+            optional_text(tag.get_track_number()),
+        ),
+        (
+            "involved_people".to_string(),
+            people_json(&tag.get_involved_people()),
+        ),
+        (
+            "musician_credits".to_string(),
+            people_json(&tag.get_musician_credits()),
+        ),
+        ("frames".to_string(), JsonValue::Array(frames)),
+    ])
+}
+
+fn get_tags(request: &Request, roots: &[PathBuf]) -> Result<JsonValue, String> {
+    let Some((_, path)) = request.query.iter().find(|(k, _)| k == "path") else {
+        return Err("missing 'path' query parameter".to_string());
+    };
+    let path = &confine(path, roots)?;
+
+    let bytes = storage::read(path)?;
+    let (tag, _) = parse::parse_bytes_with_handlers(&bytes, &dj::handlers())?;
+
+    Ok(tag_json(path, &tag))
+}
+
+// Body: {"path": "...", "output": "...", "title": "...", ...}, written in place without "output"
+fn post_tags(request: &Request, roots: &[PathBuf]) -> Result<JsonValue, String> {
+    // Anything else can be sent by a form or fetch() without the browser asking first
+    let is_json = request
+        .content_type
+        .as_deref()
+        .and_then(|x| x.split(';').next())
+        .is_some_and(|x| x.trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err("request body must be sent as Content-Type: application/json".to_string());
+    }
+
+    let text = String::from_utf8(request.body.clone())
+        .map_err(|_| "request body is not valid UTF-8".to_string())?;
+    let body = json::parse(&text)?;
+
+    let Some(path) = body.get("path").and_then(JsonValue::as_str) else {
+        return Err("missing 'path' field".to_string());
+    };
+    let output = body
+        .get("output")
+        .and_then(JsonValue::as_str)
+        .unwrap_or(path);
+    let (path, output) = (&confine(path, roots)?, &confine(output, roots)?);
+
+    let _locks = storage::lock(&[path, output])?;
+    let bytes = storage::read(path)?;
+    let (mut tag, audio_data) = parse::parse_bytes(&bytes)?;
+    let dj_frames = dj::dj_frame_bytes(&tag);
+    let protected_frames = registration::protected_frame_bytes(&tag);
+
+    let setters: [(&str, Setter); 6] = [
+        ("title", tag::Id3v2Tag::set_song_title),
+        ("artist", tag::Id3v2Tag::set_song_artist_name),
+        ("album", tag::Id3v2Tag::set_album_title),
+        ("album_artist", tag::Id3v2Tag::set_album_artist_name),
+        ("recording_time", tag::Id3v2Tag::set_recording_time),
+        ("track_number", tag::Id3v2Tag::set_track_number),
+    ];
+
+    for (key, setter) in setters {
+        match body.get(key) {
+            None | Some(JsonValue::Null) => {}
+            Some(x) => match x.to_text() {
+                Some(x) => setter(&mut tag, x + "\0")?,
+                None => return Err(format!("'{}' must be a string or number", key)),
+            },
+        }
+    }
+
+    dj::verify_dj_frames(&dj_frames, &tag)?;
+    registration::verify_protected_frames(&protected_frames, &tag)?;
+    storage::write_verified(output, &tag, &audio_data)?;
+
+    Ok(tag_json(output, &tag))
+}
+
+fn handle(stream: &TcpStream, roots: &[PathBuf]) {
+    // The Host header is the client's to choose, so the connection itself must be local too.
+    // IPv4 clients of a dual-stack listener show up as ::ffff:127.0.0.1.
+    match stream.peer_addr() {
+        Ok(x) if x.ip().to_canonical().is_loopback() => {}
+        Ok(x) => {
+            eprintln!("{} | connections from other machines are refused", x.ip());
+            return respond(
+                stream,
+                403,
+                &error_json("connections from other machines are refused"),
+            );
+        }
+        Err(x) => return eprintln!("Unable to get the client's address: {}", x),
+    }
+
+    let request = match read_request(stream) {
+        Ok(x) => x,
+        Err(x) => return respond(stream, 400, &error_json(&x)),
+    };
+    if let Err(x) = check_sender(&request) {
+        eprintln!("{} {} | {}", request.method, request.path, x);
+        return respond(stream, 403, &error_json(&x));
+    }
+
+    let now = Instant::now();
+
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/tags") => get_tags(&request, roots),
+        ("POST", "/tags") => post_tags(&request, roots),
+        (_, "/tags") => return respond(stream, 405, &error_json("use GET or POST")),
+        _ => return respond(stream, 404, &error_json("not found")),
+    };
+
+    match result {
+        Ok(x) => {
+            println!("{:?} | {} {}", now.elapsed(), request.method, request.path);
+            respond(stream, 200, &x);
+        }
+        Err(x) => {
+            eprintln!("{} {} | {}", request.method, request.path, x);
+            respond(stream, 400, &error_json(&x));
+        }
+    }
+}
+
+pub fn process_serve(args: &ServeArgs) {
+    let roots = match resolve_roots(&args.root) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    let listener = match TcpListener::bind(&args.bind) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("Unable to listen on {}: {}", args.bind, x);
+            return;
+        }
+    };
+
+    println!("Listening on http://{}", args.bind);
+
+    // Requests are handled one at a time, and files are locked against other alloy processes
+    for stream in listener.incoming() {
+        match stream {
+            Ok(x) => handle(&x, &roots),
+            Err(x) => eprintln!("Unable to accept connection: {}", x),
+        }
+    }
+}
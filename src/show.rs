@@ -0,0 +1,346 @@
+use alloy::{
+    digest, dj, encryption, extract,
+    frame_handler::CustomFrameData,
+    json::JsonValue,
+    layout, link, parse, registration, remote, storage,
+    tag::{Frame, Id3v2Tag},
+    text_encoding::LegacyCharset,
+    timestamp::{self, Timestamp},
+    utility,
+};
+
+/// Display the frames stored in a file's tag
+#[derive(clap::Args, Debug)]
+pub struct ShowArgs {
+    /// Path to the MP3 file, or an http(s) URL to read only its tag with range requests
+    #[arg(short, long)]
+    input_file: String,
+
+    /// Only show DJ software data (GEOB/PRIV frames), decoding Serato cue points and loops
+    #[arg(long)]
+    dj: bool,
+
+    /// Only show the cue points and loops Serato stored, with --format json as the JSON tag --import-cues reads
+    #[arg(long, conflicts_with = "dj")]
+    serato_cues: bool,
+
+    /// Salvage malformed tags with a warning instead of refusing the file: frames running past the end of the tag are truncated and malformed data between frames is skipped
+    #[arg(long)]
+    lenient: bool,
+
+    /// Read frame sizes as synchsafe or plain 32-bit (be32) integers whatever the tag's version says, for sources known to write them wrong, rather than detecting it, and report how they were read
+    #[arg(long, value_name = "FORMAT", value_parser = parse::FrameSizeFormat::parse)]
+    frame_size_format: Option<parse::FrameSizeFormat>,
+
+    /// Show the frames LINK frames reference instead of the links, fetched from the linked files or URLs
+    #[arg(long)]
+    resolve_links: bool,
+
+    /// Print a hexdump of each frame with this identifier (e.g. TIT2), header and payload, at its offsets in the file
+    #[arg(long, value_name = "FRAME", value_parser = utility::parse_frame_id)]
+    raw: Option<[u8; 4]>,
+
+    /// List the offset and length in the file of the header, each frame, padding, footer and the audio
+    #[arg(long)]
+    offsets: bool,
+
+    /// File holding the key to decrypt the frames alloy encrypted with --encrypt-frames, shown in place of their encrypted bytes. ALLOY_ENCRYPTION_KEY is used if not given.
+    #[arg(long, value_name = "FILE")]
+    encryption_key_file: Option<String>,
+
+    /// Output format: "canonical" prints one sorted "FRAME: value" line per value with nothing else, for diffing snapshots of a library's metadata; "json" is only for --serato-cues
+    #[arg(long, default_value = "text", value_parser = ["text", "canonical", "json"], conflicts_with_all = ["dj", "raw", "offsets"])]
+    format: String,
+
+    /// Show text marked ISO-8859-1 decoded in the legacy code page it looks like, e.g. Windows-1251, KOI8-R or Shift_JIS
+    #[cfg(feature = "charset")]
+    #[arg(long)]
+    detect_charset: bool,
+}
+
+impl ShowArgs {
+    fn legacy_charset(&self) -> LegacyCharset {
+        #[cfg(feature = "charset")]
+        if self.detect_charset {
+            return LegacyCharset::Detect;
+        }
+
+        LegacyCharset::Latin1
+    }
+}
+
+// Lines that only change when the frames do: sorted, one per value of multi-value text frames,
+// with line breaks escaped and binary data identified by its hash rather than only its size
+fn canonical_lines(tag: &Id3v2Tag) -> Vec<String> {
+    let mut lines = vec![];
+
+    for frame in tag.frames() {
+        let protection = match registration::describe_protection(tag, frame) {
+            Some(x) => format!(" [{}]", x),
+            None => String::new(),
+        };
+        let hash = match frame {
+            Frame::Picture(_) | Frame::Unknown(_) => {
+                format!(" sha256:{}", &digest::to_hex(&frame.content_hash())[..16])
+            }
+            _ => String::new(),
+        };
+
+        let description = frame.describe();
+        let mut values: Vec<&str> = description.split('\0').filter(|x| !x.is_empty()).collect();
+        if values.is_empty() {
+            values.push("");
+        }
+
+        for value in values {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            lines.push(format!("{}: {}{}{}", frame.id(), value, hash, protection));
+        }
+    }
+
+    lines.sort();
+    lines
+}
+
+// Works from the bytes alone, so frames of tags the parser rejects can still be inspected
+fn print_raw_frames(bytes: &[u8], frame_id: [u8; 4]) -> Result<(), String> {
+    let frames: Vec<_> = layout::frames(bytes)?
+        .into_iter()
+        .filter(|x| x.name.as_bytes() == frame_id)
+        .collect();
+
+    if frames.is_empty() {
+        return Err(format!(
+            "No {} frame found",
+            String::from_utf8_lossy(&frame_id)
+        ));
+    }
+
+    for frame in frames {
+        let end = frame.end().min(bytes.len());
+        println!(
+            "{} at {:#010X}, {} bytes of payload{}",
+            frame.name,
+            frame.offset,
+            frame.length - 10,
+            if end < frame.end() {
+                format!(" ({} missing from the file)", frame.end() - end)
+            } else {
+                String::new()
+            }
+        );
+
+        println!("Header:");
+        print!(
+            "{}",
+            utility::hexdump(&bytes[frame.offset..frame.offset + 10], frame.offset)
+        );
+        println!("Payload:");
+        print!(
+            "{}",
+            utility::hexdump(&bytes[frame.offset + 10..end], frame.offset + 10)
+        );
+    }
+
+    Ok(())
+}
+
+fn print_offsets(bytes: &[u8]) -> Result<(), String> {
+    println!("{:<10}  {:>10}  Element", "Offset", "Length");
+
+    for element in layout::layout(bytes)? {
+        let missing = element.end().saturating_sub(bytes.len());
+        println!(
+            "{:#010X}  {:>10}  {}{}",
+            element.offset,
+            element.length,
+            element.name,
+            if missing > 0 {
+                format!(" ({} bytes past the end of the file)", missing)
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    Ok(())
+}
+
+pub fn process_show(args: &ShowArgs) {
+    let options = parse::ParseOptions {
+        lenient: args.lenient,
+        frame_size_format: args.frame_size_format.unwrap_or_default(),
+    };
+
+    if args.format == "json" && !args.serato_cues {
+        eprintln!("--format json is only supported with --serato-cues");
+        return;
+    }
+
+    let is_remote = remote::is_remote(&args.input_file);
+    let bytes = if is_remote {
+        remote::fetch_tag_bytes(&args.input_file)
+    } else {
+        storage::read(&args.input_file)
+    };
+
+    let bytes = match bytes {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    if let Some(x) = args.raw {
+        if let Err(x) = print_raw_frames(&bytes, x) {
+            eprintln!("{}", x);
+        }
+        return;
+    }
+
+    if args.offsets {
+        if let Err(x) = print_offsets(&bytes) {
+            eprintln!("{}", x);
+        }
+        return;
+    }
+
+    let result = if is_remote {
+        parse::parse_tag_with_repairs(&bytes, &dj::handlers(), options)
+    } else {
+        extract::extract_tag(&bytes)
+            .and_then(|(x, _)| parse::parse_tag_with_repairs(&x, &dj::handlers(), options))
+    };
+
+    let (mut tag, repairs) = match result {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+
+    if args.resolve_links {
+        if let Err(x) = link::resolve_links(&mut tag) {
+            eprintln!("{}", x);
+            return;
+        }
+    }
+
+    let decrypted = match encryption::read_key(args.encryption_key_file.as_deref()) {
+        Ok(Some(x)) => match tag.decrypt_frames(&x) {
+            Ok(x) => x.len(),
+            Err(x) => {
+                eprintln!("{}", x);
+                return;
+            }
+        },
+        Ok(None) => 0,
+        Err(x) => {
+            eprintln!("{}", x);
+            return;
+        }
+    };
+    let decompressed = tag.decompress_frames();
+
+    // Only shown decoded, the file isn't modified
+    if args.legacy_charset() != LegacyCharset::Latin1 {
+        tag.reencode_utf8(args.legacy_charset());
+    }
+
+    if args.serato_cues {
+        let markers = dj::serato_markers(&tag);
+
+        if args.format == "json" {
+            let cues = markers.map_or(
+                JsonValue::Object(vec![
+                    ("cues".to_string(), JsonValue::Array(vec![])),
+                    ("loops".to_string(), JsonValue::Array(vec![])),
+                ]),
+                |x| x.to_json(),
+            );
+            println!("{}", cues);
+        } else {
+            match markers {
+                Some(x) => println!("{}", x.describe()),
+                None => println!("No Serato cue points found"),
+            }
+        }
+
+        return;
+    }
+
+    if args.format == "canonical" {
+        for line in canonical_lines(&tag) {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    let version = tag.get_version();
+    println!("{} | ID3v2.{}.{}", args.input_file, version[0], version[1]);
+
+    if let Some(x) = args.frame_size_format {
+        println!(
+            "Frame sizes read as {} ({})",
+            tag.frame_size_format().name(),
+            if x == parse::FrameSizeFormat::Auto {
+                "detected"
+            } else {
+                "forced"
+            }
+        );
+    }
+
+    if decrypted > 0 {
+        println!("Decrypted {} frame(s)", decrypted);
+    }
+    if decompressed > 0 {
+        println!("Decompressed {} frame(s)", decompressed);
+    }
+
+    for repair in &repairs {
+        println!("Repaired {}", repair.describe());
+    }
+
+    if args.dj {
+        let frames: Vec<_> = tag.frames().iter().filter(|x| dj::is_dj_frame(x)).collect();
+
+        if frames.is_empty() {
+            println!("No DJ metadata found");
+        }
+
+        for frame in frames {
+            println!("{}: {}", frame.id(), dj::describe_dj_frame(frame));
+        }
+
+        return;
+    }
+
+    let now = Timestamp::now();
+    for frame in tag.frames() {
+        let protection = match registration::describe_protection(&tag, frame) {
+            Some(x) => format!(" [{}]", x),
+            None => String::new(),
+        };
+
+        let value = match frame {
+            Frame::Text(_) if timestamp::TIMESTAMP_FRAMES.contains(&frame.id().as_str()) => {
+                timestamp::describe(&frame.describe(), &now)
+            }
+            _ => frame.describe(),
+        };
+
+        println!(
+            "{} ({}): {}{}",
+            frame.id(),
+            utility::get_field_name(frame.identifier()),
+            value,
+            protection
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process,
+};
+
+use crate::digest::{self, Sha256};
+use crate::tag::Id3v2Tag;
+
+// Filesystems share data in whole blocks, 4 KiB on btrfs and XFS by default
+const BLOCK_SIZE: usize = 4096;
+
+// Below this much audio, aligning it isn't worth the padding
+const MIN_CLONED_AUDIO: usize = 1024 * 1024;
+
+/// Files directly inside a folder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing {
+    pub locations: Vec<String>,
+    /// Files left out as no location can name them, their names not being valid UTF-8. Shown with
+    /// the invalid parts replaced.
+    pub skipped: Vec<String>,
+}
+
+/// Where files are listed, read, and written, so folders can live on disk or in object storage
+pub trait Storage {
+    fn list(&self, folder: &str) -> Result<Listing, String>;
+
+    fn read(&self, location: &str) -> Result<Vec<u8>, String>;
+
+    fn write(&self, location: &str, bytes: &[u8]) -> Result<(), String>;
+}
+
+/// Files on the local filesystem
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn list(&self, folder: &str) -> Result<Listing, String> {
+        let entries = match fs::read_dir(folder) {
+            Ok(x) => x,
+            Err(x) => return Err(format!("unable to read folder {}: {}", folder, x)),
+        };
+
+        let mut listing = Listing::default();
+        for entry in entries {
+            let entry = entry.map_err(|x| format!("unable to read folder {}: {}", folder, x))?;
+
+            match entry.file_name().into_string() {
+                Ok(x) => listing.locations.push(join(folder, &x)),
+                Err(x) => listing.skipped.push(join(folder, &x.to_string_lossy())),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    fn read(&self, location: &str) -> Result<Vec<u8>, String> {
+        fs::read(location).map_err(|x| format!("unable to read {}: {}", location, x))
+    }
+
+    fn write(&self, location: &str, bytes: &[u8]) -> Result<(), String> {
+        write_atomically(location, None, |x| x.write_all(bytes))
+    }
+}
+
+const AUDIO_MISMATCH: &str = "the written audio differs from the input";
+
+// The audio a written file must end with, from the input it was read from
+struct ExpectedAudio {
+    offset: usize,
+    length: usize,
+    hash: [u8; 32],
+}
+
+impl ExpectedAudio {
+    fn new(offset: usize, audio: &[u8]) -> ExpectedAudio {
+        ExpectedAudio {
+            offset,
+            length: audio.len(),
+            hash: digest::sha256(audio),
+        }
+    }
+
+    // Hashes the file as it is on disk, after everything was written and synced
+    fn check(&self, file: &mut File) -> io::Result<bool> {
+        if file.metadata()?.len() != (self.offset + self.length) as u64 {
+            return Ok(false);
+        }
+
+        file.seek(SeekFrom::Start(self.offset as u64))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => break,
+                x => hasher.update(&buffer[..x]),
+            }
+        }
+
+        Ok(hasher.finish() == self.hash)
+    }
+}
+
+// Written to a temporary file beside the target and renamed over it, so readers never see a
+// partially written file. With `expected`, the audio is read back first and a file whose audio
+// differs never replaces the target.
+fn write_atomically(
+    location: &str,
+    expected: Option<&ExpectedAudio>,
+    contents: impl FnOnce(&mut File) -> io::Result<()>,
+) -> Result<(), String> {
+    let path = Path::new(location);
+    let error = |x: io::Error| format!("unable to write {}: {}", location, x);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|x| format!("unable to create {}: {}", parent.display(), x))?;
+    }
+
+    let temp = path.with_file_name(format!(".{}.alloy-{}", file_name(location), process::id()));
+
+    let result = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp)
+        .and_then(|mut x| {
+            contents(&mut x)?;
+            x.sync_all()?;
+            match expected {
+                Some(expected) if !expected.check(&mut x)? => Err(io::Error::other(format!(
+                    "{}, the file was not replaced",
+                    AUDIO_MISMATCH
+                ))),
+                _ => Ok(()),
+            }
+        })
+        .and_then(|_| match fs::metadata(path) {
+            Ok(x) => fs::set_permissions(&temp, x.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| fs::rename(&temp, path));
+
+    if let Err(x) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(error(x));
+    }
+
+    Ok(())
+}
+
+// Copies the audio, which starts at `audio_offset` in `source`, after what has been written so
+// far. The part before the next block boundary is written from memory so the rest starts on a
+// block in both files; std copies between files with copy_file_range on Linux, which shares the
+// blocks on filesystems with reflinks, and falls back to reading and writing elsewhere.
+fn copy_audio(file: &mut File, source: &str, audio_offset: usize, audio: &[u8]) -> io::Result<()> {
+    let mut source = File::open(source)?;
+    if source.metadata()?.len() != (audio_offset + audio.len()) as u64 {
+        return Err(io::Error::other(
+            "the input changed while it was being tagged",
+        ));
+    }
+
+    let head = ((BLOCK_SIZE - audio_offset % BLOCK_SIZE) % BLOCK_SIZE).min(audio.len());
+    file.write_all(&audio[..head])?;
+
+    source.seek(SeekFrom::Start((audio_offset + head) as u64))?;
+    let copied = io::copy(&mut source.take((audio.len() - head) as u64), file)?;
+    if copied != (audio.len() - head) as u64 {
+        return Err(io::Error::other(
+            "the input changed while it was being tagged",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Zero bytes to pad a tag of `tag_size` bytes with so the audio keeps its offset within a
+/// filesystem block, letting `write_tagged` share the audio with the input instead of copying
+/// it. Small files aren't padded.
+pub fn alignment_padding(tag_size: usize, audio_offset: usize, audio_size: usize) -> usize {
+    if audio_size < MIN_CLONED_AUDIO {
+        return 0;
+    }
+
+    (BLOCK_SIZE + audio_offset % BLOCK_SIZE - tag_size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+// Writes the tag through a buffer, which large frames such as pictures bypass, so it is never
+// assembled in memory
+fn write_tag(file: &mut File, tag: &Id3v2Tag, padding: usize) -> io::Result<()> {
+    let mut w = BufWriter::new(file);
+    tag.write_padded_to(&mut w, padding)?;
+    w.flush()
+}
+
+/// Writes a tag, padded with `padding` zero bytes, followed by the audio read from `source`,
+/// where it starts at `audio_offset`. The tag is written a frame at a time. Between local files
+/// the audio is copied by the kernel, which on btrfs and XFS shares its blocks with the input when
+/// the tag was padded with `alignment_padding`, so tagging a large file doesn't duplicate its
+/// audio. Otherwise `audio` is written as usual. Either way the written audio is checked against
+/// `audio`, as with `write_verified`.
+pub fn write_tagged(
+    location: &str,
+    tag: &Id3v2Tag,
+    padding: usize,
+    source: &str,
+    audio_offset: usize,
+    audio: &[u8],
+) -> Result<(), String> {
+    if is_object_storage(location) {
+        return write_object(location, &tag.into_bytes_with_padding(padding), audio);
+    }
+
+    let expected = ExpectedAudio::new(tag.written_size(padding), audio);
+    write_atomically(location, Some(&expected), |x| {
+        write_tag(x, tag, padding)?;
+        if is_object_storage(source) {
+            x.write_all(audio)
+        } else {
+            copy_audio(x, source, audio_offset, audio)
+        }
+    })
+}
+
+/// Writes a tag followed by the audio of the input it was read from, then reads the audio back
+/// and compares its hash with `audio`. Local files whose audio differs are never put in place;
+/// objects, which can only be checked once uploaded, fail the write all the same.
+pub fn write_verified(location: &str, tag: &Id3v2Tag, audio: &[u8]) -> Result<(), String> {
+    if is_object_storage(location) {
+        return write_object(location, &tag.into_bytes(), audio);
+    }
+
+    let expected = ExpectedAudio::new(tag.written_size(0), audio);
+    write_atomically(location, Some(&expected), |x| {
+        write_tag(x, tag, 0)?;
+        x.write_all(audio)
+    })
+}
+
+// Objects are uploaded whole, so the tag is serialized for them
+fn write_object(location: &str, tag: &[u8], audio: &[u8]) -> Result<(), String> {
+    write(location, &[tag, audio].concat())?;
+
+    let written = read(location)?;
+    if written.get(tag.len()..).map(digest::sha256) != Some(digest::sha256(audio)) {
+        return Err(format!("unable to write {}: {}", location, AUDIO_MISMATCH));
+    }
+
+    Ok(())
+}
+
+pub fn is_object_storage(location: &str) -> bool {
+    location.starts_with("s3://")
+}
+
+/// The storage backend for a path or "s3://bucket/key" location
+pub fn for_location(location: &str) -> Result<Box<dyn Storage>, String> {
+    if !is_object_storage(location) {
+        return Ok(Box::new(LocalStorage));
+    }
+
+    #[cfg(feature = "s3")]
+    return Ok(Box::new(crate::s3::S3Storage::from_env()?));
+
+    #[cfg(not(feature = "s3"))]
+    Err(format!(
+        "unable to open {}: S3 support requires building alloy with `--features s3`",
+        location
+    ))
+}
+
+pub fn read(location: &str) -> Result<Vec<u8>, String> {
+    for_location(location)?.read(location)
+}
+
+pub fn write(location: &str, bytes: &[u8]) -> Result<(), String> {
+    for_location(location)?.write(location, bytes)
+}
+
+/// Exclusive advisory lock on a local file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(x), Ok(y)) => x.dev() == y.dev() && x.ino() == y.ino(),
+        _ => false,
+    }
+}
+
+// Files cannot be renamed over while open elsewhere
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, _path: &Path) -> bool {
+    true
+}
+
+fn lock_file(location: &str) -> Result<Option<FileLock>, String> {
+    loop {
+        let file = match File::open(location) {
+            Ok(x) => x,
+            // Nothing to protect yet, the write creates it
+            Err(x) if x.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(x) => return Err(format!("unable to lock {}: {}", location, x)),
+        };
+
+        file.lock()
+            .map_err(|x| format!("unable to lock {}: {}", location, x))?;
+
+        // Another process may have renamed a new file over this one while we waited
+        if is_same_file(&file, Path::new(location)) {
+            return Ok(Some(FileLock { _file: file }));
+        }
+    }
+}
+
+/// Locks every local file in `locations` for a read-modify-write, waiting for other alloy
+/// processes to finish with them first. Object storage locations are not locked.
+pub fn lock(locations: &[&str]) -> Result<Vec<FileLock>, String> {
+    let mut paths: Vec<String> = locations
+        .iter()
+        .filter(|x| !is_object_storage(x))
+        .map(|x| match fs::canonicalize(x) {
+            Ok(x) => x.to_string_lossy().to_string(),
+            Err(_) => x.to_string(),
+        })
+        .collect();
+
+    // A consistent order keeps two processes from each holding the lock the other waits on
+    paths.sort();
+    paths.dedup();
+
+    let mut locks = vec![];
+    for path in paths {
+        if let Some(x) = lock_file(&path)? {
+            locks.push(x);
+        }
+    }
+
+    Ok(locks)
+}
+
+/// Final component of a location
+pub fn file_name(location: &str) -> &str {
+    let location = location.trim_end_matches('/');
+
+    match location.rfind(['/', '\\']) {
+        Some(x) => &location[x + 1..],
+        None => location,
+    }
+}
+
+/// Location of the folder holding `location`, "." for a bare file name
+pub fn parent(location: &str) -> &str {
+    match location.trim_end_matches('/').rfind(['/', '\\']) {
+        Some(0) => "/",
+        Some(x) => &location[..x],
+        None => ".",
+    }
+}
+
+pub fn join(folder: &str, name: &str) -> String {
+    format!("{}/{}", folder.trim_end_matches('/'), name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn lists_files_whose_names_are_not_utf8_as_skipped() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let folder = std::env::temp_dir().join(format!("alloy-{}-listing", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("a.mp3"), b"").unwrap();
+        fs::write(folder.join(OsStr::from_bytes(b"b\xff.mp3")), b"").unwrap();
+
+        let folder_name = folder.to_str().unwrap();
+        let listing = LocalStorage.list(folder_name);
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            listing,
+            Ok(Listing {
+                locations: vec![join(folder_name, "a.mp3")],
+                skipped: vec![join(folder_name, "b\u{FFFD}.mp3")],
+            })
+        );
+    }
+}
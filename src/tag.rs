@@ -1,12 +1,369 @@
 use core::fmt;
-use std::mem;
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use crate::analysis::ReplayGain;
+use crate::chapter::{self, Chapter};
+use crate::comment::{Comment, TermsOfUse};
+use crate::commercial::{Commercial, Ownership};
+use crate::digest;
+use crate::disc::TableOfContents;
+use crate::dj;
+use crate::encryption;
+use crate::fields;
+use crate::frame_handler::{CustomFrameData, FrameHandlers};
+use crate::image_meta;
+use crate::link::Link;
+use crate::parse;
+use crate::probe::{self, Probe};
+use crate::registration::{EncryptionMethod, GroupRegistration, Signature};
+use crate::text_encoding::{self, LegacyCharset};
+use crate::timestamp::{Timestamp, TIMESTAMP_FRAMES};
+use crate::timing::{EventTimingCodes, TempoCodes};
+use crate::transform::TextLimit;
 use crate::utility::{self, convert_u32_to_safesynch};
+use crate::values::TrackNumber;
+use crate::zlib;
+
+/// The version bytes following "ID3" in every tag written: frames are always serialised as
+/// ID3v2.4 (revision 0) frames, whatever the parsed tag declared, with those of ID3v2.3 tags
+/// converted (see `Id3v2Tag::write_to`)
+pub const WRITE_VERSION: [u8; 2] = [0x04, 0x00];
+
+/// Parses a version to write, "2.4" or "2.4.0", the only one the writer implements
+pub fn parse_write_version(text: &str) -> Result<[u8; 2], String> {
+    match text.trim_start_matches(['v', 'V']) {
+        "2.4" | "2.4.0" => Ok(WRITE_VERSION),
+        _ => Err(format!(
+            "ID3v{} can't be written, only 2.4 is supported",
+            text
+        )),
+    }
+}
+
+// Tag header flags, ID3v2.4 section 3.1
+pub(crate) const HEADER_UNSYNCHRONISATION: u8 = 0b10000000;
+pub(crate) const HEADER_EXTENDED: u8 = 0b01000000;
+pub(crate) const HEADER_EXPERIMENTAL: u8 = 0b00100000;
+pub(crate) const HEADER_FOOTER: u8 = 0b00010000;
+
+// Extended header flags, ID3v2.4 section 3.2
+pub(crate) const EXTENDED_UPDATE: u8 = 0b01000000;
+pub(crate) const EXTENDED_CRC: u8 = 0b00100000;
+pub(crate) const EXTENDED_RESTRICTIONS: u8 = 0b00010000;
+
+// Frame format flags (second flag byte), ID3v2.4 section 4.1.2
+pub(crate) const FLAG_GROUPING: u8 = 0b01000000;
+pub(crate) const FLAG_COMPRESSION: u8 = 0b00001000;
+pub(crate) const FLAG_ENCRYPTION: u8 = 0b00000100;
+pub(crate) const FLAG_UNSYNCHRONISATION: u8 = 0b00000010;
+pub(crate) const FLAG_DATA_LENGTH: u8 = 0b00000001;
+
+// Frame status flags (first flag byte) and format flags of ID3v2.3, section 3.3.1, held as their
+// ID3v2.4 counterparts once parsed
+pub(crate) const V23_STATUS_FLAGS: u8 = 0b11100000;
+pub(crate) const V23_FLAG_COMPRESSION: u8 = 0b10000000;
+pub(crate) const V23_FLAG_ENCRYPTION: u8 = 0b01000000;
+pub(crate) const V23_FLAG_GROUPING: u8 = 0b00100000;
+
+/// Role and name pairs of a TIPL or TMCL frame, stored as alternating null-separated strings
+pub fn split_people_list(text: &str) -> Vec<(String, String)> {
+    let values: Vec<&str> = text.split('\0').collect();
+
+    values
+        .chunks(2)
+        .filter(|x| !x[0].is_empty())
+        .map(|x| (x[0].to_string(), x.get(1).unwrap_or(&"").to_string()))
+        .collect()
+}
+
+/// Text frames that hold a list, so `append_text` and `prepend_text` add values to them even
+/// when they hold a single one
+pub const LIST_FRAMES: [&str; 2] = ["TCON", "TLAN"];
+
+// Text frames holding a plain value (or several), which excludes TXXX and the people lists
+fn is_plain_text_frame(id: &str) -> bool {
+    (id.starts_with('T') || matches!(id, "WFED" | "MVNM" | "MVIN"))
+        && !matches!(id, "TXXX" | "TIPL" | "TMCL")
+}
+
+// Joins text to a value with a space, or returns it alone when the value is empty
+fn join_text(value: &str, text: &str, at_end: bool) -> String {
+    match (value.is_empty(), at_end) {
+        (true, _) => text.to_string(),
+        (false, true) => format!("{} {}", value, text),
+        (false, false) => format!("{} {}", text, value),
+    }
+}
 
 #[derive(Debug)]
 pub enum Frame {
     Text(Id3v2TextFrame),
     Picture(Id3v2PictureFrame),
+    Custom(Id3v2CustomFrame),
+    Unknown(Id3v2UnknownFrame),
+}
+
+impl Frame {
+    fn header(&self) -> &Id3v2FrameHeader {
+        match self {
+            Frame::Text(x) => &x.header,
+            Frame::Picture(x) => &x.header,
+            Frame::Custom(x) => &x.header,
+            Frame::Unknown(x) => &x.header,
+        }
+    }
+
+    pub(crate) fn into_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.size() + 10);
+        // Writing to a Vec can't fail
+        self.write_to(&mut result).unwrap();
+
+        debug_assert_eq!(
+            result.len(),
+            self.size() + 10,
+            "{} frame size differs from its serialized length",
+            self.id()
+        );
+
+        result
+    }
+
+    // Writes the frame header followed by the data, which is never copied into a buffer first
+    // except for handler frames, serialized by their handler
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if let Frame::Custom(x) = self {
+            let data = x.data.to_bytes();
+            w.write_all(&x.header.into_bytes(data.len()))?;
+            return w.write_all(&data);
+        }
+
+        w.write_all(&self.header().into_bytes(self.size()))?;
+
+        match self {
+            Frame::Text(x) => {
+                w.write_all(&[x.info.encoding])?;
+                w.write_all(&x.info.data)
+            }
+            Frame::Picture(x) => x.picture.write_to(w),
+            Frame::Custom(_) => Ok(()),
+            Frame::Unknown(x) => w.write_all(&x.data),
+        }
+    }
+
+    pub fn identifier(&self) -> [u8; 4] {
+        self.header().identifier
+    }
+
+    /// Size of the frame's data as written, header excluded. Only frames decoded by a handler
+    /// are serialized to tell.
+    pub fn size(&self) -> usize {
+        match self {
+            Frame::Text(x) => 1 + x.info.data.len(),
+            Frame::Picture(x) => x.picture.size(),
+            Frame::Custom(x) => x.data.to_bytes().len(),
+            Frame::Unknown(x) => x.data.len(),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self.header().id_str()
+    }
+
+    // Data of a frame kept as-is, including any group or encryption bytes in front of it
+    pub(crate) fn data(&self) -> Option<&[u8]> {
+        match self {
+            Frame::Unknown(x) => Some(&x.data),
+            _ => None,
+        }
+    }
+
+    /// Key (description) of a user-defined text frame (TXXX), or None for other frames and those
+    /// compressed, encrypted or unsynchronised
+    pub fn user_text_key(&self) -> Option<String> {
+        self.user_text().map(|(key, _)| key)
+    }
+
+    /// Key (description) and value of a user-defined text frame (TXXX), like `user_text_key`
+    pub fn user_text(&self) -> Option<(String, String)> {
+        let Frame::Unknown(x) = self else {
+            return None;
+        };
+        let flags = x.header.flags[1];
+        if &x.header.identifier != b"TXXX"
+            || flags & (FLAG_COMPRESSION | FLAG_ENCRYPTION | FLAG_UNSYNCHRONISATION) != 0
+        {
+            return None;
+        }
+
+        // A group symbol, then a data length indicator, may come before the data
+        let skipped = usize::from(flags & FLAG_GROUPING != 0)
+            + 4 * usize::from(flags & FLAG_DATA_LENGTH != 0);
+        let (&encoding, rest) = x.data.get(skipped..)?.split_first()?;
+        let (key, value) = utility::split_terminated(rest, encoding)?;
+
+        Some((
+            utility::decode_text(encoding, key),
+            utility::decode_text(encoding, value),
+        ))
+    }
+
+    /// What `Id3v2Tag::merge_frames` matches frames by: the identifier, with the key in
+    /// uppercase for user-defined text frames, e.g. "TIT2" or "TXXX:REPLAYGAIN_TRACK_GAIN"
+    pub fn merge_key(&self) -> String {
+        match self.user_text_key() {
+            Some(x) => format!("TXXX:{}", x.to_uppercase()),
+            None => self.id(),
+        }
+    }
+
+    /// SHA-256 of the frame's contents as stored, to tell apart data `describe` only summarises
+    pub fn content_hash(&self) -> [u8; 32] {
+        digest::sha256(&self.into_bytes()[10..])
+    }
+
+    /// Symbol of the group (registered by a GRID frame) the frame belongs to
+    pub fn group_symbol(&self) -> Option<u8> {
+        match self {
+            Frame::Unknown(x) if x.header.flags[1] & FLAG_GROUPING != 0 => x.data.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Symbol of the method (registered by an ENCR frame) the frame is encrypted with
+    pub fn encryption_method(&self) -> Option<u8> {
+        match self {
+            Frame::Unknown(x) if x.header.flags[1] & FLAG_ENCRYPTION != 0 => {
+                // The group symbol comes first when both are present
+                let idx = usize::from(x.header.flags[1] & FLAG_GROUPING != 0);
+                x.data.get(idx).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Human-readable summary of the frame's contents
+    pub fn describe(&self) -> String {
+        match self {
+            Frame::Text(x) if matches!(&x.header.identifier, b"TIPL" | b"TMCL") => {
+                split_people_list(&x.info.text())
+                    .iter()
+                    .map(|(role, name)| format!("{}: {}", role, name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            }
+            Frame::Text(x) => x.info.text(),
+            Frame::Picture(x) => format!(
+                "{}, {}, {}, '{}', {} bytes",
+                x.picture.mime(),
+                x.picture.picture_type_name(),
+                match image_meta::image_info(&x.picture.data) {
+                    Some(info) => format!("{}x{}", info.width, info.height),
+                    None => "unknown dimensions".to_string(),
+                },
+                x.picture.description(),
+                x.picture.data.len()
+            ),
+            Frame::Custom(x) => x.data.describe(),
+            // Grouping only puts a symbol in front of the data, which reads as usual after it
+            Frame::Unknown(x) if x.header.flags[1] == FLAG_GROUPING => {
+                let header = Id3v2FrameHeader {
+                    identifier: x.header.identifier,
+                    flags: [x.header.flags[0], 0x00],
+                };
+                match x.data.get(1..).map(|data| {
+                    parse::parse_frame_bytes(&[&header.into_bytes(data.len()), data].concat())
+                }) {
+                    Some(Ok(frame)) => frame.describe(),
+                    _ => format!("{} bytes", x.data.len()),
+                }
+            }
+            // Encrypted or compressed data can't be read as the frame's usual layout
+            Frame::Unknown(x) if x.header.flags[1] != 0 => format!("{} bytes", x.data.len()),
+            Frame::Unknown(x) if &x.header.identifier == b"CHAP" => match Chapter::parse(&x.data) {
+                Ok(chapter) => chapter.describe(),
+                Err(e) => format!("malformed chapter ({}), {} bytes", e, x.data.len()),
+            },
+            Frame::Unknown(x) if &x.header.identifier == b"ETCO" => {
+                match EventTimingCodes::parse(&x.data) {
+                    Ok(codes) => codes.describe(),
+                    Err(e) => format!(
+                        "malformed event timing codes ({}), {} bytes",
+                        e,
+                        x.data.len()
+                    ),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"SYTC" => {
+                match TempoCodes::parse(&x.data) {
+                    Ok(codes) => codes.describe(),
+                    Err(e) => format!("malformed tempo codes ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"OWNE" => match Ownership::parse(&x.data)
+            {
+                Ok(ownership) => ownership.describe(),
+                Err(e) => format!("malformed ownership ({}), {} bytes", e, x.data.len()),
+            },
+            Frame::Unknown(x) if &x.header.identifier == b"COMR" => {
+                match Commercial::parse(&x.data) {
+                    Ok(commercial) => commercial.describe(),
+                    Err(e) => format!("malformed commercial frame ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if matches!(&x.header.identifier, b"COMM" | b"USLT") => {
+                match Comment::parse(&x.data) {
+                    Ok(comment) => comment.describe(),
+                    Err(e) => format!("malformed comment ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"USER" => {
+                match TermsOfUse::parse(&x.data) {
+                    Ok(terms) => terms.describe(),
+                    Err(e) => format!("malformed terms of use ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"MCDI" => {
+                match TableOfContents::parse(&x.data) {
+                    Ok(toc) => toc.describe(),
+                    Err(e) => format!("malformed CD identifier ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"LINK" => match Link::parse(&x.data) {
+                Ok(link) => link.describe(),
+                Err(e) => format!("malformed link ({}), {} bytes", e, x.data.len()),
+            },
+            Frame::Unknown(x) if &x.header.identifier == b"ENCR" => {
+                match EncryptionMethod::parse(&x.data) {
+                    Ok(method) => method.describe(),
+                    Err(e) => format!("malformed registration ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"GRID" => {
+                match GroupRegistration::parse(&x.data) {
+                    Ok(group) => group.describe(),
+                    Err(e) => format!("malformed registration ({}), {} bytes", e, x.data.len()),
+                }
+            }
+            Frame::Unknown(x) if &x.header.identifier == b"SIGN" => match Signature::parse(&x.data)
+            {
+                Ok(signature) => signature.describe(),
+                Err(e) => format!("malformed signature ({}), {} bytes", e, x.data.len()),
+            },
+            // URL frames other than WXXX hold nothing but an ISO-8859-1 URL
+            Frame::Unknown(x)
+                if x.header.identifier[0] == b'W' && &x.header.identifier != b"WXXX" =>
+            {
+                utility::decode_text(0x00, &x.data)
+            }
+            Frame::Unknown(x) => format!("{} bytes", x.data.len()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -28,53 +385,46 @@ impl Id3v2Header {
     }
 }
 
+/// The extended header, held as ID3v2.4 lays it out whatever version it was read from
 #[derive(Debug)]
 pub struct Id3v2ExtendedHeader {
+    /// Size of the whole extended header as parsed, its own 4 size bytes included
     pub(crate) size: u32,
-    pub(crate) number_of_flag_bytes: u8,
     pub(crate) flags: u8,
-    pub(crate) b_flag_length: u8,
-    pub(crate) c_flag_length: u8,
-    pub(crate) total_frame_crc: Option<u64>,
-    pub(crate) d_flag_length: u8,
     pub(crate) restrictions: Option<u8>,
 }
 
 impl Id3v2ExtendedHeader {
+    // The CRC is left out, as it no longer matches the frames once any of them changes
     fn into_bytes(&self) -> Vec<u8> {
-        let size_bytes = utility::convert_u32_to_safesynch(self.size).to_vec();
-        let number_of_flag_bytes_vec = vec![self.number_of_flag_bytes];
-        let flag_byte = vec![self.flags];
-        let b_flag_length_byte = vec![self.b_flag_length];
-        let c_flag_length_byte = vec![self.c_flag_length];
-        let crc_bytes = match self.total_frame_crc {
-            Some(crc) => utility::convert_u64_to_safesynch(crc).to_vec(),
-            None => vec![],
-        };
-        let d_flag_length_byte = vec![self.d_flag_length];
-        let restrictions = match self.restrictions {
-            Some(r) => vec![r],
-            None => vec![],
-        };
+        let mut flags = 0x00;
+        let mut flag_data = vec![];
 
+        // The data of each flag set follows in flag order, led by its length
+        if self.flags & EXTENDED_UPDATE != 0 {
+            flags |= EXTENDED_UPDATE;
+            flag_data.push(0x00);
+        }
+        if let Some(x) = self.restrictions {
+            flags |= EXTENDED_RESTRICTIONS;
+            flag_data.extend([0x01, x]);
+        }
+
+        let size = u32::try_from(6 + flag_data.len()).unwrap();
         [
-            size_bytes,
-            number_of_flag_bytes_vec,
-            flag_byte,
-            b_flag_length_byte,
-            c_flag_length_byte,
-            crc_bytes,
-            d_flag_length_byte,
-            restrictions,
+            utility::convert_u32_to_safesynch(size).to_vec(),
+            vec![0x01, flags],
+            flag_data,
         ]
         .concat()
     }
 }
 
+/// Identifier and flags of a frame. Its size isn't kept, but derived from the frame's contents
+/// when it is written, so it can't disagree with them.
 #[derive(Debug)]
 pub struct Id3v2FrameHeader {
     pub(crate) identifier: [u8; 4],
-    pub(crate) size: u32, // 4 bytes representing a 32 bit safesynch integer
     pub(crate) flags: [u8; 2],
 }
 
@@ -83,9 +433,10 @@ impl Id3v2FrameHeader {
         String::from_utf8(self.identifier.to_vec()).unwrap()
     }
 
-    fn into_bytes(&self) -> Vec<u8> {
+    // `size` is that of the frame's data, as a 32 bit synchsafe integer
+    fn into_bytes(&self, size: usize) -> Vec<u8> {
         let identifier_bytes = self.identifier.to_vec();
-        let size_bytes = utility::convert_u32_to_safesynch(self.size).to_vec();
+        let size_bytes = utility::convert_u32_to_safesynch(u32::try_from(size).unwrap()).to_vec();
         let flag_bytes = self.flags.to_vec();
 
         [identifier_bytes, size_bytes, flag_bytes].concat()
@@ -98,12 +449,6 @@ pub struct Id3v2TextFrame {
     pub(crate) info: TextInformation,
 }
 
-impl Id3v2TextFrame {
-    fn into_bytes(&self) -> Vec<u8> {
-        [self.header.into_bytes(), self.info.into_bytes()].concat()
-    }
-}
-
 pub struct TextInformation {
     pub(crate) encoding: u8,
     pub(crate) data: Vec<u8>,
@@ -121,8 +466,8 @@ impl fmt::Debug for TextInformation {
 }
 
 impl TextInformation {
-    fn into_bytes(&self) -> Vec<u8> {
-        [vec![self.encoding], self.data.clone()].concat()
+    pub fn text(&self) -> String {
+        utility::decode_text(self.encoding, &self.data)
     }
 }
 
@@ -132,21 +477,75 @@ pub struct Id3v2PictureFrame {
     pub(crate) picture: Picture,
 }
 
-impl Id3v2PictureFrame {
-    fn into_bytes(&self) -> Vec<u8> {
-        let header_bytes = self.header.into_bytes();
-        let picture_bytes = self.picture.into_bytes();
+// A frame as `Id3v2Tag::write_to` writes it: as held, or converted from an ID3v2.3 frame
+enum WrittenFrame<'a> {
+    Held(&'a Frame),
+    Converted(Frame),
+}
 
-        [header_bytes, picture_bytes].concat()
+impl WrittenFrame<'_> {
+    fn frame(&self) -> &Frame {
+        match self {
+            WrittenFrame::Held(x) => x,
+            WrittenFrame::Converted(x) => x,
+        }
+    }
+}
+
+/// A frame alloy does not interpret, kept byte-for-byte so rewriting a tag preserves it
+#[derive(Debug)]
+pub struct Id3v2UnknownFrame {
+    pub(crate) header: Id3v2FrameHeader,
+    pub(crate) data: Vec<u8>,
+}
+
+/// A frame parsed by a registered [`crate::frame_handler::FrameHandler`]
+#[derive(Debug)]
+pub struct Id3v2CustomFrame {
+    pub(crate) header: Id3v2FrameHeader,
+    pub(crate) data: Box<dyn CustomFrameData>,
+}
+
+impl Id3v2CustomFrame {
+    pub fn data(&self) -> &dyn CustomFrameData {
+        self.data.as_ref()
     }
 }
 
+// Names of the APIC picture types, indexed by the type byte
+const PICTURE_TYPES: [&str; 21] = [
+    "Other",
+    "File icon",
+    "Other file icon",
+    "Front cover",
+    "Back cover",
+    "Leaflet page",
+    "Media",
+    "Lead artist",
+    "Artist",
+    "Conductor",
+    "Band/Orchestra",
+    "Composer",
+    "Lyricist",
+    "Recording location",
+    "During recording",
+    "During performance",
+    "Video screen capture",
+    "A bright coloured fish",
+    "Illustration",
+    "Band/artist logotype",
+    "Publisher/Studio logotype",
+];
+
+/// An attached picture. The image data is shared rather than copied when the picture is cloned,
+/// so the same cover can be held by many tags at once.
+#[derive(Clone)]
 pub struct Picture {
     pub(crate) encoding: u8, // 0x03 for utf-8
     pub(crate) mime: String,
     pub(crate) picture_type: u8, // 0x03 for cover art
     pub(crate) description: String,
-    pub(crate) data: Vec<u8>,
+    pub(crate) data: Arc<[u8]>,
 }
 
 impl fmt::Debug for Picture {
@@ -165,22 +564,83 @@ impl fmt::Debug for Picture {
 }
 
 impl Picture {
-    fn into_bytes(&self) -> Vec<u8> {
-        let description_bytes = self.description.clone().into_bytes();
-        let mime_bytes = self.mime.clone().into_bytes();
+    pub fn new(
+        mime: String,
+        picture_type: u8,
+        description: String,
+        data: impl Into<Arc<[u8]>>,
+    ) -> Picture {
+        Picture {
+            encoding: 0x03,
+            mime: mime + "\0",
+            picture_type,
+            description: description + "\0",
+            data: data.into(),
+        }
+    }
 
-        [
-            vec![self.encoding],
-            mime_bytes,
-            vec![self.picture_type],
-            description_bytes,
-            self.data.clone(),
-        ]
-        .concat()
+    pub fn mime(&self) -> &str {
+        self.mime.trim_end_matches('\0')
+    }
+
+    pub fn picture_type(&self) -> u8 {
+        self.picture_type
+    }
+
+    /// Name of the picture type from the ID3 spec, e.g. "Front cover"
+    pub fn picture_type_name(&self) -> String {
+        match PICTURE_TYPES.get(usize::from(self.picture_type)) {
+            Some(x) => x.to_string(),
+            None => format!("Unknown type {:#04X?}", self.picture_type),
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.trim_end_matches('\0')
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The image data, shared with this picture rather than copied
+    pub fn shared_data(&self) -> Arc<[u8]> {
+        Arc::clone(&self.data)
     }
 
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&[self.encoding])?;
+        w.write_all(self.mime.as_bytes())?;
+        w.write_all(&[self.picture_type])?;
+        w.write_all(self.description.as_bytes())?;
+        w.write_all(&self.data)
+    }
+
+    // Size of the frame data `write_to` writes, without assembling it
     fn size(&self) -> usize {
-        self.into_bytes().len()
+        1 + self.mime.len() + 1 + self.description.len() + self.data.len()
+    }
+}
+
+/// A modification made to a tag since it was parsed, see `Id3v2Tag::changes`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// Frame identifier, e.g. "TIT2"
+    pub frame: String,
+    /// Summary of the frame before the change, None if it was added
+    pub old: Option<String>,
+    /// Summary of the frame after the change, None if it was removed
+    pub new: Option<String>,
+}
+
+impl Change {
+    pub fn describe(&self) -> String {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => format!("{}: '{}' -> '{}'", self.frame, old, new),
+            (None, Some(new)) => format!("{}: added '{}'", self.frame, new),
+            (Some(old), None) => format!("{}: removed '{}'", self.frame, old),
+            (None, None) => format!("{}: unchanged", self.frame),
+        }
     }
 }
 
@@ -190,16 +650,60 @@ pub struct Id3v2Tag {
     pub(crate) extended_header: Option<Id3v2ExtendedHeader>,
     pub(crate) frames: Vec<Frame>,
     pub(crate) footer: Option<Id3v2Header>,
+    pub(crate) changes: Vec<Change>,
+    pub(crate) text_limit: Option<TextLimit>,
+    pub(crate) frame_sizes: parse::FrameSizeFormat,
 }
 
 impl Id3v2Tag {
+    // Text frames end in a null terminator, which isn't worth showing
+    fn summary(frame: &Frame) -> String {
+        frame.describe().trim_end_matches('\0').to_string()
+    }
+
+    // Every frame added or replaced goes through here so the change is recorded
+    pub(crate) fn put_frame(&mut self, idx: Option<usize>, new_frame: Frame) {
+        let old = idx.map(|x| Self::summary(&self.frames[x]));
+        let new = Some(Self::summary(&new_frame));
+
+        if old != new {
+            self.changes.push(Change {
+                frame: new_frame.id(),
+                old,
+                new,
+            });
+        }
+
+        match idx {
+            Some(x) => self.frames[x] = new_frame,
+            None => self.frames.push(new_frame),
+        }
+    }
+
+    // Every frame removed goes through here so the change is recorded
+    fn retain_frames(&mut self, keep: impl Fn(&Frame) -> bool) {
+        for frame in self.frames.iter().filter(|x| !keep(x)) {
+            self.changes.push(Change {
+                frame: frame.id(),
+                old: Some(Self::summary(frame)),
+                new: None,
+            });
+        }
+
+        self.frames.retain(keep);
+    }
+
+    /// Every frame added, replaced or removed since the tag was parsed, in order
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
     fn new_text_frame(&mut self, frame_id: &str, encoding: u8, data: Vec<u8>) -> Id3v2TextFrame {
         let id_bytes = frame_id.as_bytes();
         let new_frame = Id3v2TextFrame {
             // size has an additional byte for encoding
             header: Id3v2FrameHeader {
                 identifier: [id_bytes[0], id_bytes[1], id_bytes[2], id_bytes[3]],
-                size: u32::try_from(data.len()).unwrap() + 1,
                 flags: [0x00, 0x00],
             },
             info: TextInformation { encoding, data },
@@ -214,44 +718,74 @@ impl Id3v2Tag {
         Id3v2PictureFrame {
             header: Id3v2FrameHeader {
                 identifier: [id_bytes[0], id_bytes[1], id_bytes[2], id_bytes[3]],
-                size: u32::try_from(picture.size()).unwrap(),
                 flags: [0x00, 0x00],
             },
-            picture: Picture {
-                encoding: picture.encoding,
-                mime: picture.mime,
-                picture_type: 0x03,
-                description: picture.description,
-                data: picture.data,
-            },
+            picture,
         }
     }
 
-    fn set_text_frame(&mut self, frame_id: &str, data: String) -> Result<(), String> {
-        // Find frame
-        let frame_idx = self.frames.iter().position(|x| match x {
-            Frame::Text(x) => x.header.id_str() == frame_id,
-            _ => false,
-        });
+    // Text frame data with each value cut to the limit, if there is one
+    fn limit_text(&self, frame_id: &str, data: String) -> Result<String, String> {
+        let Some(limit) = &self.text_limit else {
+            return Ok(data);
+        };
 
-        if let Some(idx) = frame_idx {
-            if let Frame::Text(prev_frame) = &self.frames[idx] {
-                self.header.size -= u32::try_from(prev_frame.into_bytes().len()).unwrap();
+        let values = data
+            .trim_end_matches('\0')
+            .split('\0')
+            .map(|x| limit.apply(frame_id, x))
+            .collect::<Result<Vec<String>, String>>()?;
+        let terminator = if data.ends_with('\0') { "\0" } else { "" };
 
-                let data_bytes = data.into_bytes();
-                let new_frame = self.new_text_frame(frame_id, 0x03, data_bytes);
+        Ok(values.join("\0") + terminator)
+    }
 
-                self.header.size += u32::try_from(new_frame.into_bytes().len()).unwrap();
-                self.frames[idx] = Frame::Text(new_frame);
-            }
-        } else {
-            let new_frame = Frame::Text(self.new_text_frame(frame_id, 0x03, data.into_bytes()));
-            self.header.size += u32::try_from(match &new_frame {
-                Frame::Text(x) => x.into_bytes().len(),
-                Frame::Picture(x) => x.into_bytes().len(),
+    /// Limits the length of every text value set from now on; see `apply_text_limit` for the
+    /// values already in the tag
+    pub fn set_text_limit(&mut self, limit: Option<TextLimit>) {
+        self.text_limit = limit;
+    }
+
+    /// Cuts the text frames already in the tag to the limit set with `set_text_limit`, returning
+    /// how many changed. With `TruncationPolicy::Error` the first frame over it is an error.
+    pub fn apply_text_limit(&mut self) -> Result<usize, String> {
+        let texts: Vec<(String, String)> = self
+            .frames
+            .iter()
+            .filter_map(|x| match x {
+                Frame::Text(x) => Some((x.header.id_str(), x.info.text())),
+                _ => None,
             })
-            .unwrap();
-            self.frames.push(new_frame);
+            .collect();
+
+        let mut changed = 0;
+        for (frame_id, text) in texts {
+            let limited = self.limit_text(&frame_id, text.clone())?;
+            if limited != text {
+                self.set_text_frame(&frame_id, limited)?;
+                changed += 1;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn set_text_frame(&mut self, frame_id: &str, data: String) -> Result<(), String> {
+        let data = self.limit_text(frame_id, data)?;
+
+        // Find frame
+        // Text frames kept as-is (e.g. encrypted ones) are replaced too rather than duplicated
+        let frame_idx = self
+            .frames
+            .iter()
+            .position(|x| x.header().id_str() == frame_id);
+
+        let new_frame = Frame::Text(self.new_text_frame(frame_id, 0x03, data.into_bytes()));
+        self.put_frame(frame_idx, new_frame);
+
+        // The ID3v2.3 frame the value may have been read from would otherwise be left behind
+        if let Some(legacy) = fields::legacy_frame_id(frame_id) {
+            self.retain_frames(|x| x.id() != legacy);
         }
 
         Ok(())
@@ -264,25 +798,15 @@ impl Id3v2Tag {
         });
 
         if let Some(idx) = frame_idx {
-            if let Frame::Picture(prev_frame) = &self.frames[idx] {
-                self.header.size -= u32::try_from(prev_frame.picture.size()).unwrap();
-                self.header.size += u32::try_from(picture.size()).unwrap();
-
+            if let Frame::Picture(_) = &self.frames[idx] {
                 let new_frame = Frame::Picture(self.new_attached_picture_frame(picture));
-                self.frames[idx] = new_frame;
+                self.put_frame(Some(idx), new_frame);
             } else {
                 return Err("attempting to set a non-text frame as a picture frame".to_string());
             }
         } else {
             let new_frame = Frame::Picture(self.new_attached_picture_frame(picture));
-
-            self.header.size += u32::try_from(match &new_frame {
-                Frame::Text(x) => x.into_bytes().len(),
-                Frame::Picture(x) => x.into_bytes().len(),
-            })
-            .unwrap();
-
-            self.frames.push(new_frame);
+            self.put_frame(None, new_frame);
         }
 
         Ok(())
@@ -317,86 +841,1841 @@ impl Id3v2Tag {
         }
     }
 
-    pub fn set_cover_art(&mut self, picture: Picture) -> Result<(), String> {
-        match self.set_attached_picture_frame(picture) {
+    pub fn set_recording_time(&mut self, recording_time: String) -> Result<(), String> {
+        match self.set_text_frame("TDRC", recording_time) {
             Ok(()) => Ok(()),
             Err(x) => Err(x),
         }
     }
 
-    pub fn get_size(self) -> u64 {
-        let mut total_tag_size = 0;
+    pub fn set_track_number(&mut self, track_number: String) -> Result<(), String> {
+        match self.set_text_frame("TRCK", track_number) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
 
-        // header fixed size
-        total_tag_size += 10;
+    pub fn set_disc_number(&mut self, disc_number: String) -> Result<(), String> {
+        match self.set_text_frame("TPOS", disc_number) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
 
-        if self.extended_header.is_some() {
-            total_tag_size += self.extended_header.as_ref().unwrap().size + 10;
+    /// Sets the tempo (TBPM), in whole beats per minute
+    pub fn set_bpm(&mut self, bpm: String) -> Result<(), String> {
+        self.set_text_frame("TBPM", bpm)
+    }
+
+    /// Sets whether the track is part of a compilation (TCMP), as "1" or "0"
+    pub fn set_compilation(&mut self, compilation: bool) -> Result<(), String> {
+        self.set_text_frame("TCMP", format!("{}\0", u8::from(compilation)))
+    }
+
+    /// Sets the track number (TRCK) from a position checked by `TrackNumber`
+    pub fn set_track(&mut self, track: TrackNumber) -> Result<(), String> {
+        self.set_track_number(format!("{}\0", track))
+    }
+
+    /// Sets the disc number (TPOS) from a position checked by `TrackNumber`
+    pub fn set_disc(&mut self, disc: TrackNumber) -> Result<(), String> {
+        self.set_disc_number(format!("{}\0", disc))
+    }
+
+    pub fn set_content_group(&mut self, content_group: String) -> Result<(), String> {
+        match self.set_text_frame("TIT1", content_group) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
         }
+    }
 
-        for frame in self.frames {
-            total_tag_size += match frame {
-                Frame::Picture(x) => x.header.size + 10,
-                Frame::Text(x) => x.header.size + 10,
-            };
+    pub fn set_composer(&mut self, composer: String) -> Result<(), String> {
+        match self.set_text_frame("TCOM", composer) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
         }
+    }
 
-        if self.footer.is_some() {
-            total_tag_size += 10;
+    pub fn set_content_type(&mut self, content_type: String) -> Result<(), String> {
+        match self.set_text_frame("TCON", content_type) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    fn set_people_list(
+        &mut self,
+        frame_id: &str,
+        people: &[(String, String)],
+    ) -> Result<(), String> {
+        let values: Vec<&str> = people
+            .iter()
+            .flat_map(|(role, name)| [role.as_str(), name.as_str()])
+            .collect();
+
+        self.set_text_frame(frame_id, values.join("\0") + "\0")
+    }
+
+    /// Adds an instrument and name pair to the musician credits list (TMCL), keeping existing
+    /// entries
+    pub fn add_musician_credit(&mut self, instrument: String, name: String) -> Result<(), String> {
+        let mut musicians = self.get_musician_credits();
+        if !musicians.contains(&(instrument.clone(), name.clone())) {
+            musicians.push((instrument, name));
         }
 
-        total_tag_size.into()
+        self.set_people_list("TMCL", &musicians)
     }
 
-    pub fn into_bytes(&self) -> Vec<u8> {
-        // Return the stored information as a tag in bytes
-        let header_bytes = self.header.into_bytes();
-        let extended_header_bytes: Vec<u8> = match &self.extended_header {
-            Some(e) => e.into_bytes(),
-            None => vec![],
+    /// Conductor (TPE3) of a classical recording
+    pub fn set_conductor(&mut self, conductor: String) -> Result<(), String> {
+        self.set_text_frame("TPE3", conductor)
+    }
+
+    /// Movement name (MVNM) of a classical work, an iTunes frame stored like a text frame
+    pub fn set_movement_name(&mut self, movement_name: String) -> Result<(), String> {
+        self.set_text_frame("MVNM", movement_name)
+    }
+
+    /// Movement number (MVIN) of a classical work and how many movements it has, stored by
+    /// iTunes like a track number ("2/4")
+    pub fn set_movement_number(&mut self, number: u32, total: Option<u32>) -> Result<(), String> {
+        let value = match total {
+            Some(x) => format!("{}/{}\0", number, x),
+            None => format!("{}\0", number),
         };
-        let mut frames_bytes: Vec<u8> = vec![];
-        for frame in &self.frames {
-            let mut bytes = match frame {
-                Frame::Text(x) => x.into_bytes(),
-                Frame::Picture(x) => x.into_bytes(),
-            };
+        self.set_text_frame("MVIN", value)
+    }
 
-            frames_bytes.append(&mut bytes);
+    /// Adds a role and name pair to the involved people list (TIPL), keeping existing entries
+    pub fn add_involved_person(&mut self, role: String, name: String) -> Result<(), String> {
+        let mut people = self.get_involved_people();
+        if !people.contains(&(role.clone(), name.clone())) {
+            people.push((role, name));
         }
 
-        // -10 to account for header size that is not counted
-        let mut total_size: i32 = -10;
-        let footer_bytes: Vec<u8> = match &self.footer {
-            Some(f) => {
-                // Account for footer size
-                total_size -= 10;
-                (*f.into_bytes()).to_vec()
-            }
-            None => vec![],
-        };
+        self.set_people_list("TIPL", &people)
+    }
 
-        let mut result = [
-            header_bytes,
-            extended_header_bytes,
-            frames_bytes,
-            footer_bytes,
-        ]
-        .concat();
+    pub fn set_publisher(&mut self, publisher: String) -> Result<(), String> {
+        // TPUB is the label or publisher
+        match self.set_text_frame("TPUB", publisher) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
 
-        total_size += i32::try_from(result.len()).unwrap();
-        // Ensure header size is accurate by updating total number of bytes
-        let size_bytes = convert_u32_to_safesynch(u32::try_from(total_size).unwrap());
-        // 6 7 8 9
-        result[6] = size_bytes[0];
-        result[7] = size_bytes[1];
-        result[8] = size_bytes[2];
-        result[9] = size_bytes[3];
+    pub fn set_release_time(&mut self, release_time: String) -> Result<(), String> {
+        match self.set_text_frame("TDRL", release_time) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
 
-        // for byte in &result {
-        //     print!("{} ", byte);
-        // }
+    pub fn set_original_release_time(
+        &mut self,
+        original_release_time: String,
+    ) -> Result<(), String> {
+        self.set_text_frame("TDOR", original_release_time)
+    }
 
-        result
+    pub fn set_encoding_time(&mut self, encoding_time: String) -> Result<(), String> {
+        self.set_text_frame("TDEN", encoding_time)
+    }
+
+    /// Artist of the original recording, for covers and remixes (TOPE)
+    pub fn set_original_artist(&mut self, original_artist: String) -> Result<(), String> {
+        self.set_text_frame("TOPE", original_artist)
+    }
+
+    /// Album the original recording was released on (TOAL)
+    pub fn set_original_album(&mut self, original_album: String) -> Result<(), String> {
+        self.set_text_frame("TOAL", original_album)
+    }
+
+    /// Lyricist of the original recording (TOLY)
+    pub fn set_original_lyricist(&mut self, original_lyricist: String) -> Result<(), String> {
+        self.set_text_frame("TOLY", original_lyricist)
+    }
+
+    /// File name of the original recording (TOFN)
+    pub fn set_original_filename(&mut self, original_filename: String) -> Result<(), String> {
+        self.set_text_frame("TOFN", original_filename)
+    }
+
+    /// Sets one of the timestamp frames (`timestamp::TIMESTAMP_FRAMES`), e.g. "TDOR"
+    pub fn set_timestamp(&mut self, frame_id: &str, timestamp: &Timestamp) -> Result<(), String> {
+        if !TIMESTAMP_FRAMES.contains(&frame_id) {
+            return Err(format!("{} is not a timestamp frame", frame_id));
+        }
+
+        self.set_text_frame(frame_id, timestamp.to_string() + "\0")
+    }
+
+    pub fn set_podcast_description(&mut self, description: String) -> Result<(), String> {
+        match self.set_text_frame("TDES", description) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_podcast_category(&mut self, category: String) -> Result<(), String> {
+        match self.set_text_frame("TCAT", category) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_podcast_keywords(&mut self, keywords: String) -> Result<(), String> {
+        match self.set_text_frame("TKWD", keywords) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_podcast_id(&mut self, podcast_id: String) -> Result<(), String> {
+        match self.set_text_frame("TGID", podcast_id) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_podcast_feed_url(&mut self, feed_url: String) -> Result<(), String> {
+        // iTunes stores WFED like a text frame, with an encoding byte
+        match self.set_text_frame("WFED", feed_url) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    /// Marks the file as a podcast episode for iTunes-compatible players (PCST)
+    pub fn set_podcast_flag(&mut self) {
+        self.set_raw_frame(*b"PCST", vec![0x00, 0x00, 0x00, 0x00]);
+    }
+
+    /// Replaces all chapters (CHAP frames) and their table of contents (CTOC)
+    pub fn set_chapters(&mut self, chapters: &[Chapter]) {
+        self.retain_frames(|x| !matches!(&x.identifier(), b"CHAP" | b"CTOC"));
+
+        if chapters.is_empty() {
+            return;
+        }
+
+        self.push_raw_frame(*b"CTOC", chapter::table_of_contents_data(chapters));
+        for x in chapters {
+            self.push_raw_frame(*b"CHAP", x.into_frame_data());
+        }
+    }
+
+    pub fn set_event_timing_codes(&mut self, codes: &EventTimingCodes) {
+        self.set_raw_frame(*b"ETCO", codes.into_frame_data());
+    }
+
+    pub fn set_tempo_codes(&mut self, codes: &TempoCodes) {
+        self.set_raw_frame(*b"SYTC", codes.into_frame_data());
+    }
+
+    pub fn set_language(&mut self, language: String) -> Result<(), String> {
+        match self.set_text_frame("TLAN", language) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    /// Media the audio came from (TMED), e.g. "CD" or "TT/45"; see `media::media_type`
+    pub fn set_media_type(&mut self, media_type: String) -> Result<(), String> {
+        self.set_text_frame("TMED", media_type)
+    }
+
+    /// Type of the audio file (TFLT), e.g. "MPG/3"; see `media::file_type`
+    pub fn set_file_type(&mut self, file_type: String) -> Result<(), String> {
+        self.set_text_frame("TFLT", file_type)
+    }
+
+    // A tag can hold several comments or lyrics, one per language and description
+    fn set_comment_frame(&mut self, frame_id: [u8; 4], comment: &Comment) {
+        let new_frame = Self::new_raw_frame(frame_id, comment.into_frame_data());
+
+        let frame_idx = self.frames.iter().position(|x| match x {
+            Frame::Unknown(x) if x.header.identifier == frame_id && x.header.flags[1] == 0 => {
+                Comment::parse(&x.data).is_ok_and(|x| {
+                    x.language == comment.language && x.description == comment.description
+                })
+            }
+            _ => false,
+        });
+
+        self.put_frame(frame_idx, new_frame);
+    }
+
+    pub fn set_comment(&mut self, comment: &Comment) {
+        self.set_comment_frame(*b"COMM", comment);
+    }
+
+    pub fn set_lyrics(&mut self, lyrics: &Comment) {
+        self.set_comment_frame(*b"USLT", lyrics);
+    }
+
+    /// Sets the terms of use (USER), replacing the ones in the same language
+    pub fn set_terms_of_use(&mut self, terms: &TermsOfUse) {
+        let new_frame = Self::new_raw_frame(*b"USER", terms.into_frame_data());
+
+        let frame_idx = self.frames.iter().position(|x| match x {
+            Frame::Unknown(x) if &x.header.identifier == b"USER" && x.header.flags[1] == 0 => {
+                TermsOfUse::parse(&x.data).is_ok_and(|x| x.language == terms.language)
+            }
+            _ => false,
+        });
+
+        self.put_frame(frame_idx, new_frame);
+    }
+
+    /// Name of the internet radio station the file was broadcast by (TRSN)
+    pub fn set_radio_station_name(&mut self, station_name: String) -> Result<(), String> {
+        self.set_text_frame("TRSN", station_name)
+    }
+
+    /// Owner of the internet radio station (TRSO)
+    pub fn set_radio_station_owner(&mut self, station_owner: String) -> Result<(), String> {
+        self.set_text_frame("TRSO", station_owner)
+    }
+
+    /// Homepage of the internet radio station (WORS). URL frames have no encoding byte, so the
+    /// URL is written as ISO-8859-1 without a terminator
+    pub fn set_radio_station_url(&mut self, station_url: &str) -> Result<(), String> {
+        if !station_url.is_ascii() {
+            return Err(format!(
+                "invalid station URL '{}', URL frames only hold ASCII",
+                station_url
+            ));
+        }
+
+        self.set_raw_frame(*b"WORS", station_url.as_bytes().to_vec());
+        Ok(())
+    }
+
+    /// Copyright message (TCOP), starting with the year, e.g. "2024 Netlabel Records"
+    pub fn set_copyright(&mut self, copyright: String) -> Result<(), String> {
+        self.set_text_frame("TCOP", copyright)
+    }
+
+    /// Produced notice (TPRO), the copyright of the sound recording, starting with the year
+    pub fn set_produced_notice(&mut self, produced_notice: String) -> Result<(), String> {
+        self.set_text_frame("TPRO", produced_notice)
+    }
+
+    /// Registers an encryption method (ENCR), replacing any registration of the same symbol
+    pub fn register_encryption_method(&mut self, method: &EncryptionMethod) {
+        let new_frame = Self::new_raw_frame(*b"ENCR", method.into_frame_data());
+
+        let frame_idx = self.frames.iter().position(|x| match x {
+            Frame::Unknown(x) if &x.header.identifier == b"ENCR" => {
+                EncryptionMethod::parse(&x.data)
+                    .is_ok_and(|x| x.method_symbol == method.method_symbol)
+            }
+            _ => false,
+        });
+
+        self.put_frame(frame_idx, new_frame);
+    }
+
+    // Symbols of the methods registered as alloy's own, which it can encrypt and decrypt with
+    fn alloy_encryption_methods(&self) -> Vec<u8> {
+        self.frames
+            .iter()
+            .filter(|x| &x.identifier() == b"ENCR")
+            .filter_map(|x| EncryptionMethod::parse(x.data()?).ok())
+            .filter(|x| x.data == encryption::METHOD_DATA)
+            .map(|x| x.method_symbol)
+            .collect()
+    }
+
+    /// Encrypts every frame with one of `frame_ids` with `key`, using a method registered with
+    /// `register_encryption_method` and `encryption::METHOD_DATA`. Frames already grouped,
+    /// compressed or encrypted are left alone. Returns how many frames were encrypted.
+    pub fn encrypt_frames(
+        &mut self,
+        frame_ids: &[[u8; 4]],
+        method_symbol: u8,
+        key: &[u8; 32],
+    ) -> Result<usize, String> {
+        if !self.alloy_encryption_methods().contains(&method_symbol) {
+            return Err(format!(
+                "encryption method {:#04X} is not registered by alloy",
+                method_symbol
+            ));
+        }
+
+        if let Some(x) = frame_ids
+            .iter()
+            .find(|x| matches!(*x, b"ENCR" | b"GRID" | b"SIGN"))
+        {
+            return Err(format!(
+                "{} frames can't be encrypted, they describe the others",
+                String::from_utf8_lossy(x)
+            ));
+        }
+
+        let mut count = 0;
+        for frame in self.frames.iter_mut() {
+            let bytes = frame.into_bytes();
+            let identifier = frame.identifier();
+            if !frame_ids.contains(&identifier) || bytes[9] != 0 {
+                continue;
+            }
+
+            let plain = &bytes[10..];
+            let data = [
+                &[method_symbol][..],
+                &convert_u32_to_safesynch(u32::try_from(plain.len()).unwrap()),
+                &encryption::encrypt(key, identifier, plain),
+            ]
+            .concat();
+
+            // Encrypting changes how a frame is stored rather than what it holds, so it isn't
+            // recorded as a change
+            *frame = Frame::Unknown(Id3v2UnknownFrame {
+                header: Id3v2FrameHeader {
+                    identifier,
+                    flags: [bytes[8], FLAG_ENCRYPTION | FLAG_DATA_LENGTH],
+                },
+                data,
+            });
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Decrypts the frames encrypted with alloy's methods (see `encrypt_frames`), so they can be
+    /// read and edited like any other. Frames encrypted by other software are left alone.
+    /// Returns the identifier and method of each frame decrypted, to encrypt them again before
+    /// writing.
+    pub fn decrypt_frames(&mut self, key: &[u8; 32]) -> Result<Vec<([u8; 4], u8)>, String> {
+        let methods = self.alloy_encryption_methods();
+
+        let mut decrypted = vec![];
+        for frame in self.frames.iter_mut() {
+            let Frame::Unknown(x) = frame else {
+                continue;
+            };
+            let flags = x.header.flags[1];
+            if flags & !FLAG_DATA_LENGTH != FLAG_ENCRYPTION
+                || !x.data.first().is_some_and(|x| methods.contains(x))
+            {
+                continue;
+            }
+
+            let start = if flags & FLAG_DATA_LENGTH != 0 { 5 } else { 1 };
+            let identifier = x.header.identifier;
+            let method_symbol = x.data[0];
+            let plain = x
+                .data
+                .get(start..)
+                .ok_or("encrypted frame is truncated".to_string())
+                .and_then(|data| encryption::decrypt(key, identifier, data))
+                .map_err(|e| format!("unable to decrypt {}: {}", x.header.id_str(), e))?;
+
+            let header = Id3v2FrameHeader {
+                identifier,
+                flags: [x.header.flags[0], 0x00],
+            };
+            *frame = parse::parse_frame_bytes(&[header.into_bytes(plain.len()), plain].concat())?;
+            decrypted.push((identifier, method_symbol));
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Compresses every frame with one of `frame_ids` of at least `min_size` bytes with zlib,
+    /// setting the compression flag and the data length indicator the spec requires with it.
+    /// Frames already grouped, compressed or encrypted, and frames that wouldn't shrink, are
+    /// left alone. Returns how many frames were compressed.
+    pub fn compress_frames(&mut self, frame_ids: &[[u8; 4]], min_size: usize) -> usize {
+        let mut count = 0;
+        for frame in self.frames.iter_mut() {
+            let bytes = frame.into_bytes();
+            let identifier = frame.identifier();
+            if !frame_ids.contains(&identifier) || bytes[9] != 0 || bytes.len() - 10 < min_size {
+                continue;
+            }
+
+            let plain = &bytes[10..];
+            let data = [
+                &convert_u32_to_safesynch(u32::try_from(plain.len()).unwrap())[..],
+                &zlib::compress(plain),
+            ]
+            .concat();
+            if data.len() >= plain.len() {
+                continue;
+            }
+
+            // Like encryption, compression changes how a frame is stored rather than what it
+            // holds
+            *frame = Frame::Unknown(Id3v2UnknownFrame {
+                header: Id3v2FrameHeader {
+                    identifier,
+                    flags: [bytes[8], FLAG_COMPRESSION | FLAG_DATA_LENGTH],
+                },
+                data,
+            });
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Rewrites the text of every frame holding some as UTF-8, decoding it with the encoding its
+    /// bytes fit rather than the one it is marked with (see `text_encoding::detect`). Frames
+    /// stored compressed, encrypted or grouped are left alone. Returns the identifiers of the
+    /// frames rewritten. Text in ISO-8859-1 is decoded as `legacy` says.
+    pub fn reencode_utf8(&mut self, legacy: LegacyCharset) -> Vec<String> {
+        let mut reencoded = vec![];
+
+        for idx in 0..self.frames.len() {
+            let (header, is_text) = match &self.frames[idx] {
+                Frame::Text(x) => (&x.header, true),
+                Frame::Unknown(x) if x.header.flags[1] == 0 => (&x.header, false),
+                _ => continue,
+            };
+            let (identifier, flags) = (header.identifier, header.flags);
+
+            let bytes = self.frames[idx].into_bytes();
+            let Some(data) = text_encoding::to_utf8(&identifier, &bytes[10..], legacy) else {
+                continue;
+            };
+
+            let header = Id3v2FrameHeader { identifier, flags };
+            let frame = if is_text {
+                Frame::Text(Id3v2TextFrame {
+                    header,
+                    info: TextInformation {
+                        encoding: 0x03,
+                        data: data[1..].to_vec(),
+                    },
+                })
+            } else {
+                Frame::Unknown(Id3v2UnknownFrame { header, data })
+            };
+
+            reencoded.push(frame.id());
+            self.put_frame(Some(idx), frame);
+        }
+
+        reencoded
+    }
+
+    /// Decompresses the zlib-compressed frames that are neither grouped nor encrypted, so they
+    /// can be read and edited like any other. Frames that fail to decompress are kept as they
+    /// are. Returns how many frames were decompressed.
+    pub fn decompress_frames(&mut self) -> usize {
+        let mut count = 0;
+        for frame in self.frames.iter_mut() {
+            let Frame::Unknown(x) = frame else {
+                continue;
+            };
+            let flags = x.header.flags[1];
+            if flags & !FLAG_DATA_LENGTH != FLAG_COMPRESSION {
+                continue;
+            }
+
+            let start = if flags & FLAG_DATA_LENGTH != 0 { 4 } else { 0 };
+            let Some(Ok(plain)) = x.data.get(start..).map(zlib::decompress) else {
+                continue;
+            };
+
+            let header = Id3v2FrameHeader {
+                identifier: x.header.identifier,
+                flags: [x.header.flags[0], 0x00],
+            };
+            if let Ok(x) =
+                parse::parse_frame_bytes(&[header.into_bytes(plain.len()), plain].concat())
+            {
+                *frame = x;
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// Registers a group (GRID), replacing any registration of the same symbol
+    pub fn register_group(&mut self, group: &GroupRegistration) {
+        let new_frame = Self::new_raw_frame(*b"GRID", group.into_frame_data());
+
+        let frame_idx = self.frames.iter().position(|x| match x {
+            Frame::Unknown(x) if &x.header.identifier == b"GRID" => {
+                GroupRegistration::parse(&x.data)
+                    .is_ok_and(|x| x.group_symbol == group.group_symbol)
+            }
+            _ => false,
+        });
+
+        self.put_frame(frame_idx, new_frame);
+    }
+
+    /// Puts every frame with one of `frame_ids` in the group registered under `group_symbol` with
+    /// `register_group`, setting the grouping flag and the symbol in front of the frame's data.
+    /// Compressed and encrypted frames can be grouped too; frames already grouped are left
+    /// alone. Returns how many frames were grouped.
+    pub fn group_frames(
+        &mut self,
+        frame_ids: &[[u8; 4]],
+        group_symbol: u8,
+    ) -> Result<usize, String> {
+        if !self
+            .frames
+            .iter()
+            .filter(|x| &x.identifier() == b"GRID")
+            .filter_map(|x| GroupRegistration::parse(x.data()?).ok())
+            .any(|x| x.group_symbol == group_symbol)
+        {
+            return Err(format!("group {:#04X} is not registered", group_symbol));
+        }
+
+        if let Some(x) = frame_ids
+            .iter()
+            .find(|x| matches!(*x, b"ENCR" | b"GRID" | b"SIGN"))
+        {
+            return Err(format!(
+                "{} frames can't be grouped, they describe the others",
+                String::from_utf8_lossy(x)
+            ));
+        }
+
+        let mut count = 0;
+        for frame in self.frames.iter_mut() {
+            let bytes = frame.into_bytes();
+            let identifier = frame.identifier();
+            if !frame_ids.contains(&identifier) || bytes[9] & FLAG_GROUPING != 0 {
+                continue;
+            }
+
+            // The group symbol goes in front of any encryption method and data length
+            let data = [&[group_symbol][..], &bytes[10..]].concat();
+
+            // Grouping changes how a frame is stored rather than what it holds
+            *frame = Frame::Unknown(Id3v2UnknownFrame {
+                header: Id3v2FrameHeader {
+                    identifier,
+                    flags: [bytes[8], bytes[9] | FLAG_GROUPING],
+                },
+                data,
+            });
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Frames in the group registered under `group_symbol`
+    pub fn group_members(&self, group_symbol: u8) -> Vec<&Frame> {
+        self.frames
+            .iter()
+            .filter(|x| x.group_symbol() == Some(group_symbol))
+            .collect()
+    }
+
+    /// Removes every frame in the group registered under `group_symbol`, along with its
+    /// registration (GRID) and signatures (SIGN)
+    pub fn drop_group(&mut self, group_symbol: u8) {
+        self.retain_frames(|x| match &x.identifier() {
+            b"GRID" => x
+                .data()
+                .and_then(|x| GroupRegistration::parse(x).ok())
+                .is_none_or(|x| x.group_symbol != group_symbol),
+            b"SIGN" => x.data().and_then(|x| x.first()) != Some(&group_symbol),
+            _ => x.group_symbol() != Some(group_symbol),
+        });
+    }
+
+    pub fn set_ownership(&mut self, ownership: &Ownership) {
+        self.set_raw_frame(*b"OWNE", ownership.into_frame_data());
+    }
+
+    pub fn set_commercial(&mut self, commercial: &Commercial) {
+        self.set_raw_frame(*b"COMR", commercial.into_frame_data());
+    }
+
+    fn new_raw_frame(frame_id: [u8; 4], data: Vec<u8>) -> Frame {
+        Frame::Unknown(Id3v2UnknownFrame {
+            header: Id3v2FrameHeader {
+                identifier: frame_id,
+                flags: [0x00, 0x00],
+            },
+            data,
+        })
+    }
+
+    fn push_raw_frame(&mut self, frame_id: [u8; 4], data: Vec<u8>) {
+        self.put_frame(None, Self::new_raw_frame(frame_id, data));
+    }
+
+    // Frames without dedicated support are stored as-is, replacing an existing frame with the same ID
+    fn set_raw_frame(&mut self, frame_id: [u8; 4], data: Vec<u8>) {
+        let new_frame = Self::new_raw_frame(frame_id, data);
+
+        let idx = self.frames.iter().position(|x| x.identifier() == frame_id);
+        self.put_frame(idx, new_frame);
+    }
+
+    pub fn set_cover_art(&mut self, picture: Picture) -> Result<(), String> {
+        match self.set_attached_picture_frame(picture) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    /// Adds a file icon picture (type 0x01 or 0x02), replacing any existing picture of the same
+    /// type, since a tag may only hold one of each
+    pub fn set_file_icon(&mut self, icon: Picture) {
+        let picture_type = icon.picture_type;
+        let new_frame = Frame::Picture(self.new_attached_picture_frame(icon));
+
+        let idx = self
+            .frames
+            .iter()
+            .position(|x| matches!(x, Frame::Picture(x) if x.picture.picture_type == picture_type));
+        self.put_frame(idx, new_frame);
+    }
+
+    pub(crate) fn get_text_frame(&self, frame_id: &str) -> Option<String> {
+        self.frames
+            .iter()
+            .find_map(|x| match x {
+                Frame::Text(x) if x.header.id_str() == frame_id => Some(x.info.text()),
+                _ => None,
+            })
+            .or_else(|| self.get_legacy_text_frame(fields::legacy_frame_id(frame_id)?))
+    }
+
+    // Text of an ID3v2.3 frame standing in for an ID3v2.4 one (see `fields::VERSION_ALIASES`),
+    // such as IPLS, which isn't parsed as a text frame as its identifier doesn't start with T
+    fn get_legacy_text_frame(&self, frame_id: &str) -> Option<String> {
+        self.frames.iter().find_map(|x| match x {
+            Frame::Text(x) if x.header.id_str() == frame_id => Some(x.info.text()),
+            Frame::Unknown(x) if x.header.id_str() == frame_id && x.header.flags[1] == 0 => {
+                let (&encoding, text) = x.data.split_first()?;
+                Some(utility::decode_text(encoding, text))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn get_song_title(&self) -> Option<String> {
+        self.get_text_frame("TIT2")
+    }
+
+    pub fn get_song_artist_name(&self) -> Option<String> {
+        self.get_text_frame("TPE1")
+    }
+
+    pub fn get_album_title(&self) -> Option<String> {
+        self.get_text_frame("TALB")
+    }
+
+    pub fn get_album_artist_name(&self) -> Option<String> {
+        self.get_text_frame("TPE2")
+    }
+
+    pub fn get_recording_time(&self) -> Option<String> {
+        self.get_text_frame("TDRC")
+    }
+
+    pub fn get_track_number(&self) -> Option<String> {
+        self.get_text_frame("TRCK")
+    }
+
+    pub fn get_version(&self) -> [u8; 2] {
+        self.header.version
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// How the frame sizes were read when the tag was parsed. They are always written synchsafe
+    pub fn frame_size_format(&self) -> parse::FrameSizeFormat {
+        self.frame_sizes
+    }
+
+    pub fn set_tagging_time(&mut self, tagging_time: String) -> Result<(), String> {
+        match self.set_text_frame("TDTG", tagging_time) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_encoding_settings(&mut self, settings: String) -> Result<(), String> {
+        match self.set_text_frame("TSSE", settings) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    pub fn set_encoded_by(&mut self, encoded_by: String) -> Result<(), String> {
+        match self.set_text_frame("TENC", encoded_by) {
+            Ok(()) => Ok(()),
+            Err(x) => Err(x),
+        }
+    }
+
+    /// Records when and by which version of alloy the tag was written (TDTG, TSSE and TENC)
+    pub fn stamp(&mut self) -> Result<(), String> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|x| x.to_string())?
+            .as_secs();
+        let software = concat!("alloy ", env!("CARGO_PKG_VERSION"), "\0");
+
+        self.set_tagging_time(utility::format_timestamp(seconds) + "\0")?;
+        self.set_encoding_settings(software.to_string())?;
+        self.set_encoded_by(software.to_string())
+    }
+
+    /// Removes every frame, or every frame except attached pictures (APIC) with `keep_art`
+    pub fn clear_frames(&mut self, keep_art: bool) {
+        self.retain_frames(|x| keep_art && &x.identifier() == b"APIC");
+    }
+
+    // Whether a frame is one of `frame_ids`, or the ID3v2.3 frame one of them replaced
+    fn is_one_of(frame: &Frame, frame_ids: &[[u8; 4]]) -> bool {
+        let id = frame.id();
+
+        frame_ids.contains(&frame.identifier())
+            || frame_ids.iter().any(|x| {
+                String::from_utf8(x.to_vec())
+                    .is_ok_and(|x| fields::legacy_frame_id(&x) == Some(id.as_str()))
+            })
+    }
+
+    /// Removes every frame whose identifier is not in `frame_ids`, keeping the ID3v2.3 frames
+    /// they replaced (e.g. TYER for TDRC)
+    pub fn keep_frames(&mut self, frame_ids: &[[u8; 4]]) {
+        self.retain_frames(|x| Self::is_one_of(x, frame_ids));
+    }
+
+    /// Removes every frame whose identifier is in `frame_ids`, along with the ID3v2.3 frames
+    /// they replaced (e.g. TYER for TDRC)
+    pub fn drop_frames(&mut self, frame_ids: &[[u8; 4]]) {
+        self.retain_frames(|x| !Self::is_one_of(x, frame_ids));
+    }
+
+    /// Removes every user-defined text frame (TXXX) whose key matches one of `patterns` (see
+    /// `utility::matches_glob`), returning how many were removed
+    pub fn remove_user_text_frames(&mut self, patterns: &[String]) -> usize {
+        let matches = |x: &Frame| {
+            x.user_text_key()
+                .is_some_and(|key| patterns.iter().any(|x| utility::matches_glob(x, &key)))
+        };
+        let count = self.frames.iter().filter(|x| matches(x)).count();
+
+        self.retain_frames(|x| !matches(x));
+
+        count
+    }
+
+    /// Sets the user-defined text frame (TXXX) with this key, replacing any whose key differs
+    /// only in case, as players read ReplayGain keys in either
+    pub fn set_user_text(&mut self, key: &str, value: &str) {
+        let matches = |x: &Frame| {
+            x.user_text_key()
+                .is_some_and(|x| x.eq_ignore_ascii_case(key))
+        };
+
+        let mut data = vec![0x03];
+        data.extend_from_slice(key.as_bytes());
+        data.push(0x00);
+        data.extend_from_slice(value.as_bytes());
+
+        // A key held by several frames is written once, after the other frames
+        let idx = match self.frames.iter().filter(|x| matches(x)).count() {
+            0 => None,
+            1 => self.frames.iter().position(matches),
+            _ => {
+                self.retain_frames(|x| !matches(x));
+                None
+            }
+        };
+        self.put_frame(idx, Self::new_raw_frame(*b"TXXX", data));
+    }
+
+    /// Sets REPLAYGAIN_TRACK_GAIN and REPLAYGAIN_TRACK_PEAK
+    pub fn set_track_gain(&mut self, replay_gain: &ReplayGain) {
+        self.set_user_text("REPLAYGAIN_TRACK_GAIN", &replay_gain.gain_text());
+        self.set_user_text("REPLAYGAIN_TRACK_PEAK", &replay_gain.peak_text());
+    }
+
+    /// Sets REPLAYGAIN_ALBUM_GAIN and REPLAYGAIN_ALBUM_PEAK
+    pub fn set_album_gain(&mut self, replay_gain: &ReplayGain) {
+        self.set_user_text("REPLAYGAIN_ALBUM_GAIN", &replay_gain.gain_text());
+        self.set_user_text("REPLAYGAIN_ALBUM_PEAK", &replay_gain.peak_text());
+    }
+
+    /// Rewrites the text of text frames whose identifier is in `frame_ids`, or of every text frame
+    /// when it is empty, returning how many frames changed
+    pub fn transform_text_frames(
+        &mut self,
+        frame_ids: &[[u8; 4]],
+        transform: impl Fn(&str) -> String,
+    ) -> usize {
+        let mut changed = 0;
+
+        for idx in 0..self.frames.len() {
+            let Frame::Text(frame) = &self.frames[idx] else {
+                continue;
+            };
+
+            if !frame_ids.is_empty() && !frame_ids.contains(&frame.header.identifier) {
+                continue;
+            }
+
+            let text = frame.info.text();
+            let new_text = transform(&text);
+            if new_text == text {
+                continue;
+            }
+
+            let frame_id = frame.header.id_str();
+            let new_frame = self.new_text_frame(&frame_id, 0x03, (new_text + "\0").into_bytes());
+            self.put_frame(Some(idx), Frame::Text(new_frame));
+            changed += 1;
+        }
+
+        changed
+    }
+
+    // Adds text to a text frame's value, or as another value of a frame holding a list
+    fn extend_text_frame(
+        &mut self,
+        frame_id: [u8; 4],
+        text: &str,
+        at_end: bool,
+    ) -> Result<(), String> {
+        let id = String::from_utf8_lossy(&frame_id).to_string();
+        if !is_plain_text_frame(&id) {
+            return Err(format!("{} is not a text frame that can be extended", id));
+        }
+
+        let current = self.get_text_frame(&id).unwrap_or_default();
+        let mut values: Vec<String> = current
+            .trim_end_matches('\0')
+            .split('\0')
+            .filter(|x| !x.is_empty())
+            .map(String::from)
+            .collect();
+
+        if values.len() > 1 || (LIST_FRAMES.contains(&id.as_str()) && !values.is_empty()) {
+            if !values.iter().any(|x| x == text) {
+                if at_end {
+                    values.push(text.to_string());
+                } else {
+                    values.insert(0, text.to_string());
+                }
+            }
+        } else {
+            let value = values.pop().unwrap_or_default();
+            values.push(join_text(&value, text, at_end));
+        }
+
+        self.set_text_frame(&id, values.join("\0") + "\0")
+    }
+
+    /// Adds `text` to the end of a text frame's value after a space, or sets the frame to it if
+    /// it is missing. Frames holding several values, and those that are lists (`LIST_FRAMES`),
+    /// get it as another value instead.
+    pub fn append_text(&mut self, frame_id: [u8; 4], text: &str) -> Result<(), String> {
+        self.extend_text_frame(frame_id, text, true)
+    }
+
+    /// Adds `text` to the start of a text frame's value, the same way as `append_text`
+    pub fn prepend_text(&mut self, frame_id: [u8; 4], text: &str) -> Result<(), String> {
+        self.extend_text_frame(frame_id, text, false)
+    }
+
+    /// Sets the text frame `to` to the value of the text frame `from`, passed through `transform`.
+    /// Returns false, changing nothing, when `from` is missing or empty.
+    pub fn copy_text_frame(
+        &mut self,
+        from: [u8; 4],
+        to: [u8; 4],
+        transform: impl Fn(&str) -> String,
+    ) -> Result<bool, String> {
+        let (from, to) = (
+            String::from_utf8_lossy(&from).to_string(),
+            String::from_utf8_lossy(&to).to_string(),
+        );
+        for id in [&from, &to] {
+            if !is_plain_text_frame(id) {
+                return Err(format!("{} is not a text frame that can be copied", id));
+            }
+        }
+
+        let value = transform(
+            self.get_text_frame(&from)
+                .unwrap_or_default()
+                .trim_end_matches('\0'),
+        );
+        if value.is_empty() {
+            return Ok(false);
+        }
+
+        self.set_text_frame(&to, value + "\0")?;
+        Ok(true)
+    }
+
+    /// Sets a plain text frame by identifier, e.g. one given by a field name (see
+    /// `fields::frame_id`), with values separated by "\0"
+    pub fn set_text(&mut self, frame_id: [u8; 4], text: &str) -> Result<(), String> {
+        let id = String::from_utf8_lossy(&frame_id).to_string();
+        if !is_plain_text_frame(&id) {
+            return Err(format!("{} is not a text frame that can be set", id));
+        }
+
+        self.set_text_frame(&id, text.to_string() + "\0")
+    }
+
+    /// Replaces the frames of this tag with the frames of `other` sharing their merge key (see
+    /// `Frame::merge_key`), for the keys `take` accepts, adding those this tag lacks. Returns
+    /// how many keys were taken.
+    pub fn merge_frames(&mut self, mut other: Id3v2Tag, take: impl Fn(&str) -> bool) -> usize {
+        // Frames of each key, in the order their keys first appear
+        let mut groups: Vec<(String, Vec<Frame>)> = vec![];
+        for frame in mem::take(&mut other.frames) {
+            let key = frame.merge_key();
+            match groups.iter_mut().find(|(x, _)| *x == key) {
+                Some((_, frames)) => frames.push(frame),
+                None => groups.push((key, vec![frame])),
+            }
+        }
+
+        let mut taken = 0;
+        for (key, frames) in groups.into_iter().filter(|(x, _)| take(x)) {
+            let positions: Vec<usize> = (0..self.frames.len())
+                .filter(|x| self.frames[*x].merge_key() == key)
+                .collect();
+            let count = frames.len();
+
+            // Replaced in place as far as they go, so the changes read as replacements
+            let mut frames = frames.into_iter();
+            for idx in positions.iter().take(count) {
+                self.put_frame(Some(*idx), frames.next().unwrap());
+            }
+            for frame in frames {
+                self.put_frame(None, frame);
+            }
+
+            for idx in positions.into_iter().skip(count).rev() {
+                let frame = self.frames.remove(idx);
+                self.changes.push(Change {
+                    frame: frame.id(),
+                    old: Some(Self::summary(&frame)),
+                    new: None,
+                });
+            }
+
+            taken += 1;
+        }
+
+        taken
+    }
+
+    /// Adds `text` to the end of the comment (COMM) without a description in `language`, after a
+    /// space, or sets the comment to it if there is none
+    pub fn append_comment(&mut self, language: &str, text: &str) {
+        let current = self.frames.iter().find_map(|x| match x {
+            Frame::Unknown(x) if &x.header.identifier == b"COMM" && x.header.flags[1] == 0 => {
+                Comment::parse(&x.data)
+                    .ok()
+                    .filter(|x| x.language == language && x.description.is_empty())
+            }
+            _ => None,
+        });
+
+        self.set_comment(&Comment {
+            language: language.to_string(),
+            description: String::new(),
+            text: join_text(&current.map(|x| x.text).unwrap_or_default(), text, true),
+        });
+    }
+
+    /// Orders frames by identifier so the same set of frames always serializes to the same bytes.
+    /// Frames sharing an identifier keep their relative order.
+    pub fn sort_frames(&mut self) {
+        self.frames.sort_by_key(|x| x.identifier());
+    }
+
+    /// Adds a frame serialized by a custom handler, replacing any existing frame with the same ID
+    /// Sets a general encapsulated object (GEOB) frame from its payload, replacing the one with
+    /// the same description, as a tag holds one object per description
+    pub fn set_general_object(&mut self, data: Vec<u8>) {
+        let description = |x: &[u8]| dj::split_general_object(x).map(|(x, _)| x);
+        let new_description = description(&data);
+
+        let idx = self.frames.iter().position(|x| {
+            let payload = match x {
+                Frame::Custom(x) => x.data.to_bytes(),
+                Frame::Unknown(x) => x.data.clone(),
+                _ => return false,
+            };
+            x.identifier() == *b"GEOB" && description(&payload) == new_description
+        });
+        self.put_frame(idx, Self::new_raw_frame(*b"GEOB", data));
+    }
+
+    pub fn set_custom_frame(&mut self, frame_id: [u8; 4], data: Box<dyn CustomFrameData>) {
+        let new_frame = Frame::Custom(Id3v2CustomFrame {
+            header: Id3v2FrameHeader {
+                identifier: frame_id,
+                flags: [0x00, 0x00],
+            },
+            data,
+        });
+
+        let idx = self
+            .frames
+            .iter()
+            .position(|x| x.header().identifier == frame_id);
+        self.put_frame(idx, new_frame);
+    }
+
+    pub fn get_content_group(&self) -> Option<String> {
+        self.get_text_frame("TIT1")
+    }
+
+    pub fn get_conductor(&self) -> Option<String> {
+        self.get_text_frame("TPE3")
+    }
+
+    pub fn get_movement_name(&self) -> Option<String> {
+        self.get_text_frame("MVNM")
+    }
+
+    pub fn get_movement_number(&self) -> Option<String> {
+        self.get_text_frame("MVIN")
+    }
+
+    pub fn get_composer(&self) -> Option<String> {
+        self.get_text_frame("TCOM")
+    }
+
+    pub fn get_content_type(&self) -> Option<String> {
+        self.get_text_frame("TCON")
+    }
+
+    pub fn get_release_time(&self) -> Option<String> {
+        self.get_text_frame("TDRL")
+    }
+
+    pub fn get_original_release_time(&self) -> Option<String> {
+        self.get_text_frame("TDOR")
+    }
+
+    pub fn get_encoding_time(&self) -> Option<String> {
+        self.get_text_frame("TDEN")
+    }
+
+    pub fn get_radio_station_name(&self) -> Option<String> {
+        self.get_text_frame("TRSN")
+    }
+
+    pub fn get_radio_station_owner(&self) -> Option<String> {
+        self.get_text_frame("TRSO")
+    }
+
+    pub fn get_radio_station_url(&self) -> Option<String> {
+        self.get_raw_frame(b"WORS")
+            .map(|x| utility::decode_text(0x00, x))
+    }
+
+    pub fn get_copyright(&self) -> Option<String> {
+        self.get_text_frame("TCOP")
+    }
+
+    pub fn get_produced_notice(&self) -> Option<String> {
+        self.get_text_frame("TPRO")
+    }
+
+    pub fn get_media_type(&self) -> Option<String> {
+        self.get_text_frame("TMED")
+    }
+
+    pub fn get_file_type(&self) -> Option<String> {
+        self.get_text_frame("TFLT")
+    }
+
+    pub fn get_original_artist(&self) -> Option<String> {
+        self.get_text_frame("TOPE")
+    }
+
+    pub fn get_original_album(&self) -> Option<String> {
+        self.get_text_frame("TOAL")
+    }
+
+    pub fn get_original_lyricist(&self) -> Option<String> {
+        self.get_text_frame("TOLY")
+    }
+
+    pub fn get_original_filename(&self) -> Option<String> {
+        self.get_text_frame("TOFN")
+    }
+
+    /// One of the timestamp frames (`timestamp::TIMESTAMP_FRAMES`) parsed, None if the frame is
+    /// missing and an error if it doesn't hold a valid timestamp
+    pub fn get_timestamp(&self, frame_id: &str) -> Option<Result<Timestamp, String>> {
+        self.get_text_frame(frame_id)
+            .map(|x| Timestamp::parse(x.trim_end_matches('\0')))
+    }
+
+    /// Role and name pairs of the involved people list (TIPL), e.g. ("producer", "Rick Rubin")
+    pub fn get_involved_people(&self) -> Vec<(String, String)> {
+        split_people_list(&self.get_text_frame("TIPL").unwrap_or_default())
+    }
+
+    /// Instrument and name pairs of the musician credits list (TMCL), e.g. ("bass", "Flea")
+    pub fn get_musician_credits(&self) -> Vec<(String, String)> {
+        split_people_list(&self.get_text_frame("TMCL").unwrap_or_default())
+    }
+
+    pub fn get_chapters(&self) -> Vec<Chapter> {
+        self.frames
+            .iter()
+            .filter_map(|x| match x {
+                Frame::Unknown(x) if &x.header.identifier == b"CHAP" => {
+                    Chapter::parse(&x.data).ok()
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn get_raw_frame(&self, frame_id: &[u8; 4]) -> Option<&[u8]> {
+        self.frames.iter().find_map(|x| match x {
+            Frame::Unknown(x) if &x.header.identifier == frame_id => Some(x.data.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn get_event_timing_codes(&self) -> Option<EventTimingCodes> {
+        EventTimingCodes::parse(self.get_raw_frame(b"ETCO")?).ok()
+    }
+
+    pub fn get_tempo_codes(&self) -> Option<TempoCodes> {
+        TempoCodes::parse(self.get_raw_frame(b"SYTC")?).ok()
+    }
+
+    pub fn get_ownership(&self) -> Option<Ownership> {
+        Ownership::parse(self.get_raw_frame(b"OWNE")?).ok()
+    }
+
+    pub fn get_commercial(&self) -> Option<Commercial> {
+        Commercial::parse(self.get_raw_frame(b"COMR")?).ok()
+    }
+
+    /// Table of contents of the CD the file was ripped from (MCDI)
+    pub fn get_disc_toc(&self) -> Option<TableOfContents> {
+        TableOfContents::parse(self.get_raw_frame(b"MCDI")?).ok()
+    }
+
+    pub fn get_cover_art(&self) -> Option<&Picture> {
+        self.frames.iter().find_map(|x| match x {
+            Frame::Picture(x) => Some(&x.picture),
+            _ => None,
+        })
+    }
+
+    /// The front cover (picture type 0x03), or the first picture if none is marked as one
+    pub fn get_front_cover(&self) -> Option<&Picture> {
+        self.frames
+            .iter()
+            .find_map(|x| match x {
+                Frame::Picture(x) if x.picture.picture_type() == 0x03 => Some(&x.picture),
+                _ => None,
+            })
+            .or_else(|| self.get_cover_art())
+    }
+
+    pub fn get_size(self) -> u64 {
+        let mut total_tag_size = 0;
+
+        // header fixed size
+        total_tag_size += 10;
+
+        if self.extended_header.is_some() {
+            total_tag_size += self.extended_header.as_ref().unwrap().size + 10;
+        }
+
+        for frame in &self.frames {
+            total_tag_size += u32::try_from(frame.size()).unwrap() + 10;
+        }
+
+        if self.footer.is_some() {
+            total_tag_size += 10;
+        }
+
+        total_tag_size.into()
+    }
+
+    // Header flags describing the tag as written rather than as parsed. Frames added or changed
+    // are never unsynchronised, so the flag saying all of them are is only kept while it holds,
+    // and the writer doesn't produce experimental tags or know the undefined bits.
+    fn header_flags(&self) -> u8 {
+        let unsynchronised = !self.frames.is_empty()
+            && self
+                .frames
+                .iter()
+                .all(|x| x.header().flags[1] & FLAG_UNSYNCHRONISATION != 0);
+
+        let mut flags = 0x00;
+        if unsynchronised && self.header.flags & HEADER_UNSYNCHRONISATION != 0 {
+            flags |= HEADER_UNSYNCHRONISATION;
+        }
+        if self.extended_header.is_some() {
+            flags |= HEADER_EXTENDED;
+        }
+        if self.footer.is_some() {
+            flags |= HEADER_FOOTER;
+        }
+
+        flags
+    }
+
+    /// Header flags set in the parsed tag that are cleared when it is written, as the writer
+    /// doesn't implement them, e.g. "unsynchronisation" or "experimental"
+    pub fn cleared_header_flags(&self) -> Vec<&'static str> {
+        let cleared = self.header.flags & !self.header_flags();
+
+        [
+            (HEADER_UNSYNCHRONISATION, "unsynchronisation"),
+            (HEADER_EXPERIMENTAL, "experimental"),
+            (0b00001111, "undefined"),
+        ]
+        .iter()
+        .filter(|(flag, _)| cleared & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+
+    // TDRC text for an ID3v2.3 year, with the day and month of TDAT ("DDMM") and the time of
+    // TIME ("HHMM") when they are valid
+    fn v23_recording_time(&self, year: &str) -> String {
+        let digits = |id: &str| {
+            self.get_text_frame(id)
+                .map(|x| x.trim_end_matches('\0').to_string())
+                .filter(|x| x.len() == 4 && x.bytes().all(|x| x.is_ascii_digit()))
+        };
+
+        let mut text = year.trim_end_matches('\0').to_string();
+        if text.len() == 4 && text.bytes().all(|x| x.is_ascii_digit()) {
+            if let Some(date) = digits("TDAT") {
+                text += &format!("-{}-{}", &date[2..], &date[..2]);
+                if let Some(time) = digits("TIME") {
+                    text += &format!("T{}:{}", &time[..2], &time[2..]);
+                }
+            }
+        }
+
+        text
+    }
+
+    // The frames as written. Tags are written as ID3v2.4, so the TYER, TORY and IPLS frames of an
+    // ID3v2.3 tag become the TDRC, TDOR and TIPL frames that replaced them (see
+    // `fields::VERSION_ALIASES`), unless the tag already has those. TDAT and TIME, which ID3v2.4
+    // has no frames for, are folded into TDRC.
+    fn written_frames(&self) -> Vec<WrittenFrame<'_>> {
+        if self.header.version[0] != 3 {
+            return self.frames.iter().map(WrittenFrame::Held).collect();
+        }
+
+        let has = |id: &str| self.frames.iter().any(|x| x.id() == id);
+
+        self.frames
+            .iter()
+            .filter_map(|frame| {
+                let id = frame.id();
+                let Some((replacement, _)) = fields::VERSION_ALIASES
+                    .iter()
+                    .find(|(_, legacy)| *legacy == id)
+                else {
+                    return match (id.as_str(), frame) {
+                        ("TDAT" | "TIME", Frame::Text(_)) => None,
+                        _ => Some(WrittenFrame::Held(frame)),
+                    };
+                };
+                if has(replacement) {
+                    return None;
+                }
+
+                let header = Id3v2FrameHeader {
+                    identifier: replacement.as_bytes().try_into().unwrap(),
+                    flags: frame.header().flags,
+                };
+                let info = match frame {
+                    Frame::Text(x) if id == "TYER" => TextInformation {
+                        encoding: 0x03,
+                        data: self.v23_recording_time(&x.info.text()).into_bytes(),
+                    },
+                    Frame::Text(x) => TextInformation {
+                        encoding: x.info.encoding,
+                        data: x.info.data.clone(),
+                    },
+                    // IPLS has the layout of a text frame, but isn't parsed as one
+                    Frame::Unknown(x) if x.header.flags[1] == 0 && !x.data.is_empty() => {
+                        TextInformation {
+                            encoding: x.data[0],
+                            data: x.data[1..].to_vec(),
+                        }
+                    }
+                    _ => return Some(WrittenFrame::Held(frame)),
+                };
+
+                Some(WrittenFrame::Converted(Frame::Text(Id3v2TextFrame {
+                    header,
+                    info,
+                })))
+            })
+            .collect()
+    }
+
+    /// Bytes after the header counted in its size as written: the extended header and every
+    /// frame, without padding. Computed from the frames rather than by serializing them.
+    pub fn body_size(&self) -> usize {
+        let extended_header_size = self
+            .extended_header
+            .as_ref()
+            .map_or(0, |x| x.into_bytes().len());
+
+        extended_header_size
+            + self
+                .written_frames()
+                .iter()
+                .map(|x| x.frame().size() + 10)
+                .sum::<usize>()
+    }
+
+    /// Sets the size declared in the header (and footer) to that of the tag as it would be
+    /// written, so it no longer holds the size parsed before frames were changed
+    pub fn recompute_sizes(&mut self) {
+        let size = u32::try_from(self.body_size()).unwrap();
+
+        self.header.size = size;
+        if let Some(footer) = &mut self.footer {
+            footer.size = size;
+        }
+    }
+
+    /// Version, size, cover art, title and artist of the tag of the file at `path`, from no more
+    /// than its first few KB (see `probe::probe`)
+    pub fn probe(path: &Path) -> Result<Probe, String> {
+        probe::probe(path)
+    }
+
+    /// Reads a tag from where `source` is: its header, then exactly the size the header declares,
+    /// so the audio after it is never read. `source` is left at the audio following the tag, or
+    /// where it was if there's no tag there.
+    pub fn read_from<R: Read + Seek>(source: R) -> Result<Id3v2Tag, String> {
+        Id3v2Tag::read_from_with_handlers(source, &FrameHandlers::default())
+    }
+
+    pub fn read_from_with_handlers<R: Read + Seek>(
+        mut source: R,
+        handlers: &FrameHandlers,
+    ) -> Result<Id3v2Tag, String> {
+        let start = source.stream_position().map_err(|x| x.to_string())?;
+
+        let mut header = [0x00; 10];
+        if source.read_exact(&mut header).is_err() || !header.starts_with(b"ID3") {
+            source
+                .seek(SeekFrom::Start(start))
+                .map_err(|x| x.to_string())?;
+            return Err("no ID3v2 tag".to_string());
+        }
+
+        let mut size = u64::from(utility::convert_safesynch_to_u32(
+            header[6], header[7], header[8], header[9],
+        ));
+        if header[5] & HEADER_FOOTER != 0 {
+            size += 10;
+        }
+
+        // Taken rather than allocated up front, as a corrupt header can declare up to 256 MB
+        let mut bytes = header.to_vec();
+        source
+            .by_ref()
+            .take(size)
+            .read_to_end(&mut bytes)
+            .map_err(|x| x.to_string())?;
+        if bytes.len() < 10 + usize::try_from(size).unwrap() {
+            return Err(format!(
+                "tag declares {} bytes but the file only has {}",
+                10 + size,
+                bytes.len()
+            ));
+        }
+
+        parse::parse_tag_with_handlers(&bytes, handlers)
+    }
+
+    /// Writes the tag to `w` as `into_bytes` returns it, a frame at a time, so pictures and
+    /// other large frames are written from where they are held rather than copied into one buffer.
+    /// An ID3v2.3 tag is written as ID3v2.4, with the frames ID3v2.4 replaced converted.
+    pub fn write_to<W: Write>(&self, w: W) -> io::Result<()> {
+        self.write_padded_to(w, 0)
+    }
+
+    /// Writes the tag to `w` like `write_to`, followed by `padding` zero bytes counted in its
+    /// size. Tags with a footer aren't padded, as ID3v2.4 doesn't allow padding with a footer.
+    pub fn write_padded_to<W: Write>(&self, mut w: W, padding: usize) -> io::Result<()> {
+        let padding = if self.footer.is_some() { 0 } else { padding };
+
+        // The header and footer describe the tag as written, see `header_flags`
+        let header = |identifier: [u8; 3]| Id3v2Header {
+            identifier,
+            version: WRITE_VERSION,
+            flags: self.header_flags(),
+            size: u32::try_from(self.body_size() + padding).unwrap(),
+        };
+
+        w.write_all(&header(self.header.identifier).into_bytes())?;
+        if let Some(e) = &self.extended_header {
+            w.write_all(&e.into_bytes())?;
+        }
+        for frame in self.written_frames() {
+            frame.frame().write_to(&mut w)?;
+        }
+        io::copy(&mut io::repeat(0x00).take(padding as u64), &mut w)?;
+        // The footer repeats the header's version, flags and size
+        if let Some(f) = &self.footer {
+            w.write_all(&header(f.identifier).into_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Length in bytes of the tag as `write_padded_to` writes it with `padding`
+    pub fn written_size(&self, padding: usize) -> usize {
+        match self.footer {
+            Some(_) => 10 + self.body_size() + 10,
+            None => 10 + self.body_size() + padding,
+        }
+    }
+
+    pub fn into_bytes(&self) -> Vec<u8> {
+        self.into_bytes_with_padding(0)
+    }
+
+    /// The tag as bytes followed by `padding` zero bytes counted in its size, see
+    /// `write_padded_to`
+    pub fn into_bytes_with_padding(&self, padding: usize) -> Vec<u8> {
+        // Return the stored information as a tag in bytes
+        let mut result = Vec::with_capacity(self.written_size(padding));
+        // Writing to a Vec can't fail
+        self.write_padded_to(&mut result, padding).unwrap();
+
+        debug_assert_eq!(
+            result.len(),
+            self.written_size(padding),
+            "tag size differs from its serialized length"
+        );
+
+        result
+    }
+}
+
+/// Tags built byte by byte, as other software writes them, for tests to parse
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::utility::convert_u32_to_safesynch;
+
+    /// A frame of an ID3v2.`version` tag, whose size is a plain integer in ID3v2.3
+    pub fn frame(id: &[u8; 4], flags: [u8; 2], data: &[u8], version: u8) -> Vec<u8> {
+        let size = u32::try_from(data.len()).unwrap();
+        let size = match version {
+            3 => size.to_be_bytes(),
+            _ => convert_u32_to_safesynch(size),
+        };
+
+        [id.as_slice(), &size, &flags, data].concat()
+    }
+
+    pub fn tag_bytes(version: u8, frames: &[Vec<u8>]) -> Vec<u8> {
+        let frames = frames.concat();
+        let size = convert_u32_to_safesynch(u32::try_from(frames.len()).unwrap());
+
+        [b"ID3".as_slice(), &[version, 0x00, 0x00], &size, &frames].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{frame, tag_bytes};
+    use super::*;
+
+    fn text_frames(tag: &Id3v2Tag) -> Vec<(String, String)> {
+        tag.frames()
+            .iter()
+            .filter(|x| matches!(x, Frame::Text(_)))
+            .map(|x| (x.id(), x.describe()))
+            .collect()
+    }
+
+    #[test]
+    fn writes_v23_frames_as_the_v24_frames_replacing_them() {
+        let bytes = tag_bytes(
+            3,
+            &[
+                frame(b"TIT2", [0x00, 0x00], b"\x00Title", 3),
+                frame(b"TYER", [0x00, 0x00], b"\x001999", 3),
+                frame(b"TDAT", [0x00, 0x00], b"\x001403", 3),
+                frame(b"TIME", [0x00, 0x00], b"\x001230", 3),
+                frame(b"TORY", [0x00, 0x00], b"\x001987", 3),
+                frame(b"IPLS", [0x00, 0x00], b"\x00producer\x00Name", 3),
+            ],
+        );
+        let tag = parse::parse_tag(&bytes).unwrap();
+        let written = parse::parse_tag(&tag.into_bytes()).unwrap();
+
+        assert_eq!(written.get_version(), WRITE_VERSION);
+        assert_eq!(
+            text_frames(&written),
+            [
+                ("TIT2".to_string(), "Title".to_string()),
+                ("TDRC".to_string(), "1999-03-14T12:30".to_string()),
+                ("TDOR".to_string(), "1987".to_string()),
+                ("TIPL".to_string(), "producer: Name".to_string()),
+            ]
+        );
+        assert_eq!(written.frames().len(), 4);
+    }
+
+    #[test]
+    fn keeps_v24_frames_over_the_v23_ones_they_replaced() {
+        let bytes = tag_bytes(
+            3,
+            &[
+                frame(b"TYER", [0x00, 0x00], b"\x001999", 3),
+                frame(b"TDAT", [0x00, 0x00], b"\x001403", 3),
+            ],
+        );
+        let mut tag = parse::parse_tag(&bytes).unwrap();
+        tag.set_recording_time("2001-05-06".to_string()).unwrap();
+        let written = parse::parse_tag(&tag.into_bytes()).unwrap();
+
+        assert_eq!(
+            text_frames(&written),
+            [("TDRC".to_string(), "2001-05-06".to_string())]
+        );
+    }
+
+    #[test]
+    fn writes_v23_year_alone_when_the_date_is_invalid() {
+        let bytes = tag_bytes(
+            3,
+            &[
+                frame(b"TYER", [0x00, 0x00], b"\x001999", 3),
+                frame(b"TDAT", [0x00, 0x00], b"\x0014th", 3),
+            ],
+        );
+        let written = parse::parse_tag(&parse::parse_tag(&bytes).unwrap().into_bytes()).unwrap();
+
+        assert_eq!(
+            text_frames(&written),
+            [("TDRC".to_string(), "1999".to_string())]
+        );
+    }
+
+    #[test]
+    fn writes_v23_frame_flags_as_v24_flags() {
+        let bytes = tag_bytes(
+            3,
+            &[
+                // Tag alter preservation and read only, and a grouped frame
+                frame(b"TIT2", [0xA0, 0x00], b"\x00Title", 3),
+                frame(b"PRIV", [0x00, 0x20], b"\x07owner\x00data", 3),
+                // Encrypted and grouped: the method comes before the group symbol in ID3v2.3
+                frame(b"PRIV", [0x00, 0x60], b"\x80\x07owner\x00data", 3),
+            ],
+        );
+        let tag = parse::parse_tag(&bytes).unwrap();
+        let written = tag.into_bytes();
+
+        assert_eq!(&written[3..5], &WRITE_VERSION);
+        assert_eq!(
+            &written[10..],
+            [
+                frame(b"TIT2", [0x50, 0x00], b"\x00Title", 4),
+                frame(b"PRIV", [0x00, FLAG_GROUPING], b"\x07owner\x00data", 4),
+                frame(
+                    b"PRIV",
+                    [0x00, FLAG_GROUPING | FLAG_ENCRYPTION],
+                    b"\x07\x80owner\x00data",
+                    4
+                ),
+            ]
+            .concat()
+        );
+
+        let written = parse::parse_tag(&written).unwrap();
+        assert_eq!(written.frames()[1].group_symbol(), Some(0x07));
+        assert_eq!(written.frames()[2].group_symbol(), Some(0x07));
+        assert_eq!(written.frames()[2].encryption_method(), Some(0x80));
+    }
+
+    #[test]
+    fn writes_v24_tags_unchanged() {
+        let bytes = tag_bytes(
+            4,
+            &[
+                frame(b"TIT2", [0x00, 0x00], b"\x03Title", 4),
+                frame(b"PRIV", [0x00, FLAG_GROUPING], b"\x07owner\x00data", 4),
+            ],
+        );
+
+        assert_eq!(parse::parse_tag(&bytes).unwrap().into_bytes(), bytes);
+    }
+
+    // A tag as written must pass the same size checks as `--verify`, with every frame's size
+    // matching its serialized length (asserted by `Frame::into_bytes`)
+    fn assert_sizes_hold(tag: &Id3v2Tag) -> Vec<u8> {
+        for frame in tag.frames() {
+            frame.content_hash();
+        }
+
+        let bytes = tag.into_bytes();
+        assert_eq!(crate::integrity::audit_sizes(&bytes).unwrap(), []);
+
+        bytes
+    }
+
+    fn modified_tag(version: u8) -> Id3v2Tag {
+        let bytes = tag_bytes(
+            version,
+            &[
+                frame(b"TIT2", [0x00, 0x00], b"\x00Title", version),
+                frame(b"TPE1", [0x00, 0x00], b"\x00Artist", version),
+                frame(b"TXXX", [0x00, 0x00], b"\x00KEY\x00value", version),
+                frame(b"PRIV", [0x00, 0x00], b"owner\x00data", version),
+            ],
+        );
+        let mut tag = parse::parse_tag(&bytes).unwrap();
+
+        tag.set_song_title("A much longer title than before".to_string())
+            .unwrap();
+        tag.set_album_title("Album — with UTF-8".to_string())
+            .unwrap();
+        tag.set_comment(&Comment {
+            language: "eng".to_string(),
+            description: String::new(),
+            text: "Comment".repeat(40),
+        });
+        tag.set_cover_art(Picture::new(
+            "image/png".to_string(),
+            0x03,
+            "Front".to_string(),
+            vec![0x89; 300],
+        ))
+        .unwrap();
+        tag.drop_frames(&[*b"TPE1"]);
+
+        tag
+    }
+
+    #[test]
+    fn modified_tags_round_trip_with_consistent_sizes() {
+        for version in [3, 4] {
+            let tag = modified_tag(version);
+            let bytes = assert_sizes_hold(&tag);
+
+            let parsed = parse::parse_tag(&bytes).unwrap();
+            assert_eq!(
+                parsed.get_song_title().unwrap().trim_end_matches('\0'),
+                "A much longer title than before"
+            );
+            assert_eq!(parsed.frames().len(), tag.frames().len());
+            assert_eq!(parsed.into_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn compressed_frames_keep_consistent_sizes() {
+        let mut tag = modified_tag(4);
+        assert_eq!(tag.compress_frames(&[*b"COMM"], 0), 1);
+        let bytes = assert_sizes_hold(&tag);
+
+        let mut parsed = parse::parse_tag(&bytes).unwrap();
+        assert_eq!(parsed.decompress_frames(), 1);
+        assert_sizes_hold(&parsed);
+    }
+
+    #[test]
+    fn recompute_sizes_matches_the_written_tag() {
+        let mut tag = modified_tag(3);
+        assert_ne!(usize::try_from(tag.header.size).unwrap(), tag.body_size());
+
+        tag.recompute_sizes();
+        let bytes = assert_sizes_hold(&tag);
+
+        assert_eq!(usize::try_from(tag.header.size).unwrap(), bytes.len() - 10);
+        assert_eq!(&bytes[6..10], &convert_u32_to_safesynch(tag.header.size));
+    }
+
+    #[test]
+    fn reverses_v23_unsynchronisation_when_writing() {
+        // Frame sizes count the data without the 0x00 inserted after each 0xFF
+        let title = frame(b"TIT2", [0x00, 0x00], b"\x00x\xFF\xE0x", 3);
+        let private = frame(b"PRIV", [0x00, 0x00], b"o\x00\xFF\x00", 3);
+        let unsynchronised = [
+            &title[..12],
+            b"\xFF\x00\xE0x",
+            &private[..12],
+            b"\xFF\x00\x00",
+        ]
+        .concat();
+        let mut bytes = tag_bytes(3, &[unsynchronised]);
+        bytes[5] |= HEADER_UNSYNCHRONISATION;
+
+        let tag = parse::parse_tag(&bytes).unwrap();
+        assert_eq!(tag.get_song_title().unwrap(), "x\u{FF}\u{E0}x");
+        assert_eq!(tag.frames()[1].data(), Some(&b"o\x00\xFF\x00"[..]));
+        assert_eq!(tag.cleared_header_flags(), ["unsynchronisation"]);
+
+        let written = assert_sizes_hold(&tag);
+        assert_eq!(written[5] & HEADER_UNSYNCHRONISATION, 0);
+        let parsed = parse::parse_tag(&written).unwrap();
+        assert_eq!(parsed.get_song_title().unwrap(), "x\u{FF}\u{E0}x");
+        assert_eq!(parsed.frames()[1].data(), Some(&b"o\x00\xFF\x00"[..]));
+    }
+
+    #[test]
+    fn writes_the_tag_streamed_as_serialized() {
+        let tag = modified_tag(3);
+        for padding in [0, 1024] {
+            let mut written = vec![];
+            tag.write_padded_to(&mut written, padding).unwrap();
+
+            assert_eq!(written, tag.into_bytes_with_padding(padding));
+            assert_eq!(written.len(), tag.written_size(padding));
+        }
+    }
+
+    #[test]
+    fn padded_and_footer_tags_keep_consistent_sizes() {
+        let tag = modified_tag(4);
+        let padded = tag.into_bytes_with_padding(1024);
+        assert_eq!(crate::integrity::audit_sizes(&padded).unwrap(), []);
+        assert_eq!(
+            parse::parse_tag(&padded).unwrap().into_bytes(),
+            tag.into_bytes()
+        );
+
+        let mut bytes = tag_bytes(4, &[frame(b"TIT2", [0x00, 0x00], b"\x03Title", 4)]);
+        bytes[5] |= HEADER_FOOTER;
+        let footer = [b"3DI".as_slice(), &bytes[3..10]].concat();
+        let mut tag = parse::parse_tag(&[bytes, footer].concat()).unwrap();
+        tag.set_song_title("Another title".to_string()).unwrap();
+
+        let written = assert_sizes_hold(&tag);
+        assert_eq!(&written[written.len() - 10..written.len() - 7], b"3DI");
+        assert_eq!(&written[written.len() - 4..], &written[6..10]);
     }
 }
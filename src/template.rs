@@ -0,0 +1,64 @@
+use crate::fields;
+use crate::tag::Id3v2Tag;
+
+pub(crate) fn tag_value(tag: &Id3v2Tag, name: &str) -> Option<Option<String>> {
+    // The year of the recording time, which may be a full timestamp
+    if name == "year" {
+        return Some(
+            tag.get_text_frame("TDRC")
+                .map(|x| x.chars().take(4).collect()),
+        );
+    }
+
+    fields::find(name).map(|x| tag.get_text_frame(x.frame_id))
+}
+
+/// Expands the placeholders in a value with the tag's fields (`fields::FIELDS` and "year") or the
+/// given file properties. Missing fields expand to nothing, unknown placeholders are an error,
+/// and "{{" and "}}" stand for literal braces.
+pub fn expand(
+    template: &str,
+    tag: &Id3v2Tag,
+    properties: &[(&str, String)],
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let Some((name, rest)) = chars.as_str().split_once('}') else {
+                    return Err(format!("unclosed placeholder in '{}'", template));
+                };
+
+                let value = match properties.iter().find(|(x, _)| *x == name) {
+                    Some((_, x)) => Some(x.clone()),
+                    None => match tag_value(tag, name) {
+                        Some(x) => x,
+                        None => return Err(format!("unknown placeholder {{{}}}", name)),
+                    },
+                };
+
+                result += value.as_deref().unwrap_or("").trim_end_matches('\0');
+                chars = rest.chars();
+            }
+            '}' => {
+                return Err(format!(
+                    "unmatched '}}' in '{}', use '}}}}' for a brace",
+                    template
+                ))
+            }
+            x => result.push(x),
+        }
+    }
+
+    Ok(result)
+}
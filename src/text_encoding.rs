@@ -0,0 +1,153 @@
+#[cfg(feature = "charset")]
+use crate::charset;
+use crate::utility;
+
+/// How text in ISO-8859-1 is decoded: as such, or with the `charset` feature in the legacy code
+/// page its bytes look like (see `charset::detect`), for taggers that wrote e.g. Windows-1251
+/// or Shift_JIS and marked it ISO-8859-1
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LegacyCharset {
+    #[default]
+    Latin1,
+    #[cfg(feature = "charset")]
+    Detect,
+}
+
+impl LegacyCharset {
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            LegacyCharset::Latin1 => utility::decode_text(0x00, bytes),
+            #[cfg(feature = "charset")]
+            LegacyCharset::Detect => match charset::decode(bytes) {
+                Some((text, _)) => text,
+                None => utility::decode_text(0x00, bytes),
+            },
+        }
+    }
+}
+
+/// The encoding `bytes` actually fit, for text marked with `encoding`. Taggers commonly write
+/// UTF-8 marked as ISO-8859-1, and ISO-8859-1 or UTF-8 marked as UTF-16, so those are told apart
+/// by the bytes rather than trusted: UTF-16 has a BOM, or a zero byte for each ASCII character.
+pub fn detect(encoding: u8, bytes: &[u8]) -> u8 {
+    // Terminators aren't part of the text
+    let end = bytes.iter().rposition(|x| *x != 0x00).map_or(0, |x| x + 1);
+    let content = &bytes[..end];
+    let is_utf8 = std::str::from_utf8(content).is_ok();
+    let zeros = content.iter().filter(|x| **x == 0x00).count();
+    let ascii = content.iter().filter(|x| x.is_ascii()).count();
+
+    match encoding {
+        0x01 if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) => 0x01,
+        0x01 | 0x02 if content.is_empty() || zeros * 4 >= content.len() => encoding,
+        0x01 | 0x02 if is_utf8 => 0x03,
+        // Mostly non-ASCII, e.g. CJK text, which leaves no zero bytes in UTF-16
+        0x01 | 0x02 if bytes.len().is_multiple_of(2) && ascii * 2 < content.len() => encoding,
+        0x01 | 0x02 => 0x00,
+        0x00 if is_utf8 && !content.is_ascii() => 0x03,
+        0x00 => 0x00,
+        _ if is_utf8 => 0x03,
+        _ => 0x00,
+    }
+}
+
+/// Decodes text marked with `encoding` using the encoding it was actually written in
+pub fn decode_lenient(encoding: u8, bytes: &[u8], legacy: LegacyCharset) -> String {
+    match detect(encoding, bytes) {
+        0x00 => legacy.decode(bytes),
+        x => utility::decode_text(x, bytes),
+    }
+}
+
+// Text frames, and the other frames sharing their layout (see parse::parse_frame)
+fn is_text_frame(frame_id: &[u8; 4]) -> bool {
+    frame_id[0] == b'T' || matches!(frame_id, b"WFED" | b"MVNM" | b"MVIN")
+}
+
+/// The payload of a frame holding text with its text rewritten as UTF-8, or None when it already
+/// is or the frame holds no text. Covers text frames, TXXX, WXXX (whose URL is always
+/// ISO-8859-1), COMM, USLT and USER.
+pub fn to_utf8(frame_id: &[u8; 4], data: &[u8], legacy: LegacyCharset) -> Option<Vec<u8>> {
+    let (&encoding, rest) = data.split_first()?;
+
+    // COMM, USLT and USER have a language between the encoding and the text
+    let language_size = if matches!(frame_id, b"COMM" | b"USLT" | b"USER") {
+        3
+    } else {
+        0
+    };
+    let language = rest.get(..language_size)?;
+    let rest = &rest[language_size..];
+
+    let actual = detect(encoding, rest);
+    if encoding == 0x03 && actual == 0x03 {
+        return None;
+    }
+
+    let decode = |x: &[u8]| match actual {
+        0x00 => legacy.decode(x),
+        _ => utility::decode_text(actual, x),
+    };
+
+    let text = match frame_id {
+        b"TXXX" | b"WXXX" | b"COMM" | b"USLT" => {
+            let (description, value) = utility::split_terminated(rest, actual)?;
+            let value = if frame_id == b"WXXX" {
+                value.to_vec()
+            } else {
+                decode(value).into_bytes()
+            };
+
+            [decode(description).into_bytes(), vec![0x00], value].concat()
+        }
+        b"USER" => decode(rest).into_bytes(),
+        x if is_text_frame(x) => decode(rest).into_bytes(),
+        _ => return None,
+    };
+
+    Some([&[0x03], language, &text].concat())
+}
+
+// Characters of Windows-1251 bytes 0x80 to 0xBF; 0xC0 to 0xFF are А to я in order
+const CP1251_HIGH: [char; 64] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', //
+    '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ', //
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', //
+    '\u{98}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ', //
+    '\u{A0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', //
+    'Ё', '©', 'Є', '«', '¬', '\u{AD}', '®', 'Ї', //
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', //
+    'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї', //
+];
+
+// Characters of Windows-1252 bytes 0x80 to 0x9F, the rest match ISO-8859-1. Unassigned bytes map
+// to the control character of the same value, as decoders commonly do.
+const CP1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', //
+    'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}', //
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', //
+    '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ', //
+];
+
+/// Decodes Windows-1251, the Cyrillic code page
+pub fn decode_cp1251(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|x| match x {
+            0x00..=0x7F => char::from(*x),
+            0x80..=0xBF => CP1251_HIGH[usize::from(x - 0x80)],
+            _ => char::from_u32(0x0410 + u32::from(x - 0xC0)).unwrap(),
+        })
+        .collect()
+}
+
+/// Encodes text as Windows-1252 (ISO-8859-1 with printable characters in 0x80 to 0x9F), or None
+/// if a character doesn't fit
+pub fn encode_cp1252(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|x| match CP1252_HIGH.iter().position(|c| *c == x) {
+            Some(idx) => Some(0x80 + idx as u8),
+            None => u8::try_from(u32::from(x)).ok(),
+        })
+        .collect()
+}
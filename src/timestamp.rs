@@ -0,0 +1,352 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::utility;
+
+/// ID3v2.4 frames holding a timestamp: recording, release, original release, encoding and
+/// tagging time
+pub const TIMESTAMP_FRAMES: [&str; 5] = ["TDRC", "TDRL", "TDOR", "TDEN", "TDTG"];
+
+/// A timestamp as ID3v2.4 stores it, `yyyy[-MM[-dd[THH[:mm[:ss]]]]]`: only the year is required,
+/// and each part is only present when the one before it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+    pub hour: Option<u8>,
+    pub minute: Option<u8>,
+    pub second: Option<u8>,
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn number(text: &str, digits: usize, name: &str, range: (u8, u8)) -> Result<u8, String> {
+    if text.len() != digits || !text.bytes().all(|x| x.is_ascii_digit()) {
+        return Err(format!(
+            "invalid {} '{}', expected {} digits",
+            name, text, digits
+        ));
+    }
+
+    let value: u8 = text.parse().unwrap();
+    if value < range.0 || value > range.1 {
+        return Err(format!(
+            "{} {} is out of range, expected {} to {}",
+            name, value, range.0, range.1
+        ));
+    }
+
+    Ok(value)
+}
+
+// Days since 1970-01-01 (proleptic Gregorian calendar), the inverse of the civil date in
+// utility::format_timestamp
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+// "3 days ago", or "in 3 days" for a count in the future
+fn relative(count: i64, unit: &str) -> String {
+    let plural = if count.abs() == 1 { "" } else { "s" };
+
+    if count < 0 {
+        format!("in {} {}{}", -count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+impl Timestamp {
+    /// Parses a timestamp, accepting the ID3v2.4 format and the common variations people type:
+    /// "2003", "2003-05", "2003/05/17", "2003-05-17 21:30" or "2003-05-17T21:30:00". Values are
+    /// checked, so "2003-02-30" is an error.
+    pub fn parse(text: &str) -> Result<Timestamp, String> {
+        let text = text.trim().trim_end_matches('\0');
+        let error = |x: String| format!("invalid timestamp '{}': {}", text, x);
+
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time.trim())),
+            None => (text, None),
+        };
+
+        let mut date_parts = date.split(['-', '/']);
+        let year = date_parts.next().unwrap_or_default();
+        if year.len() != 4 || !year.bytes().all(|x| x.is_ascii_digit()) {
+            return Err(error(format!("expected a 4 digit year, found '{}'", year)));
+        }
+        if year == "0000" {
+            return Err(error("year 0000 is out of range".to_string()));
+        }
+
+        let mut timestamp = Timestamp {
+            year: year.parse().unwrap(),
+            month: None,
+            day: None,
+            hour: None,
+            minute: None,
+            second: None,
+        };
+
+        if let Some(x) = date_parts.next() {
+            timestamp.month = Some(number(x, 2, "month", (1, 12)).map_err(error)?);
+        }
+
+        if let Some(x) = date_parts.next() {
+            let max = days_in_month(timestamp.year, timestamp.month.unwrap());
+            timestamp.day = Some(number(x, 2, "day", (1, max)).map_err(error)?);
+        }
+
+        if let Some(x) = date_parts.next() {
+            return Err(error(format!("unexpected '{}' after the day", x)));
+        }
+
+        let Some(time) = time else {
+            return Ok(timestamp);
+        };
+
+        if timestamp.day.is_none() {
+            return Err(error("a time needs a full date before it".to_string()));
+        }
+
+        let mut time_parts = time.split(':');
+        timestamp.hour =
+            Some(number(time_parts.next().unwrap_or_default(), 2, "hour", (0, 23)).map_err(error)?);
+
+        if let Some(x) = time_parts.next() {
+            timestamp.minute = Some(number(x, 2, "minute", (0, 59)).map_err(error)?);
+        }
+
+        if let Some(x) = time_parts.next() {
+            timestamp.second = Some(number(x, 2, "second", (0, 59)).map_err(error)?);
+        }
+
+        if let Some(x) = time_parts.next() {
+            return Err(error(format!("unexpected '{}' after the seconds", x)));
+        }
+
+        Ok(timestamp)
+    }
+
+    /// The current time in UTC
+    pub fn now() -> Timestamp {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+
+        Timestamp::parse(&utility::format_timestamp(seconds)).unwrap()
+    }
+
+    /// How long before (or after) `now` the timestamp is, e.g. "5 years ago" or "yesterday", to
+    /// the precision the timestamp has: "2019" is only compared by year
+    pub fn relative_to(&self, now: &Timestamp) -> String {
+        let (Some(month), Some(now_month)) = (self.month, now.month) else {
+            let years = i64::from(now.year) - i64::from(self.year);
+            return if years == 0 {
+                "this year".to_string()
+            } else {
+                relative(years, "year")
+            };
+        };
+
+        let (Some(day), Some(now_day)) = (self.day, now.day) else {
+            let months = (i64::from(now.year) * 12 + i64::from(now_month))
+                - (i64::from(self.year) * 12 + i64::from(month));
+            return match months {
+                0 => "this month".to_string(),
+                x if x.abs() < 12 => relative(x, "month"),
+                x => relative(x / 12, "year"),
+            };
+        };
+
+        let days =
+            days_from_civil(now.year, now_month, now_day) - days_from_civil(self.year, month, day);
+        match days {
+            0 => "today".to_string(),
+            1 => "yesterday".to_string(),
+            -1 => "tomorrow".to_string(),
+            x if x.abs() < 31 => relative(x, "day"),
+            x if x.abs() < 365 => relative(x / 30, "month"),
+            x => relative(x / 365, "year"),
+        }
+    }
+
+    /// The date, if the timestamp has a day
+    #[cfg(feature = "chrono")]
+    pub fn to_naive_date(&self) -> Option<chrono::NaiveDate> {
+        chrono::NaiveDate::from_ymd_opt(
+            i32::from(self.year),
+            u32::from(self.month?),
+            u32::from(self.day?),
+        )
+    }
+
+    /// The date and time, if the timestamp has an hour; missing minutes and seconds are zero
+    #[cfg(feature = "chrono")]
+    pub fn to_naive_date_time(&self) -> Option<chrono::NaiveDateTime> {
+        self.to_naive_date()?.and_hms_opt(
+            u32::from(self.hour?),
+            u32::from(self.minute.unwrap_or(0)),
+            u32::from(self.second.unwrap_or(0)),
+        )
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDate> for Timestamp {
+    fn from(date: chrono::NaiveDate) -> Timestamp {
+        use chrono::Datelike;
+
+        Timestamp {
+            year: u16::try_from(date.year()).unwrap_or(0),
+            month: Some(date.month() as u8),
+            day: Some(date.day() as u8),
+            hour: None,
+            minute: None,
+            second: None,
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDateTime> for Timestamp {
+    fn from(date_time: chrono::NaiveDateTime) -> Timestamp {
+        use chrono::Timelike;
+
+        Timestamp {
+            hour: Some(date_time.hour() as u8),
+            minute: Some(date_time.minute() as u8),
+            second: Some(date_time.second() as u8),
+            ..Timestamp::from(date_time.date())
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+
+        let parts = [
+            ("-", self.month),
+            ("-", self.day),
+            ("T", self.hour),
+            (":", self.minute),
+            (":", self.second),
+        ];
+        for (separator, value) in parts {
+            let Some(value) = value else {
+                break;
+            };
+            write!(f, "{}{:02}", separator, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A timestamp frame's value for display: the normalized timestamp with how long ago it was, e.g.
+/// "2019-03-02 (5 years ago)", or why the stored text is invalid
+pub fn describe(text: &str, now: &Timestamp) -> String {
+    match Timestamp::parse(text) {
+        Ok(x) => format!("{} ({})", x, x.relative_to(now)),
+        Err(x) => x,
+    }
+}
+
+/// Checks a timestamp given on the command line, returning it in the ID3v2.4 format
+pub fn normalize(text: &str) -> Result<String, String> {
+    Timestamp::parse(text).map(|x| x.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_forms_people_type() {
+        for (text, normalized) in [
+            ("2003", "2003"),
+            ("2003-05", "2003-05"),
+            ("2003/05/17", "2003-05-17"),
+            ("2003-05-17 21:30", "2003-05-17T21:30"),
+            ("2003-05-17T21:30:00", "2003-05-17T21:30:00"),
+            (" 2003-05-17T21\0\0", "2003-05-17T21"),
+            ("2004-02-29", "2004-02-29"),
+            ("2000-02-29", "2000-02-29"),
+        ] {
+            assert_eq!(normalize(text).as_deref(), Ok(normalized), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn refuses_malformed_timestamps() {
+        for (text, error) in [
+            ("", "expected a 4 digit year, found ''"),
+            ("03", "expected a 4 digit year, found '03'"),
+            ("２００３", "expected a 4 digit year"),
+            ("0000", "year 0000 is out of range"),
+            ("2003-5", "invalid month '5', expected 2 digits"),
+            ("2003-13", "month 13 is out of range, expected 1 to 12"),
+            ("2003-00", "month 0 is out of range"),
+            ("2003-02-29", "day 29 is out of range, expected 1 to 28"),
+            ("1900-02-29", "day 29 is out of range"),
+            ("2003-04-31", "day 31 is out of range, expected 1 to 30"),
+            ("2003-05-17-01", "unexpected '01' after the day"),
+            ("2003-05 21:30", "a time needs a full date before it"),
+            ("2003-05-17T", "invalid hour '', expected 2 digits"),
+            ("2003-05-17T24", "hour 24 is out of range, expected 0 to 23"),
+            ("2003-05-17T21:60", "minute 60 is out of range"),
+            (
+                "2003-05-17T21:30:5",
+                "invalid second '5', expected 2 digits",
+            ),
+            (
+                "2003-05-17T21:30:00:00",
+                "unexpected '00' after the seconds",
+            ),
+            ("2003-05-17T21:30:00Z", "invalid second '00Z'"),
+        ] {
+            let result = Timestamp::parse(text).unwrap_err();
+            assert!(result.contains(error), "{:?}: {}", text, result);
+        }
+    }
+
+    #[test]
+    fn describes_timestamps_relative_to_now() {
+        let now = Timestamp::parse("2024-03-01T12:00").unwrap();
+
+        for (text, description) in [
+            ("2019", "2019 (5 years ago)"),
+            ("2024", "2024 (this year)"),
+            ("2023-12", "2023-12 (3 months ago)"),
+            ("2024-02-29", "2024-02-29 (yesterday)"),
+            ("2024-03-02", "2024-03-02 (tomorrow)"),
+            ("2024-01-01", "2024-01-01 (2 months ago)"),
+            ("2026-03-01", "2026-03-01 (in 2 years)"),
+        ] {
+            assert_eq!(describe(text, &now), description);
+        }
+        assert!(describe("2024-02-30", &now).starts_with("invalid timestamp '2024-02-30'"));
+    }
+}
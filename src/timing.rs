@@ -0,0 +1,324 @@
+use crate::{
+    chapter,
+    json::{self, JsonValue},
+    utility,
+};
+
+/// Unit of the timestamps in ETCO and SYTC frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    MpegFrames,
+    Milliseconds,
+}
+
+impl TimeFormat {
+    fn from_byte(byte: u8) -> Result<TimeFormat, String> {
+        match byte {
+            0x01 => Ok(TimeFormat::MpegFrames),
+            0x02 => Ok(TimeFormat::Milliseconds),
+            x => Err(format!("unknown time stamp format {:#04X?}", x)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            TimeFormat::MpegFrames => 0x01,
+            TimeFormat::Milliseconds => 0x02,
+        }
+    }
+
+    fn format_time(self, time: u32) -> String {
+        match self {
+            TimeFormat::MpegFrames => format!("frame {}", time),
+            TimeFormat::Milliseconds => utility::format_milliseconds(time),
+        }
+    }
+}
+
+// Event types 0x00-0x16 in the order the ID3v2.4 specification lists them
+const EVENT_NAMES: [&str; 23] = [
+    "padding",
+    "end_of_initial_silence",
+    "intro_start",
+    "main_part_start",
+    "outro_start",
+    "outro_end",
+    "verse_start",
+    "refrain_start",
+    "interlude_start",
+    "theme_start",
+    "variation_start",
+    "key_change",
+    "time_change",
+    "momentary_unwanted_noise",
+    "sustained_noise",
+    "sustained_noise_end",
+    "intro_end",
+    "main_part_end",
+    "verse_end",
+    "refrain_end",
+    "theme_end",
+    "profanity",
+    "profanity_end",
+];
+
+pub fn event_name(event_type: u8) -> String {
+    match event_type {
+        x if usize::from(x) < EVENT_NAMES.len() => EVENT_NAMES[usize::from(x)].to_string(),
+        x @ 0xE0..=0xEF => format!("sync_{:x}", x - 0xE0),
+        0xFD => "audio_end".to_string(),
+        0xFE => "audio_file_end".to_string(),
+        x => format!("reserved_{:#04x}", x),
+    }
+}
+
+fn event_type(name: &str) -> Option<u8> {
+    if let Some(x) = EVENT_NAMES.iter().position(|x| *x == name) {
+        return u8::try_from(x).ok();
+    }
+
+    match name {
+        "audio_end" => Some(0xFD),
+        "audio_file_end" => Some(0xFE),
+        x => match x.strip_prefix("sync_") {
+            Some(x) => u8::from_str_radix(x, 16)
+                .ok()
+                .filter(|x| *x <= 0x0F)
+                .map(|x| x + 0xE0),
+            None => None,
+        },
+    }
+}
+
+fn read_time(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Contents of an ETCO frame: events such as "intro_start" at points in the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventTimingCodes {
+    pub time_format: TimeFormat,
+    // (event type, time)
+    pub events: Vec<(u8, u32)>,
+}
+
+impl EventTimingCodes {
+    pub fn parse(data: &[u8]) -> Result<EventTimingCodes, String> {
+        let Some(format_byte) = data.first() else {
+            return Err("event timing codes are empty".to_string());
+        };
+
+        let time_format = TimeFormat::from_byte(*format_byte)?;
+        let events = data[1..]
+            .chunks(5)
+            .map(|x| match x.len() {
+                5 => Ok((x[0], read_time(&x[1..]))),
+                _ => Err("event timing codes are truncated".to_string()),
+            })
+            .collect::<Result<Vec<(u8, u32)>, String>>()?;
+
+        Ok(EventTimingCodes {
+            time_format,
+            events,
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        let mut data = vec![self.time_format.to_byte()];
+
+        for (event_type, time) in &self.events {
+            data.push(*event_type);
+            data.extend_from_slice(&time.to_be_bytes());
+        }
+
+        data
+    }
+
+    pub fn describe(&self) -> String {
+        self.events
+            .iter()
+            .map(|(event_type, time)| {
+                format!(
+                    "{} at {}",
+                    event_name(*event_type),
+                    self.time_format.format_time(*time)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Contents of a SYTC frame: the tempo in beats per minute from each point in the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoCodes {
+    pub time_format: TimeFormat,
+    // (beats per minute, time), 0 is beat-free and 1 a single beat followed by beat-free audio
+    pub tempos: Vec<(u16, u32)>,
+}
+
+impl TempoCodes {
+    pub fn parse(data: &[u8]) -> Result<TempoCodes, String> {
+        let Some(format_byte) = data.first() else {
+            return Err("tempo codes are empty".to_string());
+        };
+
+        let time_format = TimeFormat::from_byte(*format_byte)?;
+        let mut tempos = vec![];
+        let mut idx = 1;
+
+        while idx < data.len() {
+            // 0xFF means the tempo continues into the next byte, for tempos of 255 and above
+            let mut bpm = u16::from(data[idx]);
+            idx += 1;
+            if bpm == 0xFF {
+                let Some(x) = data.get(idx) else {
+                    return Err("tempo codes are truncated".to_string());
+                };
+                bpm += u16::from(*x);
+                idx += 1;
+            }
+
+            let Some(time) = data.get(idx..idx + 4) else {
+                return Err("tempo codes are truncated".to_string());
+            };
+            tempos.push((bpm, read_time(time)));
+            idx += 4;
+        }
+
+        Ok(TempoCodes {
+            time_format,
+            tempos,
+        })
+    }
+
+    pub fn into_frame_data(&self) -> Vec<u8> {
+        let mut data = vec![self.time_format.to_byte()];
+
+        for (bpm, time) in &self.tempos {
+            if *bpm >= 0xFF {
+                data.push(0xFF);
+                data.push(u8::try_from(bpm - 0xFF).unwrap_or(u8::MAX));
+            } else {
+                data.push(u8::try_from(*bpm).unwrap());
+            }
+            data.extend_from_slice(&time.to_be_bytes());
+        }
+
+        data
+    }
+
+    pub fn describe(&self) -> String {
+        self.tempos
+            .iter()
+            .map(|(bpm, time)| {
+                let tempo = match bpm {
+                    0 => "beat-free".to_string(),
+                    1 => "single beat".to_string(),
+                    x => format!("{} BPM", x),
+                };
+                format!("{} from {}", tempo, self.time_format.format_time(*time))
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+fn json_time(value: Option<&JsonValue>, time_format: TimeFormat) -> Result<u32, String> {
+    match (value, time_format) {
+        (Some(JsonValue::Number(x)), _) if *x >= 0.0 => Ok(*x as u32),
+        (Some(JsonValue::String(x)), TimeFormat::Milliseconds) => chapter::parse_time(x),
+        (Some(_), TimeFormat::MpegFrames) => Err("'time' must be a frame number".to_string()),
+        _ => Err("'time' must be milliseconds or a [h:]mm:ss[.mmm] time".to_string()),
+    }
+}
+
+/// Reads event and tempo codes from a JSON description:
+/// `{"time_format": "ms", "events": [{"type": "intro_start", "time": "0:01.5"}],
+/// "tempo": [{"bpm": 128, "time": 0}]}`. Either list may be omitted, and times are in
+/// milliseconds ("ms", the default) or MPEG frames ("frames").
+pub fn parse_timing_json(
+    text: &str,
+) -> Result<(Option<EventTimingCodes>, Option<TempoCodes>), String> {
+    let description = json::parse(text)?;
+
+    let time_format = match description.get("time_format").and_then(JsonValue::as_str) {
+        None | Some("ms") => TimeFormat::Milliseconds,
+        Some("frames") => TimeFormat::MpegFrames,
+        Some(x) => {
+            return Err(format!(
+                "unknown time_format '{}', expected \"ms\" or \"frames\"",
+                x
+            ))
+        }
+    };
+
+    let list = |key: &str| match description.get(key) {
+        None => Ok(None),
+        Some(x) => match x.as_array() {
+            Some(x) => Ok(Some(x)),
+            None => Err(format!("'{}' must be an array", key)),
+        },
+    };
+
+    let events = match list("events")? {
+        Some(values) => {
+            let mut events = vec![];
+
+            for (idx, value) in values.iter().enumerate() {
+                let error = |x: String| format!("event {}: {}", idx + 1, x);
+
+                let event_type = match value.get("type") {
+                    Some(JsonValue::Number(x)) => u8::try_from(*x as u32).ok(),
+                    Some(JsonValue::String(x)) => event_type(x),
+                    _ => None,
+                }
+                .ok_or_else(|| error("'type' must be an event name or number".to_string()))?;
+
+                events.push((
+                    event_type,
+                    json_time(value.get("time"), time_format).map_err(error)?,
+                ));
+            }
+
+            // Events must be in chronological order
+            events.sort_by_key(|(_, time)| *time);
+
+            Some(EventTimingCodes {
+                time_format,
+                events,
+            })
+        }
+        None => None,
+    };
+
+    let tempos = match list("tempo")? {
+        Some(values) => {
+            let mut tempos = vec![];
+
+            for (idx, value) in values.iter().enumerate() {
+                let error = |x: String| format!("tempo {}: {}", idx + 1, x);
+
+                let bpm = match value.get("bpm").and_then(JsonValue::as_f64) {
+                    Some(x) if (0.0..=510.0).contains(&x) => x.round() as u16,
+                    _ => return Err(error("'bpm' must be between 0 and 510".to_string())),
+                };
+
+                tempos.push((
+                    bpm,
+                    json_time(value.get("time"), time_format).map_err(error)?,
+                ));
+            }
+
+            tempos.sort_by_key(|(_, time)| *time);
+
+            Some(TempoCodes {
+                time_format,
+                tempos,
+            })
+        }
+        None => None,
+    };
+
+    Ok((events, tempos))
+}
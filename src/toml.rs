@@ -0,0 +1,368 @@
+use crate::json::JsonValue;
+
+// Arrays nested deeper than this are refused, as each level recurses
+const MAX_DEPTH: usize = 128;
+
+/// Minimal TOML reader for configuration files: `[table]` headers and `key = value` lines, with
+/// strings, integers, floats, booleans and arrays (which may span lines). The document is
+/// returned as a JSON object, with each table as an object in it.
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        idx: 0,
+        line: 1,
+        depth: 0,
+    };
+
+    let mut root: Vec<(String, JsonValue)> = vec![];
+    let mut table: Option<String> = None;
+
+    loop {
+        parser.skip_blank();
+        if parser.idx == parser.bytes.len() {
+            break;
+        }
+
+        if parser.bytes[parser.idx] == b'[' {
+            parser.idx += 1;
+            parser.skip_space();
+            let name = parser.parse_key()?;
+            parser.skip_space();
+            parser.expect(b']')?;
+            parser.end_of_line()?;
+
+            if root.iter().any(|(key, _)| *key == name) {
+                return Err(parser.error(&format!("'{}' is defined twice", name)));
+            }
+            root.push((name.clone(), JsonValue::Object(vec![])));
+            table = Some(name);
+            continue;
+        }
+
+        let key = parser.parse_key()?;
+        parser.skip_space();
+        parser.expect(b'=')?;
+        parser.skip_space();
+        let value = parser.parse_value()?;
+        parser.end_of_line()?;
+
+        let entries = match &table {
+            Some(name) => match root.iter_mut().find(|(x, _)| x == name) {
+                Some((_, JsonValue::Object(x))) => x,
+                _ => unreachable!(),
+            },
+            None => &mut root,
+        };
+        if entries.iter().any(|(x, _)| *x == key) {
+            return Err(parser.error(&format!("'{}' is defined twice", key)));
+        }
+        entries.push((key, value));
+    }
+
+    Ok(JsonValue::Object(root))
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    idx: usize,
+    line: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid TOML on line {}: {}", self.line, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.idx).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.idx += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.idx += 1;
+        }
+    }
+
+    // Spaces, line breaks and comments, which may appear between lines and inside arrays
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r') => self.idx += 1,
+                Some(b'\n') => {
+                    self.idx += 1;
+                    self.line += 1;
+                }
+                Some(b'#') => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.idx += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_space();
+        if self.peek() == Some(b'#') {
+            while !matches!(self.peek(), None | Some(b'\n')) {
+                self.idx += 1;
+            }
+        }
+        if self.peek() == Some(b'\r') {
+            self.idx += 1;
+        }
+
+        match self.peek() {
+            None => Ok(()),
+            Some(b'\n') => {
+                self.idx += 1;
+                self.line += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<String, String> {
+        if self.peek() == Some(b'"') {
+            return self.parse_string();
+        }
+
+        let start = self.idx;
+        while self
+            .peek()
+            .is_some_and(|x| x.is_ascii_alphanumeric() || x == b'_' || x == b'-')
+        {
+            self.idx += 1;
+        }
+
+        if self.idx == start {
+            return Err(self.error("expected a key"));
+        }
+
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.idx]).to_string())
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b'\'') => self.parse_literal_string().map(JsonValue::String),
+            Some(b'[') if self.depth == MAX_DEPTH => Err(self.error(&format!(
+                "arrays nested more than {} levels deep",
+                MAX_DEPTH
+            ))),
+            Some(b'[') => {
+                self.depth += 1;
+                let value = self.parse_array();
+                self.depth -= 1;
+                value
+            }
+            Some(b't') if self.bytes[self.idx..].starts_with(b"true") => {
+                self.idx += 4;
+                Ok(JsonValue::Bool(true))
+            }
+            Some(b'f') if self.bytes[self.idx..].starts_with(b"false") => {
+                self.idx += 5;
+                Ok(JsonValue::Bool(false))
+            }
+            Some(x) if x.is_ascii_digit() || x == b'-' || x == b'+' => self.parse_number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.idx;
+        while self
+            .peek()
+            .is_some_and(|x| x.is_ascii_alphanumeric() || matches!(x, b'+' | b'-' | b'.' | b'_'))
+        {
+            self.idx += 1;
+        }
+
+        // Underscores may separate digits, e.g. 1_048_576
+        let text = String::from_utf8_lossy(&self.bytes[start..self.idx]).replace('_', "");
+        match text.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(JsonValue::Number(x)),
+            _ => Err(self.error(&format!("invalid number '{}'", text))),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut result: Vec<u8> = vec![];
+
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.idx += 1;
+
+            match byte {
+                b'"' => break,
+                b'\n' => return Err(self.error("unterminated string")),
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("unterminated escape"));
+                    };
+                    self.idx += 1;
+
+                    result.push(match escape {
+                        b'"' => b'"',
+                        b'\\' => b'\\',
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                x => result.push(x),
+            }
+        }
+
+        String::from_utf8(result).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    // Single-quoted strings have no escapes
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        self.expect(b'\'')?;
+        let start = self.idx;
+
+        while !matches!(self.peek(), None | Some(b'\'' | b'\n')) {
+            self.idx += 1;
+        }
+        let text = String::from_utf8(self.bytes[start..self.idx].to_vec())
+            .map_err(|_| self.error("invalid UTF-8 in string"))?;
+        self.expect(b'\'')
+            .map_err(|_| self.error("unterminated string"))?;
+
+        Ok(text)
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'[')?;
+        let mut values = vec![];
+
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(b']') {
+                self.idx += 1;
+                return Ok(JsonValue::Array(values));
+            }
+
+            values.push(self.parse_value()?);
+
+            // A trailing comma before the closing bracket is allowed
+            self.skip_blank();
+            match self.peek() {
+                Some(b',') => self.idx += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(x: &str) -> JsonValue {
+        JsonValue::String(x.to_string())
+    }
+
+    #[test]
+    fn parses_tables_and_values() {
+        let document = parse(
+            "# Defaults\r\n\
+             name = \"a \\\"b\\\"\\tc\" # a comment\r\n\
+             \"quoted key\" = 'C:\\path'\n\
+             \n\
+             [limits]\n\
+             size = 1_048_576\n\
+             ratio = -0.5\n\
+             strict = false\n\
+             kinds = [\n  \"a\", # first\n  'b',\n]\n\
+             nested = [[1, 2], []]",
+        )
+        .unwrap();
+
+        assert_eq!(
+            document,
+            JsonValue::Object(vec![
+                ("name".to_string(), string("a \"b\"\tc")),
+                ("quoted key".to_string(), string("C:\\path")),
+                (
+                    "limits".to_string(),
+                    JsonValue::Object(vec![
+                        ("size".to_string(), JsonValue::Number(1048576.0)),
+                        ("ratio".to_string(), JsonValue::Number(-0.5)),
+                        ("strict".to_string(), JsonValue::Bool(false)),
+                        (
+                            "kinds".to_string(),
+                            JsonValue::Array(vec![string("a"), string("b")])
+                        ),
+                        (
+                            "nested".to_string(),
+                            JsonValue::Array(vec![
+                                JsonValue::Array(vec![
+                                    JsonValue::Number(1.0),
+                                    JsonValue::Number(2.0)
+                                ]),
+                                JsonValue::Array(vec![]),
+                            ])
+                        ),
+                    ])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_documents() {
+        for (text, error) in [
+            ("key", "line 1: expected '='"),
+            ("= 1", "line 1: expected a key"),
+            ("key =", "line 1: expected a value"),
+            ("key = tru", "line 1: expected a value"),
+            ("key = 1 2", "line 1: expected the end of the line"),
+            ("key = 1.2.3", "line 1: invalid number '1.2.3'"),
+            ("key = +inf", "line 1: invalid number '+inf'"),
+            ("key = \"open", "line 1: unterminated string"),
+            ("key = \"open\n\"", "line 1: unterminated string"),
+            ("key = 'open", "line 1: unterminated string"),
+            ("key = \"\\q\"", "line 1: invalid escape"),
+            ("key = \"\\", "line 1: unterminated escape"),
+            ("key = [1 2]", "line 1: expected ',' or ']'"),
+            ("key = [1,", "line 1: expected a value"),
+            ("key = [\n1,\n2", "line 3: expected ',' or ']'"),
+            ("[table", "line 1: expected ']'"),
+            ("[table] key = 1", "line 1: expected the end of the line"),
+            ("a = 1\n\n# comment\nb = ", "line 4: expected a value"),
+            ("a = 1\na = 2", "'a' is defined twice"),
+            ("[t]\na = 1\n[t]", "'t' is defined twice"),
+        ] {
+            let result = parse(text).unwrap_err();
+            assert!(result.ends_with(error), "{:?}: {}", text, result);
+        }
+    }
+
+    #[test]
+    fn refuses_deeply_nested_arrays() {
+        let nested = |depth| "key = ".to_string() + &"[".repeat(depth) + &"]".repeat(depth);
+
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(parse(&("key = ".to_string() + &"[".repeat(100_000))).is_err());
+    }
+}
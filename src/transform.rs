@@ -0,0 +1,303 @@
+use crate::{text_encoding, utility};
+
+// Named entities seen in scraped metadata, numeric ones are decoded separately
+const HTML_ENTITIES: [(&str, &str); 32] = [
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", "\u{A0}"),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("sbquo", "‚"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("bdquo", "„"),
+    ("hellip", "…"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("copy", "©"),
+    ("reg", "®"),
+    ("trade", "™"),
+    ("deg", "°"),
+    ("middot", "·"),
+    ("times", "×"),
+    ("eacute", "é"),
+    ("egrave", "è"),
+    ("aacute", "á"),
+    ("oacute", "ó"),
+    ("iacute", "í"),
+    ("uacute", "ú"),
+    ("ntilde", "ñ"),
+    ("ouml", "ö"),
+    ("uuml", "ü"),
+];
+
+// Longest entity body decoded, e.g. "#x1F600"
+const MAX_ENTITY_LENGTH: usize = 10;
+
+fn decode_entity(entity: &str) -> Option<String> {
+    let code = if let Some(x) = entity
+        .strip_prefix("#x")
+        .or_else(|| entity.strip_prefix("#X"))
+    {
+        u32::from_str_radix(x, 16).ok()
+    } else if let Some(x) = entity.strip_prefix('#') {
+        x.parse().ok()
+    } else {
+        return HTML_ENTITIES
+            .iter()
+            .find(|(name, _)| *name == entity)
+            .map(|(_, x)| x.to_string());
+    };
+
+    char::from_u32(code?).map(String::from)
+}
+
+/// Decodes HTML entities such as `&amp;`, `&#39;` and `&#x2019;`, leaving anything that isn't a
+/// known entity as it is. Text is decoded once, so "&amp;amp;" becomes "&amp;".
+pub fn decode_html_entities(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        result += &rest[..start];
+        rest = &rest[start..];
+
+        let decoded = rest
+            .char_indices()
+            .take(MAX_ENTITY_LENGTH + 2)
+            .find(|(_, x)| *x == ';')
+            .and_then(|(end, _)| Some((end, decode_entity(&rest[1..end])?)));
+
+        match decoded {
+            Some((end, x)) => {
+                result += &x;
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result + rest
+}
+
+/// Replaces typographic quotes, dashes, ellipses and non-breaking spaces with plain ASCII
+pub fn normalize_punctuation(text: &str) -> String {
+    let mut result = String::new();
+
+    for c in text.chars() {
+        match c {
+            '‘' | '’' | '‚' | '‛' | '′' => result.push('\''),
+            '“' | '”' | '„' | '‟' | '″' => result.push('"'),
+            '‐' | '‑' | '‒' | '–' | '—' | '―' => result.push('-'),
+            '…' => result += "...",
+            '\u{A0}' | '\u{202F}' => result.push(' '),
+            x => result.push(x),
+        }
+    }
+
+    result
+}
+
+/// Cleans up text from web pages and APIs: HTML entities are decoded and typographic
+/// punctuation is normalized
+pub fn clean_text(text: &str) -> String {
+    normalize_punctuation(&decode_html_entities(text))
+}
+
+/// Repairs text that went through the wrong encoding, when the repair is unambiguous:
+///
+/// - UTF-8 read as ISO-8859-1 or Windows-1252, e.g. "CafÃ©" for "Café", which is repaired when
+///   re-encoding the characters gives valid UTF-8 (twice, for text double-encoded twice)
+/// - Windows-1251 (Cyrillic) read as ISO-8859-1, e.g. "Êèíî" for "Кино", which is repaired when
+///   most of the letters are accented ones from the upper half of ISO-8859-1 rather than ASCII
+///
+/// Anything else is returned as it is.
+pub fn fix_mojibake(text: &str) -> String {
+    let mut text = text.to_string();
+
+    for _ in 0..2 {
+        match text_encoding::encode_cp1252(&text).map(String::from_utf8) {
+            Some(Ok(x)) if x != text => text = x,
+            _ => break,
+        }
+    }
+
+    let upper_half = text
+        .chars()
+        .filter(|x| ('\u{C0}'..='\u{FF}').contains(x))
+        .count();
+    let ascii_letters = text.chars().filter(char::is_ascii_alphabetic).count();
+    if upper_half >= 3 && upper_half > ascii_letters {
+        if let Some(bytes) = text
+            .chars()
+            .map(|x| u8::try_from(u32::from(x)).ok())
+            .collect::<Option<Vec<u8>>>()
+        {
+            return text_encoding::decode_cp1251(&bytes);
+        }
+    }
+
+    text
+}
+
+// Where a credit of several artists continues after the first, matched case-insensitively
+const ARTIST_SEPARATORS: [&str; 9] = [
+    " feat. ",
+    " feat ",
+    " ft. ",
+    " featuring ",
+    " & ",
+    " x ",
+    " vs. ",
+    ", ",
+    "; ",
+];
+
+/// The first artist of a credit, e.g. "Daft Punk" for "Daft Punk feat. Pharrell Williams" and
+/// "Calvin Harris & Dua Lipa", or the first value of a frame holding several. A bare "/" doesn't
+/// separate artists, so "AC/DC" is kept whole.
+pub fn first_artist(text: &str) -> String {
+    let first = text.split('\0').next().unwrap_or_default();
+    let lower = first.to_lowercase();
+
+    // Lowercasing can change byte lengths outside ASCII, so offsets are only trusted on ASCII
+    let end = ARTIST_SEPARATORS
+        .iter()
+        .filter_map(|x| lower.find(x))
+        .filter(|_| lower.len() == first.len())
+        .min()
+        .unwrap_or(first.len());
+
+    first[..end].trim().to_string()
+}
+
+/// A change to a value copied from one frame to another with `--copy-frame`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyTransform {
+    /// Copied as it is
+    None,
+    /// Only the first artist, see `first_artist`
+    FirstArtistOnly,
+    /// Only the first value of a frame holding several
+    FirstValue,
+    /// Cleaned up like `--clean-text`, see `clean_text`
+    Clean,
+}
+
+impl CopyTransform {
+    pub fn parse(text: &str) -> Result<CopyTransform, String> {
+        match text {
+            "first-artist-only" => Ok(CopyTransform::FirstArtistOnly),
+            "first-value" => Ok(CopyTransform::FirstValue),
+            "clean" => Ok(CopyTransform::Clean),
+            _ => Err(format!(
+                "unknown transform '{}', expected first-artist-only, first-value or clean",
+                text
+            )),
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            CopyTransform::None => text.to_string(),
+            CopyTransform::FirstArtistOnly => first_artist(text),
+            CopyTransform::FirstValue => text.split('\0').next().unwrap_or_default().to_string(),
+            CopyTransform::Clean => clean_text(text),
+        }
+    }
+}
+
+/// A copy of one text frame's value into another, given as "FROM:TO" or "FROM:TO:TRANSFORM",
+/// e.g. "TPE1:TPE2:first-artist-only"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameCopy {
+    pub from: [u8; 4],
+    pub to: [u8; 4],
+    pub transform: CopyTransform,
+}
+
+impl FrameCopy {
+    pub fn parse(text: &str) -> Result<FrameCopy, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(format!(
+                "invalid copy '{}', expected FROM:TO or FROM:TO:TRANSFORM",
+                text
+            ));
+        }
+
+        Ok(FrameCopy {
+            from: utility::parse_frame_id(parts[0])?,
+            to: utility::parse_frame_id(parts[1])?,
+            transform: match parts.get(2) {
+                Some(x) => CopyTransform::parse(x)?,
+                None => CopyTransform::None,
+            },
+        })
+    }
+}
+
+/// What happens to a text value longer than a `TextLimit`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TruncationPolicy {
+    /// Cut at the limit
+    Truncate,
+    /// Cut one character short of the limit and end with "…"
+    Ellipsis,
+    /// Refuse the value
+    Error,
+}
+
+impl TruncationPolicy {
+    pub fn parse(text: &str) -> Result<TruncationPolicy, String> {
+        match text {
+            "truncate" => Ok(TruncationPolicy::Truncate),
+            "ellipsis" => Ok(TruncationPolicy::Ellipsis),
+            "error" => Ok(TruncationPolicy::Error),
+            _ => Err(format!(
+                "unknown policy '{}', expected truncate, ellipsis or error",
+                text
+            )),
+        }
+    }
+}
+
+/// Longest text value, in characters, a tag's text frames may hold; some devices crash on
+/// longer ones. Frames holding several values are limited value by value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLimit {
+    pub max_chars: usize,
+    pub policy: TruncationPolicy,
+}
+
+impl TextLimit {
+    /// The value of `frame_id` cut to the limit, or an error with `TruncationPolicy::Error`
+    pub fn apply(&self, frame_id: &str, value: &str) -> Result<String, String> {
+        let length = value.chars().count();
+        if length <= self.max_chars {
+            return Ok(value.to_string());
+        }
+
+        match self.policy {
+            TruncationPolicy::Truncate => Ok(value.chars().take(self.max_chars).collect()),
+            TruncationPolicy::Ellipsis if self.max_chars > 0 => Ok(value
+                .chars()
+                .take(self.max_chars - 1)
+                .chain(['…'])
+                .collect()),
+            TruncationPolicy::Ellipsis => Ok(String::new()),
+            TruncationPolicy::Error => Err(format!(
+                "{} is {} characters long, over the limit of {}",
+                frame_id, length, self.max_chars
+            )),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::fields;
+
 pub fn convert_safesynch_to_u32(byte0: u8, byte1: u8, byte2: u8, byte3: u8) -> u32 {
     u32::from(byte0) << 21 | u32::from(byte1) << 14 | u32::from(byte2) << 7 | u32::from(byte3)
 }
@@ -29,6 +31,22 @@ pub fn convert_u64_to_safesynch(value: u64) -> [u8; 5] {
     [byte0, byte1, byte2, byte3, byte4]
 }
 
+/// Reverses unsynchronisation, which follows every 0xFF byte that could be mistaken for the start
+/// of an MPEG frame (or is followed by 0x00) with an inserted 0x00
+pub fn resynchronise(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut after_ff = false;
+
+    for &x in bytes {
+        if !(after_ff && x == 0x00) {
+            result.push(x);
+        }
+        after_ff = x == 0xFF;
+    }
+
+    result
+}
+
 pub fn get_field_name(identifier: [u8; 4]) -> String {
     let binding = String::from_utf8(identifier.to_vec()).unwrap();
     let ascii_id = binding.as_str();
@@ -38,7 +56,9 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
         "APIC" => "Attached picture".to_string(),
         "ASPI" => "Audio seek point index".to_string(),
         "COMM" => "Comments".to_string(),
+        "CHAP" => "Chapter".to_string(),
         "COMR" => "Commercial frame".to_string(),
+        "CTOC" => "Table of contents".to_string(),
 
         "ENCR" => "Encryption method registration".to_string(),
         "EQU2" => "Equalisation (2)".to_string(),
@@ -51,12 +71,15 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
 
         "MCDI" => "Music CD identifier".to_string(),
         "MLLT" => "MPEG location lookup table".to_string(),
+        "MVIN" => "Movement number/count (iTunes)".to_string(),
+        "MVNM" => "Movement name (iTunes)".to_string(),
 
         "OWNE" => "Ownership frame".to_string(),
 
         "PRIV" => "Private frame".to_string(),
         "PCNT" => "Play counter".to_string(),
         "POPM" => "Popularimeter".to_string(),
+        "PCST" => "Podcast flag (iTunes)".to_string(),
         "POSS" => "Position synchronisation frame".to_string(),
 
         "RBUF" => "Recommended buffer size".to_string(),
@@ -72,8 +95,10 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
         "TBPM" => "BPM (beats per minute)".to_string(),
         "TCOM" => "Composer".to_string(),
         "TCON" => "Content type".to_string(),
+        "TCAT" => "Podcast category (iTunes)".to_string(),
         "TCOP" => "Copyright message".to_string(),
         "TDEN" => "Encoding time".to_string(),
+        "TDES" => "Podcast description (iTunes)".to_string(),
         "TDLY" => "Playlist delay".to_string(),
         "TDOR" => "Original release time".to_string(),
         "TDRC" => "Recording time".to_string(),
@@ -82,11 +107,13 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
         "TENC" => "Encoded by".to_string(),
         "TEXT" => "Lyricist/Text writer".to_string(),
         "TFLT" => "File type".to_string(),
+        "TGID" => "Podcast identifier (iTunes)".to_string(),
         "TIPL" => "Involved people list".to_string(),
         "TIT1" => "Content group description".to_string(),
         "TIT2" => "Title/songname/content description".to_string(),
         "TIT3" => "Subtitle/Description refinement".to_string(),
         "TKEY" => "Initial key".to_string(),
+        "TKWD" => "Podcast keywords (iTunes)".to_string(),
         "TLAN" => "Language(s)".to_string(),
         "TLEN" => "Length".to_string(),
         "TMCL" => "Musician credits list".to_string(),
@@ -120,6 +147,7 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
 
         "WCOM" => "Commercial information".to_string(),
         "WCOP" => "Copyright/Legal information".to_string(),
+        "WFED" => "Podcast feed URL (iTunes)".to_string(),
         "WOAF" => "Official audio file webpage".to_string(),
         "WOAR" => "Official artist/performer webpage".to_string(),
         "WOAS" => "Official audio source webpage".to_string(),
@@ -129,4 +157,270 @@ pub fn get_field_name(identifier: [u8; 4]) -> String {
         // "WXXX" => "User defined URL link frame".to_string(),
         _ => "Unknown frame".to_string(),
     }
-}
\ No newline at end of file
+}
+
+/// Frame identifier from text such as "TIT2" (four uppercase letters or digits), or from the
+/// name of a field such as "title" (see `fields::FIELDS`)
+pub fn parse_frame_id(text: &str) -> Result<[u8; 4], String> {
+    let text = text.trim();
+
+    match text.as_bytes().first_chunk::<4>() {
+        Some(x)
+            if text.len() == 4
+                && x.iter()
+                    .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit()) =>
+        {
+            Ok(*x)
+        }
+        _ => fields::frame_id(text)
+            .ok_or_else(|| format!("invalid frame identifier or field name '{}'", text)),
+    }
+}
+
+/// Frame identifier and text from "FRAME=TEXT", e.g. "TIT2=(Remastered)"
+pub fn parse_frame_text(text: &str) -> Result<([u8; 4], String), String> {
+    match text.split_once('=') {
+        Some((frame_id, value)) if !value.is_empty() => {
+            Ok((parse_frame_id(frame_id)?, value.to_string()))
+        }
+        _ => Err(format!("invalid '{}', expected FRAME=TEXT", text)),
+    }
+}
+
+/// Size from text such as "300k" or "2M", where k and M are multiples of 1024 bytes
+pub fn parse_byte_size(text: &str) -> Result<usize, String> {
+    let lower = text.trim().to_ascii_lowercase();
+    let number = lower.trim_end_matches(['b', 'k', 'm']);
+    let multiplier = match &lower[number.len()..] {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        _ => return Err(format!("invalid size '{}', expected e.g. 300k or 2M", text)),
+    };
+
+    match number.trim().parse::<usize>() {
+        Ok(x) => Ok(x * multiplier),
+        Err(_) => Err(format!("invalid size '{}', expected e.g. 300k or 2M", text)),
+    }
+}
+
+/// Whether `text` matches a pattern where "*" stands for any run of characters and "?" for any
+/// one, ignoring case, e.g. "MUSICBRAINZ_*"
+pub fn matches_glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Where the last "*" was and the text it matched up to, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(x) if *x == '?' || *x == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+/// Splits off a string terminated according to its ID3 text encoding, returning it and the rest
+pub fn split_terminated(bytes: &[u8], encoding: u8) -> Option<(&[u8], &[u8])> {
+    match encoding {
+        0x01 | 0x02 => {
+            let end = bytes.chunks_exact(2).position(|x| x == [0x00, 0x00])? * 2;
+            Some((&bytes[..end], &bytes[end + 2..]))
+        }
+        _ => {
+            let end = bytes.iter().position(|x| *x == 0x00)?;
+            Some((&bytes[..end], &bytes[end + 1..]))
+        }
+    }
+}
+
+pub fn decode_text(encoding: u8, bytes: &[u8]) -> String {
+    let text = match encoding {
+        // ISO-8859-1 maps directly onto the first 256 code points
+        0x00 => bytes.iter().map(|x| char::from(*x)).collect(),
+        // UTF-16 with BOM, or UTF-16BE without
+        0x01 | 0x02 => {
+            let little_endian = encoding == 0x01 && bytes.starts_with(&[0xFF, 0xFE]);
+            let start = if encoding == 0x01 && bytes.len() >= 2 {
+                2
+            } else {
+                0
+            };
+            let units: Vec<u16> = bytes[start..]
+                .chunks_exact(2)
+                .map(|x| {
+                    if little_endian {
+                        u16::from_le_bytes([x[0], x[1]])
+                    } else {
+                        u16::from_be_bytes([x[0], x[1]])
+                    }
+                })
+                .collect();
+
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    };
+
+    text.trim_end_matches('\0').to_string()
+}
+
+// 64-bit FNV-1a, used to compare file contents without keeping them in memory
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+/// Percent-encodes everything but unreserved characters (RFC 3986), and '/' unless `encode_slash`
+pub fn percent_encode(text: &str, encode_slash: bool) -> String {
+    let mut result = String::new();
+
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(char::from(byte))
+            }
+            b'/' if !encode_slash => result.push('/'),
+            x => result += &format!("%{:02X}", x),
+        }
+    }
+
+    result
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let buffer = u32::from(chunk[0]) << 16
+            | u32::from(*chunk.get(1).unwrap_or(&0)) << 8
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let value = (buffer >> (18 - idx * 6)) & 0x3F;
+                result.push(char::from(ALPHABET[usize::try_from(value).unwrap()]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+// Lenient base64 decoding: whitespace is skipped and padding is optional
+pub fn decode_base64(text: &[u8]) -> Result<Vec<u8>, String> {
+    let mut result = vec![];
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in text {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            x if x.is_ascii_whitespace() => continue,
+            x => return Err(format!("invalid base64 byte {:#04X?}", x)),
+        };
+
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            result.push(u8::try_from((buffer >> bits) & 0xFF).unwrap());
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn format_milliseconds(ms: u32) -> String {
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+/// ID3v2.4 timestamp "yyyy-MM-ddTHH:mm:ss" (UTC) for seconds since the Unix epoch
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = i64::try_from(seconds / 86400).unwrap();
+    let time = seconds % 86400;
+
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Hexdump in the style of `xxd`, 16 bytes a line, with offsets counted from `start`
+pub fn hexdump(bytes: &[u8], start: usize) -> String {
+    let mut result = String::new();
+
+    for (idx, line) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|x| format!("{:02X}", x)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|x| {
+                if x.is_ascii_graphic() || *x == b' ' {
+                    char::from(*x)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        result += &format!(
+            "{:08X}  {:<23}  {:<23}  |{}|\n",
+            start + idx * 16,
+            hex[..hex.len().min(8)].join(" "),
+            hex[hex.len().min(8)..].join(" "),
+            ascii
+        );
+    }
+
+    result
+}
@@ -0,0 +1,58 @@
+use core::fmt;
+
+// Widest number accepted, which no release comes near
+const MAX_DIGITS: usize = 4;
+
+/// Position of a track in a release or of a disc in a set, written to TRCK or TPOS as "3" or
+/// "3/12"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackNumber {
+    pub number: u32,
+    pub total: Option<u32>,
+}
+
+impl TrackNumber {
+    /// A position checked the way `parse` checks text: both numbers start at 1, and the total
+    /// can't be less than the number
+    pub fn new(number: u32, total: Option<u32>) -> Result<TrackNumber, String> {
+        if number == 0 || total == Some(0) {
+            return Err("numbering starts at 1".to_string());
+        }
+
+        if let Some(total) = total.filter(|x| *x < number) {
+            return Err(format!("{} is past the total of {}", number, total));
+        }
+
+        Ok(TrackNumber { number, total })
+    }
+
+    /// Parses "3" or "3/12", with up to 4 digits in each number
+    pub fn parse(text: &str) -> Result<TrackNumber, String> {
+        let digits = |x: &str| {
+            let x = x.trim();
+            if x.is_empty() || x.len() > MAX_DIGITS || !x.bytes().all(|x| x.is_ascii_digit()) {
+                return Err(format!(
+                    "invalid '{}', expected a number of up to {} digits, optionally followed by /TOTAL",
+                    text, MAX_DIGITS
+                ));
+            }
+            Ok(x.parse::<u32>().unwrap())
+        };
+
+        let (number, total) = match text.split_once('/') {
+            Some((number, total)) => (digits(number)?, Some(digits(total)?)),
+            None => (digits(text)?, None),
+        };
+
+        TrackNumber::new(number, total).map_err(|x| format!("invalid '{}': {}", text, x))
+    }
+}
+
+impl fmt::Display for TrackNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.total {
+            Some(total) => write!(f, "{}/{}", self.number, total),
+            None => write!(f, "{}", self.number),
+        }
+    }
+}
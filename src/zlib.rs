@@ -0,0 +1,554 @@
+// Lengths 3 to 258 and distances 1 to 32768 are coded as a symbol plus extra bits, RFC 1951
+// section 3.2.5
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// Order the code length code lengths of a dynamic block are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions with the same hash are tried for a match
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+/// Adler-32 checksum, which ends a zlib stream
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // 5552 bytes is the most that can be summed before b could overflow
+    for chunk in data.chunks(5552) {
+        for x in chunk {
+            a += u32::from(*x);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    // Fields are packed starting from the least significant bit
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += bits;
+
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// Code and length of a literal/length symbol in the fixed Huffman code
+fn fixed_literal_code(symbol: u16) -> (u32, u32) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    }
+}
+
+// Symbol index and extra bits of a length or distance
+fn split_value(value: u16, base: &[u16], extra: &[u8]) -> (usize, u32, u32) {
+    let idx = base.iter().rposition(|x| *x <= value).unwrap();
+    (idx, u32::from(value - base[idx]), u32::from(extra[idx]))
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+// Records the position so later matches can find it
+fn insert(head: &mut [usize], prev: &mut [usize], data: &[u8], idx: usize) {
+    if idx + MIN_MATCH <= data.len() {
+        let h = hash(&data[idx..]);
+        prev[idx % WINDOW_SIZE] = head[h];
+        head[h] = idx;
+    }
+}
+
+/// Compresses `data` as a zlib stream (RFC 1950) of one deflate block with the fixed Huffman
+/// codes, which suits the text and small binary payloads of frames
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        bytes: vec![0x78, 0x9C],
+        buffer: 0,
+        count: 0,
+    };
+
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    // Latest position of each hash, and the position before it with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut idx = 0;
+    while idx < data.len() {
+        let mut best = (0, 0);
+
+        if idx + MIN_MATCH <= data.len() {
+            let max = MAX_MATCH.min(data.len() - idx);
+            let mut candidate = head[hash(&data[idx..])];
+
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || idx - candidate > WINDOW_SIZE || candidate >= idx {
+                    break;
+                }
+
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[idx..idx + max])
+                    .take_while(|(x, y)| x == y)
+                    .count();
+                if length > best.0 {
+                    best = (length, idx - candidate);
+                    if length == max {
+                        break;
+                    }
+                }
+
+                candidate = prev[candidate % WINDOW_SIZE];
+            }
+        }
+
+        if best.0 >= MIN_MATCH {
+            let (length, distance) = best;
+
+            let (symbol, extra, bits) = split_value(length as u16, &LENGTH_BASE, &LENGTH_EXTRA);
+            let (code, code_bits) = fixed_literal_code(257 + symbol as u16);
+            writer.write_code(code, code_bits);
+            writer.write(extra, bits);
+
+            let (symbol, extra, bits) =
+                split_value(distance as u16, &DISTANCE_BASE, &DISTANCE_EXTRA);
+            writer.write_code(symbol as u32, 5);
+            writer.write(extra, bits);
+
+            for x in idx..idx + length {
+                insert(&mut head, &mut prev, data, x);
+            }
+            idx += length;
+        } else {
+            let (code, code_bits) = fixed_literal_code(u16::from(data[idx]));
+            writer.write_code(code, code_bits);
+
+            insert(&mut head, &mut prev, data, idx);
+            idx += 1;
+        }
+    }
+
+    let (code, code_bits) = fixed_literal_code(256);
+    writer.write_code(code, code_bits);
+
+    let mut bytes = writer.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    // Position in bits
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let Some(byte) = self.data.get(self.position / 8) else {
+            return Err("compressed data is truncated".to_string());
+        };
+
+        let bit = (byte >> (self.position % 8)) & 1;
+        self.position += 1;
+        Ok(u32::from(bit))
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for x in 0..count {
+            value |= self.bit()? << x;
+        }
+        Ok(value)
+    }
+}
+
+// A canonical Huffman code: how many codes there are of each length, and the symbols in code
+// order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for x in lengths {
+            counts[usize::from(*x)] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for x in 1..16 {
+            offsets[x] = offsets[x - 1] + counts[x - 1];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, x) in lengths.iter().enumerate() {
+            if *x != 0 {
+                symbols[usize::from(offsets[usize::from(*x)])] = symbol as u16;
+                offsets[usize::from(*x)] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    // Reads a code one bit at a time, comparing it with the first code of each length
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..16 {
+            code |= reader.bit()? as i32;
+            let count = i32::from(self.counts[length]);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0; 19];
+    for x in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*x] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            x @ 0..=15 => (x as u8, 1),
+            16 => match lengths.last() {
+                Some(x) => (*x, 3 + reader.bits(2)?),
+                None => return Err("length repeated before any was given".to_string()),
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+
+    if lengths.len() > literals + distances {
+        return Err("code lengths run past the end of the table".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literal_code: &Huffman,
+    distance_code: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literal_code.decode(reader)?;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let idx = usize::from(symbol - 257);
+                let (Some(base), Some(extra)) = (LENGTH_BASE.get(idx), LENGTH_EXTRA.get(idx))
+                else {
+                    return Err("invalid length symbol".to_string());
+                };
+                let length = usize::from(*base) + reader.bits(u32::from(*extra))? as usize;
+
+                let idx = usize::from(distance_code.decode(reader)?);
+                let (Some(base), Some(extra)) = (DISTANCE_BASE.get(idx), DISTANCE_EXTRA.get(idx))
+                else {
+                    return Err("invalid distance symbol".to_string());
+                };
+                let distance = usize::from(*base) + reader.bits(u32::from(*extra))? as usize;
+
+                if distance > output.len() {
+                    return Err("distance points before the start of the data".to_string());
+                }
+
+                // The match may overlap what it produces, so it is copied byte by byte
+                let start = output.len() - distance;
+                for x in 0..length {
+                    output.push(output[start + x]);
+                }
+            }
+        }
+    }
+}
+
+/// Decompresses a zlib stream (RFC 1950), checking its checksum
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (Some(cmf), Some(flg)) = (data.first(), data.get(1)) else {
+        return Err("compressed data is truncated".to_string());
+    };
+    if cmf & 0x0F != 8 || (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 != 0 {
+        return Err("not a zlib stream".to_string());
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib streams with a preset dictionary are not supported".to_string());
+    }
+
+    let mut reader = BitReader {
+        data: &data[2..],
+        position: 0,
+    };
+    let mut output = vec![];
+
+    loop {
+        let last = reader.bit()? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                // Stored blocks start on a byte boundary
+                let start = reader.position.div_ceil(8);
+                let Some(header) = reader.data.get(start..start + 4) else {
+                    return Err("compressed data is truncated".to_string());
+                };
+                let length = usize::from(u16::from_le_bytes([header[0], header[1]]));
+                let Some(bytes) = reader.data.get(start + 4..start + 4 + length) else {
+                    return Err("compressed data is truncated".to_string());
+                };
+                output.extend_from_slice(bytes);
+                reader.position = (start + 4 + length) * 8;
+            }
+            1 => {
+                let (literal_code, distance_code) = fixed_codes();
+                inflate_block(&mut reader, &mut output, &literal_code, &distance_code)?;
+            }
+            2 => {
+                let (literal_code, distance_code) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literal_code, &distance_code)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    let end = 2 + reader.position.div_ceil(8);
+    match data.get(end..end + 4) {
+        Some(x) if u32::from_be_bytes(x.try_into().unwrap()) == adler32(&output) => Ok(output),
+        Some(_) => Err("checksum mismatch".to_string()),
+        None => Err("compressed data is truncated".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|x| u8::from_str_radix(&text[x..x + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Bytes from a fixed xorshift sequence, so failures are reproducible
+    fn random_bytes(length: usize) -> Vec<u8> {
+        let mut state: u32 = 0x9E37_79B9;
+
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        compressed
+    }
+
+    #[test]
+    fn adler32_matches_reference_values() {
+        assert_eq!(adler32(b""), 0x0000_0001);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // Long enough that the sums are reduced between chunks
+        assert_eq!(adler32(&vec![0xFF; 100_000]), 0x149A_302C);
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        let compressed = round_trip(b"");
+        assert_eq!(compressed, from_hex("789c030000000001"));
+    }
+
+    #[test]
+    fn round_trips_random_data() {
+        for length in [1, 2, 3, 257, 4096, WINDOW_SIZE + 1000] {
+            round_trip(&random_bytes(length));
+        }
+    }
+
+    #[test]
+    fn round_trips_long_repeats() {
+        // Distance 1 matches overlap the bytes they copy
+        let compressed = round_trip(&vec![b'a'; 100_000]);
+        assert!(compressed.len() < 1000, "{} bytes", compressed.len());
+
+        let text = "Lyrics repeat, and so do their choruses. ".repeat(2000);
+        let compressed = round_trip(text.as_bytes());
+        assert!(
+            compressed.len() < text.len() / 20,
+            "{} bytes",
+            compressed.len()
+        );
+
+        // Matches of exactly the longest length, and repeats further back than the window
+        let block = random_bytes(MAX_MATCH);
+        round_trip(&[block.clone(), block.clone(), block].concat());
+        let far = random_bytes(WINDOW_SIZE + 100);
+        round_trip(&[far.as_slice(), &far[..500]].concat());
+    }
+
+    #[test]
+    fn decompresses_stored_blocks() {
+        let data = b"hello, world";
+        let checksum = adler32(data).to_be_bytes();
+
+        // One final stored block: header bits, then LEN and NLEN from the next byte boundary
+        let stream = [
+            &[0x78, 0x01, 0x01, 0x0C, 0x00, 0xF3, 0xFF][..],
+            data,
+            &checksum,
+        ]
+        .concat();
+        assert_eq!(decompress(&stream).unwrap(), data);
+
+        // A stored block followed by a final one
+        let stream = [
+            &[0x78, 0x01, 0x00, 0x05, 0x00, 0xFA, 0xFF][..],
+            &data[..5],
+            &[0x01, 0x07, 0x00, 0xF8, 0xFF],
+            &data[5..],
+            &checksum,
+        ]
+        .concat();
+        assert_eq!(decompress(&stream).unwrap(), data);
+    }
+
+    #[test]
+    fn decompresses_fixed_blocks() {
+        // Written by zlib at level 9
+        assert_eq!(decompress(&from_hex("78da030000000001")).unwrap(), b"");
+        assert_eq!(
+            decompress(&from_hex("78dacb48cdc9c957c8402701680308b1")).unwrap(),
+            b"hello hello hello hello"
+        );
+    }
+
+    #[test]
+    fn decompresses_dynamic_blocks() {
+        let data: Vec<String> = (0..60).map(|x| (x * x).to_string()).collect();
+
+        // Written by zlib at level 9
+        let stream = from_hex(
+            "78da158ec101c0300802576104516b74ffc50a2f1205bc00d13870901f6ad087692cc10830a5dddaca71b2\
+             c8939f740f952dbfd2f2754bb7f1e5e17b83916fa44fffa7f96a7ff2ddb85741861a4855d19d4c95b37c\
+             a57deef3dd31c098e48988db661061862968e28c435634b283225322c7afe7d97a7bf29513a57e438bbc\
+             d457bdfc01ea2d2e92",
+        );
+        assert_eq!(decompress(&stream).unwrap(), data.join(" ").as_bytes());
+    }
+
+    #[test]
+    fn rejects_damaged_streams() {
+        let mut compressed = compress(b"hello hello hello hello");
+
+        assert!(decompress(&[]).is_err());
+        assert!(decompress(&[0x78]).is_err());
+        // Not deflate, and a header failing its check
+        assert!(decompress(&[0x79, 0x9C, 0x03, 0x00]).is_err());
+        assert!(decompress(&[0x78, 0x9D, 0x03, 0x00]).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1]).is_err());
+        assert!(decompress(&compressed[..4]).is_err());
+
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0x01;
+        assert_eq!(
+            decompress(&compressed),
+            Err("checksum mismatch".to_string())
+        );
+    }
+}